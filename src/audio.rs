@@ -0,0 +1,455 @@
+use crate::{game::GameEvent, loading::join_all, playfield::PLAYFIELD_SIZE};
+use macroquad::{
+    audio::{load_sound_from_bytes, play_sound, PlaySoundParams, Sound},
+    file::load_file,
+    math::IVec2,
+};
+use serde::Deserialize;
+use std::{collections::HashMap, fs, path::Path};
+use strum::{EnumIter, IntoEnumIterator};
+
+const SOUND_EFFECTS_FOLDER: &str = "sfx";
+const SOUND_EFFECTS_MANIFEST: &str = "sfx.json";
+const SOUND_EFFECT_COOLDOWN_MS: u64 = 30; // a sound isn't played again until it's this old
+const PAN_FAR_GAIN: f32 = 0.3; // volume of a panned sound in the opposite speaker
+const PAN_THRESHOLD: f32 = 0.3; // share of the half playfield width from its center a sound is panned beyond
+
+// The sounds played for the game's events
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumIter)]
+pub enum SoundEffect {
+    Move,
+    Rotate,
+    // the active rustomino touched down on the stack
+    Land,
+    Lock,
+    Hold,
+    LineClear,
+    Rustris,
+    LevelUp,
+    GameOver,
+}
+
+impl SoundEffect {
+    /// the name used in the manifest, the default file is the name with a .wav extension
+    fn name(&self) -> &'static str {
+        match self {
+            SoundEffect::Move => "move",
+            SoundEffect::Rotate => "rotate",
+            SoundEffect::Land => "land",
+            SoundEffect::Lock => "lock",
+            SoundEffect::Hold => "hold",
+            SoundEffect::LineClear => "line_clear",
+            SoundEffect::Rustris => "rustris",
+            SoundEffect::LevelUp => "level_up",
+            SoundEffect::GameOver => "game_over",
+        }
+    }
+
+    /// the sound played for the event, if it has one
+    fn for_event(event: &GameEvent) -> Option<Self> {
+        match event {
+            GameEvent::Move(_) => Some(SoundEffect::Move),
+            GameEvent::Rotate(_) => Some(SoundEffect::Rotate),
+            GameEvent::Land(_) => Some(SoundEffect::Land),
+            GameEvent::Lock(_) => Some(SoundEffect::Lock),
+            GameEvent::Hold => Some(SoundEffect::Hold),
+            GameEvent::LinesCleared(cleared_lines) if cleared_lines.lines == 4 => {
+                Some(SoundEffect::Rustris)
+            }
+            GameEvent::LinesCleared(_) => Some(SoundEffect::LineClear),
+            GameEvent::LevelUp(_) => Some(SoundEffect::LevelUp),
+            GameEvent::GameOver => Some(SoundEffect::GameOver),
+            GameEvent::Spawn
+            | GameEvent::HardDrop(_)
+            | GameEvent::Scored(_)
+            | GameEvent::ScoreMilestone(_) => None,
+        }
+    }
+}
+
+// An sfx.json manifest in the sfx folder so sound packs can change the files,
+// every value is optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SoundManifest {
+    // sound effect name to its file name, e.g. "line_clear": "clear.wav",
+    // or to its settings, e.g. "move": { "volume": 0.5, "cooldown_ms": 60 }
+    sounds: HashMap<String, SoundEntry>,
+    // played instead of the line clear for each consecutive clear of a combo,
+    // from the second clear of the combo, the last one repeats for longer combos
+    combo: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SoundEntry {
+    File(String),
+    Settings(SoundSettings),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct SoundSettings {
+    file: Option<String>,
+    volume: f32,      // multiplies the sound effects volume
+    cooldown_ms: u64, // events in this time after the sound is played are silent
+}
+
+impl Default for SoundSettings {
+    fn default() -> Self {
+        SoundSettings {
+            file: None,
+            volume: 1.,
+            cooldown_ms: SOUND_EFFECT_COOLDOWN_MS,
+        }
+    }
+}
+
+impl SoundEntry {
+    fn settings(&self) -> SoundSettings {
+        match self {
+            SoundEntry::File(file) => SoundSettings {
+                file: Some(file.clone()),
+                ..Default::default()
+            },
+            SoundEntry::Settings(settings) => SoundSettings {
+                file: settings.file.clone(),
+                ..*settings
+            },
+        }
+    }
+}
+
+// A loaded sound effect with its manifest settings
+struct LoadedSound {
+    sound: PannedSound,
+    volume: f32,
+    cooldown: f64, // seconds
+}
+
+// The loaded sound effects, missing ones are silent
+pub struct SoundEffects {
+    sounds: HashMap<SoundEffect, LoadedSound>,
+    combo: Vec<PannedSound>,
+    cooldowns: Cooldowns,
+}
+
+impl SoundEffects {
+    /// Loads every sound effect in the assets' sfx folder
+    /// with the file names in its manifest
+    pub async fn load(assets_path: &Path) -> Self {
+        let sfx_path = assets_path.join(SOUND_EFFECTS_FOLDER);
+        let manifest_path = sfx_path.join(SOUND_EFFECTS_MANIFEST);
+        let manifest: SoundManifest = match fs::read_to_string(&manifest_path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                log::warn!("invalid sound manifest {:?}: {}", manifest_path, e);
+                SoundManifest::default()
+            }),
+            Err(e) => {
+                log::info!("no sound manifest {:?}: {}", manifest_path, e);
+                SoundManifest::default()
+            }
+        };
+        let sound_loads = SoundEffect::iter().map(|effect| {
+            let settings = manifest
+                .sounds
+                .get(effect.name())
+                .map_or_else(SoundSettings::default, SoundEntry::settings);
+            let file_name = settings
+                .file
+                .unwrap_or_else(|| format!("{}.wav", effect.name()));
+            let sound_path = sfx_path.join(file_name);
+            async move {
+                let sound = load_sound_effect(&sound_path).await;
+                (
+                    effect,
+                    sound.map(|sound| LoadedSound {
+                        sound,
+                        volume: settings.volume,
+                        cooldown: settings.cooldown_ms as f64 / 1000.,
+                    }),
+                )
+            }
+        });
+        let combo_loads = manifest.combo.iter().map(|file_name| {
+            let sound_path = sfx_path.join(file_name);
+            async move { load_sound_effect(&sound_path).await }
+        });
+        let (sounds, combo) = crate::loading::join(
+            join_all(sound_loads.collect()),
+            join_all(combo_loads.collect()),
+        )
+        .await;
+        SoundEffects {
+            sounds: sounds
+                .into_iter()
+                .filter_map(|(effect, sound)| Some((effect, sound?)))
+                .collect(),
+            combo: combo.into_iter().flatten().collect(),
+            cooldowns: Cooldowns::default(),
+        }
+    }
+
+    /// plays the sound for the event at the provided volume, from 0 to 1,
+    /// `time` is in seconds and suppresses repeats within the sound's cooldown,
+    /// with `panning` the sound comes from the rustomino's side of the playfield
+    pub fn play_event(&mut self, event: &GameEvent, volume: f32, time: f64, panning: bool) {
+        let Some(effect) = SoundEffect::for_event(event) else {
+            return;
+        };
+        let Some(loaded) = self.sounds.get(&effect) else {
+            return;
+        };
+        if !self.cooldowns.start(effect, time, loaded.cooldown) {
+            return;
+        }
+        let volume = volume * loaded.volume;
+        let pan = match event_slots(event) {
+            Some(slots) if panning => Pan::for_slots(slots),
+            _ => Pan::Center,
+        };
+        // clears during a combo rise in pitch, rustrises keep their own sound
+        if let GameEvent::LinesCleared(cleared_lines) = event {
+            if cleared_lines.lines < 4 && cleared_lines.combo > 0 && !self.combo.is_empty() {
+                let index = (cleared_lines.combo - 1).min(self.combo.len() - 1);
+                play_at_volume(self.combo[index].get(pan), volume);
+                return;
+            }
+        }
+        play_at_volume(loaded.sound.get(pan), volume);
+    }
+
+    /// plays a short sound so the player can judge the volume, from 0 to 1
+    pub fn play_sample(&self, volume: f32) {
+        if let Some(loaded) = self.sounds.get(&SoundEffect::Lock) {
+            play_at_volume(loaded.sound.center, volume * loaded.volume);
+        }
+    }
+}
+
+// The time each sound was last played, so events in quick succession
+// like the moves of a held direction don't stack copies of the same sound
+#[derive(Default)]
+struct Cooldowns {
+    last_played: HashMap<SoundEffect, f64>,
+}
+
+impl Cooldowns {
+    /// starts the sound's cooldown at the time in seconds,
+    /// returns false without starting it if the last one hasn't finished
+    fn start(&mut self, effect: SoundEffect, time: f64, cooldown: f64) -> bool {
+        if self
+            .last_played
+            .get(&effect)
+            .is_some_and(|last_played| time - last_played < cooldown)
+        {
+            return false;
+        }
+        self.last_played.insert(effect, time);
+        true
+    }
+}
+
+/// the playfield slots of the rustomino the event is about
+fn event_slots(event: &GameEvent) -> Option<&[IVec2; 4]> {
+    match event {
+        GameEvent::Move(slots)
+        | GameEvent::Rotate(slots)
+        | GameEvent::Land(slots)
+        | GameEvent::Lock(slots) => Some(slots),
+        GameEvent::LinesCleared(cleared_lines) => Some(&cleared_lines.locked),
+        _ => None,
+    }
+}
+
+// The side of the stereo field a sound is played from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pan {
+    Left,
+    Center,
+    Right,
+}
+
+impl Pan {
+    /// the side of the playfield the rustomino's columns are on
+    fn for_slots(slots: &[IVec2; 4]) -> Self {
+        let column = slots.iter().map(|slot| slot.x as f32).sum::<f32>() / slots.len() as f32;
+        // from -1 at the left wall to 1 at the right wall
+        let position = column / (PLAYFIELD_SIZE[0] - 1) as f32 * 2. - 1.;
+        if position < -PAN_THRESHOLD {
+            Pan::Left
+        } else if position > PAN_THRESHOLD {
+            Pan::Right
+        } else {
+            Pan::Center
+        }
+    }
+}
+
+// A sound effect and copies of it panned left and right,
+// macroquad can't pan a sound so the copies are made when it's loaded
+struct PannedSound {
+    center: Sound,
+    sides: Option<[Sound; 2]>, // left and right, only mono 16 bit wav files are panned
+}
+
+impl PannedSound {
+    fn get(&self, pan: Pan) -> Sound {
+        match (pan, self.sides) {
+            (Pan::Left, Some([left, _])) => left,
+            (Pan::Right, Some([_, right])) => right,
+            _ => self.center,
+        }
+    }
+}
+
+/// loads a sound effect and its panned copies, returns None if it can't be loaded
+async fn load_sound_effect(sound_path: &Path) -> Option<PannedSound> {
+    log::info!("loading sound effect: {:?}", sound_path);
+    let bytes = match load_file(&sound_path.to_string_lossy()).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            log::warn!("unable to read sound effect {:?}: {}", sound_path, e);
+            return None;
+        }
+    };
+    let center = match load_sound_from_bytes(&bytes).await {
+        Ok(sound) => sound,
+        Err(e) => {
+            log::warn!("unable to load sound effect {:?}: {}", sound_path, e);
+            return None;
+        }
+    };
+    let (Some(left), Some(right)) = (
+        panned_wav(&bytes, [1., PAN_FAR_GAIN]),
+        panned_wav(&bytes, [PAN_FAR_GAIN, 1.]),
+    ) else {
+        log::debug!("sound effect {:?} isn't panned", sound_path);
+        return Some(PannedSound {
+            center,
+            sides: None,
+        });
+    };
+    let sides = match (
+        load_sound_from_bytes(&left).await,
+        load_sound_from_bytes(&right).await,
+    ) {
+        (Ok(left), Ok(right)) => Some([left, right]),
+        _ => None,
+    };
+    Some(PannedSound { center, sides })
+}
+
+/// makes a stereo copy of a mono 16 bit pcm wav file
+/// with the provided left and right channel gains, None for other formats
+fn panned_wav(bytes: &[u8], gains: [f32; 2]) -> Option<Vec<u8>> {
+    let u16_at = |offset: usize| -> Option<u16> {
+        Some(u16::from_le_bytes(
+            bytes.get(offset..offset + 2)?.try_into().ok()?,
+        ))
+    };
+    let u32_at = |offset: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(
+            bytes.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+    if bytes.get(0..4)? != b"RIFF" || bytes.get(8..12)? != b"WAVE" {
+        return None;
+    }
+    let mut offset = 12;
+    let mut sample_rate = None;
+    while let (Some(id), Some(size)) = (bytes.get(offset..offset + 4), u32_at(offset + 4)) {
+        match id {
+            b"fmt " => {
+                // pcm, one channel, 16 bits per sample
+                let format = (
+                    u16_at(offset + 8)?,
+                    u16_at(offset + 10)?,
+                    u16_at(offset + 22)?,
+                );
+                if format != (1, 1, 16) {
+                    return None;
+                }
+                sample_rate = u32_at(offset + 12);
+            }
+            b"data" => {
+                let sample_rate = sample_rate?;
+                let samples = bytes.get(offset + 8..offset + 8 + size as usize)?;
+                let data_size = size * 2;
+                let mut wav = Vec::with_capacity(44 + data_size as usize);
+                wav.extend_from_slice(b"RIFF");
+                wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+                wav.extend_from_slice(b"WAVEfmt ");
+                wav.extend_from_slice(&16u32.to_le_bytes());
+                wav.extend_from_slice(&1u16.to_le_bytes()); // pcm
+                wav.extend_from_slice(&2u16.to_le_bytes()); // channels
+                wav.extend_from_slice(&sample_rate.to_le_bytes());
+                wav.extend_from_slice(&(sample_rate * 4).to_le_bytes()); // byte rate
+                wav.extend_from_slice(&4u16.to_le_bytes()); // block align
+                wav.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+                wav.extend_from_slice(b"data");
+                wav.extend_from_slice(&data_size.to_le_bytes());
+                for sample in samples.chunks_exact(2) {
+                    let sample = i16::from_le_bytes([sample[0], sample[1]]) as f32;
+                    for gain in gains {
+                        wav.extend_from_slice(&((sample * gain) as i16).to_le_bytes());
+                    }
+                }
+                return Some(wav);
+            }
+            _ => {}
+        }
+        offset += 8 + size as usize + size as usize % 2;
+    }
+    None
+}
+
+/// plays a sound once, silent sounds aren't played
+fn play_at_volume(sound: Sound, volume: f32) {
+    if volume <= 0. {
+        return;
+    }
+    play_sound(
+        sound,
+        PlaySoundParams {
+            looped: false,
+            volume,
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeats_inside_the_cooldown_are_suppressed() {
+        let cooldown = SOUND_EFFECT_COOLDOWN_MS as f64 / 1000.;
+        let mut cooldowns = Cooldowns::default();
+        assert!(cooldowns.start(SoundEffect::Move, 1., cooldown));
+        // twenty moves in one frame play once
+        let played = (0..20)
+            .filter(|_| cooldowns.start(SoundEffect::Move, 1., cooldown))
+            .count();
+        assert_eq!(played, 0);
+        assert!(!cooldowns.start(SoundEffect::Move, 1. + cooldown / 2., cooldown));
+        // other sounds have their own cooldowns
+        assert!(cooldowns.start(SoundEffect::Rotate, 1., cooldown));
+        assert!(cooldowns.start(SoundEffect::Move, 1. + cooldown, cooldown));
+    }
+
+    #[test]
+    fn manifest_entries_are_file_names_or_settings() {
+        let manifest: SoundManifest = serde_json::from_str(
+            r#"{ "sounds": { "lock": "click.wav", "move": { "volume": 0.5 } } }"#,
+        )
+        .unwrap();
+        let lock = manifest.sounds["lock"].settings();
+        assert_eq!(lock.file.as_deref(), Some("click.wav"));
+        assert_eq!(lock.cooldown_ms, SOUND_EFFECT_COOLDOWN_MS);
+        let moves = manifest.sounds["move"].settings();
+        assert_eq!(moves.file, None);
+        assert_eq!(moves.volume, 0.5);
+        assert_eq!(moves.cooldown_ms, SOUND_EFFECT_COOLDOWN_MS);
+    }
+}
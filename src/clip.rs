@@ -0,0 +1,162 @@
+use crate::{
+    game::{GameState, RustrisGame},
+    summary,
+};
+use image::RgbaImage;
+#[cfg(feature = "gif")]
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, Frame,
+};
+use macroquad::prelude::*;
+use std::{
+    collections::VecDeque,
+    fs, io,
+    path::{Path, PathBuf},
+    thread::{self, JoinHandle},
+};
+
+// clips are only recorded when the gif encoder is built in
+pub const AVAILABLE: bool = cfg!(feature = "gif");
+
+const CLIP_FILE_PREFIX: &str = "rustris-clip";
+const CLIP_SECONDS: f64 = 8.; // the end of the game kept for the clip
+const CLIP_FRAME_RATE: u32 = 10;
+// frames are scaled down to this width, keeping the window's shape
+const CLIP_WIDTH: u32 = 480;
+// the encoder's color quantization, 1 is the best and slowest, 30 the fastest
+#[cfg(feature = "gif")]
+const CLIP_ENCODER_SPEED: i32 = 10;
+
+// Keeps the last seconds of the game being played, captured from the window,
+// so the player can save them as an animated gif at game over
+pub struct ClipRecorder {
+    frames: VecDeque<RgbaImage>,
+    frame_time: f64,           // seconds since the last frame was captured
+    play_time: f64,            // at the last capture, a new game starts a new clip
+    screen: Option<Texture2D>, // the window's size, the screen is copied to it
+    saving: Option<JoinHandle<io::Result<PathBuf>>>,
+}
+
+impl ClipRecorder {
+    pub fn new() -> Self {
+        ClipRecorder {
+            frames: VecDeque::new(),
+            frame_time: 0.,
+            play_time: 0.,
+            screen: None,
+            saving: None,
+        }
+    }
+
+    /// Captures the window while the game is played, called after the frame is drawn
+    pub fn update(&mut self, game: &RustrisGame, delta_time: f64) {
+        if !AVAILABLE || !matches!(game.state, GameState::Playing) {
+            return;
+        }
+        if game.play_time() < self.play_time {
+            log::debug!("new game, discarding {} clip frames", self.frames.len());
+            self.frames.clear();
+        }
+        self.play_time = game.play_time();
+        self.frame_time += delta_time;
+        if self.frame_time < 1. / CLIP_FRAME_RATE as f64 {
+            return;
+        }
+        self.frame_time = 0.;
+        let frame = self.capture();
+        self.frames.push_back(frame);
+        if self.frames.len() > (CLIP_SECONDS * CLIP_FRAME_RATE as f64) as usize {
+            self.frames.pop_front();
+        }
+    }
+
+    // the window scaled down to the clip's width
+    fn capture(&mut self) -> RgbaImage {
+        let (width, height) = (screen_width() as u32, screen_height() as u32);
+        let screen = match self.screen {
+            Some(screen) if screen.width() as u32 == width && screen.height() as u32 == height => {
+                screen
+            }
+            screen => {
+                if let Some(screen) = screen {
+                    screen.delete();
+                }
+                let pixels = vec![0; width as usize * height as usize * 4];
+                let screen = Texture2D::from_rgba8(width as u16, height as u16, &pixels);
+                self.screen = Some(screen);
+                screen
+            }
+        };
+        // changing the camera draws the frame to the window before it's copied
+        set_default_camera();
+        screen.grab_screen();
+        let image = screen.get_texture_data();
+
+        // the screen is copied upside down, the gif is opaque
+        let clip_height = (height * CLIP_WIDTH / width.max(1)).max(1);
+        RgbaImage::from_fn(CLIP_WIDTH, clip_height, |x, y| {
+            let screen_x = (x * width / CLIP_WIDTH).min(width - 1);
+            let screen_y = height - 1 - (y * height / clip_height).min(height - 1);
+            let index = (screen_y * width + screen_x) as usize * 4;
+            let [r, g, b] = [0, 1, 2].map(|channel| image.bytes[index + channel]);
+            image::Rgba([r, g, b, u8::MAX])
+        })
+    }
+
+    /// Encodes the captured frames as a gif in the folder on another thread,
+    /// the result is returned by saved
+    pub fn save(&mut self, folder: PathBuf) {
+        if self.saving.is_some() {
+            return;
+        }
+        let frames: Vec<RgbaImage> = self.frames.iter().cloned().collect();
+        log::info!("saving a clip of {} frames", frames.len());
+        self.saving = Some(thread::spawn(move || {
+            fs::create_dir_all(&folder)?;
+            let path = folder.join(format!("{CLIP_FILE_PREFIX}-{}.gif", summary::unix_time()));
+            encode(frames, &path)?;
+            log::info!("clip saved: {:?}", path);
+            Ok(path)
+        }));
+    }
+
+    /// the clip's path or why it couldn't be saved, once it's finished
+    pub fn saved(&mut self) -> Option<io::Result<PathBuf>> {
+        if !self.saving.as_ref()?.is_finished() {
+            return None;
+        }
+        let result = self
+            .saving
+            .take()?
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("clip encoder panicked")));
+        Some(result)
+    }
+}
+
+// writes the frames as a gif that loops forever
+#[cfg(feature = "gif")]
+fn encode(frames: Vec<RgbaImage>, path: &Path) -> io::Result<()> {
+    let file = io::BufWriter::new(fs::File::create(path)?);
+    let mut encoder = GifEncoder::new_with_speed(file, CLIP_ENCODER_SPEED);
+    encoder
+        .set_repeat(Repeat::Infinite)
+        .map_err(io::Error::other)?;
+    let delay = Delay::from_numer_denom_ms(1000, CLIP_FRAME_RATE);
+    encoder
+        .encode_frames(
+            frames
+                .into_iter()
+                .map(|frame| Frame::from_parts(frame, 0, 0, delay)),
+        )
+        .map_err(io::Error::other)
+}
+
+#[cfg(not(feature = "gif"))]
+fn encode(_frames: Vec<RgbaImage>, _path: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "built without the gif feature",
+    ))
+}
@@ -1,4 +1,5 @@
 use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use strum::{EnumIter, IntoEnumIterator};
 
@@ -10,12 +11,58 @@ const ROTATE_CCW_KEYS: [Option<KeyCode>; 2] = [Some(KeyCode::LeftControl), Some(
 const SOFT_DROP_KEYS: [Option<KeyCode>; 2] = [Some(KeyCode::Down), Some(KeyCode::S)];
 const HARD_DROP_KEYS: [Option<KeyCode>; 2] = [Some(KeyCode::Space), None];
 const HOLD_KEYS: [Option<KeyCode>; 2] = [Some(KeyCode::LeftShift), Some(KeyCode::C)];
+const CYCLE_PIECE_KEYS: [Option<KeyCode>; 2] = [Some(KeyCode::Tab), None];
+const LOCK_PIECE_KEYS: [Option<KeyCode>; 2] = [Some(KeyCode::L), None];
+const TOGGLE_GHOST_KEYS: [Option<KeyCode>; 2] = [Some(KeyCode::G), None];
+const VOLUME_DOWN_KEYS: [Option<KeyCode>; 2] = [Some(KeyCode::Minus), Some(KeyCode::KpSubtract)];
+const VOLUME_UP_KEYS: [Option<KeyCode>; 2] = [Some(KeyCode::Equal), Some(KeyCode::KpAdd)];
+const MUTE_KEYS: [Option<KeyCode>; 2] = [Some(KeyCode::M), None];
 
-// input repeat delays
-const TRANSLATE_ACTION_DELAY: f64 = 0.3;
-const TRANSLATE_ACTION_REPEAT_DELAY: f64 = 0.025;
+// versus controls, player 1 on the left of the keyboard and player 2 on the arrows or numpad
+const VERSUS_LEFT_KEYS: [[Option<KeyCode>; 2]; 2] = [
+    [Some(KeyCode::A), None],
+    [Some(KeyCode::Left), Some(KeyCode::Kp4)],
+];
+const VERSUS_RIGHT_KEYS: [[Option<KeyCode>; 2]; 2] = [
+    [Some(KeyCode::D), None],
+    [Some(KeyCode::Right), Some(KeyCode::Kp6)],
+];
+const VERSUS_ROTATE_CW_KEYS: [[Option<KeyCode>; 2]; 2] = [
+    [Some(KeyCode::W), None],
+    [Some(KeyCode::Up), Some(KeyCode::Kp8)],
+];
+const VERSUS_ROTATE_CCW_KEYS: [[Option<KeyCode>; 2]; 2] = [
+    [Some(KeyCode::Q), Some(KeyCode::LeftControl)],
+    [Some(KeyCode::RightControl), Some(KeyCode::Kp7)],
+];
+const VERSUS_SOFT_DROP_KEYS: [[Option<KeyCode>; 2]; 2] = [
+    [Some(KeyCode::S), None],
+    [Some(KeyCode::Down), Some(KeyCode::Kp5)],
+];
+const VERSUS_HARD_DROP_KEYS: [[Option<KeyCode>; 2]; 2] = [
+    [Some(KeyCode::Space), None],
+    [Some(KeyCode::RightShift), Some(KeyCode::Kp0)],
+];
+const VERSUS_HOLD_KEYS: [[Option<KeyCode>; 2]; 2] = [
+    [Some(KeyCode::LeftShift), Some(KeyCode::C)],
+    [Some(KeyCode::Slash), Some(KeyCode::Kp9)],
+];
+
+// environment variables naming a control's keys replace its defaults at startup,
+// e.g. RUSTRIS_KEY_LEFT=A or RUSTRIS_KEY_HOLD=LeftShift,C
+const KEYS_ENV_PREFIX: &str = "RUSTRIS_KEY_";
+
+// input repeat delays, left and right default to these until the auto shift settings change
+pub const AUTO_SHIFT_DELAY_MS: u32 = 300;
+pub const AUTO_REPEAT_DELAY_MS: u32 = 25;
+const TRANSLATE_ACTION_DELAY: f64 = AUTO_SHIFT_DELAY_MS as f64 / 1000.;
+const TRANSLATE_ACTION_REPEAT_DELAY: f64 = AUTO_REPEAT_DELAY_MS as f64 / 1000.;
+// frame counts are converted to time at this rate, the rate delays are shared at
+pub const FRAMES_PER_SECOND: f64 = 60.;
 const SOFT_DROP_ACTION_DELAY: f64 = 0.2;
 const SOFT_DROP_ACTION_REPEAT_DELAY: f64 = 0.03;
+const VOLUME_ACTION_DELAY: f64 = 0.4;
+const VOLUME_ACTION_REPEAT_DELAY: f64 = 0.1;
 
 // TODO: implement saving and loading inputs from file
 
@@ -36,6 +83,9 @@ pub enum Controls {
     SoftDrop,
     HardDrop,
     Hold,
+    CyclePiece, // practice games only
+    LockPiece,  // sandbox games only
+    ToggleGhost,
 }
 
 impl Controls {
@@ -43,9 +93,14 @@ impl Controls {
         match self {
             Controls::Left | Controls::Right => Some(TRANSLATE_ACTION_DELAY),
             Controls::SoftDrop => Some(SOFT_DROP_ACTION_DELAY),
+            // holding hard drop would drop every rustomino as it spawns
             _ => None,
         }
     }
+    /// true if holding the control repeats its action
+    pub fn repeats(&self) -> bool {
+        self.action_delay().is_some()
+    }
     pub fn action_repeat_delay(&self) -> Option<f64> {
         match self {
             Controls::Left | Controls::Right => Some(TRANSLATE_ACTION_REPEAT_DELAY),
@@ -53,6 +108,14 @@ impl Controls {
             _ => None,
         }
     }
+    /// the control performed by this control's keys when the drop keys are swapped
+    pub fn with_drops_swapped(&self) -> Controls {
+        match self {
+            Controls::SoftDrop => Controls::HardDrop,
+            Controls::HardDrop => Controls::SoftDrop,
+            _ => self.clone(),
+        }
+    }
     pub fn default_keys(&self) -> [Option<KeyCode>; 2] {
         match self {
             Controls::Left => LEFT_KEYS,
@@ -62,14 +125,236 @@ impl Controls {
             Controls::SoftDrop => SOFT_DROP_KEYS,
             Controls::HardDrop => HARD_DROP_KEYS,
             Controls::Hold => HOLD_KEYS,
+            Controls::CyclePiece => CYCLE_PIECE_KEYS,
+            Controls::LockPiece => LOCK_PIECE_KEYS,
+            Controls::ToggleGhost => TOGGLE_GHOST_KEYS,
+        }
+    }
+    // the name of the control in its keys environment variable and in input scripts
+    fn name(&self) -> &'static str {
+        match self {
+            Controls::Left => "LEFT",
+            Controls::Right => "RIGHT",
+            Controls::RotateCW => "ROTATE_CW",
+            Controls::RotateCCW => "ROTATE_CCW",
+            Controls::SoftDrop => "SOFT_DROP",
+            Controls::HardDrop => "HARD_DROP",
+            Controls::Hold => "HOLD",
+            Controls::CyclePiece => "CYCLE_PIECE",
+            Controls::LockPiece => "LOCK_PIECE",
+            Controls::ToggleGhost => "TOGGLE_GHOST",
         }
     }
+    /// the control with the provided name, ignoring case
+    pub fn from_name(name: &str) -> Option<Controls> {
+        Controls::iter().find(|control| control.name().eq_ignore_ascii_case(name))
+    }
+    /// the keys of the player, 0 or 1, in a versus match,
+    /// the practice, sandbox and ghost controls aren't used
+    pub fn versus_keys(&self, player: usize) -> [Option<KeyCode>; 2] {
+        match self {
+            Controls::Left => VERSUS_LEFT_KEYS[player],
+            Controls::Right => VERSUS_RIGHT_KEYS[player],
+            Controls::RotateCW => VERSUS_ROTATE_CW_KEYS[player],
+            Controls::RotateCCW => VERSUS_ROTATE_CCW_KEYS[player],
+            Controls::SoftDrop => VERSUS_SOFT_DROP_KEYS[player],
+            Controls::HardDrop => VERSUS_HARD_DROP_KEYS[player],
+            Controls::Hold => VERSUS_HOLD_KEYS[player],
+            Controls::CyclePiece | Controls::LockPiece | Controls::ToggleGhost => [None, None],
+        }
+    }
+}
+
+// The controls whose keys were pressed or down in a frame, read from the keyboard
+// or sent by the opponent in an online match
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputFrame {
+    pressed: u16, // a bit for each control in the order of Controls
+    down: u16,
+}
+
+impl InputFrame {
+    fn bit(control: &Controls) -> u16 {
+        1 << control.clone() as u16
+    }
+    /// a frame with only the control's key pressed, how the cpu opponent plays
+    pub fn press(control: &Controls) -> InputFrame {
+        InputFrame {
+            pressed: InputFrame::bit(control),
+            down: InputFrame::bit(control),
+        }
+    }
+    pub fn pressed(&self, control: &Controls) -> bool {
+        self.pressed & InputFrame::bit(control) != 0
+    }
+    pub fn down(&self, control: &Controls) -> bool {
+        self.down & InputFrame::bit(control) != 0
+    }
+    /// adds a control's key that's down, pressed this frame or still held from an earlier one
+    pub fn hold(&mut self, control: &Controls, pressed: bool) {
+        self.down |= InputFrame::bit(control);
+        if pressed {
+            self.pressed |= InputFrame::bit(control);
+        }
+    }
+    /// the keys still down in a later frame, without pressing them again
+    pub fn held(&self) -> InputFrame {
+        InputFrame {
+            pressed: 0,
+            down: self.down,
+        }
+    }
+    /// adds a later frame's keys, so a key pressed and released between frames isn't lost
+    pub fn merge(&mut self, later: InputFrame) {
+        self.pressed |= later.pressed;
+        self.down = later.down;
+    }
+}
+
+// The unit the auto shift delays are shown and changed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
+pub enum TimingUnit {
+    Milliseconds,
+    Frames, // at 60 fps, how competitive players share their delays
+}
+
+impl TimingUnit {
+    const MILLISECONDS_STEP: u32 = 5;
+
+    /// the delay in the unit, e.g. "300ms" or "18F"
+    pub fn format(&self, delay_ms: u32) -> String {
+        match self {
+            TimingUnit::Milliseconds => format!("{delay_ms}ms"),
+            TimingUnit::Frames => format!("{}F", ms_to_frames(delay_ms)),
+        }
+    }
+
+    /// the delay a step longer or shorter, up to the maximum.
+    /// Frame delays are kept to whole frames
+    pub fn step(&self, delay_ms: u32, longer: bool, max_ms: u32) -> u32 {
+        let delay_ms = match self {
+            TimingUnit::Milliseconds if longer => delay_ms + TimingUnit::MILLISECONDS_STEP,
+            TimingUnit::Milliseconds => delay_ms.saturating_sub(TimingUnit::MILLISECONDS_STEP),
+            TimingUnit::Frames if longer => frames_to_ms(ms_to_frames(delay_ms) + 1),
+            TimingUnit::Frames => frames_to_ms(ms_to_frames(delay_ms).saturating_sub(1)),
+        };
+        delay_ms.min(max_ms)
+    }
+}
+
+impl std::fmt::Display for TimingUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimingUnit::Milliseconds => write!(f, "Milliseconds"),
+            TimingUnit::Frames => write!(f, "Frames"),
+        }
+    }
+}
+
+fn ms_to_frames(delay_ms: u32) -> u32 {
+    (delay_ms as f64 * FRAMES_PER_SECOND / 1000.).round() as u32
+}
+
+fn frames_to_ms(frames: u32) -> u32 {
+    (frames as f64 * 1000. / FRAMES_PER_SECOND).round() as u32
+}
+
+// Controls that work in every game state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumIter)]
+pub enum GlobalControls {
+    VolumeDown,
+    VolumeUp,
+    Mute,
+}
+
+impl GlobalControls {
+    pub fn action_delay(&self) -> Option<f64> {
+        match self {
+            GlobalControls::VolumeDown | GlobalControls::VolumeUp => Some(VOLUME_ACTION_DELAY),
+            GlobalControls::Mute => None,
+        }
+    }
+    pub fn action_repeat_delay(&self) -> Option<f64> {
+        match self {
+            GlobalControls::VolumeDown | GlobalControls::VolumeUp => {
+                Some(VOLUME_ACTION_REPEAT_DELAY)
+            }
+            GlobalControls::Mute => None,
+        }
+    }
+    pub fn default_keys(&self) -> [Option<KeyCode>; 2] {
+        match self {
+            GlobalControls::VolumeDown => VOLUME_DOWN_KEYS,
+            GlobalControls::VolumeUp => VOLUME_UP_KEYS,
+            GlobalControls::Mute => MUTE_KEYS,
+        }
+    }
+}
+
+pub struct GlobalControlStates {
+    pub input_map: HashMap<GlobalControls, [Option<KeyCode>; 2]>,
+    pub input_states: HashMap<GlobalControls, InputState>,
+}
+
+impl Default for GlobalControlStates {
+    fn default() -> Self {
+        Self {
+            input_map: GlobalControls::iter()
+                .map(|control| (control, control.default_keys()))
+                .collect(),
+            input_states: GlobalControls::iter()
+                .map(|control| (control, InputState::default()))
+                .collect(),
+        }
+    }
+}
+
+impl GlobalControlStates {
+    /// updates the input states from the keyboard and returns the controls performed this frame,
+    /// held controls repeat after their action delay like the playing controls
+    pub fn update(&mut self, delta_time: f64) -> Vec<GlobalControls> {
+        let mut performed = vec![];
+        for (control, keys) in &self.input_map {
+            let state = self.input_states.entry(*control).or_default();
+            if keys.iter().flatten().any(|key| is_key_pressed(*key)) {
+                *state = InputState::Down(0.);
+                performed.push(*control);
+                continue;
+            }
+            if !keys.iter().flatten().any(|key| is_key_down(*key)) {
+                *state = InputState::Up;
+                continue;
+            }
+            match state {
+                InputState::Down(down_time) => {
+                    if let Some(action_delay) = control.action_delay() {
+                        *down_time += delta_time;
+                        if *down_time >= action_delay {
+                            *state = InputState::Held(0.);
+                            performed.push(*control);
+                        }
+                    }
+                }
+                InputState::Held(held_time) => {
+                    *held_time += delta_time;
+                    if let Some(action_repeat_delay) = control.action_repeat_delay() {
+                        if *held_time >= action_repeat_delay {
+                            *state = InputState::Held(0.);
+                            performed.push(*control);
+                        }
+                    }
+                }
+                InputState::Up => (),
+            }
+        }
+        performed
+    }
 }
 
 pub struct ControlStates {
     pub input_map: HashMap<Controls, [Option<KeyCode>; 2]>,
-    pub key_map: HashMap<KeyCode, Controls>,
     pub input_states: HashMap<Controls, InputState>,
+    pub hard_drop_lockout: f64, // seconds until hard drop can be used again
 }
 
 impl Default for ControlStates {
@@ -80,54 +365,248 @@ impl Default for ControlStates {
                     .map(|i| (i.clone(), i.default_keys()))
                     .collect()
             },
-            key_map: {
-                LEFT_KEYS
-                    .iter()
-                    .flatten()
-                    .map(|e| (*e, Controls::Left))
-                    .chain(RIGHT_KEYS.iter().flatten().map(|e| (*e, Controls::Right)))
-                    .chain(
-                        ROTATE_CW_KEYS
-                            .iter()
-                            .flatten()
-                            .map(|e| (*e, Controls::RotateCW)),
-                    )
-                    .chain(
-                        ROTATE_CCW_KEYS
-                            .iter()
-                            .flatten()
-                            .map(|e| (*e, Controls::RotateCCW)),
-                    )
-                    .chain(
-                        SOFT_DROP_KEYS
-                            .iter()
-                            .flatten()
-                            .map(|e| (*e, Controls::SoftDrop)),
-                    )
-                    .chain(
-                        HARD_DROP_KEYS
-                            .iter()
-                            .flatten()
-                            .map(|e| (*e, Controls::HardDrop)),
-                    )
-                    .chain(HOLD_KEYS.iter().flatten().map(|e| (*e, Controls::Hold)))
-                    .collect::<HashMap<KeyCode, Controls>>()
-            },
             input_states: {
                 Controls::iter()
                     .map(|e| (e, InputState::default()))
                     .collect::<HashMap<Controls, InputState>>()
             },
+            hard_drop_lockout: 0.,
         }
     }
 }
 
 impl ControlStates {
+    /// the control states of the player, 0 or 1, in a versus match
+    pub fn versus(player: usize) -> Self {
+        Self {
+            input_map: Controls::iter()
+                .map(|control| (control.clone(), control.versus_keys(player)))
+                .collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Replaces the keys of the controls named by RUSTRIS_KEY_ environment variables,
+    /// for scripted testing and accessibility tools.
+    /// A variable with an unknown key name is logged and the control keeps its keys
+    pub fn apply_env_keys(&mut self) {
+        for control in Controls::iter() {
+            let var = format!("{KEYS_ENV_PREFIX}{}", control.name());
+            let Ok(value) = std::env::var(&var) else {
+                continue;
+            };
+            match parse_keys(&value) {
+                Ok(keys) => {
+                    log::info!("{} keys from {}: {:?}", control.name(), var, keys);
+                    self.input_map.insert(control, keys);
+                }
+                Err(e) => log::warn!("ignoring {}={}: {}", var, value, e),
+            }
+        }
+    }
+
+    /// the controls whose keys are pressed or down this frame
+    pub fn read_keys(&self) -> InputFrame {
+        self.keys_frame(is_key_pressed, is_key_down)
+    }
+
+    // a control is down while any of its keys are, it's only released once they're all up
+    fn keys_frame(
+        &self,
+        key_pressed: impl Fn(KeyCode) -> bool,
+        key_down: impl Fn(KeyCode) -> bool,
+    ) -> InputFrame {
+        let mut frame = InputFrame::default();
+        for (control, keys) in &self.input_map {
+            if keys.iter().flatten().any(|key| key_pressed(*key)) {
+                frame.pressed |= InputFrame::bit(control);
+            }
+            if keys.iter().flatten().any(|key| key_down(*key)) {
+                frame.down |= InputFrame::bit(control);
+            }
+        }
+        frame
+    }
+
     pub fn clear_inputs(&mut self) {
         for input in Controls::iter() {
             self.input_states
                 .entry(input.clone())
                 .and_modify(|e| *e = InputState::Up);
         }
+        self.hard_drop_lockout = 0.;
+    }
+
+    /// restarts the auto shift delay of the held left and right controls
+    pub fn reset_auto_shift(&mut self) {
+        for control in [Controls::Left, Controls::Right] {
+            self.input_states.entry(control).and_modify(|state| {
+                if *state != InputState::Up {
+                    *state = InputState::Down(0.)
+                }
+            });
+        }
+    }
+
+    /// true if hard drop can be used, the key has to be released after each hard drop
+    /// so holding or quickly pressing it again doesn't drop the next rustomino
+    pub fn hard_drop_ready(&self) -> bool {
+        self.input_states.get(&Controls::HardDrop) == Some(&InputState::Up)
+            && self.hard_drop_lockout <= 0.
+    }
+}
+
+// one or two comma separated key names, e.g. "LeftShift,C"
+fn parse_keys(value: &str) -> Result<[Option<KeyCode>; 2], String> {
+    let names: Vec<&str> = value.split(',').map(str::trim).collect();
+    if names.len() > 2 {
+        return Err("more than two keys".to_owned());
+    }
+    let mut keys = [None; 2];
+    for (slot, name) in keys.iter_mut().zip(names) {
+        *slot = Some(key_from_name(name).ok_or(format!("unknown key: {name:?}"))?);
+    }
+    Ok(keys)
+}
+
+// the key with the provided name, ignoring case. The names match macroquad's key codes,
+// digits can be written without the Key prefix. Escape is kept for the menus
+fn key_from_name(name: &str) -> Option<KeyCode> {
+    let key = match name.to_ascii_lowercase().as_str() {
+        "a" => KeyCode::A,
+        "b" => KeyCode::B,
+        "c" => KeyCode::C,
+        "d" => KeyCode::D,
+        "e" => KeyCode::E,
+        "f" => KeyCode::F,
+        "g" => KeyCode::G,
+        "h" => KeyCode::H,
+        "i" => KeyCode::I,
+        "j" => KeyCode::J,
+        "k" => KeyCode::K,
+        "l" => KeyCode::L,
+        "m" => KeyCode::M,
+        "n" => KeyCode::N,
+        "o" => KeyCode::O,
+        "p" => KeyCode::P,
+        "q" => KeyCode::Q,
+        "r" => KeyCode::R,
+        "s" => KeyCode::S,
+        "t" => KeyCode::T,
+        "u" => KeyCode::U,
+        "v" => KeyCode::V,
+        "w" => KeyCode::W,
+        "x" => KeyCode::X,
+        "y" => KeyCode::Y,
+        "z" => KeyCode::Z,
+        "0" | "key0" => KeyCode::Key0,
+        "1" | "key1" => KeyCode::Key1,
+        "2" | "key2" => KeyCode::Key2,
+        "3" | "key3" => KeyCode::Key3,
+        "4" | "key4" => KeyCode::Key4,
+        "5" | "key5" => KeyCode::Key5,
+        "6" | "key6" => KeyCode::Key6,
+        "7" | "key7" => KeyCode::Key7,
+        "8" | "key8" => KeyCode::Key8,
+        "9" | "key9" => KeyCode::Key9,
+        "space" => KeyCode::Space,
+        "enter" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "insert" => KeyCode::Insert,
+        "delete" => KeyCode::Delete,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "leftshift" => KeyCode::LeftShift,
+        "rightshift" => KeyCode::RightShift,
+        "leftcontrol" => KeyCode::LeftControl,
+        "rightcontrol" => KeyCode::RightControl,
+        "leftalt" => KeyCode::LeftAlt,
+        "rightalt" => KeyCode::RightAlt,
+        "minus" => KeyCode::Minus,
+        "equal" => KeyCode::Equal,
+        "comma" => KeyCode::Comma,
+        "period" => KeyCode::Period,
+        "slash" => KeyCode::Slash,
+        "semicolon" => KeyCode::Semicolon,
+        "apostrophe" => KeyCode::Apostrophe,
+        "leftbracket" => KeyCode::LeftBracket,
+        "rightbracket" => KeyCode::RightBracket,
+        "backslash" => KeyCode::Backslash,
+        "graveaccent" => KeyCode::GraveAccent,
+        "kp0" => KeyCode::Kp0,
+        "kp1" => KeyCode::Kp1,
+        "kp2" => KeyCode::Kp2,
+        "kp3" => KeyCode::Kp3,
+        "kp4" => KeyCode::Kp4,
+        "kp5" => KeyCode::Kp5,
+        "kp6" => KeyCode::Kp6,
+        "kp7" => KeyCode::Kp7,
+        "kp8" => KeyCode::Kp8,
+        "kp9" => KeyCode::Kp9,
+        "kpadd" => KeyCode::KpAdd,
+        "kpsubtract" => KeyCode::KpSubtract,
+        "kpmultiply" => KeyCode::KpMultiply,
+        "kpdivide" => KeyCode::KpDivide,
+        "kpdecimal" => KeyCode::KpDecimal,
+        "kpenter" => KeyCode::KpEnter,
+        "f1" => KeyCode::F1,
+        "f2" => KeyCode::F2,
+        "f3" => KeyCode::F3,
+        "f4" => KeyCode::F4,
+        "f5" => KeyCode::F5,
+        "f6" => KeyCode::F6,
+        "f7" => KeyCode::F7,
+        "f8" => KeyCode::F8,
+        "f9" => KeyCode::F9,
+        "f10" => KeyCode::F10,
+        "f11" => KeyCode::F11,
+        "f12" => KeyCode::F12,
+        _ => return None,
+    };
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_control_is_released_once_both_its_keys_are_up() {
+        let controls = ControlStates::default();
+        assert_eq!(SOFT_DROP_KEYS, [Some(KeyCode::Down), Some(KeyCode::S)]);
+        let frame = |down: &[KeyCode]| controls.keys_frame(|_| false, |key| down.contains(&key));
+
+        assert!(frame(&[KeyCode::Down, KeyCode::S]).down(&Controls::SoftDrop));
+        // either key still down keeps it held
+        assert!(frame(&[KeyCode::Down]).down(&Controls::SoftDrop));
+        assert!(frame(&[KeyCode::S]).down(&Controls::SoftDrop));
+        assert!(!frame(&[]).down(&Controls::SoftDrop));
+        // another control's key doesn't hold it
+        assert!(!frame(&[KeyCode::Left]).down(&Controls::SoftDrop));
+    }
+
+    #[test]
+    fn only_the_moves_and_soft_drop_repeat() {
+        let repeating: Vec<Controls> = Controls::iter().filter(Controls::repeats).collect();
+        assert_eq!(
+            repeating,
+            [Controls::Left, Controls::Right, Controls::SoftDrop]
+        );
+        assert_eq!(Controls::HardDrop.action_delay(), None);
+        assert_eq!(Controls::HardDrop.action_repeat_delay(), None);
+        assert_eq!(
+            Controls::SoftDrop.action_delay(),
+            Some(SOFT_DROP_ACTION_DELAY)
+        );
+        // swapped drop keys repeat as the control they perform
+        assert!(Controls::HardDrop.with_drops_swapped().repeats());
+        assert!(!Controls::SoftDrop.with_drops_swapped().repeats());
     }
 }
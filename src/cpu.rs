@@ -0,0 +1,335 @@
+use crate::{
+    controls::{Controls, InputFrame},
+    game::RustrisGame,
+    playfield::{RustrisPlayfield, TranslationDirection},
+    rustomino::{Rotation, RotationKicks, Rustomino},
+    settings::Settings,
+};
+use ::rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, fmt::Display};
+use strum::EnumIter;
+
+// weights of the board's features in a placement's score, a well known set for line clearing
+const HEIGHT_WEIGHT: f64 = -0.51; // the sum of the column heights
+const LINES_WEIGHT: f64 = 0.76;
+const HOLES_WEIGHT: f64 = -0.36;
+const BUMPINESS_WEIGHT: f64 = -0.18;
+// the best placements of the active rustomino are scored again with the next one placed after them
+const LOOKAHEAD_PLACEMENTS: usize = 3;
+// a mistake picks one of the placements after the best
+const MISTAKE_PLACEMENTS: usize = 4;
+
+// How quickly and how well the cpu opponent plays
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
+pub enum CpuDifficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl CpuDifficulty {
+    // seconds before the first move of each rustomino
+    fn think_time(&self) -> f64 {
+        match self {
+            CpuDifficulty::Easy => 0.8,
+            CpuDifficulty::Normal => 0.4,
+            CpuDifficulty::Hard => 0.15,
+        }
+    }
+    // seconds between moves
+    fn move_time(&self) -> f64 {
+        match self {
+            CpuDifficulty::Easy => 0.2,
+            CpuDifficulty::Normal => 0.1,
+            CpuDifficulty::Hard => 0.04,
+        }
+    }
+    // the chance a rustomino isn't placed in the best spot
+    fn mistake_rate(&self) -> f64 {
+        match self {
+            CpuDifficulty::Easy => 0.3,
+            CpuDifficulty::Normal => 0.1,
+            CpuDifficulty::Hard => 0.,
+        }
+    }
+}
+
+impl Display for CpuDifficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CpuDifficulty::Easy => write!(f, "Easy"),
+            CpuDifficulty::Normal => write!(f, "Normal"),
+            CpuDifficulty::Hard => write!(f, "Hard"),
+        }
+    }
+}
+
+// A versus opponent that plays with the same controls as a player.
+// It only reads the board and the rustominos the player can see,
+// and tries every placement on a copy of the playfield
+pub struct Cpu {
+    difficulty: CpuDifficulty,
+    plan: Option<VecDeque<Controls>>, // the controls left to press for the active rustomino
+    planned_piece: usize,             // rustominos locked before the plan was made
+    wait: f64,                        // seconds until the next press
+}
+
+impl Cpu {
+    pub fn new(difficulty: CpuDifficulty) -> Self {
+        log::info!("cpu opponent difficulty: {}", difficulty);
+        Cpu {
+            difficulty,
+            plan: None,
+            planned_piece: 0,
+            wait: 0.,
+        }
+    }
+
+    /// the control the cpu presses this frame, a placement is chosen for each new rustomino
+    pub fn update(&mut self, game: &RustrisGame, delta_time: f64) -> InputFrame {
+        if game.playfield.active_rustomino.is_none() {
+            return InputFrame::default();
+        }
+        // a rustomino that locked before its plan finished leaves the rest unused
+        if game.pieces_locked() != self.planned_piece {
+            self.plan = None;
+        }
+        self.wait -= delta_time;
+        let Some(plan) = &mut self.plan else {
+            self.plan = Some(self.choose(game));
+            self.planned_piece = game.pieces_locked();
+            self.wait = self.difficulty.think_time();
+            return InputFrame::default();
+        };
+        if self.wait > 0. {
+            return InputFrame::default();
+        }
+        self.wait = self.difficulty.move_time();
+        plan.pop_front()
+            .map_or_else(InputFrame::default, |control| InputFrame::press(&control))
+    }
+
+    // the controls that move the active rustomino to the chosen placement
+    fn choose(&self, game: &RustrisGame) -> VecDeque<Controls> {
+        let candidates = ranked_placements(
+            &game.playfield,
+            game.next_rustomino.as_ref(),
+            game.settings.rotation_kicks,
+        );
+        let mut rng = ::rand::thread_rng();
+        let choice = if candidates.len() > 1 && rng.gen_bool(self.difficulty.mistake_rate()) {
+            rng.gen_range(1..candidates.len().min(MISTAKE_PLACEMENTS + 1))
+        } else {
+            0
+        };
+        log::debug!(
+            "cpu placement {} of {}: {:?}",
+            choice,
+            candidates.len(),
+            candidates.get(choice).map(|placement| &placement.controls)
+        );
+        candidates
+            .into_iter()
+            .nth(choice)
+            .map_or_else(VecDeque::new, |placement| placement.controls.into())
+    }
+}
+
+/// the player's settings for a game the cpu plays,
+/// it presses the controls by name, not their keys
+pub fn settings(player: &Settings) -> Settings {
+    let mut settings = player.clone();
+    settings.swap_drop_keys = false;
+    settings
+}
+
+/// where the cpu would drop the active rustomino, shown to the player as a placement hint
+pub fn best_placement(
+    playfield: &RustrisPlayfield,
+    next: Option<&Rustomino>,
+    kicks: RotationKicks,
+) -> Option<Rustomino> {
+    ranked_placements(playfield, next, kicks)
+        .into_iter()
+        .next()
+        .map(|placement| placement.landed)
+}
+
+// Where the active rustomino can be dropped and the board it leaves
+struct Placement {
+    controls: Vec<Controls>, // rotations then moves then a hard drop
+    landed: Rustomino,
+    playfield: RustrisPlayfield,
+    lines: usize,
+    score: f64,
+}
+
+// the active rustomino's placements best first,
+// the best few are scored again with the next rustomino placed after them
+fn ranked_placements(
+    playfield: &RustrisPlayfield,
+    next: Option<&Rustomino>,
+    kicks: RotationKicks,
+) -> Vec<Placement> {
+    let mut candidates = placements(playfield, kicks);
+    sort_by_score(&mut candidates);
+    let Some(next) = next else {
+        return candidates;
+    };
+    for placement in candidates.iter_mut().take(LOOKAHEAD_PLACEMENTS) {
+        let mut playfield = placement.playfield.clone();
+        // a next rustomino that can't spawn would top out
+        placement.score = if playfield.set_active(next.clone().reset()) {
+            let next_score = placements(&playfield, kicks)
+                .iter()
+                .map(|next| next.score)
+                .fold(f64::MIN, f64::max);
+            placement.lines as f64 * LINES_WEIGHT + next_score
+        } else {
+            f64::MIN
+        };
+    }
+    let lookahead = candidates.len().min(LOOKAHEAD_PLACEMENTS);
+    sort_by_score(&mut candidates[..lookahead]);
+    candidates
+}
+
+// every rotation of the active rustomino dropped from every column it can move to
+fn placements(playfield: &RustrisPlayfield, kicks: RotationKicks) -> Vec<Placement> {
+    let mut playfield = playfield.clone();
+    playfield.set_hide_ghost(true);
+    let turns: [&[Controls]; 4] = [
+        &[],
+        &[Controls::RotateCW],
+        &[Controls::RotateCW, Controls::RotateCW],
+        &[Controls::RotateCCW],
+    ];
+    let mut placements = vec![];
+    for turn in turns {
+        let mut rotated = playfield.clone();
+        let rotated_all = turn.iter().all(|control| {
+            let rotation = match control {
+                Controls::RotateCCW => Rotation::Ccw,
+                _ => Rotation::Cw,
+            };
+            rotated.rotate_active(rotation, kicks)
+        });
+        if !rotated_all {
+            continue;
+        }
+        placements.extend(place(rotated.clone(), turn.to_vec()));
+        for shift in [Controls::Left, Controls::Right] {
+            let mut moved = rotated.clone();
+            let mut controls = turn.to_vec();
+            loop {
+                let direction = match shift {
+                    Controls::Left => TranslationDirection::Left,
+                    _ => TranslationDirection::Right,
+                };
+                if !moved.translate_active(direction) {
+                    break;
+                }
+                controls.push(shift.clone());
+                placements.extend(place(moved.clone(), controls.clone()));
+            }
+        }
+    }
+    placements
+}
+
+// hard drops and locks the active rustomino and scores the board
+fn place(mut playfield: RustrisPlayfield, mut controls: Vec<Controls>) -> Option<Placement> {
+    playfield.hard_drop_active();
+    let landed = playfield.active_rustomino.clone()?;
+    playfield.lock_active();
+    let lines = playfield.clear_completed_lines().len();
+    controls.push(Controls::HardDrop);
+    let height: usize = playfield.column_heights().iter().sum();
+    let score = height as f64 * HEIGHT_WEIGHT
+        + lines as f64 * LINES_WEIGHT
+        + playfield.hole_count() as f64 * HOLES_WEIGHT
+        + playfield.bumpiness() as f64 * BUMPINESS_WEIGHT;
+    Some(Placement {
+        controls,
+        landed,
+        playfield,
+        lines,
+        score,
+    })
+}
+
+// best first
+fn sort_by_score(placements: &mut [Placement]) {
+    placements.sort_by(|a, b| b.score.total_cmp(&a.score));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rustomino::RustominoType;
+    use macroquad::prelude::{ivec2, IVec2};
+
+    // the board typed as on the debug board with the rustomino in its starting position
+    fn playfield(board: &str, rtype: RustominoType) -> RustrisPlayfield {
+        let mut playfield = RustrisPlayfield::new();
+        playfield.load_ascii(board).unwrap();
+        assert!(playfield.set_active(Rustomino::new(rtype)));
+        playfield
+    }
+
+    fn landed_slots(landed: &Rustomino) -> Vec<IVec2> {
+        let mut slots = landed.playfield_slots().to_vec();
+        slots.sort_by_key(|slot| (slot.x, slot.y));
+        slots
+    }
+
+    #[test]
+    fn an_i_is_stood_up_in_the_well() {
+        let playfield = playfield(
+            "IIII.IIIII/IIII.IIIII/IIII.IIIII/IIII.IIIII",
+            RustominoType::I,
+        );
+        let landed = best_placement(&playfield, None, RotationKicks::Srs).unwrap();
+        assert_eq!(
+            landed_slots(&landed),
+            [ivec2(4, 0), ivec2(4, 1), ivec2(4, 2), ivec2(4, 3)]
+        );
+    }
+
+    #[test]
+    fn a_t_is_turned_over_into_the_notch() {
+        let playfield = playfield("IIII.IIIII/III...IIII", RustominoType::T);
+        let best = &ranked_placements(&playfield, None, RotationKicks::Srs)[0];
+        assert_eq!(
+            best.controls,
+            [Controls::RotateCW, Controls::RotateCW, Controls::HardDrop]
+        );
+        assert_eq!(best.lines, 2);
+        assert_eq!(
+            landed_slots(&best.landed),
+            [ivec2(3, 1), ivec2(4, 0), ivec2(4, 1), ivec2(5, 1)]
+        );
+        let hint = best_placement(&playfield, None, RotationKicks::Srs).unwrap();
+        assert_eq!(landed_slots(&hint), landed_slots(&best.landed));
+    }
+
+    // cargo test --release -- --ignored placement_hint_benchmark
+    #[test]
+    #[ignore]
+    fn placement_hint_benchmark() {
+        const RUNS: u32 = 200;
+        let playfield = playfield(
+            "IIIII.IIII/IIII.IIIII/III..IIIII/.II..III.I/..I...I...",
+            RustominoType::T,
+        );
+        let next = Rustomino::new(RustominoType::S);
+        let start = std::time::Instant::now();
+        for _ in 0..RUNS {
+            best_placement(&playfield, Some(&next), RotationKicks::Srs).unwrap();
+        }
+        let average = start.elapsed() / RUNS;
+        println!("placement hint with a next rustomino: {average:?}");
+        assert!(average < std::time::Duration::from_millis(1));
+    }
+}
@@ -0,0 +1,54 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// mixed into the date so daily seeds don't match seeds typed by hand
+const DAILY_SEED_SALT: u64 = 0x5275_7374_7269_7321; // "Rustris!"
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// today's date in UTC as yyyymmdd, so every player shares the same day
+pub fn today() -> u32 {
+    date(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs()),
+    )
+}
+
+/// the UTC date of a time in seconds since the unix epoch as yyyymmdd
+pub fn date(unix_time: u64) -> u32 {
+    date_from_days((unix_time / SECONDS_PER_DAY) as i64)
+}
+
+/// the rustomino bag's seed for the date's challenge
+pub fn seed(date: u32) -> u64 {
+    u64::from(date) ^ DAILY_SEED_SALT
+}
+
+/// formats a yyyymmdd date as yyyy-mm-dd
+pub fn format_date(date: u32) -> String {
+    format!(
+        "{}-{:02}-{:02}",
+        date / 10_000,
+        date / 100 % 100,
+        date % 100
+    )
+}
+
+// converts days since the unix epoch to a yyyymmdd date in the proleptic gregorian calendar
+fn date_from_days(days: i64) -> u32 {
+    // shifted so eras of 400 years start on March 1st, leap days end each era's years
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153; // 0 is March
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year * 10_000 + month * 100 + day) as u32
+}
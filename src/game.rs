@@ -1,40 +1,84 @@
 use crate::{
-    controls::{self, ControlStates, Controls, InputState},
+    clip::{self, ClipRecorder},
+    controls::{
+        self, ControlStates, Controls, GlobalControlStates, GlobalControls, InputFrame, InputState,
+    },
+    cpu::{self, Cpu, CpuDifficulty},
+    daily,
+    high_scores::{self, GameMode, HighScores},
+    language::{self, Strings},
+    loading,
+    menu::{Menu, MenuItem, ProfileItem, ScrollText, TextInput},
+    modifier::Modifier,
+    online::{self, OnlineMatch},
     playfield::{RustrisPlayfield, TranslationDirection, PLAYFIELD_SIZE},
-    rustomino::{Rotation, Rustomino, RustominoBag, RustominoState},
+    profiles::{self, ProfileStore},
+    replay::{self, Recorder, Replay},
+    rustomino::{PieceWeights, Rotation, Rustomino, RustominoBag, RustominoState, RustominoType},
+    scoring::{self, ClearResult},
+    script::{self, Script},
+    settings::{self, SettingChoices, SettingItem, Settings},
+    sound, storage,
+    summary::{self, GameStats, Summary, SummaryFormat},
     view,
 };
-use macroquad::{
-    audio::{load_sound, play_sound, set_sound_volume, PlaySoundParams, Sound},
-    prelude::*,
+use ::rand::{seq::IteratorRandom, Rng, SeedableRng};
+use macroquad::prelude::*;
+use rand_xoshiro::Xoshiro256PlusPlus;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    f64::consts::E,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
 };
-use std::f64::consts::E;
-use strum::IntoEnumIterator;
+use strum::{EnumIter, IntoEnumIterator};
 
 // GAMEPLAY CONSTANTS
 const GRAVITY_NUMERATOR: f64 = 1.0;
 const GRAVITY_FACTOR: f64 = 0.1; // used to slow or increase gravity factor
 const STARTING_LEVEL: usize = 0;
 const LINES_PER_LEVEL: usize = 10; // number of lines that need to be cleared before level advances
+const SCORE_MILESTONE: usize = 10_000; // points between the score milestones the player is shown
 const LOCKDOWN_MAX_TIME: f64 = 0.5; // how long to wait before locking block
 const LOCKDOWN_MAX_RESETS: u32 = 15; // maximum number of times the lockdown timer can be reset
+const GAME_OVER_ANIMATION_TIME: f64 = 1.0; // seconds for the board to turn grey after a top out
+const HOLD_SWAP_TIME: f64 = 0.15; // seconds the held and incoming rustominos take to swap places
+const LOW_FRAME_RATE: f64 = 20.; // frames per second below which the game can pause itself
+const FRAME_RATE_WINDOW: usize = 60; // frames averaged to detect a low frame rate
+const MENU_IDLE_DEMO_TIME: f64 = 15.; // seconds without a key press before the menu demo starts
+const DEMO_DIFFICULTY: CpuDifficulty = CpuDifficulty::Normal; // plays the demo at a modest speed
 
-// SCORING CONSTANTS
-const SINGLE_LINE_SCORE: usize = 100;
-const DOUBLE_LINE_SCORE: usize = 300;
-const TRIPLE_LINE_SCORE: usize = 500;
-const RUSTRIS_SCORE: usize = 800;
+// number of occupied corners around a T rustomino's center required for a t-spin
+const T_SPIN_CORNERS: usize = 3;
 
 // ASSET CONSTANTS
 const ASSETS_FOLDER: &str = "assets";
-const MUSIC_VOL: f32 = 0.1;
-const MUSIC_VOLUME_CHANGE: f32 = 0.025;
+pub const WINDOW_ICON: &str = "rustris256x256.ico";
+
+const WHATS_NEW_FILE: &str = "whats_new.txt";
+
+// DEBUG CONSTANTS
+const DEBUG_BOARD_MAX_LENGTH: usize = 250; // enough for every row of the playfield
+
+// PERSISTENCE CONSTANTS
+const SAVE_FILE: &str = "savegame.json";
 
 pub enum GameState {
     Menu,
     Playing,
     Paused,
     GameOver,
+    Settings,
+    Practice,
+    WhatsNew,
+    HighScores,
+    Profiles,
+    Versus,     // the match is run by the main loop's Versus
+    Online,     // hosting or joining an online versus match
+    Replays,    // the saved replays, the one being watched is run by the main loop's ReplayViewer
+    DebugBoard, // debug builds only
 }
 
 pub struct RustrisGame {
@@ -42,6 +86,25 @@ pub struct RustrisGame {
     pub next_rustomino: Option<Rustomino>,
     pub held_rustomino: Option<Rustomino>,
     pub state: GameState,
+    pub menu: Menu<MenuItem>,
+    pub settings: Settings,
+    pub settings_menu: Menu<SettingItem>,
+    pub practice_input: TextInput, // opening sequence typed on the practice screen
+    pub whats_new: ScrollText,     // version notes loaded at startup
+    pub high_scores: HighScores,   // the active profile's, saved when a score is added
+    pub profiles: ProfileStore,    // every file the player's games use is found through it
+    pub profile_menu: Menu<ProfileItem>,
+    pub profile_input: Option<TextInput>, // a new or renamed profile's name being typed
+    pub online_input: TextInput,          // the address of the online host to join
+    pub online_lobby: Option<online::Lobby>, // waiting for the online opponent
+    pub initials_input: Option<TextInput>, // typed at game over when the score made the list
+    pub summary_export: Option<SummaryFormat>, // requested at game over, saved by the main loop
+    pub summary_message: Option<String>,  // where the last summary was saved or why it wasn't
+    pub clip_export: bool, // requested at game over, saved by the main loop's clip recorder
+    pub strings: Strings,  // the language setting's text, updated by the main loop
+    pub debug_board_input: TextInput, // board typed on the debug board screen
+    pub replays: Vec<replay::ReplayEntry>, // listed when the replays screen is opened
+    pub replay_menu: Menu<usize>, // the index of each replay in the list
     pub level: usize,
     pub score: usize,
     rustomino_bag: RustominoBag,
@@ -49,35 +112,299 @@ pub struct RustrisGame {
     total_lines_cleared: usize,
     hold_used: bool, // if user has held a rustomino, resets on lock
     lockdown_resets: u32,
+    combo: Option<usize>, // number of consecutive line clears, None if the last lock didn't clear
+    back_to_back: bool,   // if the last line clear was a rustris or t-spin
+    last_move_rotation: bool, // if the last successful move was a rotation, used for t-spins
+    practice: bool,       // if the game was started from the practice screen
+    level_time: f64,      // time played at the current level, used by the gravity ramp
+    level_pieces: usize,  // rustominos locked at the current level, used by the gravity ramp
+    line_clear: Option<LineClear>, // completed lines waiting to collapse
+    events: Vec<GameEvent>, // events for the view's effects and sounds, taken every frame
+    play_time: f64,       // time spent playing this game, excluding pauses
+    mystery: bool,        // if a random modifier is applied at each level
+    daily: Option<u32>,   // the date of the daily challenge being played, as yyyymmdd
+    modifier: Option<Modifier>, // the mystery modifier for the current level
+    sandbox: bool,        // no gravity or lockdown, pieces lock when the player locks them
+    versus: bool,         // one of the players in a versus match, never saved
+    versus_cpu: bool,     // the versus match chosen from the menu is against the cpu
+    demo: bool,           // played by the cpu behind the idle menu, never saved or recorded
+    scripted: bool,       // played by an input script or a replay, never saved or recorded
+    exit_requested: bool, // the player chose to exit from a menu
+    game_over_time: f64,  // time since the game ended, drives the game over animation
+    game_over_rule: Option<GameOverRule>, // the rule that ended the game
+    hold_swap: Option<HoldSwap>, // the rustominos moving after a hold
+    buffered_controls: Vec<Controls>, // controls used during the hold swap, performed after it
+    frame_rate: FrameRate, // recent frame times while playing
+    low_frame_rate_pause: bool, // the game paused itself because of a low frame rate
+    ghost_hidden: bool,   // the player hid the ghost with the toggle key during this game
+    stats: GameStats,     // pieces and clears counted for the session summary
+    pending_garbage: VecDeque<Garbage>, // versus garbage received, added at the next lock without a clear
+    placement_hint: Option<Rustomino>, // where the active rustomino is best dropped, when hints are shown
+    sent_garbage: usize, // versus garbage lines to send, taken by the match every frame
 }
 
 impl RustrisGame {
-    fn new(playfield: RustrisPlayfield) -> Self {
+    fn new(playfield: RustrisPlayfield, settings: Settings, profiles: ProfileStore) -> Self {
         RustrisGame {
             playfield,
             next_rustomino: None,
             held_rustomino: None,
             state: GameState::Menu, // Start the game at the menu screen
+            menu: main_menu(&profiles),
+            rustomino_bag: RustominoBag::new(settings.piece_weights),
+            settings,
+            settings_menu: Menu::new(SettingItem::iter().collect()),
+            practice_input: TextInput::default(),
+            whats_new: ScrollText::default(),
+            high_scores: HighScores::default(),
+            profile_menu: profile_menu(&profiles),
+            profiles,
+            profile_input: None,
+            online_input: TextInput::with_max_length(online::ADDRESS_LENGTH),
+            online_lobby: None,
+            initials_input: None,
+            summary_export: None,
+            summary_message: None,
+            clip_export: false,
+            strings: Strings::default(),
+            debug_board_input: TextInput::default(),
+            replays: vec![],
+            replay_menu: Menu::new(vec![]),
             level: STARTING_LEVEL,
             score: 0,
-            rustomino_bag: RustominoBag::new(),
             gravity_delay: gravity_delay(0),
             total_lines_cleared: 0,
             hold_used: false,
             lockdown_resets: 0,
+            combo: None,
+            back_to_back: false,
+            last_move_rotation: false,
+            practice: false,
+            level_time: 0.,
+            level_pieces: 0,
+            line_clear: None,
+            events: vec![],
+            play_time: 0.,
+            mystery: false,
+            daily: None,
+            modifier: None,
+            sandbox: false,
+            versus: false,
+            versus_cpu: false,
+            demo: false,
+            scripted: false,
+            exit_requested: false,
+            game_over_time: 0.,
+            game_over_rule: None,
+            hold_swap: None,
+            buffered_controls: vec![],
+            frame_rate: FrameRate::default(),
+            low_frame_rate_pause: false,
+            ghost_hidden: false,
+            stats: GameStats::default(),
+            pending_garbage: VecDeque::new(),
+            placement_hint: None,
+            sent_garbage: 0,
+        }
+    }
+
+    /// Captures the state needed to continue this game later
+    pub fn snapshot(&self) -> GameSnapshot {
+        // lines waiting to collapse have already been scored
+        let mut playfield = self.playfield.clone();
+        if self.line_clear.is_some() {
+            playfield.clear_completed_lines();
+        }
+        GameSnapshot {
+            playfield,
+            next_rustomino: self.next_rustomino.clone(),
+            held_rustomino: self.held_rustomino.clone(),
+            level: self.level,
+            score: self.score,
+            rustomino_bag: self.rustomino_bag.clone(),
+            total_lines_cleared: self.total_lines_cleared,
+            hold_used: self.hold_used,
+            lockdown_resets: self.lockdown_resets,
+            combo: self.combo,
+            back_to_back: self.back_to_back,
+            practice: self.practice,
+            level_time: self.level_time,
+            level_pieces: self.level_pieces,
+            play_time: self.play_time,
+            mystery: self.mystery,
+            daily: self.daily,
+            modifier: self.modifier,
+            sandbox: self.sandbox,
+            stats: self.stats.clone(),
+        }
+    }
+
+    /// Recreates a game from a snapshot, the game is ready to play
+    pub fn restore(snapshot: GameSnapshot, settings: Settings, profiles: ProfileStore) -> Self {
+        let mut game = RustrisGame {
+            next_rustomino: snapshot.next_rustomino,
+            held_rustomino: snapshot.held_rustomino,
+            state: GameState::Playing,
+            level: snapshot.level,
+            score: snapshot.score,
+            rustomino_bag: snapshot.rustomino_bag,
+            gravity_delay: gravity_delay(snapshot.level),
+            total_lines_cleared: snapshot.total_lines_cleared,
+            hold_used: snapshot.hold_used,
+            lockdown_resets: snapshot.lockdown_resets,
+            combo: snapshot.combo,
+            back_to_back: snapshot.back_to_back,
+            practice: snapshot.practice,
+            level_time: snapshot.level_time,
+            level_pieces: snapshot.level_pieces,
+            play_time: snapshot.play_time,
+            mystery: snapshot.mystery,
+            daily: snapshot.daily,
+            modifier: snapshot.modifier,
+            sandbox: snapshot.sandbox,
+            stats: snapshot.stats,
+            ..RustrisGame::new(snapshot.playfield, settings, profiles)
+        };
+        game.apply_ghost_style();
+        game
+    }
+
+    /// true while the hold action can't be used,
+    /// until the active rustomino is locked
+    pub fn hold_used(&self) -> bool {
+        self.hold_used
+    }
+
+    /// true if the player can cycle the active rustomino's type,
+    /// lines cleared while it's enabled aren't scored
+    pub fn piece_cycling(&self) -> bool {
+        self.practice && self.settings.piece_cycling
+    }
+
+    /// true if the best placement is outlined, never in games with high scores or versus matches
+    pub fn placement_hints(&self) -> bool {
+        (self.practice || self.sandbox) && self.settings.placement_hints
+    }
+
+    pub fn placement_hint(&self) -> Option<&Rustomino> {
+        self.placement_hint.as_ref()
+    }
+
+    // finds the best placement for a new or changed active rustomino
+    fn update_placement_hint(&mut self) {
+        if !self.placement_hints() {
+            self.placement_hint = None;
+            return;
+        }
+        let start = std::time::Instant::now();
+        self.placement_hint = cpu::best_placement(
+            &self.playfield,
+            self.next_rustomino.as_ref(),
+            self.settings.rotation_kicks,
+        );
+        log::debug!("placement hint found in {:?}", start.elapsed());
+    }
+
+    /// time spent playing this game in seconds, paused time isn't counted
+    pub fn play_time(&self) -> f64 {
+        self.play_time
+    }
+
+    /// time spent playing the current level in seconds
+    pub fn level_time(&self) -> f64 {
+        self.level_time
+    }
+
+    /// true if the game has no gravity and isn't scored
+    pub fn sandbox(&self) -> bool {
+        self.sandbox
+    }
+
+    /// the mode the game's score is recorded under, None if it isn't recorded
+    pub fn mode(&self) -> Option<GameMode> {
+        if self.practice || self.sandbox || self.versus || self.demo || self.scripted {
+            None
+        } else if let Some(date) = self.daily {
+            Some(GameMode::Daily(date))
+        } else if self.mystery {
+            Some(GameMode::Mystery)
+        } else {
+            Some(GameMode::Marathon)
+        }
+    }
+
+    /// the mystery modifier active for the current level
+    pub fn modifier(&self) -> Option<Modifier> {
+        self.modifier
+    }
+
+    /// takes the events that happened since the last call, used for effects
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// the completed lines being animated before they collapse
+    pub fn line_clear(&self) -> Option<&LineClear> {
+        self.line_clear.as_ref()
+    }
+
+    // advances the line clear animation, collapsing the lines once it's done
+    // returns true while the animation is running
+    // gravity, spawning and inputs wait for it to finish
+    fn update_line_clear(&mut self, delta_time: f64) -> bool {
+        let Some(line_clear) = self.line_clear.as_mut() else {
+            return false;
+        };
+        line_clear.time += delta_time;
+        if line_clear.time < line_clear.delay {
+            return true;
+        }
+        self.playfield.clear_completed_lines();
+        self.line_clear = None;
+        false
+    }
+
+    /// the held and incoming rustominos moving between the hold box and the playfield
+    pub fn hold_swap(&self) -> Option<&HoldSwap> {
+        self.hold_swap.as_ref()
+    }
+
+    // advances the hold swap animation, performing the buffered controls once it's done
+    // returns true while the animation is running, gravity waits for it to finish
+    fn update_hold_swap(&mut self, delta_time: f64) -> bool {
+        let Some(hold_swap) = self.hold_swap.as_mut() else {
+            return false;
+        };
+        hold_swap.time += delta_time;
+        if hold_swap.time < HOLD_SWAP_TIME {
+            return true;
         }
+        self.hold_swap = None;
+        for control in std::mem::take(&mut self.buffered_controls) {
+            control_handler(&control, self)();
+        }
+        false
     }
 
     fn playing_update(&mut self, delta_time: f64) {
+        self.level_time += delta_time;
+        // sandbox rustominos only move and lock when the player acts
+        if self.sandbox {
+            return;
+        }
+        self.update_gravity_delay();
         let Some(current_state) = self.playfield.get_active_state() else {
             return;
         };
         match current_state {
-            RustominoState::Falling { time } if time + delta_time >= self.gravity_delay => {
+            RustominoState::Falling { time }
+                if time + delta_time >= self.modified_gravity_delay() =>
+            {
                 // check to see if the block can still fall
                 if self.playfield.active_can_fall() {
                     // apply gravity if it can
                     self.playfield.apply_gravity();
+                    self.last_move_rotation = false;
                     // reset the accumulated time
                     self.playfield
                         .set_active_state(RustominoState::Falling { time: 0. });
@@ -93,6 +420,7 @@ impl RustrisGame {
 
                     self.playfield
                         .set_active_state(RustominoState::Lockdown { time: 0. });
+                    self.events.push(GameEvent::Land(self.active_slots()));
                 }
             }
             RustominoState::Falling { time } => {
@@ -100,6 +428,18 @@ impl RustrisGame {
                     time: time + delta_time,
                 });
             }
+            // with classic lock the rustomino stays landed wherever it's moved
+            // and locks on the next gravity tick
+            RustominoState::Lockdown { time } if self.settings.classic_lock => {
+                if time + delta_time >= self.modified_gravity_delay() {
+                    log::info!("classic lock on gravity tick");
+                    self.lock();
+                } else {
+                    self.playfield.set_active_state(RustominoState::Lockdown {
+                        time: time + delta_time,
+                    });
+                }
+            }
             RustominoState::Lockdown { time: _ }
                 if self.lockdown_resets >= LOCKDOWN_MAX_RESETS
                     && !self.playfield.active_can_fall() =>
@@ -126,10 +466,45 @@ impl RustrisGame {
         }
     }
 
+    // applies the gravity ramp, moving the gravity delay from this level's
+    // toward the next level's as the player progresses through the level
+    fn update_gravity_delay(&mut self) {
+        let progress = match self.settings.gravity_ramp {
+            GravityRamp::Stepped => return,
+            GravityRamp::Time => self.level_time / self.settings.gravity_ramp_length as f64,
+            GravityRamp::Pieces => {
+                self.level_pieces as f64 / self.settings.gravity_ramp_length as f64
+            }
+        };
+        let level_delay = gravity_delay(self.level);
+        let next_level_delay = gravity_delay(self.level + 1);
+        self.gravity_delay =
+            level_delay - (level_delay - next_level_delay) * progress.clamp(0., 1.);
+    }
+
+    // the gravity delay with the mystery modifier applied
+    fn modified_gravity_delay(&self) -> f64 {
+        self.gravity_delay
+            * self
+                .modifier
+                .map_or(1., |modifier| modifier.gravity_factor())
+    }
+
+    // the daily challenge ignores the setting, rotating would use the bag's random numbers
+    // and deal a different sequence than other players get
+    fn random_orientation(&self) -> bool {
+        self.settings.random_orientation && self.daily.is_none()
+    }
+
     fn ensure_next_rustomino(&mut self) {
         // make sure next_rustomino is available
         if self.next_rustomino.is_none() {
-            self.next_rustomino = Some(self.rustomino_bag.get_next_rustomino());
+            let mut rustomino = self.rustomino_bag.get_next_rustomino();
+            // the preview shows the orientation it will spawn in
+            if self.random_orientation() {
+                rustomino = self.rustomino_bag.randomly_rotated(rustomino);
+            }
+            self.next_rustomino = Some(rustomino);
         }
     }
 
@@ -144,16 +519,45 @@ impl RustrisGame {
             // this makes sure next_rustomino is set
             self.ensure_next_rustomino();
             // add the next rustomino to the playfield
-            if !self.playfield.set_active(active_rustomino) {
+            if !self.spawn(active_rustomino) {
                 // game over if it can't be placed without a collision
-                self.game_over();
+                self.game_over(GameOverRule::BlockOut);
             }
         }
     }
 
+    // adds a rustomino to the playfield in its starting position
+    // and resets the state tracked for the previous active rustomino
+    // so spawned and held rustominos behave the same,
+    // returns false if it blocked out by colliding with a locked block.
+    // With the spawn nudge it's moved up into the buffer rows first if that avoids the collision,
+    // with block out turned off a colliding rustomino replaces the locked blocks it overlaps
+    fn spawn(&mut self, rustomino: Rustomino) -> bool {
+        self.lockdown_resets = 0;
+        self.last_move_rotation = false;
+        self.events.push(GameEvent::Spawn);
+        // a reset rustomino is falling with no accumulated gravity time,
+        // a random orientation is kept unless it collides where the usual one doesn't
+        let mut rustomino = match rustomino.clone().reset_keeping_rotation() {
+            rotated if self.random_orientation() && self.playfield.fits(&rotated) => rotated,
+            _ => rustomino.reset(),
+        };
+        if self.settings.spawn_nudge {
+            rustomino = self.playfield.nudge_up(rustomino);
+        }
+        let spawned = self.playfield.set_active(rustomino);
+        if !spawned && !self.settings.block_out {
+            log::info!("rustomino spawned over locked blocks, block out is off");
+        }
+        self.update_placement_hint();
+        spawned || !self.settings.block_out
+    }
+
     fn translate(&mut self, direction: TranslationDirection) {
         log::debug!("translate called, direction: {:?}", direction);
         if self.playfield.translate_active(direction) {
+            self.last_move_rotation = false;
+            self.events.push(GameEvent::Move(self.active_slots()));
             self.increment_lockdown_resets();
         }
         log::trace!("playfield:\n{}", self.playfield);
@@ -161,7 +565,12 @@ impl RustrisGame {
 
     fn rotate(&mut self, rotation: Rotation) {
         log::debug!("rotate called, direction: {:?}", rotation);
-        if self.playfield.rotate_active(rotation) {
+        if self
+            .playfield
+            .rotate_active(rotation, self.settings.rotation_kicks)
+        {
+            self.last_move_rotation = true;
+            self.events.push(GameEvent::Rotate(self.active_slots()));
             self.increment_lockdown_resets();
         }
         log::trace!("playfield:\n{}", self.playfield);
@@ -170,7 +579,9 @@ impl RustrisGame {
     // performs a soft drop
     fn soft_drop(&mut self) {
         log::debug!("soft drop called");
-        if !self.playfield.translate_active(TranslationDirection::Down) {
+        if self.playfield.translate_active(TranslationDirection::Down) {
+            self.last_move_rotation = false;
+        } else if !self.sandbox && self.settings.soft_drop_lock {
             log::info!("soft drop called when block is on stack");
             self.lock();
         }
@@ -178,12 +589,53 @@ impl RustrisGame {
     }
 
     fn hard_drop(&mut self) {
-        self.playfield.hard_drop_active();
+        let rtype = self.playfield.active_rustomino.as_ref().map(|r| r.rtype);
+        let distance = self.playfield.hard_drop_active();
+        if let (Some(rtype), Some(active)) = (rtype, &self.playfield.active_rustomino) {
+            self.events.push(GameEvent::HardDrop(HardDropped {
+                rtype,
+                landed: active.playfield_slots(),
+                distance: -distance.y,
+            }));
+        }
+        if distance != IVec2::ZERO {
+            self.last_move_rotation = false;
+        }
         log::info!("hard drop");
         self.lock();
         log::trace!("playfield:\n{}", self.playfield);
     }
 
+    // Locks the active rustomino where it is, sandbox games only
+    fn lock_piece(&mut self) {
+        if !self.sandbox {
+            return;
+        }
+        log::info!("locking active rustomino in place");
+        self.lock();
+        log::trace!("playfield:\n{}", self.playfield);
+    }
+
+    // Changes the active rustomino to the next type in place,
+    // the type is left unchanged if the next type would collide
+    fn cycle_piece(&mut self) {
+        if !self.piece_cycling() {
+            return;
+        }
+        let Some(rtype) = self.playfield.active_rustomino.as_ref().map(|r| r.rtype) else {
+            return;
+        };
+        let rtypes: Vec<RustominoType> = RustominoType::iter().collect();
+        let index = rtypes.iter().position(|t| *t == rtype).unwrap_or(0);
+        let next_rtype = rtypes[(index + 1) % rtypes.len()];
+        if self.playfield.change_active_type(next_rtype) {
+            log::debug!("cycled active rustomino to {:?}", next_rtype);
+            self.last_move_rotation = false;
+            self.update_placement_hint();
+        }
+        log::trace!("playfield:\n{}", self.playfield);
+    }
+
     // Hold action. Hold a rustomino for later use.
     // If a rustomino has not yet been held, the active rustomino is held,
     // and the next rustomino is added to the playfield
@@ -197,6 +649,23 @@ impl RustrisGame {
             return;
         }
 
+        // nothing can be held while lines are clearing
+        if self.line_clear.is_some() {
+            return;
+        }
+
+        // where the incoming rustomino and the outgoing one's blocks are drawn from
+        let source = if self.held_rustomino.is_some() {
+            HoldSource::Hold
+        } else {
+            HoldSource::Next
+        };
+        let outgoing = self
+            .playfield
+            .active_rustomino
+            .as_ref()
+            .map(|rustomino| rustomino.playfield_slots());
+
         // check to see if there is a held rustomino
         let rustomino = if self.held_rustomino.is_some() {
             // take the held_rustomino
@@ -217,40 +686,255 @@ impl RustrisGame {
         // trigger game over in the unusual circumstance
         // a collision with a locked block occurs
         // when the hold piece is added to the board
-        if !self.playfield.set_active(rustomino.reset()) {
+        if !self.spawn(rustomino) {
             log::info!("couldn't add held piece to board, collided with lock block");
-            self.game_over();
+            self.game_over(GameOverRule::BlockOut);
+        } else if let (Some(outgoing), false) = (outgoing, self.settings.reduced_motion) {
+            // with reduced motion the rustominos swap instantly
+            self.hold_swap = Some(HoldSwap {
+                source,
+                outgoing,
+                time: 0.,
+            });
         }
 
         // prevent the player from taking the hold action again
         // until the next rustomino is locked
         self.hold_used = true;
+        self.events.push(GameEvent::Hold);
     }
 
     fn pause(&mut self) {
         log::info!("game paused");
+        self.menu = pause_menu();
         self.state = GameState::Paused;
     }
 
+    /// true if the game paused itself because the frame rate was too low to play fairly
+    pub fn low_frame_rate_pause(&self) -> bool {
+        self.low_frame_rate_pause
+    }
+
+    // opens the debug board screen over the game, debug builds only
+    fn open_debug_board(&mut self) {
+        log::info!("debug board opened");
+        self.debug_board_input = TextInput::with_max_length(DEBUG_BOARD_MAX_LENGTH);
+        self.state = GameState::DebugBoard;
+    }
+
+    // replaces the locked blocks with garbage up to the typed height
+    // or the typed board, see RustrisPlayfield::load_ascii
+    // the active rustomino is dealt again on the new board
+    fn load_debug_board(&mut self, command: &str) -> Result<(), String> {
+        if self.line_clear.take().is_some() {
+            self.playfield.clear_completed_lines();
+        }
+        let active_rustomino = self.playfield.take_active();
+        let result = match command.trim().parse::<usize>() {
+            Ok(height) => {
+                self.playfield.fill_garbage(height);
+                Ok(())
+            }
+            Err(_) => self.playfield.load_ascii(command),
+        };
+        if let Some(rustomino) = active_rustomino {
+            if !self.spawn(rustomino) {
+                self.game_over(GameOverRule::BlockOut);
+            }
+        }
+        result
+    }
+
+    // starts a game played by the input script from its seed and board
+    // with the default settings, the player's are loaded again when it finishes
+    fn start_script(&mut self, script: &Script) -> Result<(), String> {
+        let seed = script.seed().unwrap_or_else(::rand::random);
+        log::info!("starting scripted game {:?} seed: {}", script.path, seed);
+        self.settings = Settings::for_script();
+        self.rustomino_bag = RustominoBag::with_seed(seed, self.settings.piece_weights);
+        self.scripted = true;
+        if let Some(board) = script.board() {
+            self.playfield.load_ascii(board)?;
+        }
+        self.resume();
+        Ok(())
+    }
+
+    // the game's state when its script finished, printed so runs can be compared
+    fn script_report(&self, script: &Script) -> String {
+        let state = match (&self.state, self.game_over_rule) {
+            (GameState::GameOver, Some(rule)) => format!("Game Over ({rule})"),
+            _ => "Playing".to_owned(),
+        };
+        [
+            format!("script: {:?}", script.path),
+            format!("frames: {}", script.frame()),
+            format!("seed: {}", self.rustomino_bag.seed()),
+            format!("state: {state}"),
+            format!("score: {}", self.score),
+            format!("level: {}", self.level),
+            format!("lines: {}", self.total_lines_cleared),
+            format!("pieces: {}", self.stats.pieces),
+            self.playfield.to_string(),
+        ]
+        .join("\n")
+    }
+
+    // tracks the frame rate while playing, pausing the game
+    // if it stays below the low frame rate and the setting is on
+    // returns true if the game was paused
+    fn update_frame_rate(&mut self, delta_time: f64) -> bool {
+        self.frame_rate.update(delta_time);
+        if !self.settings.low_frame_rate_pause {
+            return false;
+        }
+        let Some(fps) = self
+            .frame_rate
+            .average()
+            .filter(|fps| *fps < LOW_FRAME_RATE)
+        else {
+            return false;
+        };
+        log::warn!("pausing, low frame rate: {:.1} fps", fps);
+        self.pause();
+        self.low_frame_rate_pause = true;
+        true
+    }
+
     fn resume(&mut self) {
         log::info!("game resumed");
+        // the frame rate is measured again from the next frame
+        self.frame_rate = FrameRate::default();
+        self.low_frame_rate_pause = false;
+        self.apply_ghost_style();
+        // the hints setting may have been changed while the game was paused
+        self.update_placement_hint();
         self.state = GameState::Playing;
     }
 
-    fn game_over(&mut self) {
-        log::info!("Game Over! Score: {}", self.score);
+    // the playfield doesn't track the ghost while it's turned off or hidden
+    fn apply_ghost_style(&mut self) {
+        self.playfield.set_hide_ghost(
+            self.ghost_hidden || self.settings.ghost_style == view::GhostStyle::Off,
+        );
+    }
+
+    // hides or shows the ghost without changing the ghost setting,
+    // the ghost's cells are removed or placed right away
+    fn toggle_ghost(&mut self) {
+        self.ghost_hidden = !self.ghost_hidden;
+        log::info!("ghost hidden: {}", self.ghost_hidden);
+        self.apply_ghost_style();
+    }
+
+    fn game_over(&mut self, rule: GameOverRule) {
+        log::info!("Game Over! {} Score: {}", rule, self.score);
+        self.events.push(GameEvent::GameOver);
+        self.game_over_time = 0.;
+        self.game_over_rule = Some(rule);
         self.state = GameState::GameOver;
+        if self.demo || self.scripted {
+            return;
+        }
+        // the player is asked for their initials if the score made the list
+        if let Some(mode) = self.mode() {
+            if self.high_scores.qualifies(mode, self.score) {
+                self.initials_input =
+                    Some(TextInput::with_max_length(high_scores::INITIALS_LENGTH));
+            }
+        }
+    }
+
+    /// the game's results for the session summary
+    pub fn summary(&self) -> Summary {
+        let mode = match self.mode() {
+            Some(mode) => mode.to_string(),
+            None if self.sandbox => MenuItem::Sandbox.to_string(),
+            None if self.versus => MenuItem::Versus.to_string(),
+            None => MenuItem::Practice.to_string(),
+        };
+        Summary {
+            mode,
+            score: self.score,
+            level: self.level,
+            lines: self.total_lines_cleared,
+            play_time: self.play_time,
+            stats: self.stats.clone(),
+            seed: self.rustomino_bag.seed(),
+            playfield: self.playfield.clone(),
+        }
+    }
+
+    // records the score under the typed initials
+    fn record_high_score(&mut self) {
+        let (Some(mode), Some(input)) = (self.mode(), self.initials_input.take()) else {
+            return;
+        };
+        self.high_scores.insert(
+            mode,
+            &input.text,
+            self.score,
+            self.total_lines_cleared,
+            self.level,
+        );
+        self.high_scores.save(&self.profiles);
+    }
+
+    /// how far the game over animation has greyed out the board, from 0 to 1
+    /// reduced motion skips straight to the end
+    pub fn game_over_progress(&self) -> f32 {
+        if self.settings.reduced_motion {
+            return 1.;
+        }
+        (self.game_over_time / GAME_OVER_ANIMATION_TIME).clamp(0., 1.) as f32
+    }
+
+    /// the rule that ended the game, None while it's being played
+    pub fn game_over_rule(&self) -> Option<GameOverRule> {
+        self.game_over_rule
+    }
+
+    // skips the rest of the game over animation
+    fn finish_game_over_animation(&mut self) {
+        self.game_over_time = GAME_OVER_ANIMATION_TIME;
     }
 
     fn new_game(self) -> Self {
-        RustrisGame::new(RustrisPlayfield::new())
+        RustrisGame {
+            whats_new: self.whats_new,
+            high_scores: self.high_scores,
+            ..RustrisGame::new(RustrisPlayfield::new(), self.settings, self.profiles)
+        }
+    }
+
+    // plays as the profile, or the guest for None, with its own settings and high scores,
+    // returning to the main menu
+    fn switch_profile(&mut self, name: Option<&str>) {
+        let mut profiles = std::mem::take(&mut self.profiles);
+        profiles.select(name);
+        *self = RustrisGame {
+            whats_new: std::mem::take(&mut self.whats_new),
+            high_scores: HighScores::load(&profiles),
+            ..RustrisGame::new(RustrisPlayfield::new(), Settings::load(&profiles), profiles)
+        };
     }
 
     fn increase_game_level(&mut self) {
         self.level += 1;
         log::info!("increasing game level to {}", self.level);
+        self.events.push(GameEvent::LevelUp(self.level));
         // get the gravity tick delay for the next level
         self.gravity_delay = gravity_delay(self.level);
+        log::info!("new gravity_delay {}", self.gravity_delay);
+        // the gravity ramp starts again from this level's delay
+        self.level_time = 0.;
+        self.level_pieces = 0;
+        // the new modifier replaces the last one, chosen by the bag so replays get the same one
+        if self.mystery {
+            let modifier = Modifier::random(self.modifier, self.rustomino_bag.rng());
+            log::info!("mystery modifier for level {}: {}", self.level, modifier);
+            self.modifier = Some(modifier);
+        }
     }
 
     fn lock(&mut self) {
@@ -266,23 +950,113 @@ impl RustrisGame {
         );
 
         // if the block we've been asked to lock is fully
-        // out of bounds the game is over
+        // out of bounds the game is over, unless lock out is turned off.
+        // Then it's locked in the hidden buffer rows, where the next rustominos collide with it
         if fully_out_of_bounds(&rustomino.playfield_slots()) {
             log::debug!("block we are locking is fully out of playfield");
-            self.game_over();
-            return;
+            if self.settings.lock_out {
+                self.game_over(GameOverRule::LockOut);
+                return;
+            }
         }
 
+        // t-spins are detected before the rustomino is locked
+        let t_spin = rustomino.rtype == RustominoType::T
+            && self.last_move_rotation
+            && self.playfield.active_corners_occupied() >= T_SPIN_CORNERS;
+
+        let slots = rustomino.playfield_slots();
         self.hold_used = false;
+        self.placement_hint = None;
         self.playfield.lock_active();
+        self.events.push(GameEvent::Lock(slots));
+        self.level_pieces += 1;
+        self.stats.pieces += 1;
+        log::debug!(
+            "stack: heights {:?} holes: {} bumpiness: {}",
+            self.playfield.column_heights(),
+            self.playfield.hole_count(),
+            self.playfield.bumpiness()
+        );
 
         self.lockdown_resets = 0;
-        self.handle_completed_lines();
+        self.last_move_rotation = false;
+        let result = self.handle_completed_lines(t_spin, slots);
+        if self.versus {
+            self.exchange_garbage(result);
+        }
+    }
+
+    // a versus clear cancels the pending garbage line for line and sends the rest,
+    // a lock that doesn't clear lines receives the pending garbage
+    fn exchange_garbage(&mut self, result: Option<ClearResult>) {
+        let cleared = result.is_some_and(|result| result.lines > 0);
+        if !cleared {
+            while let Some(garbage) = self.pending_garbage.pop_front() {
+                if !self
+                    .playfield
+                    .insert_garbage(garbage.lines, garbage.hole, garbage.rtype)
+                {
+                    self.pending_garbage.clear();
+                    self.game_over(GameOverRule::Garbage);
+                    return;
+                }
+            }
+            return;
+        }
+        let mut attack = result.map_or(0, |result| result.garbage_lines());
+        while attack > 0 {
+            let Some(garbage) = self.pending_garbage.front_mut() else {
+                break;
+            };
+            let cancelled = attack.min(garbage.lines);
+            garbage.lines -= cancelled;
+            attack -= cancelled;
+            if garbage.lines == 0 {
+                self.pending_garbage.pop_front();
+            }
+        }
+        log::debug!(
+            "garbage sent: {} pending: {}",
+            attack,
+            self.pending_garbage()
+        );
+        self.sent_garbage += attack;
+    }
+
+    /// the lines of each versus attack waiting to be added, the oldest first
+    pub fn pending_attacks(&self) -> impl Iterator<Item = usize> + '_ {
+        self.pending_garbage.iter().map(|garbage| garbage.lines)
+    }
+
+    /// the lines of versus garbage waiting to be added to the playfield
+    pub fn pending_garbage(&self) -> usize {
+        self.pending_garbage
+            .iter()
+            .map(|garbage| garbage.lines)
+            .sum()
+    }
+
+    /// the rustominos locked this game
+    pub fn pieces_locked(&self) -> usize {
+        self.stats.pieces
+    }
+
+    /// the active rustomino's playfield slots, for the events about it
+    fn active_slots(&self) -> [IVec2; 4] {
+        self.playfield
+            .active_rustomino
+            .as_ref()
+            .map_or([IVec2::ZERO; 4], |rustomino| rustomino.playfield_slots())
     }
 
     // increment the number of lockdown resets
     // and reset the lockdown time to 0
     fn increment_lockdown_resets(&mut self) {
+        // classic lock has no lockdown resets
+        if self.settings.classic_lock {
+            return;
+        }
         let Some(active_state) = self.playfield.get_active_state() else {
             return;
         };
@@ -323,24 +1097,91 @@ impl RustrisGame {
         }
     }
 
-    fn handle_completed_lines(&mut self) {
-        let cleared_lines = self.playfield.clear_completed_lines();
-        if cleared_lines.is_empty() {
-            return;
+    fn handle_completed_lines(&mut self, t_spin: bool, locked: [IVec2; 4]) -> Option<ClearResult> {
+        let completed_lines = self.playfield.completed_lines();
+        let num_lines_cleared = completed_lines.len();
+        if num_lines_cleared > 0 {
+            self.events.push(GameEvent::LinesCleared(ClearedLines {
+                blocks: self.playfield.locked_blocks(&completed_lines),
+                lines: num_lines_cleared,
+                perfect_clear: self.playfield.is_perfect_clear(&completed_lines),
+                combo: self.combo.map_or(0, |combo| combo + 1),
+                locked,
+            }));
+            if self.settings.line_clear_delay_ms > 0 {
+                // the lines are collapsed once the delay is over
+                self.line_clear = Some(LineClear {
+                    lines: completed_lines,
+                    time: 0.,
+                    delay: self.settings.line_clear_delay_ms as f64 / 1000.,
+                });
+            } else {
+                self.playfield.clear_completed_lines();
+            }
+        }
+        if num_lines_cleared == 0 {
+            // the combo is broken by a lock that doesn't clear lines
+            self.combo = None;
+            if !t_spin {
+                return None;
+            }
         }
 
-        let num_lines_cleared = cleared_lines.len();
+        let difficult = ClearResult::is_difficult(num_lines_cleared, t_spin);
+        if num_lines_cleared > 0 {
+            self.combo = Some(self.combo.map_or(0, |combo| combo + 1));
+            self.stats.max_combo = self.stats.max_combo.max(self.combo.unwrap_or(0));
+        }
 
-        // score the completed lines and append it to the total score
-        let score = score_cleared_lines(num_lines_cleared, self.level);
-        self.score += score;
+        // score the clear and append it to the total score
+        let result = scoring::score_clear(
+            &self.settings.scoring_table(),
+            num_lines_cleared,
+            t_spin,
+            self.level,
+            self.combo.unwrap_or(0),
+            difficult && self.back_to_back,
+        );
+        let mut points = 0;
+        if !self.piece_cycling() && !self.sandbox {
+            let previous_score = self.score;
+            points = result.score * self.modifier.map_or(1, |m| m.score_multiplier());
+            self.score += points;
+            // the highest milestone crossed by this clear is announced
+            let milestone = self.score / SCORE_MILESTONE;
+            if milestone > previous_score / SCORE_MILESTONE {
+                self.events
+                    .push(GameEvent::ScoreMilestone(milestone * SCORE_MILESTONE));
+            }
+        }
+        self.events.push(GameEvent::Scored(ClearResult {
+            score: points,
+            ..result
+        }));
+        *self
+            .stats
+            .clears
+            .entry(result.clear_type().to_owned())
+            .or_default() += 1;
         log::info!(
-            "scored! game_level: {} score: {} lines cleared: {}",
+            "scored! game_level: {} score: {} lines cleared: {} t-spin: {} back to back: {} combo: {}",
             self.level,
-            score,
-            num_lines_cleared
+            result.score,
+            num_lines_cleared,
+            t_spin,
+            result.back_to_back,
+            result.combo
         );
 
+        // t-spins without lines don't break back to back
+        if num_lines_cleared > 0 {
+            self.back_to_back = difficult;
+        }
+
+        if num_lines_cleared == 0 {
+            return Some(result);
+        }
+
         // track the total number of lines cleared
         self.total_lines_cleared += num_lines_cleared;
         log::info!(
@@ -352,205 +1193,1924 @@ impl RustrisGame {
         if self.total_lines_cleared >= (self.level + 1) * LINES_PER_LEVEL {
             self.increase_game_level();
         }
+        Some(result)
     }
 }
 
-fn score_cleared_lines(num_lines: usize, level: usize) -> usize {
-    // Single line 100xlevel
-    // Double line 300xlevel
-    // Triple line 500xlevel
-    // Rustris (4 lines) 800xlevel
-    let score = match num_lines {
-        1 => SINGLE_LINE_SCORE,
-        2 => DOUBLE_LINE_SCORE,
-        3 => TRIPLE_LINE_SCORE,
-        4 => RUSTRIS_SCORE,
-        _ => {
-            panic!("impossible number of lines cleared")
-        }
-    } * (level + 1);
-    score
+// The parts of a game that are saved to disk
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GameSnapshot {
+    playfield: RustrisPlayfield,
+    next_rustomino: Option<Rustomino>,
+    held_rustomino: Option<Rustomino>,
+    level: usize,
+    score: usize,
+    rustomino_bag: RustominoBag,
+    total_lines_cleared: usize,
+    hold_used: bool,
+    lockdown_resets: u32,
+    combo: Option<usize>,
+    back_to_back: bool,
+    #[serde(default)]
+    practice: bool,
+    #[serde(default)]
+    level_time: f64,
+    #[serde(default)]
+    level_pieces: usize,
+    #[serde(default)]
+    play_time: f64,
+    #[serde(default)]
+    mystery: bool,
+    #[serde(default)]
+    daily: Option<u32>,
+    #[serde(default)]
+    modifier: Option<Modifier>,
+    #[serde(default)]
+    sandbox: bool,
+    #[serde(default)]
+    stats: GameStats,
 }
 
-// checks to see if ALL of the slots in the provided
-// slots array are above the playfield
-fn fully_out_of_bounds(&slots: &[IVec2; 4]) -> bool {
-    for slot in slots {
-        if slot[1] < PLAYFIELD_SIZE[1] {
-            return false;
-        }
+impl GameSnapshot {
+    /// the seed the game's rustominos are dealt from
+    pub fn seed(&self) -> u64 {
+        self.rustomino_bag.seed()
     }
-    true
 }
 
-/// calculate the gravity delay for the provided level
-/// returns fractional seconds
-fn gravity_delay(level: usize) -> f64 {
-    let gravity_delay =
-        ((GRAVITY_NUMERATOR / (level as f64 + 0.001)).log(E) * GRAVITY_FACTOR + 0.3).max(0.001);
-    log::info!("new gravity_delay {}", gravity_delay);
-    gravity_delay
+// Things that happened during a frame that the view shows effects
+// and the sound effects are played for,
+// the rustomino events carry its playfield slots so their sounds can be panned
+pub enum GameEvent {
+    Spawn,
+    Move([IVec2; 4]),
+    Rotate([IVec2; 4]),
+    // the active rustomino can't fall any further
+    Land([IVec2; 4]),
+    HardDrop(HardDropped),
+    Lock([IVec2; 4]),
+    Hold,
+    LinesCleared(ClearedLines),
+    // a clear or t-spin was scored, with the points added to the score
+    Scored(ClearResult),
+    LevelUp(usize),
+    // the score crossed a multiple of the milestone points
+    ScoreMilestone(usize),
+    GameOver,
 }
 
-// run the game
-pub async fn run() {
-    log::info!("startup: initializing Rustris;");
+// The blocks removed by a line clear
+pub struct ClearedLines {
+    pub blocks: Vec<([usize; 2], RustominoType)>,
+    pub lines: usize,
+    pub perfect_clear: bool,
+    pub combo: usize,       // consecutive clears before this one
+    pub locked: [IVec2; 4], // playfield slots of the rustomino that completed the lines
+}
 
-    // initialize the game and control states
-    let mut game = RustrisGame::new(RustrisPlayfield::new());
-    let mut controls = controls::ControlStates::default();
+// The rustomino moved by a hard drop
+pub struct HardDropped {
+    pub rtype: RustominoType,
+    pub landed: [IVec2; 4], // playfield slots it dropped to
+    pub distance: i32,      // rows it fell
+}
 
-    log::info!("loading Resources");
-    // find our assets path
-    let assets_path = find_folder::Search::ParentsThenKids(2, 2)
-        .for_folder(ASSETS_FOLDER)
-        .expect("unable to find assets folder");
+// Completed lines shown briefly before they're collapsed
+pub struct LineClear {
+    pub lines: Vec<usize>,
+    time: f64,
+    delay: f64, // seconds until the lines collapse
+}
 
-    // load the font
-    let font_path = assets_path.join("04b30.ttf");
-    log::info!("loading font: {:?}", font_path);
-    let font = load_ttf_font(&font_path.to_string_lossy())
-        .await
-        .expect("unable to load font");
+impl LineClear {
+    /// how far through the animation the clear is, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        (self.time / self.delay).clamp(0., 1.) as f32
+    }
+}
 
-    // configure UI fonts
-    let font_20pt = TextParams {
-        font,
-        font_size: 20,
-        ..Default::default()
-    };
-    let font_30pt = TextParams {
-        font,
-        font_size: 30,
-        ..Default::default()
-    };
+// The times of the most recent frames
+#[derive(Default)]
+struct FrameRate {
+    frame_times: VecDeque<f64>,
+}
 
-    // load the background music
-    let background_path = assets_path.join("background.ogg");
-    log::info!("loading background music: {:?}", background_path);
-    let background_music = load_sound(&background_path.to_string_lossy())
-        .await
-        .expect("unable to load background music");
-
-    // play background music
-    let mut music_volume = MUSIC_VOL;
-    log::info!("playing background music at volume: {music_volume}");
-    play_sound(
-        background_music,
-        PlaySoundParams {
-            looped: true,
-            volume: music_volume,
-        },
-    );
+impl FrameRate {
+    fn update(&mut self, delta_time: f64) {
+        self.frame_times.push_back(delta_time);
+        if self.frame_times.len() > FRAME_RATE_WINDOW {
+            self.frame_times.pop_front();
+        }
+    }
 
-    let mut last_update = get_time();
+    /// the average frames per second over the window
+    /// returns None until a full window of frames has been measured
+    fn average(&self) -> Option<f64> {
+        if self.frame_times.len() < FRAME_RATE_WINDOW {
+            return None;
+        }
+        let total: f64 = self.frame_times.iter().sum();
+        Some(self.frame_times.len() as f64 / total)
+    }
+}
 
-    loop {
-        clear_background(view::BACKGROUND_COLOR);
+// Where the rustomino taken by a hold action came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HoldSource {
+    Hold,
+    Next,
+}
 
-        // handle global controls
-        handle_global_inputs(&background_music, &mut music_volume);
+// The held and incoming rustominos swapping places after a hold
+pub struct HoldSwap {
+    pub source: HoldSource,
+    pub outgoing: [IVec2; 4], // playfield slots the held rustomino left
+    time: f64,
+}
 
-        let now = get_time();
-        let delta_time = now - last_update;
+impl HoldSwap {
+    /// how far through the animation the swap is, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        (self.time / HOLD_SWAP_TIME).clamp(0., 1.) as f32
+    }
+}
 
-        // handle the game states
-        match game.state {
-            GameState::Menu => {
-                // handle the user's inputs
-                if is_key_pressed(KeyCode::Enter) {
-                    controls.clear_inputs();
-                    game.resume();
-                }
-            }
+// The rule that ended a game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameOverRule {
+    // a rustomino locked entirely above the visible playfield
+    LockOut,
+    // a rustomino spawned overlapping a locked block
+    BlockOut,
+    // versus garbage pushed locked blocks off the top of the playfield
+    Garbage,
+}
+
+impl Display for GameOverRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameOverRule::LockOut => write!(f, "Lock Out"),
+            GameOverRule::BlockOut => write!(f, "Block Out"),
+            GameOverRule::Garbage => write!(f, "Top Out"),
+        }
+    }
+}
+
+// Lines of garbage sent by one versus clear, every line has its hole in the same column
+#[derive(Debug, Clone, Copy)]
+struct Garbage {
+    lines: usize,
+    hole: usize,
+    rtype: RustominoType, // the color of the garbage blocks
+}
+
+// Two players each with their own game drawn on half of the view,
+// on one keyboard, against the cpu or over the network.
+// The first to top out loses and the other player's game stops
+pub struct Versus {
+    pub players: [RustrisGame; 2],
+    controls: [ControlStates; 2],
+    pub effects: [view::Effects; 2],
+    pub finished: bool,
+    pub winner: Option<usize>, // None for a draw, when both top out on the same frame
+    // picks the garbage holes, seeded with the match so online players pick the same ones
+    garbage_rng: Xoshiro256PlusPlus,
+    pub online: Option<OnlineMatch>,
+    pub cpu: Option<Cpu>, // plays the second board in a match against the cpu
+}
+
+impl Versus {
+    /// a match on one keyboard, or against the cpu when it was chosen from the menu
+    fn new(game: &RustrisGame, keys: &ControlStates) -> Self {
+        if game.versus_cpu {
+            return Versus::against_cpu(game, keys);
+        }
+        log::info!("starting versus match");
+        let controls = [0, 1].map(ControlStates::versus);
+        Versus::start(game, game.settings.clone(), ::rand::random(), controls)
+    }
+
+    // the player uses the single player keys on the first board,
+    // the cpu presses the second board's controls itself
+    fn against_cpu(game: &RustrisGame, keys: &ControlStates) -> Self {
+        log::info!("starting versus match against the cpu");
+        let mut controls = [ControlStates::default(), ControlStates::default()];
+        controls[0].input_map.clone_from(&keys.input_map);
+        let mut versus = Versus::start(game, game.settings.clone(), ::rand::random(), controls);
+        versus.players[1].settings = cpu::settings(&game.settings);
+        versus.cpu = Some(Cpu::new(game.settings.cpu_difficulty));
+        versus
+    }
+
+    /// a match against the opponent who just connected, the local player
+    /// uses the single player keys and both games use the host's settings
+    fn online(game: &RustrisGame, connected: online::Connected, keys: &ControlStates) -> Self {
+        log::info!(
+            "starting online versus match as player {}",
+            connected.local + 1
+        );
+        let mut controls = [ControlStates::default(), ControlStates::default()];
+        controls[connected.local]
+            .input_map
+            .clone_from(&keys.input_map);
+        let mut versus = Versus::start(game, connected.settings.clone(), connected.seed, controls);
+        versus.online = Some(OnlineMatch::new(connected));
+        versus
+    }
+
+    fn start(
+        game: &RustrisGame,
+        settings: Settings,
+        seed: u64,
+        controls: [ControlStates; 2],
+    ) -> Self {
+        let mut garbage_rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+        let players = [0, 1].map(|_| {
+            let seed = if settings.versus_same_pieces {
+                seed
+            } else {
+                garbage_rng.gen()
+            };
+            let mut player = RustrisGame {
+                rustomino_bag: RustominoBag::with_seed(seed, settings.piece_weights),
+                strings: game.strings.clone(),
+                versus: true,
+                ..RustrisGame::new(
+                    RustrisPlayfield::new(),
+                    settings.clone(),
+                    game.profiles.clone(),
+                )
+            };
+            player.resume();
+            player
+        });
+        Versus {
+            players,
+            controls,
+            effects: Default::default(),
+            finished: false,
+            winner: None,
+            garbage_rng,
+            online: None,
+            cpu: None,
+        }
+    }
+
+    fn update(&mut self, delta_time: f64) {
+        if self.finished {
+            for player in &mut self.players {
+                if let GameState::GameOver = player.state {
+                    player.game_over_time += delta_time;
+                }
+            }
+            if let Some(online) = &mut self.online {
+                online.flush(delta_time);
+            }
+            return;
+        }
+        match self.online.take() {
+            Some(online) => self.update_online(online, delta_time),
+            None => {
+                let mut keys = self.controls.each_ref().map(ControlStates::read_keys);
+                if let Some(cpu) = &mut self.cpu {
+                    keys[1] = cpu.update(&self.players[1], delta_time);
+                }
+                self.play_frame(keys, delta_time);
+            }
+        }
+    }
+
+    // plays the frames both players' keys have arrived for
+    fn update_online(&mut self, mut online: OnlineMatch, delta_time: f64) {
+        online.update(self.controls[online.local].read_keys(), delta_time);
+        let mut result = Ok(());
+        while result.is_ok() && !self.finished {
+            match online.next_frame() {
+                Ok(Some(keys)) => {
+                    self.play_frame(keys, online::FRAME_TIME);
+                    result = online.frame_played(|| self.state_hash());
+                }
+                Ok(None) => break,
+                Err(e) => result = Err(e),
+            }
+        }
+        if let Err(e) = result {
+            log::warn!("online match ended: {}", e);
+            online.error = Some(e);
+            self.finished = true;
+        }
+        self.online = Some(online);
+    }
+
+    fn play_frame(&mut self, keys: [InputFrame; 2], delta_time: f64) {
+        for ((player, controls), keys) in self.players.iter_mut().zip(&mut self.controls).zip(keys)
+        {
+            update_playing(player, controls, keys, delta_time);
+        }
+        // each attack is queued for the opponent with its own hole
+        for sender in 0..2 {
+            let lines = std::mem::take(&mut self.players[sender].sent_garbage);
+            if lines > 0 {
+                let hole = self.garbage_rng.gen_range(0..PLAYFIELD_SIZE[0] as usize);
+                let rtype = RustominoType::iter()
+                    .choose(&mut self.garbage_rng)
+                    .unwrap_or(RustominoType::I);
+                log::info!("player {} sent {} lines of garbage", sender + 1, lines);
+                self.players[1 - sender]
+                    .pending_garbage
+                    .push_back(Garbage { lines, hole, rtype });
+            }
+        }
+        let topped_out = self
+            .players
+            .each_ref()
+            .map(|player| matches!(player.state, GameState::GameOver));
+        if topped_out.contains(&true) {
+            self.finished = true;
+            self.winner = topped_out.iter().position(|topped_out| !topped_out);
+            log::info!(
+                "versus match over, winner: {:?} scores: {} {}",
+                self.winner,
+                self.players[0].score,
+                self.players[1].score
+            );
+        }
+    }
+
+    // a hash of both players' boards, online players compare them to find a desync
+    fn state_hash(&self) -> u64 {
+        let mut hasher = online::StateHasher::default();
+        for player in &self.players {
+            hasher.write(player.playfield.to_string().as_bytes());
+            hasher.write_number(player.score);
+            hasher.write_number(player.total_lines_cleared);
+            hasher.write_number(player.pending_garbage());
+        }
+        hasher.finish()
+    }
+
+    // the board played by the player at this computer with the single player keys,
+    // None when two players share the keyboard
+    fn local_player(&self) -> Option<usize> {
+        match (&self.online, &self.cpu) {
+            (Some(online), _) => Some(online.local),
+            (None, Some(_)) => Some(0),
+            (None, None) => None,
+        }
+    }
+
+    /// true once the boards that topped out have turned grey and the results are shown
+    pub fn results_shown(&self) -> bool {
+        self.finished
+            && self.players.iter().all(|player| {
+                !matches!(player.state, GameState::GameOver) || player.game_over_progress() >= 1.
+            })
+    }
+
+    /// the reason an online match ended early
+    pub fn online_error(&self) -> Option<&str> {
+        self.online
+            .as_ref()
+            .and_then(|online| online.error.as_deref())
+    }
+
+    // tells the online opponent the player is leaving
+    fn leave(&mut self) {
+        if let Some(online) = &mut self.online {
+            online.leave();
+        }
+    }
+}
+
+// A game played by the cpu behind the menu after it's been left idle.
+// None of it is saved, recorded or heard, and it's dropped as soon as the menu is left
+pub struct Demo {
+    pub game: RustrisGame,
+    controls: ControlStates,
+    cpu: Cpu,
+}
+
+impl Demo {
+    fn new(game: &RustrisGame) -> Self {
+        log::info!("starting menu demo");
+        let mut demo = RustrisGame {
+            rustomino_bag: RustominoBag::new(game.settings.piece_weights),
+            strings: game.strings.clone(),
+            demo: true,
+            ..RustrisGame::new(
+                RustrisPlayfield::new(),
+                game.settings.clone(),
+                game.profiles.clone(),
+            )
+        };
+        demo.settings = cpu::settings(&game.settings);
+        demo.resume();
+        Demo {
+            game: demo,
+            controls: ControlStates::default(),
+            cpu: Cpu::new(DEMO_DIFFICULTY),
+        }
+    }
+
+    // a demo that tops out starts over once its board has turned grey
+    fn update(&mut self, game: &RustrisGame, delta_time: f64) {
+        if let GameState::GameOver = self.game.state {
+            self.game.game_over_time += delta_time;
+            if self.game.game_over_progress() >= 1. {
+                *self = Demo::new(game);
+            }
+            return;
+        }
+        let keys = self.cpu.update(&self.game, delta_time);
+        update_playing(&mut self.game, &mut self.controls, keys, delta_time);
+        self.game.events.clear();
+    }
+}
+
+// A recorded game watched from the replays screen, its inputs are played back
+// at the recorded timing through the same update as the game that was recorded.
+// Seeking plays the recording again from its start
+pub struct ReplayViewer {
+    pub game: RustrisGame,
+    pub replay: Replay,
+    controls: ControlStates,
+    next_frame: usize,     // the index of the next recorded frame to play
+    time: f64,             // recorded time due to be played
+    elapsed: f64,          // recorded time played so far
+    end_hash: Option<u64>, // the played back game's state hash, once every frame has been played
+    pub duration: f64,
+    pub paused: bool,
+    pub speed: usize, // the index of the playback speed in replay::SPEEDS
+}
+
+impl ReplayViewer {
+    fn new(game: &RustrisGame, replay: Replay) -> Self {
+        log::info!("watching {} replay from {}", replay.mode, replay.timestamp);
+        ReplayViewer {
+            game: ReplayViewer::start(&replay, game.strings.clone(), game.profiles.clone()),
+            duration: replay.duration(),
+            replay,
+            controls: ControlStates::default(),
+            next_frame: 0,
+            time: 0.,
+            elapsed: 0.,
+            end_hash: None,
+            paused: false,
+            speed: replay::NORMAL_SPEED,
+        }
+    }
+
+    // the recorded game before its first frame, never saved or recorded again
+    fn start(replay: &Replay, strings: Strings, profiles: ProfileStore) -> RustrisGame {
+        RustrisGame {
+            strings,
+            scripted: true,
+            ..RustrisGame::restore(replay.start.clone(), replay.settings.clone(), profiles)
+        }
+    }
+
+    fn update(&mut self, delta_time: f64) {
+        if let GameState::GameOver = self.game.state {
+            self.game.game_over_time += delta_time;
+        }
+        if self.paused {
+            return;
+        }
+        self.time += delta_time * replay::SPEEDS[self.speed];
+        while let Some(frame) = self.replay.frames.get(self.next_frame) {
+            if frame.delta_time > self.time {
+                break;
+            }
+            self.time -= frame.delta_time;
+            self.step();
+        }
+    }
+
+    // plays the next recorded frame
+    fn step(&mut self) {
+        let frame = self.replay.frames[self.next_frame];
+        if frame.resumed {
+            self.controls.clear_inputs();
+            self.game.resume();
+        }
+        update_playing(
+            &mut self.game,
+            &mut self.controls,
+            frame.keys,
+            frame.delta_time,
+        );
+        self.game.events.clear();
+        self.elapsed += frame.delta_time;
+        self.next_frame += 1;
+        if self.finished() {
+            self.end_hash = Some(replay::state_hash(&self.game.snapshot()));
+        }
+    }
+
+    // jumps forward or back by the offset in seconds by playing the recording from its start
+    fn seek(&mut self, offset: f64) {
+        let target = (self.elapsed + offset).max(0.);
+        log::debug!("replay seeking to {:.1}s", target);
+        self.game = ReplayViewer::start(
+            &self.replay,
+            std::mem::take(&mut self.game.strings),
+            std::mem::take(&mut self.game.profiles),
+        );
+        self.controls = ControlStates::default();
+        self.next_frame = 0;
+        self.time = 0.;
+        self.elapsed = 0.;
+        self.end_hash = None;
+        while let Some(frame) = self.replay.frames.get(self.next_frame) {
+            if self.elapsed + frame.delta_time > target {
+                break;
+            }
+            self.step();
+        }
+        // a game that has ended is shown grey at once
+        if let GameState::GameOver = self.game.state {
+            self.game.game_over_time = GAME_OVER_ANIMATION_TIME;
+        }
+    }
+
+    /// recorded time played so far in seconds
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    /// true once every recorded frame has been played
+    pub fn finished(&self) -> bool {
+        self.next_frame == self.replay.frames.len()
+    }
+
+    /// true if the played back game didn't end with the recorded score and state,
+    /// the recording or the game's update has changed since it was saved
+    pub fn desynced(&self) -> bool {
+        self.finished()
+            && (self.game.score != self.replay.score
+                || self
+                    .replay
+                    .end_hash
+                    .is_some_and(|hash| Some(hash) != self.end_hash))
+    }
+}
+
+// How gravity increases within a level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
+pub enum GravityRamp {
+    // gravity only changes when the level increases
+    Stepped,
+    // gravity increases with the time played at the current level
+    Time,
+    // gravity increases with the rustominos locked at the current level
+    Pieces,
+}
+
+impl Display for GravityRamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GravityRamp::Stepped => write!(f, "Stepped"),
+            GravityRamp::Time => write!(f, "Time"),
+            GravityRamp::Pieces => write!(f, "Pieces"),
+        }
+    }
+}
+
+fn main_menu(profiles: &ProfileStore) -> Menu<MenuItem> {
+    let mut items = vec![
+        MenuItem::NewGame,
+        MenuItem::Practice,
+        MenuItem::Mystery,
+        MenuItem::Daily,
+        MenuItem::Sandbox,
+        MenuItem::Versus,
+        MenuItem::VersusCpu,
+        MenuItem::Online,
+        MenuItem::HighScores,
+        MenuItem::Replays,
+        MenuItem::Settings,
+        MenuItem::WhatsNew,
+        MenuItem::Profiles,
+        MenuItem::Exit,
+    ];
+    if saved_game_exists(profiles) {
+        items.insert(0, MenuItem::Continue);
+    }
+    Menu::new(items)
+}
+
+// the profiles followed by the guest and a new profile, the one playing is selected
+fn profile_menu(profiles: &ProfileStore) -> Menu<ProfileItem> {
+    let mut items: Vec<ProfileItem> = (0..profiles.names().len())
+        .map(ProfileItem::Profile)
+        .collect();
+    items.extend([ProfileItem::Guest, ProfileItem::New]);
+    let mut menu = Menu::new(items);
+    // the guest follows the profiles
+    menu.selected = profiles
+        .names()
+        .iter()
+        .position(|name| Some(name.as_str()) == profiles.active())
+        .unwrap_or(profiles.names().len());
+    menu
+}
+
+fn pause_menu() -> Menu<MenuItem> {
+    Menu::new(vec![
+        MenuItem::Resume,
+        MenuItem::SaveAndQuit,
+        MenuItem::Exit,
+    ])
+}
+
+fn saved_game_exists(profiles: &ProfileStore) -> bool {
+    profiles.data_file(SAVE_FILE).exists()
+}
+
+fn save_game(game: &RustrisGame) -> io::Result<()> {
+    storage::write_json(&game.profiles.data_file(SAVE_FILE), &game.snapshot())
+}
+
+fn load_game(settings: Settings, profiles: ProfileStore) -> io::Result<RustrisGame> {
+    let snapshot = storage::read_json(&profiles.data_file(SAVE_FILE))?;
+    Ok(RustrisGame::restore(snapshot, settings, profiles))
+}
+
+// saves the game in progress and the settings and stops the music before exiting
+fn exit(game: &RustrisGame, music: &mut sound::Music) {
+    log::info!("exiting Rustris");
+    if let (GameState::Playing | GameState::Paused, false) = (&game.state, game.scripted) {
+        match save_game(game) {
+            Ok(()) => log::info!("game saved"),
+            Err(e) => log::error!("unable to save game: {}", e),
+        }
+    }
+    if let Err(e) = game.settings.save(&game.profiles) {
+        log::error!("unable to save settings: {}", e);
+    }
+    music.stop();
+}
+
+fn delete_saved_game(profiles: &ProfileStore) {
+    let save_path = profiles.data_file(SAVE_FILE);
+    if save_path.exists() {
+        if let Err(e) = fs::remove_file(&save_path) {
+            log::error!("unable to delete saved game {:?}: {}", save_path, e);
+        }
+    }
+}
+
+/// finds the assets folder near the executable or working directory
+pub fn assets_path() -> Result<PathBuf, find_folder::Error> {
+    find_folder::Search::ParentsThenKids(2, 2).for_folder(ASSETS_FOLDER)
+}
+
+// checks to see if ALL of the slots in the provided
+// slots array are above the playfield
+fn fully_out_of_bounds(&slots: &[IVec2; 4]) -> bool {
+    for slot in slots {
+        if slot[1] < PLAYFIELD_SIZE[1] {
+            return false;
+        }
+    }
+    true
+}
+
+/// calculate the gravity delay for the provided level
+/// returns fractional seconds
+fn gravity_delay(level: usize) -> f64 {
+    let gravity_delay =
+        ((GRAVITY_NUMERATOR / (level as f64 + 0.001)).log(E) * GRAVITY_FACTOR + 0.3).max(0.001);
+    log::trace!("gravity_delay for level {}: {}", level, gravity_delay);
+    gravity_delay
+}
+
+// run the game
+pub async fn run() {
+    log::info!("startup: initializing Rustris;");
+
+    // initialize the game and control states
+    let profiles = ProfileStore::load();
+    let mut game = RustrisGame::new(RustrisPlayfield::new(), Settings::load(&profiles), profiles);
+    // the players sharing the install pick who's playing
+    if game.profiles.pick_at_launch() {
+        game.state = GameState::Profiles;
+    }
+    let mut controls = controls::ControlStates::default();
+    controls.apply_env_keys();
+    let mut global_controls = controls::GlobalControlStates::default();
+
+    // a script named on the command line plays a game in place of the keyboard,
+    // with --fast it's played at once without drawing and the game's state is printed
+    let mut script = match Script::from_args() {
+        Ok(script) => script,
+        Err(e) => {
+            log::error!("unable to load input script: {}", e);
+            None
+        }
+    };
+    if let Some(playing) = &mut script {
+        if let Err(e) = game.start_script(playing) {
+            log::error!("unable to start input script: {}", e);
+            script = None;
+        } else if playing.fast {
+            while play_script(&mut game, &mut controls, playing, 1) {
+                game.take_events();
+            }
+            println!("{}", game.script_report(playing));
+            return;
+        }
+    }
+
+    log::info!("loading Resources");
+    // find our assets path
+    let assets_path = assets_path().expect("unable to find assets folder");
+
+    // load the font, themes, music and sound effects together,
+    // the built-in theme is always available and missing sounds are silent
+    let loading::Resources {
+        font,
+        themes,
+        mut music,
+        mut sound_effects,
+    } = loading::Resources::load(&assets_path).await;
+
+    // configure UI fonts
+    let font_20pt = TextParams {
+        font,
+        font_size: 20,
+        ..Default::default()
+    };
+    let font_30pt = TextParams {
+        font,
+        font_size: 30,
+        ..Default::default()
+    };
+
+    // load the translations of the view's text, English is always available
+    let languages = language::Languages::load(&assets_path);
+
+    let setting_choices = SettingChoices {
+        themes: themes.names(),
+        languages: languages.names(),
+        music: music.choices(),
+    };
+
+    // load the version notes for the what's new panel
+    game.whats_new = load_whats_new(&assets_path);
+
+    game.high_scores = HighScores::load(&game.profiles);
+
+    let mut sfx_volume = game.settings.sfx_playback_volume();
+
+    let mut last_update = get_time();
+
+    // closing the window exits the same way as the exit menu item
+    prevent_quit();
+
+    // time spent playing every game since startup, excluding pauses
+    let mut session_play_time = 0.;
+
+    // effects are started by game events and drawn over the playfield
+    let mut effects = view::Effects::default();
+    // shown over every screen for a moment after the music volume is adjusted
+    let mut volume_indicator = view::VolumeIndicator::default();
+    // the name of a new background track is shown for a moment
+    let mut now_playing = view::NowPlaying::default();
+
+    // logs the draw calls of a frame in debug builds
+    let mut draw_call_counter = view::DrawCallCounter::default();
+
+    // the layout is recomputed whenever the window is resized or the board scale or position changes
+    let mut view_settings = view::ViewSettings::new(
+        game.settings.screen_mode.view_dimensions(),
+        game.settings.board_scale,
+        [game.settings.board_offset_x, game.settings.board_offset_y],
+    );
+
+    // the scaled canvas screen mode draws to this before it's fit to the window
+    let canvas = view::Canvas::new();
+
+    // the last seconds of the game, saved as a gif at game over
+    let mut clip_recorder = ClipRecorder::new();
+
+    // the window was created with the saved fullscreen state
+    let mut fullscreen = game.settings.fullscreen;
+
+    // the local versus match, while the game is in the versus state
+    let mut versus: Option<Versus> = None;
+
+    // the game the cpu plays behind the menu once it's left idle
+    let mut demo: Option<Demo> = None;
+    let mut menu_idle_time = 0.;
+
+    // games with high scores are recorded from the menu and their replay saved at game over
+    let mut recorder: Option<Recorder> = None;
+    // the replay being watched, while the game is in the replays state.
+    // A replay named on the command line is watched at launch
+    let mut replay_viewer = match replay::path_from_args() {
+        Ok(Some(path)) => match Replay::load(&path) {
+            Ok(replay) => {
+                game.strings = languages.get(&game.settings.language).clone();
+                game.replays = replay::list(&game.profiles.data_file(replay::REPLAYS_FOLDER));
+                game.replay_menu = Menu::new((0..game.replays.len()).collect());
+                game.state = GameState::Replays;
+                Some(ReplayViewer::new(&game, replay))
+            }
+            Err(e) => {
+                log::error!("unable to load replay {:?}: {}", path, e);
+                None
+            }
+        },
+        Ok(None) => None,
+        Err(e) => {
+            log::error!("unable to load replay: {}", e);
+            None
+        }
+    };
+
+    loop {
+        let view_dimensions = game.settings.screen_mode.view_dimensions();
+        let board_offset = [game.settings.board_offset_x, game.settings.board_offset_y];
+        if view_settings.view_w != view_dimensions[0]
+            || view_settings.view_h != view_dimensions[1]
+            || view_settings.board_scale != game.settings.board_scale
+            || view_settings.board_offset != board_offset
+        {
+            log::debug!(
+                "layout changed: {}x{} board scale: {}% board offset: {:?}",
+                view_dimensions[0],
+                view_dimensions[1],
+                game.settings.board_scale,
+                board_offset
+            );
+            view_settings =
+                view::ViewSettings::new(view_dimensions, game.settings.board_scale, board_offset);
+        }
+
+        // the fullscreen setting may have been changed this frame,
+        // the windowed size is remembered for the next session
+        if fullscreen != game.settings.fullscreen {
+            fullscreen = game.settings.fullscreen;
+            log::debug!("fullscreen: {}", fullscreen);
+            set_fullscreen(fullscreen);
+        } else if !fullscreen {
+            game.settings.window_size = Some([screen_width() as u32, screen_height() as u32]);
+        }
+
+        let screen_mode = game.settings.screen_mode;
+        if screen_mode == view::ScreenMode::Canvas {
+            canvas.begin();
+        }
+
+        let theme = themes.get(&game.settings.theme);
+        clear_background(theme.background_color);
+
+        let now = get_time();
+        let delta_time = now - last_update;
+
+        // handle global controls, the mute key is typed on the text input screens
+        // and the volume keys change the speed of the replay being watched
+        let typing = matches!(
+            game.state,
+            GameState::Practice | GameState::DebugBoard | GameState::Online
+        ) || game.initials_input.is_some()
+            || game.profile_input.is_some();
+        if handle_global_inputs(
+            &mut global_controls,
+            &mut game.settings,
+            &game.profiles,
+            typing,
+            replay_viewer.is_some(),
+            delta_time,
+        ) {
+            volume_indicator.show();
+        }
+        volume_indicator.update(delta_time);
+
+        // characters typed this frame, read every frame so they don't queue up
+        let typed_chars = typed_chars();
+
+        // handle the game states
+        match game.state {
+            GameState::Menu => {
+                // effects from the last game aren't shown in the next one
+                effects.clear();
+                recorder = None;
+                let key_pressed = get_last_key_pressed().is_some();
+                match &mut demo {
+                    // any key returns to the menu without selecting anything
+                    Some(_) if key_pressed => {
+                        log::info!("menu demo dismissed");
+                        demo = None;
+                        menu_idle_time = 0.;
+                    }
+                    Some(demo) => demo.update(&game, delta_time),
+                    None => {
+                        menu_idle_time = if key_pressed {
+                            0.
+                        } else {
+                            menu_idle_time + delta_time
+                        };
+                        // handle the user's inputs
+                        handle_menu_inputs(&mut game, &mut controls);
+                        if let (GameState::Playing, Some(mode)) = (&game.state, game.mode()) {
+                            recorder = Some(Recorder::start(
+                                mode,
+                                game.snapshot(),
+                                game.settings.clone(),
+                            ));
+                        }
+                        if menu_idle_time >= MENU_IDLE_DEMO_TIME && game.menu.confirming.is_none() {
+                            demo = Some(Demo::new(&game));
+                        }
+                    }
+                }
+                // nothing from the demo carries into the screen the menu opened
+                if !matches!(game.state, GameState::Menu) {
+                    demo = None;
+                    menu_idle_time = 0.;
+                }
+            }
             GameState::Playing => {
                 // pause the game immediately
                 // clear all other inputs and continue
                 if is_key_pressed(KeyCode::Escape) {
                     game.pause();
                     controls.clear_inputs();
+                    recorder.iter_mut().for_each(Recorder::pause);
+                } else if game.update_frame_rate(delta_time) {
+                    controls.clear_inputs();
+                    recorder.iter_mut().for_each(Recorder::pause);
+                } else if cfg!(debug_assertions) && is_key_pressed(KeyCode::F4) {
+                    game.open_debug_board();
+                    controls.clear_inputs();
+                    // a board loaded by hand can't be played back
+                    recorder = None;
                 } else {
-                    game.ready_playfield();
-                    handle_playing_inputs(&mut controls, &mut game);
-                    handle_held_playing_inputs(&mut controls, &mut game, delta_time);
-                    game.playing_update(delta_time);
+                    session_play_time += delta_time;
+                    match &mut script {
+                        Some(playing) => {
+                            let frames = playing.frames_due(delta_time);
+                            if !play_script(&mut game, &mut controls, playing, frames) {
+                                println!("{}", game.script_report(playing));
+                                script = None;
+                                game.settings = Settings::load(&game.profiles);
+                            }
+                        }
+                        None => {
+                            let keys = controls.read_keys();
+                            if let Some(recorder) = &mut recorder {
+                                recorder.record(keys, delta_time);
+                            }
+                            update_playing(&mut game, &mut controls, keys, delta_time);
+                        }
+                    }
                 }
             }
-            GameState::Paused => {
-                if is_key_pressed(KeyCode::Escape) {
-                    controls.clear_inputs();
-                    game.resume();
-                }
+            GameState::Paused => {
+                if is_key_pressed(KeyCode::Escape) && game.menu.confirming.is_none() {
+                    controls.clear_inputs();
+                    game.resume();
+                } else {
+                    handle_menu_inputs(&mut game, &mut controls);
+                }
+            }
+            GameState::GameOver => {
+                game.game_over_time += delta_time;
+                if game.game_over_progress() >= 1. && game.initials_input.is_some() {
+                    handle_initials_inputs(&mut game, &typed_chars);
+                } else if is_key_pressed(KeyCode::Enter) {
+                    if game.game_over_progress() < 1. {
+                        // the first press skips the animation to the game over panel
+                        game.finish_game_over_animation();
+                    } else {
+                        controls.clear_inputs();
+                        effects.clear();
+                        game = game.new_game();
+                    }
+                } else if game.game_over_progress() >= 1. {
+                    handle_summary_inputs(&mut game);
+                }
+            }
+            GameState::Settings => {
+                handle_settings_inputs(&mut game, &setting_choices);
+            }
+            GameState::Practice => {
+                handle_practice_inputs(&mut game, &mut controls, &typed_chars);
+            }
+            GameState::WhatsNew => {
+                handle_whats_new_inputs(&mut game);
+            }
+            GameState::HighScores => {
+                handle_high_scores_inputs(&mut game);
+            }
+            GameState::Profiles => {
+                handle_profiles_inputs(&mut game, &typed_chars);
+            }
+            GameState::Versus => {
+                if is_key_pressed(KeyCode::Escape) {
+                    log::info!("versus match left");
+                    if let Some(versus) = &mut versus {
+                        versus.leave();
+                    }
+                    versus = None;
+                    game.menu = main_menu(&game.profiles);
+                    game.state = GameState::Menu;
+                } else {
+                    let versus = versus.get_or_insert_with(|| Versus::new(&game, &controls));
+                    if !versus.results_shown() {
+                        let finished = versus.finished;
+                        if !finished {
+                            session_play_time += delta_time;
+                        }
+                        versus.update(delta_time);
+                        if let (false, true, Some(local)) =
+                            (finished, versus.finished, versus.local_player())
+                        {
+                            export_stats(&versus.players[local], &game.settings, &game.profiles);
+                        }
+                    } else if is_key_pressed(KeyCode::Enter) && versus.online.is_none() {
+                        *versus = Versus::new(&game, &controls);
+                    }
+                }
+            }
+            GameState::Online => {
+                if let Some(connected) = handle_online_inputs(&mut game, &typed_chars, delta_time) {
+                    controls.clear_inputs();
+                    versus = Some(Versus::online(&game, connected, &controls));
+                    game.state = GameState::Versus;
+                }
+            }
+            GameState::Replays => match &mut replay_viewer {
+                Some(_) if is_key_pressed(KeyCode::Escape) => {
+                    log::info!("replay closed");
+                    replay_viewer = None;
+                }
+                Some(viewer) => {
+                    handle_replay_viewer_inputs(viewer);
+                    viewer.update(delta_time);
+                }
+                None => replay_viewer = handle_replays_inputs(&mut game),
+            },
+            GameState::DebugBoard => {
+                handle_debug_board_inputs(&mut game, &mut controls, &typed_chars);
+            }
+        }
+
+        // the volume settings take effect immediately,
+        // a sample is played when the sound effects volume changes so it can be judged
+        let music_volume = game.settings.music_playback_volume();
+        // the theme's intense track takes over late in a game unless the music is off
+        let intense_music = match game.state {
+            GameState::Playing | GameState::Paused | GameState::GameOver => {
+                theme.intense_music(game.level)
+            }
+            _ => None,
+        };
+        let music_selection = match intense_music {
+            Some(track) if game.settings.music != sound::Music::OFF => track,
+            _ => &game.settings.music,
+        };
+        if let Some(track) = music.update(music_selection, music_volume, &game.state, delta_time) {
+            now_playing.show(track);
+        }
+        now_playing.update(delta_time);
+        if game.settings.sfx_playback_volume() != sfx_volume {
+            sfx_volume = game.settings.sfx_playback_volume();
+            log::debug!("sound effects volume: {}", sfx_volume);
+            sound_effects.play_sample(sfx_volume);
+        }
+
+        // the language may have been changed or the game recreated this frame
+        let strings = languages.get(&game.settings.language);
+        if game.strings.language != strings.language {
+            game.strings = strings.clone();
+        }
+
+        // start effects and play sounds for the game's events
+        for event in game.take_events() {
+            if let (GameEvent::GameOver, false) = (&event, game.scripted) {
+                export_stats(&game, &game.settings, &game.profiles);
+                if let Some(recorder) = recorder.take() {
+                    let folder = game.profiles.data_file(replay::REPLAYS_FOLDER);
+                    if let Err(e) = recorder.save(game.score, &game.snapshot(), &folder) {
+                        log::error!("unable to save replay: {}", e);
+                    }
+                }
+            }
+            sound_effects.play_event(&event, sfx_volume, now, game.settings.stereo_panning);
+            effects.handle_event(&view_settings, theme, &game.settings, strings, &event);
+        }
+        if let GameState::Playing | GameState::GameOver = game.state {
+            effects.update(&view_settings, &game.settings, delta_time, game.level);
+        }
+        // each versus player's effects are placed on their half of the view
+        if let Some(versus) = &mut versus {
+            for (index, (player, effects)) in versus
+                .players
+                .iter_mut()
+                .zip(&mut versus.effects)
+                .enumerate()
+            {
+                let player_view = view_settings.split(index);
+                for event in player.take_events() {
+                    sound_effects.play_event(&event, sfx_volume, now, game.settings.stereo_panning);
+                    effects.handle_event(&player_view, theme, &player.settings, strings, &event);
+                }
+                effects.update(&player_view, &player.settings, delta_time, player.level);
+            }
+        }
+
+        // draw the menus, game, overlays, etc.
+        match (&versus, &demo, &replay_viewer) {
+            (Some(versus), _, _) => {
+                view::draw_versus(&game, versus, &view_settings, theme, &font_20pt, &font_30pt)
+            }
+            (None, _, Some(viewer)) => view::draw_replay(
+                viewer,
+                &view_settings,
+                theme,
+                &effects,
+                &font_20pt,
+                &font_30pt,
+            ),
+            (None, Some(demo), None) => view::draw_demo(
+                &game,
+                &demo.game,
+                &view_settings,
+                theme,
+                &effects,
+                &font_20pt,
+                &font_30pt,
+            ),
+            (None, None, None) => view::draw(
+                &game,
+                &view_settings,
+                theme,
+                &effects,
+                session_play_time,
+                &font_20pt,
+                &font_30pt,
+            ),
+        }
+
+        volume_indicator.draw(
+            &view_settings,
+            &font_20pt,
+            strings,
+            game.settings.music_volume,
+        );
+        now_playing.draw(&view_settings, &font_20pt, strings);
+
+        // the summary is saved after the frame is drawn, the image is drawn offscreen
+        if let Some(format) = game.summary_export.take() {
+            let folder = game.settings.summary_folder.path(&game.profiles);
+            let result = summary::export(&game.summary(), format, &folder, theme, &font_20pt);
+            game.summary_message = Some(match result {
+                Ok(path) => language::fill(
+                    &strings.summary_saved,
+                    path.file_name().unwrap_or_default().to_string_lossy(),
+                ),
+                Err(e) => {
+                    log::error!("unable to save session summary: {}", e);
+                    strings.summary_failed.clone()
+                }
+            });
+            if screen_mode == view::ScreenMode::Canvas {
+                canvas.begin();
+            } else {
+                set_default_camera();
+            }
+        }
+
+        // the screen mode may have been changed in the settings this frame
+        if screen_mode == view::ScreenMode::Canvas {
+            canvas.end(BLACK);
+        }
+
+        // the clip is captured from the finished frame and encoded on another thread
+        clip_recorder.update(&game, delta_time);
+        if std::mem::take(&mut game.clip_export) {
+            clip_recorder.save(game.settings.summary_folder.path(&game.profiles));
+            game.summary_message = Some(strings.clip_saving.clone());
+        }
+        if let Some(result) = clip_recorder.saved() {
+            let message = match result {
+                Ok(path) => language::fill(
+                    &strings.summary_saved,
+                    path.file_name().unwrap_or_default().to_string_lossy(),
+                ),
+                Err(e) => {
+                    log::error!("unable to save clip: {}", e);
+                    strings.clip_failed.clone()
+                }
+            };
+            // a new game may have started while it was saved
+            if let GameState::GameOver = game.state {
+                game.summary_message = Some(message);
+            }
+        }
+
+        draw_call_counter.update();
+
+        last_update = get_time();
+
+        if game.exit_requested || is_quit_requested() {
+            exit(&game, &mut music);
+            break;
+        }
+
+        next_frame().await;
+    }
+}
+
+// returns a closure which handles the provided
+// control for the game
+fn control_handler<'a>(control: &'a Controls, game: &'a mut RustrisGame) -> Box<dyn FnMut() + 'a> {
+    // controls used during a hold swap are performed once it's done
+    if game.hold_swap.is_some() {
+        return Box::new(|| game.buffered_controls.push(control.clone()));
+    }
+    // the mystery modifier can change which control is performed
+    let control = match game.modifier {
+        Some(modifier) => modifier.control(control),
+        None => control.clone(),
+    };
+    match control {
+        Controls::Left => Box::new(|| game.translate(TranslationDirection::Left)),
+        Controls::Right => Box::new(|| game.translate(TranslationDirection::Right)),
+        Controls::RotateCW => Box::new(|| game.rotate(Rotation::Cw)),
+        Controls::RotateCCW => Box::new(|| game.rotate(Rotation::Ccw)),
+        Controls::SoftDrop => Box::new(|| game.soft_drop()),
+        Controls::HardDrop => Box::new(|| game.hard_drop()),
+        Controls::Hold => Box::new(|| game.hold()),
+        Controls::CyclePiece => Box::new(|| game.cycle_piece()),
+        Controls::LockPiece => Box::new(|| game.lock_piece()),
+        Controls::ToggleGhost => Box::new(|| game.toggle_ghost()),
+    }
+}
+
+fn handle_menu_inputs(game: &mut RustrisGame, controls: &mut ControlStates) {
+    // waiting for the player to confirm the selected item
+    if let Some(item) = game.menu.confirming {
+        if is_key_pressed(KeyCode::Enter) {
+            game.menu.confirming = None;
+            select_menu_item(item, game, controls);
+        } else if is_key_pressed(KeyCode::Escape) {
+            game.menu.confirming = None;
+        }
+        return;
+    }
+
+    if is_key_pressed(KeyCode::Up) {
+        game.menu.select_previous();
+    }
+    if is_key_pressed(KeyCode::Down) {
+        game.menu.select_next();
+    }
+    if is_key_pressed(KeyCode::Enter) {
+        let Some(item) = game.menu.selected_item() else {
+            return;
+        };
+        // only ask for confirmation if there's a saved game to lose
+        if item.confirmation().is_some() && saved_game_exists(&game.profiles) {
+            game.menu.confirming = Some(item);
+        } else {
+            select_menu_item(item, game, controls);
+        }
+    }
+}
+
+fn select_menu_item(item: MenuItem, game: &mut RustrisGame, controls: &mut ControlStates) {
+    log::debug!("menu item selected: {:?}", item);
+    match item {
+        MenuItem::Continue => match load_game(game.settings.clone(), game.profiles.clone()) {
+            Ok(mut saved_game) => {
+                log::info!("continuing saved game");
+                // the save is removed once it's been restored
+                delete_saved_game(&game.profiles);
+                controls.clear_inputs();
+                saved_game.whats_new = std::mem::take(&mut game.whats_new);
+                saved_game.high_scores = std::mem::take(&mut game.high_scores);
+                *game = saved_game;
+            }
+            Err(e) => {
+                log::error!("unable to load saved game: {}", e);
+                game.menu = main_menu(&game.profiles);
+            }
+        },
+        MenuItem::NewGame
+        | MenuItem::Practice
+        | MenuItem::Mystery
+        | MenuItem::Daily
+        | MenuItem::Sandbox => {
+            // only one game is saved, starting a new one discards it
+            delete_saved_game(&game.profiles);
+            start_game(item, game, controls);
+        }
+        // the main loop starts the match, the saved game is kept
+        MenuItem::Versus => {
+            game.versus_cpu = false;
+            game.state = GameState::Versus;
+        }
+        MenuItem::VersusCpu => {
+            game.versus_cpu = true;
+            game.state = GameState::Versus;
+        }
+        MenuItem::Online => game.state = GameState::Online,
+        MenuItem::Settings => {
+            game.settings_menu.selected = 0;
+            game.state = GameState::Settings;
+        }
+        MenuItem::HighScores => {
+            game.high_scores.shown_mode = GameMode::default();
+            game.state = GameState::HighScores;
+        }
+        MenuItem::Replays => {
+            game.replays = replay::list(&game.profiles.data_file(replay::REPLAYS_FOLDER));
+            game.replay_menu = Menu::new((0..game.replays.len()).collect());
+            game.state = GameState::Replays;
+        }
+        MenuItem::WhatsNew => {
+            game.whats_new.scroll = 0;
+            game.state = GameState::WhatsNew;
+        }
+        MenuItem::Profiles => {
+            game.profile_menu = profile_menu(&game.profiles);
+            game.state = GameState::Profiles;
+        }
+        MenuItem::Resume => {
+            controls.clear_inputs();
+            game.resume();
+        }
+        // the game in progress is saved on the way out
+        MenuItem::Exit => game.exit_requested = true,
+        MenuItem::SaveAndQuit => match save_game(game) {
+            Ok(()) => {
+                log::info!("game saved, returning to menu");
+                *game = RustrisGame {
+                    whats_new: std::mem::take(&mut game.whats_new),
+                    high_scores: std::mem::take(&mut game.high_scores),
+                    ..RustrisGame::new(
+                        RustrisPlayfield::new(),
+                        game.settings.clone(),
+                        std::mem::take(&mut game.profiles),
+                    )
+                };
+            }
+            Err(e) => log::error!("unable to save game: {}", e),
+        },
+    }
+}
+
+// starts the kind of game chosen from the menu,
+// practice games start once the opening sequence is typed
+fn start_game(item: MenuItem, game: &mut RustrisGame, controls: &mut ControlStates) {
+    // the settings may have changed since the game was created
+    game.rustomino_bag = RustominoBag::new(game.settings.piece_weights);
+    match item {
+        MenuItem::Practice => {
+            game.practice_input = TextInput::default();
+            game.state = GameState::Practice;
+            return;
+        }
+        MenuItem::Mystery => {
+            log::info!("starting mystery game");
+            game.mystery = true;
+        }
+        MenuItem::Daily => {
+            let date = daily::today();
+            log::info!("starting daily challenge for {}", date);
+            // every player gets the same rustominos, so the weights setting isn't used
+            game.rustomino_bag = RustominoBag::with_seed(daily::seed(date), PieceWeights::Even);
+            game.daily = Some(date);
+        }
+        MenuItem::Sandbox => {
+            log::info!("starting sandbox game");
+            game.sandbox = true;
+        }
+        _ => {}
+    }
+    controls.clear_inputs();
+    game.resume();
+}
+
+fn handle_settings_inputs(game: &mut RustrisGame, choices: &SettingChoices) {
+    if is_key_pressed(KeyCode::Escape) {
+        game.menu = main_menu(&game.profiles);
+        game.state = GameState::Menu;
+        return;
+    }
+    if is_key_pressed(KeyCode::Up) {
+        game.settings_menu.select_previous();
+    }
+    if is_key_pressed(KeyCode::Down) {
+        game.settings_menu.select_next();
+    }
+    let Some(item) = game.settings_menu.selected_item() else {
+        return;
+    };
+    let forward = if is_key_pressed(KeyCode::Left) {
+        false
+    } else if is_key_pressed(KeyCode::Right) || is_key_pressed(KeyCode::Enter) {
+        true
+    } else {
+        return;
+    };
+    item.change(&mut game.settings, forward, choices);
+    if let Err(e) = game.settings.save(&game.profiles) {
+        log::error!("unable to save settings: {}", e);
+    }
+}
+
+// the player types the opening sequence for a practice game
+fn handle_practice_inputs(
+    game: &mut RustrisGame,
+    controls: &mut ControlStates,
+    typed_chars: &[char],
+) {
+    if is_key_pressed(KeyCode::Escape) {
+        game.menu = main_menu(&game.profiles);
+        game.state = GameState::Menu;
+        return;
+    }
+    for c in typed_chars {
+        game.practice_input.push(*c);
+    }
+    if is_key_pressed(KeyCode::Backspace) {
+        game.practice_input.pop();
+    }
+    if is_key_pressed(KeyCode::Enter) {
+        match RustominoType::parse_sequence(&game.practice_input.text) {
+            Ok(sequence) => {
+                log::info!("starting practice game");
+                game.rustomino_bag =
+                    RustominoBag::with_sequence(sequence, game.settings.piece_weights);
+                game.practice = true;
+                controls.clear_inputs();
+                game.resume();
+            }
+            Err(c) => {
+                log::debug!("invalid practice sequence: {}", game.practice_input.text);
+                game.practice_input.error = Some(format!("Invalid Piece: {c}"));
+            }
+        }
+    }
+}
+
+// the developer types a garbage height or a board to reproduce a bug
+fn handle_debug_board_inputs(
+    game: &mut RustrisGame,
+    controls: &mut ControlStates,
+    typed_chars: &[char],
+) {
+    if is_key_pressed(KeyCode::Escape) {
+        controls.clear_inputs();
+        game.resume();
+        return;
+    }
+    for c in typed_chars {
+        game.debug_board_input.push(*c);
+    }
+    if is_key_pressed(KeyCode::Backspace) {
+        game.debug_board_input.pop();
+    }
+    if is_key_pressed(KeyCode::Enter) {
+        let command = game.debug_board_input.text.clone();
+        match game.load_debug_board(&command) {
+            Ok(()) => {
+                controls.clear_inputs();
+                if let GameState::DebugBoard = game.state {
+                    game.resume();
+                }
+            }
+            Err(e) => {
+                log::debug!("invalid debug board {}: {}", command, e);
+                game.debug_board_input.error = Some(e);
+            }
+        }
+    }
+}
+
+// the player scrolls through the version notes
+fn handle_whats_new_inputs(game: &mut RustrisGame) {
+    if is_key_pressed(KeyCode::Escape) {
+        game.menu = main_menu(&game.profiles);
+        game.state = GameState::Menu;
+        return;
+    }
+    if is_key_pressed(KeyCode::Up) {
+        game.whats_new.scroll_up();
+    }
+    if is_key_pressed(KeyCode::Down) {
+        game.whats_new.scroll_down();
+    }
+}
+
+// the player switches between the modes' high scores
+fn handle_high_scores_inputs(game: &mut RustrisGame) {
+    if is_key_pressed(KeyCode::Escape) {
+        game.menu = main_menu(&game.profiles);
+        game.state = GameState::Menu;
+        return;
+    }
+    let modes = GameMode::shown(daily::today());
+    let shown = modes
+        .iter()
+        .position(|mode| *mode == game.high_scores.shown_mode)
+        .unwrap_or_default();
+    if is_key_pressed(KeyCode::Left) {
+        game.high_scores.shown_mode = modes[(shown + modes.len() - 1) % modes.len()];
+    }
+    if is_key_pressed(KeyCode::Right) {
+        game.high_scores.shown_mode = modes[(shown + 1) % modes.len()];
+    }
+}
+
+// the player picks a saved replay to watch, returns it once it's been read
+fn handle_replays_inputs(game: &mut RustrisGame) -> Option<ReplayViewer> {
+    if is_key_pressed(KeyCode::Escape) {
+        game.menu = main_menu(&game.profiles);
+        game.state = GameState::Menu;
+        return None;
+    }
+    if is_key_pressed(KeyCode::Up) {
+        game.replay_menu.select_previous();
+    }
+    if is_key_pressed(KeyCode::Down) {
+        game.replay_menu.select_next();
+    }
+    if !is_key_pressed(KeyCode::Enter) {
+        return None;
+    }
+    let index = game.replay_menu.selected_item()?;
+    match Replay::load(&game.replays[index].path) {
+        Ok(replay) => Some(ReplayViewer::new(game, replay)),
+        // the replay is taken off the list
+        Err(e) => {
+            log::error!("unable to load replay: {}", e);
+            game.replays.remove(index);
+            game.replay_menu = Menu::new((0..game.replays.len()).collect());
+            None
+        }
+    }
+}
+
+// space pauses the replay, left and right jump back or forward
+// and the volume keys slow it down or speed it up
+fn handle_replay_viewer_inputs(viewer: &mut ReplayViewer) {
+    if is_key_pressed(KeyCode::Space) {
+        viewer.paused = !viewer.paused;
+    }
+    if is_key_pressed(KeyCode::Left) {
+        viewer.seek(-replay::SEEK_TIME);
+    }
+    if is_key_pressed(KeyCode::Right) {
+        viewer.seek(replay::SEEK_TIME);
+    }
+    if is_key_pressed(KeyCode::Minus) || is_key_pressed(KeyCode::KpSubtract) {
+        viewer.speed = viewer.speed.saturating_sub(1);
+    }
+    if is_key_pressed(KeyCode::Equal) || is_key_pressed(KeyCode::KpAdd) {
+        viewer.speed = (viewer.speed + 1).min(replay::SPEEDS.len() - 1);
+    }
+}
+
+// the player picks the profile to play as, or creates, renames or deletes one,
+// a new profile is played as once it's named
+// the online screen, the player types the host's address to join or hosts the match,
+// returns the match once the opponent has connected
+fn handle_online_inputs(
+    game: &mut RustrisGame,
+    typed_chars: &[char],
+    delta_time: f64,
+) -> Option<online::Connected> {
+    if let Some(lobby) = &mut game.online_lobby {
+        if is_key_pressed(KeyCode::Escape) {
+            log::info!("stopped waiting for the online opponent");
+            game.online_lobby = None;
+            return None;
+        }
+        let result = lobby.update(&game.settings, delta_time);
+        if !matches!(result, Ok(None)) {
+            game.online_lobby = None;
+        }
+        return result.unwrap_or_else(|e| {
+            game.online_input.error = Some(e);
+            None
+        });
+    }
+    if is_key_pressed(KeyCode::Escape) {
+        game.menu = main_menu(&game.profiles);
+        game.state = GameState::Menu;
+        return None;
+    }
+    for c in typed_chars.iter().filter(|c| online::is_address_char(**c)) {
+        game.online_input.push(*c);
+    }
+    if is_key_pressed(KeyCode::Backspace) {
+        game.online_input.pop();
+    }
+    let lobby = if is_key_pressed(KeyCode::Enter) {
+        online::Lobby::join(&game.online_input.text)
+    } else if is_key_pressed(KeyCode::Tab) {
+        online::Lobby::host(&game.settings.online_host_address)
+    } else {
+        return None;
+    };
+    match lobby {
+        Ok(lobby) => game.online_lobby = Some(lobby),
+        Err(e) => game.online_input.error = Some(e),
+    }
+    None
+}
+
+fn handle_profiles_inputs(game: &mut RustrisGame, typed_chars: &[char]) {
+    if let Some(mut input) = game.profile_input.take() {
+        if is_key_pressed(KeyCode::Escape) {
+            return;
+        }
+        for c in typed_chars.iter().filter(|c| profiles::is_name_char(**c)) {
+            input.push(*c);
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            input.pop();
+        }
+        if !is_key_pressed(KeyCode::Enter) {
+            game.profile_input = Some(input);
+            return;
+        }
+        let result = match game.profile_menu.selected_item() {
+            Some(ProfileItem::Profile(index)) => {
+                let old_name = game.profiles.names()[index].clone();
+                game.profiles.rename(&old_name, &input.text)
+            }
+            _ => game
+                .profiles
+                .create(&input.text)
+                .map(|name| game.switch_profile(Some(&name))),
+        };
+        match result {
+            Ok(()) => game.profile_menu = profile_menu(&game.profiles),
+            Err(e) => {
+                input.error = Some(e);
+                game.profile_input = Some(input);
             }
-            GameState::GameOver => {
-                if is_key_pressed(KeyCode::Enter) {
-                    controls.clear_inputs();
-                    game = game.new_game();
+        }
+        return;
+    }
+    if let Some(item) = game.profile_menu.confirming {
+        if is_key_pressed(KeyCode::Enter) {
+            if let ProfileItem::Profile(index) = item {
+                let name = game.profiles.names()[index].clone();
+                if game.profiles.active() == Some(name.as_str()) {
+                    // the guest plays with its own settings once the profile is gone
+                    game.switch_profile(None);
+                    game.state = GameState::Profiles;
                 }
+                game.profiles.delete(&name);
+            }
+            game.profile_menu = profile_menu(&game.profiles);
+        } else if is_key_pressed(KeyCode::Escape) {
+            game.profile_menu.confirming = None;
+        }
+        return;
+    }
+    if is_key_pressed(KeyCode::Escape) {
+        game.menu = main_menu(&game.profiles);
+        game.state = GameState::Menu;
+        return;
+    }
+    if is_key_pressed(KeyCode::Up) {
+        game.profile_menu.select_previous();
+    }
+    if is_key_pressed(KeyCode::Down) {
+        game.profile_menu.select_next();
+    }
+    let Some(item) = game.profile_menu.selected_item() else {
+        return;
+    };
+    match item {
+        ProfileItem::Profile(index) => {
+            if is_key_pressed(KeyCode::Enter) {
+                let name = game.profiles.names()[index].clone();
+                game.switch_profile(Some(&name));
+            } else if is_key_pressed(KeyCode::R) {
+                let mut input = TextInput::with_max_length(profiles::PROFILE_NAME_LENGTH);
+                input.text.clone_from(&game.profiles.names()[index]);
+                game.profile_input = Some(input);
+            } else if is_key_pressed(KeyCode::Delete) {
+                game.profile_menu.confirming = Some(item);
             }
         }
+        ProfileItem::Guest if is_key_pressed(KeyCode::Enter) => game.switch_profile(None),
+        ProfileItem::New if is_key_pressed(KeyCode::Enter) => {
+            game.profile_input = Some(TextInput::with_max_length(profiles::PROFILE_NAME_LENGTH));
+        }
+        _ => (),
+    }
+}
 
-        // draw the menus, game, overlays, etc.
-        view::draw(&game, &font_20pt, &font_30pt);
+// the player saves a summary of the finished game
+fn handle_summary_inputs(game: &mut RustrisGame) {
+    if is_key_pressed(KeyCode::T) {
+        game.summary_export = Some(SummaryFormat::Text);
+    } else if is_key_pressed(KeyCode::P) {
+        game.summary_export = Some(SummaryFormat::Image);
+    } else if clip::AVAILABLE && is_key_pressed(KeyCode::G) {
+        game.clip_export = true;
+    }
+}
 
-        last_update = get_time();
+// the player types their initials for a new high score, Enter records it
+fn handle_initials_inputs(game: &mut RustrisGame, typed_chars: &[char]) {
+    let Some(input) = &mut game.initials_input else {
+        return;
+    };
+    for c in typed_chars.iter().filter(|c| c.is_ascii_alphanumeric()) {
+        input.push(c.to_ascii_uppercase());
+    }
+    if is_key_pressed(KeyCode::Backspace) {
+        input.pop();
+    }
+    if is_key_pressed(KeyCode::Enter) {
+        game.record_high_score();
+    }
+}
 
-        next_frame().await;
+/// loads the version notes shown on the what's new panel
+/// a missing or unreadable file shows a message instead
+fn load_whats_new(assets_path: &Path) -> ScrollText {
+    let whats_new_path = assets_path.join(WHATS_NEW_FILE);
+    log::info!("loading version notes: {:?}", whats_new_path);
+    match fs::read_to_string(&whats_new_path) {
+        Ok(text) => ScrollText::new(&text),
+        Err(e) => {
+            log::warn!("unable to read version notes {:?}: {}", whats_new_path, e);
+            ScrollText::new("No notes available.")
+        }
     }
 }
 
-// returns a closure which handles the provided
-// control for the game
-fn control_handler<'a>(control: &'a Controls, game: &'a mut RustrisGame) -> Box<dyn FnMut() + 'a> {
-    match *control {
-        Controls::Left => Box::new(|| game.translate(TranslationDirection::Left)),
-        Controls::Right => Box::new(|| game.translate(TranslationDirection::Right)),
-        Controls::RotateCW => Box::new(|| game.rotate(Rotation::Cw)),
-        Controls::RotateCCW => Box::new(|| game.rotate(Rotation::Ccw)),
-        Controls::SoftDrop => Box::new(|| game.soft_drop()),
-        Controls::HardDrop => Box::new(|| game.hard_drop()),
-        Controls::Hold => Box::new(|| game.hold()),
+/// returns the characters typed since the last frame in the order they were typed
+fn typed_chars() -> Vec<char> {
+    let mut typed_chars = vec![];
+    while let Some(c) = get_char_pressed() {
+        typed_chars.push(c);
     }
+    // macroquad returns the most recently typed character first
+    typed_chars.reverse();
+    typed_chars
 }
 
-fn handle_global_inputs(background_music: &Sound, music_volume: &mut f32) {
-    // volume down
-    if is_key_pressed(KeyCode::Minus) || is_key_pressed(KeyCode::KpSubtract) {
-        *music_volume -= MUSIC_VOLUME_CHANGE;
-        *music_volume = music_volume.clamp(0.0, 1.0);
-        set_sound_volume(*background_music, *music_volume);
-        log::debug!("volume decrease {}", music_volume);
+// the music volume and mute settings are changed and saved, the music follows them in the main loop
+// returns true if the music volume was adjusted
+fn handle_global_inputs(
+    global_controls: &mut GlobalControlStates,
+    settings: &mut Settings,
+    profiles: &ProfileStore,
+    typing: bool,
+    replaying: bool,
+    delta_time: f64,
+) -> bool {
+    let mut volume_adjusted = false;
+    let performed = global_controls.update(delta_time);
+    for control in &performed {
+        match control {
+            GlobalControls::VolumeDown | GlobalControls::VolumeUp if !replaying => {
+                let louder = *control == GlobalControls::VolumeUp;
+                settings.music_volume = settings::step_volume(settings.music_volume, louder);
+                log::debug!("music volume changed: {}%", settings.music_volume);
+                volume_adjusted = true;
+            }
+            GlobalControls::Mute if !typing => {
+                settings.mute = !settings.mute;
+                log::debug!("mute toggled: {}", settings.mute);
+            }
+            _ => continue,
+        }
+        if let Err(e) = settings.save(profiles) {
+            log::error!("unable to save settings: {}", e);
+        }
     }
-    // volume up
-    if is_key_pressed(KeyCode::Equal) || is_key_pressed(KeyCode::KpAdd) {
-        *music_volume += MUSIC_VOLUME_CHANGE;
-        *music_volume = music_volume.clamp(0.0, 1.0);
-        set_sound_volume(*background_music, *music_volume);
-        log::debug!("volume increase {}", music_volume);
+    volume_adjusted
+}
+
+// one frame of a game being played, by the player or either player of a versus match
+fn update_playing(
+    game: &mut RustrisGame,
+    controls: &mut ControlStates,
+    keys: InputFrame,
+    delta_time: f64,
+) {
+    game.play_time += delta_time;
+    // inputs are still handled while lines are clearing so held
+    // inputs keep charging, there's no active rustomino for them to move
+    let clearing = game.update_line_clear(delta_time);
+    // inputs are buffered while a hold swap is running
+    let swapping = game.update_hold_swap(delta_time);
+    if !clearing {
+        game.ready_playfield();
+    }
+    let pieces = game.stats.pieces;
+    handle_playing_inputs(controls, game, keys);
+    handle_held_playing_inputs(controls, game, delta_time);
+    if !clearing && !swapping {
+        game.playing_update(delta_time);
+    }
+    // without das carry a held direction charges again for the next rustomino
+    if !game.settings.das_carry && game.stats.pieces != pieces {
+        controls.reset_auto_shift();
+    }
+}
+
+// appends the local player's finished game to the statistics export,
+// with the local player's settings even when the match used the host's
+fn export_stats(player: &RustrisGame, settings: &Settings, profiles: &ProfileStore) {
+    let result = summary::export_stats(
+        &player.summary(),
+        settings.stats_export,
+        &settings.stats_folder,
+        profiles,
+    );
+    if let Err(e) = result {
+        log::error!("unable to export game statistics: {}", e);
+    }
+}
+
+// plays the script's next frames, returns false once it has finished or the game has ended
+fn play_script(
+    game: &mut RustrisGame,
+    controls: &mut ControlStates,
+    script: &mut Script,
+    frames: usize,
+) -> bool {
+    for _ in 0..frames {
+        let Some(keys) = script.next_keys() else {
+            return false;
+        };
+        update_playing(game, controls, keys, script::FRAME_TIME);
+        if !matches!(game.state, GameState::Playing) {
+            return false;
+        }
     }
+    true
 }
 
-fn handle_playing_inputs(control_states: &mut ControlStates, game: &mut RustrisGame) {
+// the controls are handled in the same order every frame
+// so online players pressing several keys at once play the same moves
+fn handle_playing_inputs(
+    control_states: &mut ControlStates,
+    game: &mut RustrisGame,
+    keys: InputFrame,
+) {
     // iterate through the controls
-    for (input, keys) in &control_states.input_map.clone() {
-        // iterate through the configured keys for the control
-        for key in keys.iter().flatten() {
-            if is_key_pressed(*key) {
-                control_states
-                    .input_states
-                    .entry(input.clone())
-                    .and_modify(|e| *e = InputState::Down(0.0));
-                // call game function for this input
-                control_handler(input, game)();
-                // ignore other input bindings for this control
-                break;
-            } else if is_key_released(*key) {
-                control_states
-                    .input_states
-                    .entry(input.clone())
-                    .and_modify(|e| *e = InputState::Up);
+    for control in Controls::iter() {
+        // the input states are kept for the control the keys perform,
+        // so the swapped hard drop keys repeat and the soft drop keys don't
+        let input = &if game.settings.swap_drop_keys {
+            control.with_drops_swapped()
+        } else {
+            control.clone()
+        };
+        // a press before the hard drop key is released is ignored
+        let ignored = *input == Controls::HardDrop && !control_states.hard_drop_ready();
+        if keys.pressed(&control) && !ignored {
+            control_states
+                .input_states
+                .entry(input.clone())
+                .and_modify(|e| *e = InputState::Down(0.0));
+            game.stats.key_presses += 1;
+            // call game function for this input
+            control_handler(input, game)();
+            if *input == Controls::HardDrop {
+                control_states.hard_drop_lockout =
+                    game.settings.hard_drop_lockout_ms as f64 / 1000.;
             }
         }
+        // the control is released once none of its keys are down
+        // this is checked after the press so a key pressed and released
+        // in the same frame acts once and can't start repeating,
+        // and it runs before the held inputs so a release stops repeats this frame
+        if !keys.down(&control) {
+            control_states
+                .input_states
+                .entry(input.clone())
+                .and_modify(|e| *e = InputState::Up);
+        }
+    }
+}
+
+// the delays before a held control repeats and between its repeats,
+// left and right use the player's auto shift settings
+fn repeat_delays(control: &Controls, settings: &Settings) -> (Option<f64>, Option<f64>) {
+    match control {
+        Controls::Left | Controls::Right => (
+            Some(settings.auto_shift_delay_ms as f64 / 1000.),
+            Some(settings.auto_repeat_delay_ms as f64 / 1000.),
+        ),
+        control => (control.action_delay(), control.action_repeat_delay()),
     }
 }
 
@@ -563,8 +3123,15 @@ fn handle_held_playing_inputs(
     game: &mut RustrisGame,
     delta_time: f64,
 ) {
-    // iterate through the controls
-    for control in Controls::iter() {
+    control_states.hard_drop_lockout -= delta_time;
+    // iterate through the controls that repeat, hard drop never does
+    for control in Controls::iter().filter(Controls::repeats) {
+        let (action_delay, action_repeat_delay) = repeat_delays(&control, &game.settings);
+        // without a repeat delay left and right move as far as they can
+        let repeats = match action_repeat_delay {
+            Some(delay) if delay <= 0. => PLAYFIELD_SIZE[0],
+            _ => 1,
+        };
         control_states
             .input_states
             .entry(control.clone()) // modify in place
@@ -572,7 +3139,7 @@ fn handle_held_playing_inputs(
                 InputState::Down(down_time) => {
                     // check to see if the key is repeatable
                     // and if the down time is longer than the action delay for this input
-                    if let Some(action_delay) = control.action_delay() {
+                    if let Some(action_delay) = action_delay {
                         *down_time += delta_time;
                         if *down_time >= action_delay {
                             *e = InputState::Held(0.);
@@ -592,19 +3159,775 @@ fn handle_held_playing_inputs(
                 // check if held was just set
                 if *held_time == 0. {
                     // call the game control handler function
-                    control_handler(&control, game)();
+                    for _ in 0..repeats {
+                        control_handler(&control, game)();
+                    }
                 }
                 // check to see if the key is repeatable
                 // and if the key has been held longer than the repeat delay for the input
-                if let Some(action_repeat_delay) = control.action_repeat_delay() {
+                if let Some(action_repeat_delay) = action_repeat_delay {
                     if *held_time >= action_repeat_delay {
                         // reset the held state time
                         *state = InputState::Held(0.);
                         // call the game control handler function
-                        control_handler(&control, game)();
+                        for _ in 0..repeats {
+                            control_handler(&control, game)();
+                        }
                     }
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playfield::{SlotState, PLAYFIELD_SLOTS};
+    use std::collections::HashMap;
+
+    // an empty folder for a test's files
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rustris-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // a marathon game being played with the default settings
+    fn playing_game() -> RustrisGame {
+        let mut game = RustrisGame::new(
+            RustrisPlayfield::new(),
+            Settings::default(),
+            ProfileStore::default(),
+        );
+        game.resume();
+        game
+    }
+
+    // plays the script to its end in a new game
+    fn scripted_game(dir: &Path, script: &str) -> RustrisGame {
+        let path = dir.join("script.txt");
+        fs::write(&path, script).unwrap();
+        let mut script = Script::load(&path).unwrap();
+        let mut game = playing_game();
+        game.start_script(&script).unwrap();
+        let mut controls = ControlStates::default();
+        while play_script(&mut game, &mut controls, &mut script, 1) {}
+        game
+    }
+
+    // plays frames with the keys pressed on the first of them
+    fn play(game: &mut RustrisGame, controls: &mut ControlStates, keys: InputFrame, frames: usize) {
+        update_playing(game, controls, keys, script::FRAME_TIME);
+        for _ in 1..frames {
+            update_playing(game, controls, keys.held(), script::FRAME_TIME);
+        }
+    }
+
+    #[test]
+    fn saved_game_round_trip() {
+        let mut game = playing_game();
+        let mut controls = ControlStates::default();
+        play(
+            &mut game,
+            &mut controls,
+            InputFrame::press(&Controls::Hold),
+            30,
+        );
+        play(
+            &mut game,
+            &mut controls,
+            InputFrame::press(&Controls::HardDrop),
+            1,
+        );
+        play(&mut game, &mut controls, InputFrame::default(), 20);
+        play(
+            &mut game,
+            &mut controls,
+            InputFrame::press(&Controls::Left),
+            1,
+        );
+        play(&mut game, &mut controls, InputFrame::default(), 60);
+        assert!(game.held_rustomino.is_some());
+        assert_eq!(game.stats.pieces, 1);
+
+        let saved = serde_json::to_string(&game.snapshot()).unwrap();
+        let mut restored = RustrisGame::restore(
+            serde_json::from_str(&saved).unwrap(),
+            Settings::default(),
+            ProfileStore::default(),
+        );
+        assert!(matches!(restored.state, GameState::Playing));
+        assert_eq!(serde_json::to_string(&restored.snapshot()).unwrap(), saved);
+
+        // both games deal and drop the same rustominos from here
+        let mut restored_controls = ControlStates::default();
+        for _ in 0..3 {
+            let keys = InputFrame::press(&Controls::HardDrop);
+            play(&mut game, &mut controls, keys, 1);
+            play(&mut restored, &mut restored_controls, keys, 1);
+            play(&mut game, &mut controls, InputFrame::default(), 20);
+            play(
+                &mut restored,
+                &mut restored_controls,
+                InputFrame::default(),
+                20,
+            );
+        }
+        assert_eq!(restored.playfield.to_string(), game.playfield.to_string());
+        assert_eq!(restored.score, game.score);
+    }
+
+    #[test]
+    fn exported_csv_reads_back() {
+        let dir = test_dir("stats-export");
+        let game = scripted_game(
+            &dir,
+            "seed 7\n10 tap hard_drop\n20 tap left\n30 tap hard_drop\n40 tap hard_drop\n60 end\n",
+        );
+        let folder = dir.to_string_lossy();
+        for _ in 0..2 {
+            summary::export_stats(
+                &game.summary(),
+                summary::StatsExport::Csv,
+                &folder,
+                &game.profiles,
+            )
+            .unwrap();
+        }
+
+        let csv = fs::read_to_string(dir.join("rustris-stats.csv")).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        // the header is only written once
+        assert_eq!(lines.len(), 3);
+        let columns: Vec<&str> = lines[0].split(',').collect();
+        assert_eq!(columns.len(), 19);
+        let row: HashMap<&str, &str> = columns.into_iter().zip(lines[1].split(',')).collect();
+        assert_eq!(row["seed"], "7");
+        assert_eq!(row["mode"], game.summary().mode);
+        assert_eq!(row["score"], game.score.to_string());
+        assert_eq!(row["lines"], game.total_lines_cleared.to_string());
+        assert_eq!(row["pieces"], "3");
+        assert_eq!(row["single"], "0");
+        let duration: f64 = row["duration"].parse().unwrap();
+        assert!((duration - 61. * script::FRAME_TIME).abs() < 0.001);
+        let pps: f64 = row["pps"].parse().unwrap();
+        assert!((pps - 3. / duration).abs() < 0.01);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn replay_plays_back_to_the_recorded_score() {
+        let dir = test_dir("replay");
+        let mut game = playing_game();
+        game.rustomino_bag = RustominoBag::with_seed(11, game.settings.piece_weights);
+        let mut controls = ControlStates::default();
+        let mut recorder =
+            Recorder::start(GameMode::Marathon, game.snapshot(), game.settings.clone());
+        // uneven frame times, moves, rotations and a pause in the middle
+        let mut frame = 0;
+        while let GameState::Playing = game.state {
+            let keys = match frame % 48 {
+                0 => InputFrame::press(&Controls::HardDrop),
+                12 if frame % 96 == 12 => InputFrame::press(&Controls::Left),
+                20 => InputFrame::press(&Controls::RotateCW),
+                30 if frame % 144 == 30 => InputFrame::press(&Controls::Right),
+                _ => InputFrame::default(),
+            };
+            let delta_time = if frame % 3 == 0 { 0.02 } else { 0.015 };
+            if frame == 100 {
+                game.pause();
+                controls.clear_inputs();
+                recorder.pause();
+                game.resume();
+            }
+            recorder.record(keys, delta_time);
+            update_playing(&mut game, &mut controls, keys, delta_time);
+            frame += 1;
+        }
+        recorder.save(game.score, &game.snapshot(), &dir).unwrap();
+
+        let entries = replay::list(&dir);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].score, game.score);
+        let replay = Replay::load(&entries[0].path).unwrap();
+        let mut viewer = ReplayViewer::new(&game, replay);
+        assert!(!viewer.finished());
+        viewer.update(viewer.duration + 1.);
+        assert!(viewer.finished());
+        assert!(!viewer.desynced());
+        assert!(matches!(viewer.game.state, GameState::GameOver));
+        assert_eq!(
+            viewer.game.playfield.to_string(),
+            game.playfield.to_string()
+        );
+
+        // seeking back plays the start again and reaches the same end
+        viewer.seek(-replay::SEEK_TIME);
+        assert!(!viewer.finished());
+        assert!(viewer.duration > replay::SEEK_TIME);
+        assert!(viewer.elapsed() <= viewer.duration - replay::SEEK_TIME);
+        viewer.seek(replay::SEEK_TIME * 2.);
+        assert!(viewer.finished());
+        assert_eq!(viewer.game.score, game.score);
+        assert_eq!(
+            viewer.game.playfield.to_string(),
+            game.playfield.to_string()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fixture_replays_play_back_without_desyncing() {
+        // replays saved by earlier versions, in the tests folder
+        let game = playing_game();
+        for name in ["replay-v1.json", "replay-v2.json"] {
+            let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+            let replay = Replay::load(&path.join(name)).unwrap();
+            let end_hash = replay.end_hash;
+            let mut viewer = ReplayViewer::new(&game, replay);
+            viewer.update(viewer.duration + 1.);
+            assert!(viewer.finished());
+            assert!(!viewer.desynced(), "{name} desynced");
+            if let Some(end_hash) = end_hash {
+                // a different end is noticed even when the score matches
+                assert_eq!(viewer.end_hash, Some(end_hash));
+                viewer.replay.end_hash = Some(end_hash ^ 1);
+                assert!(viewer.desynced());
+            }
+        }
+    }
+
+    // a game whose stack is the height in rows, with the first column open so no line is complete
+    fn topped_out_game(
+        height: usize,
+        spawn_nudge: bool,
+        lock_out: bool,
+        block_out: bool,
+    ) -> RustrisGame {
+        let mut game = playing_game();
+        game.rustomino_bag = RustominoBag::with_seed(3, game.settings.piece_weights);
+        game.settings.spawn_nudge = spawn_nudge;
+        game.settings.lock_out = lock_out;
+        game.settings.block_out = block_out;
+        game.playfield
+            .load_ascii(&vec![".IIIIIIIII"; height].join("/"))
+            .unwrap();
+        game
+    }
+
+    #[test]
+    fn block_out_ends_the_game_when_a_rustomino_spawns_overlapping() {
+        // every rustomino overlaps the stack in the first buffer row
+        let mut game = topped_out_game(PLAYFIELD_SLOTS[1] - 1, false, true, true);
+        let mut controls = ControlStates::default();
+        play(&mut game, &mut controls, InputFrame::default(), 1);
+        assert!(matches!(game.state, GameState::GameOver));
+        assert_eq!(game.game_over_rule(), Some(GameOverRule::BlockOut));
+    }
+
+    #[test]
+    fn without_block_out_a_rustomino_spawns_over_the_stack() {
+        let stack_height = PLAYFIELD_SLOTS[1] - 1;
+        let mut game = topped_out_game(stack_height, false, true, false);
+        let mut controls = ControlStates::default();
+        play(&mut game, &mut controls, InputFrame::default(), 1);
+        assert!(matches!(game.state, GameState::Playing));
+        // it replaces the locked blocks it overlaps instead of moving up
+        let active = game.playfield.active_rustomino.clone().unwrap();
+        let slots = active.playfield_slots();
+        assert!(slots.iter().any(|slot| slot.y < stack_height as i32));
+        for slot in slots {
+            assert!(matches!(
+                game.playfield.slots[slot.y as usize][slot.x as usize],
+                SlotState::Occupied(_)
+            ));
+        }
+        // only locking it above the visible playfield ends the game
+        play(
+            &mut game,
+            &mut controls,
+            InputFrame::press(&Controls::HardDrop),
+            60,
+        );
+        assert!(matches!(game.state, GameState::GameOver));
+        assert_eq!(game.game_over_rule(), Some(GameOverRule::LockOut));
+    }
+
+    #[test]
+    fn without_block_out_or_lock_out_the_game_goes_on() {
+        let mut game = topped_out_game(PLAYFIELD_SLOTS[1] - 1, false, false, false);
+        let mut controls = ControlStates::default();
+        for _ in 0..3 {
+            play(
+                &mut game,
+                &mut controls,
+                InputFrame::press(&Controls::HardDrop),
+                1,
+            );
+            play(&mut game, &mut controls, InputFrame::default(), 60);
+        }
+        assert!(matches!(game.state, GameState::Playing));
+        assert_eq!(game.stats.pieces, 3);
+    }
+
+    #[test]
+    fn lock_out_ends_the_game_when_a_rustomino_locks_above_the_playfield() {
+        // the spawn nudge moves it into the buffer rows, where it can't fall
+        let mut game = topped_out_game(PLAYFIELD_SIZE[1] as usize, true, true, true);
+        let mut controls = ControlStates::default();
+        play(&mut game, &mut controls, InputFrame::default(), 1);
+        assert!(matches!(game.state, GameState::Playing));
+        let active = game.playfield.active_rustomino.clone().unwrap();
+        assert!(fully_out_of_bounds(&active.playfield_slots()));
+        play(
+            &mut game,
+            &mut controls,
+            InputFrame::press(&Controls::HardDrop),
+            60,
+        );
+        assert!(matches!(game.state, GameState::GameOver));
+        assert_eq!(game.game_over_rule(), Some(GameOverRule::LockOut));
+        assert_eq!(game.stats.pieces, 0);
+    }
+
+    #[test]
+    fn without_lock_out_a_rustomino_locks_in_the_buffer_rows() {
+        let mut game = topped_out_game(PLAYFIELD_SIZE[1] as usize, true, false, true);
+        let mut controls = ControlStates::default();
+        play(&mut game, &mut controls, InputFrame::default(), 1);
+        let active = game.playfield.active_rustomino.clone().unwrap();
+        play(
+            &mut game,
+            &mut controls,
+            InputFrame::press(&Controls::HardDrop),
+            1,
+        );
+        assert_eq!(game.stats.pieces, 1);
+        for slot in active.playfield_slots() {
+            assert!(matches!(
+                game.playfield.slots[slot.y as usize][slot.x as usize],
+                SlotState::Locked(_)
+            ));
+        }
+        // the next rustomino has no room left and blocks out
+        play(&mut game, &mut controls, InputFrame::default(), 60);
+        assert!(matches!(game.state, GameState::GameOver));
+        assert_eq!(game.game_over_rule(), Some(GameOverRule::BlockOut));
+    }
+
+    // a versus player's game with attacks of the lines waiting for it
+    fn versus_game(pending: &[usize]) -> RustrisGame {
+        let mut game = playing_game();
+        game.versus = true;
+        game.pending_garbage = pending
+            .iter()
+            .map(|&lines| Garbage {
+                lines,
+                hole: 0,
+                rtype: RustominoType::I,
+            })
+            .collect();
+        game
+    }
+
+    fn versus_clear(lines: usize) -> Option<ClearResult> {
+        Some(ClearResult {
+            lines,
+            t_spin: false,
+            back_to_back: false,
+            combo: 0,
+            score: 0,
+        })
+    }
+
+    #[test]
+    fn clears_cancel_pending_garbage_and_send_the_rest() {
+        // a rustris's four lines cancel the oldest attack and part of the next
+        let mut game = versus_game(&[1, 2, 3]);
+        game.exchange_garbage(versus_clear(4));
+        assert_eq!(game.pending_attacks().collect::<Vec<_>>(), [2]);
+        assert_eq!(game.sent_garbage, 0);
+
+        // what's left after cancelling every attack is sent
+        let mut game = versus_game(&[1]);
+        game.exchange_garbage(versus_clear(4));
+        assert_eq!(game.pending_garbage(), 0);
+        assert_eq!(game.sent_garbage, 3);
+
+        // a single sends nothing and cancels nothing
+        let mut game = versus_game(&[2]);
+        game.exchange_garbage(versus_clear(1));
+        assert_eq!(game.pending_garbage(), 2);
+        assert_eq!(game.sent_garbage, 0);
+    }
+
+    #[test]
+    fn locks_without_a_clear_receive_pending_garbage() {
+        let mut game = versus_game(&[1, 2]);
+        game.exchange_garbage(None);
+        assert_eq!(game.pending_garbage(), 0);
+        assert_eq!(game.sent_garbage, 0);
+        assert_eq!(game.playfield.column_heights()[1], 3);
+        assert_eq!(game.playfield.column_heights()[0], 0);
+        assert!(matches!(game.state, GameState::Playing));
+
+        // garbage that pushes blocks off the top ends the game
+        let mut game = versus_game(&[PLAYFIELD_SLOTS[1]]);
+        game.playfield.load_ascii("IIII......").unwrap();
+        game.exchange_garbage(None);
+        assert!(matches!(game.state, GameState::GameOver));
+        assert_eq!(game.game_over_rule(), Some(GameOverRule::Garbage));
+    }
+
+    #[test]
+    fn sent_garbage_is_queued_for_the_opponent() {
+        let game = playing_game();
+        let mut versus = Versus::start(&game, Settings::default(), 5, Default::default());
+        versus.players[0].sent_garbage = 3;
+        versus.play_frame(Default::default(), script::FRAME_TIME);
+        assert_eq!(versus.players[0].sent_garbage, 0);
+        assert_eq!(versus.players[1].pending_attacks().collect::<Vec<_>>(), [3]);
+        assert_eq!(versus.players[0].pending_garbage(), 0);
+    }
+
+    #[test]
+    fn hard_drop_waits_for_a_release_and_the_lockout() {
+        let mut game = playing_game();
+        game.settings.hard_drop_lockout_ms = 100;
+        let mut controls = ControlStates::default();
+        let hard_drop = InputFrame::press(&Controls::HardDrop);
+        play(&mut game, &mut controls, hard_drop, 1);
+        assert_eq!(game.stats.pieces, 1);
+        assert!(!controls.hard_drop_ready());
+
+        // released inside the lockout window, a press is ignored
+        play(&mut game, &mut controls, InputFrame::default(), 1);
+        assert!(!controls.hard_drop_ready());
+        play(&mut game, &mut controls, hard_drop, 1);
+        play(&mut game, &mut controls, InputFrame::default(), 1);
+        assert_eq!(game.stats.pieces, 1);
+
+        // once the lockout has run out it drops again
+        play(&mut game, &mut controls, InputFrame::default(), 6);
+        assert!(controls.hard_drop_ready());
+        play(&mut game, &mut controls, hard_drop, 1);
+        assert_eq!(game.stats.pieces, 2);
+
+        // holding the key past the lockout, or pressing it again before it's seen released,
+        // doesn't drop until it's released
+        play(&mut game, &mut controls, hard_drop, 20);
+        assert_eq!(game.stats.pieces, 2);
+        assert!(!controls.hard_drop_ready());
+        play(&mut game, &mut controls, InputFrame::default(), 1);
+        play(&mut game, &mut controls, hard_drop, 1);
+        assert_eq!(game.stats.pieces, 3);
+    }
+
+    // the column of the rustomino after a lock with left held from the last one,
+    // after each of the frames
+    fn left_held_through_a_lock(das_carry: bool, frames: usize) -> Vec<i32> {
+        let mut game = playing_game();
+        game.settings.das_carry = das_carry;
+        let mut controls = ControlStates::default();
+        let left = InputFrame::press(&Controls::Left);
+        play(&mut game, &mut controls, left, 30);
+        let mut hard_drop = left.held();
+        hard_drop.hold(&Controls::HardDrop, true);
+        play(&mut game, &mut controls, hard_drop, 1);
+        assert_eq!(game.stats.pieces, 1);
+        (0..frames)
+            .map(|_| {
+                play(&mut game, &mut controls, left.held(), 1);
+                let rustomino = game.playfield.active_rustomino.as_ref().unwrap();
+                rustomino.translation.x
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_held_direction_charges_again_after_a_lock_without_das_carry() {
+        let auto_shift_frames =
+            (Settings::default().auto_shift_delay_ms as f64 / 1000. / script::FRAME_TIME).ceil()
+                as usize;
+        let columns = left_held_through_a_lock(false, auto_shift_frames + 5);
+        let spawn_column = columns[0];
+        assert!(columns[..auto_shift_frames - 1]
+            .iter()
+            .all(|&x| x == spawn_column));
+        assert!(*columns.last().unwrap() < spawn_column);
+
+        // with das carry it keeps shifting from the first frame
+        let columns = left_held_through_a_lock(true, 1);
+        assert!(columns[0] < spawn_column);
+    }
+
+    // replaces the active rustomino with a new one of the type in its spawn position
+    fn spawn_type(game: &mut RustrisGame, rtype: RustominoType) {
+        game.playfield.take_active();
+        assert!(game.spawn(Rustomino::new(rtype)));
+    }
+
+    // an O dropped into the gap of the two bottom rows completes them
+    fn two_line_clear_game(line_clear_delay_ms: u32) -> RustrisGame {
+        let mut game = playing_game();
+        game.settings.line_clear_delay_ms = line_clear_delay_ms;
+        game.playfield.load_ascii("IIII..IIII/IIII..IIII").unwrap();
+        spawn_type(&mut game, RustominoType::O);
+        game
+    }
+
+    #[test]
+    fn completed_lines_collapse_after_the_line_clear_delay() {
+        let mut game = two_line_clear_game(100);
+        let mut controls = ControlStates::default();
+        play(
+            &mut game,
+            &mut controls,
+            InputFrame::press(&Controls::HardDrop),
+            1,
+        );
+        assert_eq!(game.total_lines_cleared, 2);
+        // the lines stay on the board and nothing spawns until the delay is over
+        play(&mut game, &mut controls, InputFrame::default(), 4);
+        assert!(game.line_clear().is_some());
+        assert_eq!(game.playfield.column_heights()[0], 2);
+        assert!(game.playfield.active_rustomino.is_none());
+
+        play(&mut game, &mut controls, InputFrame::default(), 4);
+        assert!(game.line_clear().is_none());
+        assert_eq!(game.playfield.column_heights()[0], 0);
+        assert!(game.playfield.active_rustomino.is_some());
+
+        // without a delay they collapse as the rustomino locks
+        let mut game = two_line_clear_game(0);
+        let mut controls = ControlStates::default();
+        play(
+            &mut game,
+            &mut controls,
+            InputFrame::press(&Controls::HardDrop),
+            1,
+        );
+        assert!(game.line_clear().is_none());
+        assert_eq!(game.playfield.column_heights()[0], 0);
+    }
+
+    #[test]
+    fn hold_swaps_the_active_rustomino_into_the_board_once_per_lock() {
+        let mut game = playing_game();
+        let mut controls = ControlStates::default();
+        play(&mut game, &mut controls, InputFrame::default(), 10);
+        let rtype = |rustomino: Option<&Rustomino>| rustomino.map(|rustomino| rustomino.rtype);
+        let first = rtype(game.playfield.active_rustomino.as_ref()).unwrap();
+        let next = rtype(game.next_rustomino.as_ref()).unwrap();
+
+        let hold = InputFrame::press(&Controls::Hold);
+        play(&mut game, &mut controls, hold, 1);
+        assert_eq!(rtype(game.held_rustomino.as_ref()), Some(first));
+        // the next rustomino spawns at the top straight away and is replaced in the preview
+        let active = game.playfield.active_rustomino.clone().unwrap();
+        assert_eq!(active.rtype, next);
+        assert_eq!(active.translation, Rustomino::new(next).translation);
+        assert!(game.next_rustomino.is_some());
+        assert!(game.hold_swap().is_some());
+
+        // a second hold before the rustomino locks does nothing
+        play(&mut game, &mut controls, InputFrame::default(), 1);
+        play(&mut game, &mut controls, hold, 1);
+        assert_eq!(rtype(game.held_rustomino.as_ref()), Some(first));
+        assert_eq!(rtype(game.playfield.active_rustomino.as_ref()), Some(next));
+
+        // after a lock the held rustomino comes back
+        play(&mut game, &mut controls, InputFrame::default(), 30);
+        assert!(game.hold_swap().is_none());
+        play(
+            &mut game,
+            &mut controls,
+            InputFrame::press(&Controls::HardDrop),
+            1,
+        );
+        play(&mut game, &mut controls, InputFrame::default(), 1);
+        let spawned = rtype(game.playfield.active_rustomino.as_ref()).unwrap();
+        play(&mut game, &mut controls, hold, 1);
+        assert_eq!(rtype(game.playfield.active_rustomino.as_ref()), Some(first));
+        assert_eq!(rtype(game.held_rustomino.as_ref()), Some(spawned));
+
+        // with reduced motion they swap without the animation
+        let mut game = playing_game();
+        game.settings.reduced_motion = true;
+        let mut controls = ControlStates::default();
+        play(&mut game, &mut controls, InputFrame::default(), 1);
+        play(&mut game, &mut controls, hold, 1);
+        assert!(game.held_rustomino.is_some());
+        assert!(game.hold_swap().is_none());
+    }
+
+    #[test]
+    fn spawn_nudge_moves_a_colliding_rustomino_into_the_buffer() {
+        // the stack reaches the first buffer row, where an I spawns
+        let height = PLAYFIELD_SLOTS[1] - 1;
+        let mut game = topped_out_game(height, true, true, true);
+        spawn_type(&mut game, RustominoType::I);
+        let active = game.playfield.active_rustomino.as_ref().unwrap();
+        assert!(active
+            .playfield_slots()
+            .iter()
+            .all(|slot| slot.y == PLAYFIELD_SLOTS[1] as i32 - 1));
+
+        // without the nudge it blocks out where it spawns
+        let mut game = topped_out_game(height, false, true, true);
+        game.playfield.take_active();
+        assert!(!game.spawn(Rustomino::new(RustominoType::I)));
+
+        // a rustomino with no room above the stack still blocks out
+        let mut game = topped_out_game(height, true, true, true);
+        game.playfield.take_active();
+        assert!(!game.spawn(Rustomino::new(RustominoType::T)));
+    }
+
+    // a game whose active rustomino is resting on the floor, soft dropped once
+    fn soft_dropped_on_the_floor(soft_drop_lock: bool) -> (RustrisGame, ControlStates) {
+        let mut game = playing_game();
+        game.settings.soft_drop_lock = soft_drop_lock;
+        let mut controls = ControlStates::default();
+        play(&mut game, &mut controls, InputFrame::default(), 1);
+        while game.playfield.translate_active(TranslationDirection::Down) {}
+        play(
+            &mut game,
+            &mut controls,
+            InputFrame::press(&Controls::SoftDrop),
+            1,
+        );
+        (game, controls)
+    }
+
+    #[test]
+    fn soft_drop_on_the_stack_locks_with_soft_drop_lock() {
+        let (game, _) = soft_dropped_on_the_floor(true);
+        assert_eq!(game.stats.pieces, 1);
+
+        // without it the rustomino waits out its lock delay
+        let (mut game, mut controls) = soft_dropped_on_the_floor(false);
+        assert_eq!(game.stats.pieces, 0);
+        assert!(game.playfield.active_rustomino.is_some());
+        let lock_delay = game.gravity_delay + LOCKDOWN_MAX_TIME;
+        let frames = (lock_delay / script::FRAME_TIME).ceil() as usize + 1;
+        play(&mut game, &mut controls, InputFrame::default(), frames);
+        assert_eq!(game.stats.pieces, 1);
+    }
+
+    // a game whose active rustomino has just landed on the floor
+    fn landed_game(classic_lock: bool) -> (RustrisGame, ControlStates) {
+        let mut game = playing_game();
+        game.settings.classic_lock = classic_lock;
+        let mut controls = ControlStates::default();
+        play(&mut game, &mut controls, InputFrame::default(), 1);
+        while game.playfield.translate_active(TranslationDirection::Down) {}
+        while !matches!(
+            game.playfield.get_active_state(),
+            Some(RustominoState::Lockdown { .. })
+        ) {
+            play(&mut game, &mut controls, InputFrame::default(), 1);
+        }
+        (game, controls)
+    }
+
+    #[test]
+    fn classic_lock_locks_a_slid_rustomino_on_the_next_gravity_tick() {
+        let slide_frames = (0.4 / script::FRAME_TIME).round() as usize;
+        assert!(2. * slide_frames as f64 * script::FRAME_TIME > LOCKDOWN_MAX_TIME);
+        let left = InputFrame::press(&Controls::Left);
+
+        let (mut game, mut controls) = landed_game(true);
+        let tick_frames = (game.gravity_delay / script::FRAME_TIME).ceil() as usize;
+        assert!(tick_frames > slide_frames + 1);
+        play(
+            &mut game,
+            &mut controls,
+            InputFrame::default(),
+            slide_frames,
+        );
+        let column = game
+            .playfield
+            .active_rustomino
+            .as_ref()
+            .unwrap()
+            .translation
+            .x;
+        play(&mut game, &mut controls, left, 1);
+        let slid = game
+            .playfield
+            .active_rustomino
+            .as_ref()
+            .unwrap()
+            .translation
+            .x;
+        assert_eq!(slid, column - 1);
+        assert_eq!(game.stats.pieces, 0);
+        play(
+            &mut game,
+            &mut controls,
+            InputFrame::default(),
+            tick_frames - slide_frames,
+        );
+        assert_eq!(game.stats.pieces, 1);
+
+        // otherwise the slide resets the lockdown, it hasn't locked after the lock delay
+        let (mut game, mut controls) = landed_game(false);
+        play(
+            &mut game,
+            &mut controls,
+            InputFrame::default(),
+            slide_frames,
+        );
+        play(&mut game, &mut controls, left, 1);
+        play(
+            &mut game,
+            &mut controls,
+            InputFrame::default(),
+            slide_frames,
+        );
+        assert_eq!(game.stats.pieces, 0);
+        play(
+            &mut game,
+            &mut controls,
+            InputFrame::default(),
+            slide_frames,
+        );
+        assert_eq!(game.stats.pieces, 1);
+    }
+
+    #[test]
+    fn a_soft_drop_tapped_between_frames_drops_once() {
+        let mut game = playing_game();
+        let mut controls = ControlStates::default();
+        play(&mut game, &mut controls, InputFrame::default(), 1);
+        let row = |game: &RustrisGame| {
+            game.playfield
+                .active_rustomino
+                .as_ref()
+                .unwrap()
+                .translation
+                .y
+        };
+        let spawn_row = row(&game);
+
+        // pressed and released before the frame, the key isn't down any more
+        let mut tap = InputFrame::press(&Controls::SoftDrop);
+        tap.merge(InputFrame::default());
+        assert!(tap.pressed(&Controls::SoftDrop) && !tap.down(&Controls::SoftDrop));
+        play(&mut game, &mut controls, tap, 1);
+        let dropped_row = row(&game);
+        assert!(spawn_row - dropped_row <= 1);
+        assert_eq!(
+            controls.input_states.get(&Controls::SoftDrop),
+            Some(&InputState::Up)
+        );
+
+        // it doesn't start repeating, and gravity hasn't ticked yet
+        let frames = (game.gravity_delay / script::FRAME_TIME) as usize - 2;
+        play(&mut game, &mut controls, InputFrame::default(), frames);
+        assert_eq!(row(&game), dropped_row);
+    }
+}
@@ -0,0 +1,311 @@
+use crate::{daily, profiles::ProfileStore, storage, summary};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt::Display, fs, path::Path};
+
+const HIGH_SCORES_FILE: &str = "high_scores.json";
+// increased when the file's format changes, older files are migrated when they're read
+const HIGH_SCORES_VERSION: u32 = 1;
+pub const MAX_HIGH_SCORES: usize = 10; // scores kept for each mode
+pub const INITIALS_LENGTH: usize = 3;
+const NO_INITIALS: &str = "---"; // recorded when the player doesn't type any
+
+// The modes with their own high scores, practice and sandbox games aren't recorded
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameMode {
+    #[default]
+    Marathon,
+    Mystery,
+    Daily(u32), // each day's challenge has its own scores, the date is yyyymmdd
+}
+
+impl GameMode {
+    /// the modes shown on the high scores screen, the daily challenge is today's
+    pub fn shown(today: u32) -> [GameMode; 3] {
+        [
+            GameMode::Marathon,
+            GameMode::Mystery,
+            GameMode::Daily(today),
+        ]
+    }
+
+    // the name the mode's scores are saved under
+    fn key(&self) -> String {
+        match self {
+            GameMode::Daily(date) => format!("Daily {date}"),
+            mode => mode.to_string(),
+        }
+    }
+}
+
+impl Display for GameMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameMode::Marathon => write!(f, "Marathon"),
+            GameMode::Mystery => write!(f, "Mystery"),
+            GameMode::Daily(date) => write!(f, "Daily {}", daily::format_date(*date)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighScore {
+    pub initials: String,
+    pub score: usize,
+    pub lines: usize,
+    pub level: usize,
+    pub timestamp: u64, // seconds since the unix epoch when the game ended
+}
+
+// The best scores of each mode, highest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighScores {
+    version: u32,
+    modes: HashMap<String, Vec<HighScore>>, // keyed by GameMode::key
+    // the mode shown on the high scores screen
+    #[serde(skip)]
+    pub shown_mode: GameMode,
+    // the file was written by a newer version of rustris, it's never overwritten
+    #[serde(skip)]
+    read_only: bool,
+}
+
+impl Default for HighScores {
+    fn default() -> Self {
+        HighScores {
+            version: HIGH_SCORES_VERSION,
+            modes: HashMap::new(),
+            shown_mode: GameMode::default(),
+            read_only: false,
+        }
+    }
+}
+
+impl HighScores {
+    /// Reads the active profile's high scores.
+    /// A file that can't be read is backed up next to it and the scores start fresh,
+    /// a file from a newer version is left as it is and no scores are saved
+    pub fn load(profiles: &ProfileStore) -> Self {
+        let path = profiles.data_file(HIGH_SCORES_FILE);
+        if !path.exists() {
+            return HighScores::default();
+        }
+        log::info!("loading high scores: {:?}", path);
+        HighScores::read(&path)
+    }
+
+    fn read(path: &Path) -> Self {
+        let value = storage::read_json(path).map_err(|e| e.to_string());
+        if let Some(version) = value
+            .as_ref()
+            .ok()
+            .and_then(file_version)
+            .filter(|version| *version > HIGH_SCORES_VERSION as u64)
+        {
+            log::warn!(
+                "high scores {:?} are from a newer version ({}), they won't be changed",
+                path,
+                version
+            );
+            return HighScores {
+                read_only: true,
+                ..HighScores::default()
+            };
+        }
+        match value.and_then(migrate) {
+            Ok(high_scores) => high_scores,
+            Err(e) => {
+                // each recovery keeps its own backup
+                let backup_path = path.with_extension(format!("json.{}.bak", summary::unix_time()));
+                log::warn!(
+                    "unable to read high scores {:?}: {}, backing up to {:?}",
+                    path,
+                    e,
+                    backup_path
+                );
+                if let Err(e) = fs::rename(path, &backup_path) {
+                    log::error!("unable to back up high scores {:?}: {}", path, e);
+                }
+                HighScores::default()
+            }
+        }
+    }
+
+    pub fn save(&self, profiles: &ProfileStore) {
+        self.write(&profiles.data_file(HIGH_SCORES_FILE));
+    }
+
+    fn write(&self, path: &Path) {
+        if self.read_only {
+            log::warn!("not saving high scores over a newer version's {:?}", path);
+            return;
+        }
+        if let Err(e) = storage::write_json(path, self) {
+            log::error!("unable to save high scores {:?}: {}", path, e);
+        }
+    }
+
+    /// the mode's scores, highest first
+    pub fn scores(&self, mode: GameMode) -> &[HighScore] {
+        self.modes
+            .get(&mode.key())
+            .map_or(&[], |scores| scores.as_slice())
+    }
+
+    pub fn best(&self, mode: GameMode) -> Option<usize> {
+        self.scores(mode).first().map(|high_score| high_score.score)
+    }
+
+    /// true if the score would make the mode's list
+    pub fn qualifies(&self, mode: GameMode, score: usize) -> bool {
+        let scores = self.scores(mode);
+        !self.read_only
+            && score > 0
+            && (scores.len() < MAX_HIGH_SCORES || scores.iter().any(|high| score > high.score))
+    }
+
+    /// Adds the score to the mode's list, ties are placed after the earlier scores
+    pub fn insert(
+        &mut self,
+        mode: GameMode,
+        initials: &str,
+        score: usize,
+        lines: usize,
+        level: usize,
+    ) {
+        let initials = if initials.is_empty() {
+            NO_INITIALS.to_owned()
+        } else {
+            initials.to_uppercase()
+        };
+        let timestamp = summary::unix_time();
+        log::info!("new {} high score: {} {}", mode, initials, score);
+        let scores = self.modes.entry(mode.key()).or_default();
+        let rank = scores.partition_point(|high_score| high_score.score >= score);
+        scores.insert(
+            rank,
+            HighScore {
+                initials,
+                score,
+                lines,
+                level,
+                timestamp,
+            },
+        );
+        scores.truncate(MAX_HIGH_SCORES);
+    }
+}
+
+fn file_version(value: &serde_json::Value) -> Option<u64> {
+    value.get("version").and_then(|version| version.as_u64())
+}
+
+/// upgrades the file's contents to the current version,
+/// files written by newer versions of rustris can't be read
+fn migrate(value: serde_json::Value) -> Result<HighScores, String> {
+    let version = file_version(&value).ok_or("missing version")?;
+    // each older version is converted to the next one here before parsing
+    match version {
+        v if v == HIGH_SCORES_VERSION as u64 => {
+            serde_json::from_value(value).map_err(|e| e.to_string())
+        }
+        v => Err(format!("unsupported version {v}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    // an empty folder for a test's files
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rustris-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn filled(scores: &[usize]) -> HighScores {
+        let mut high_scores = HighScores::default();
+        for score in scores {
+            high_scores.insert(GameMode::Marathon, "abc", *score, 0, 0);
+        }
+        high_scores
+    }
+
+    #[test]
+    fn qualification() {
+        let mode = GameMode::Marathon;
+        assert!(!HighScores::default().qualifies(mode, 0));
+        assert!(HighScores::default().qualifies(mode, 1));
+
+        let full = filled(&[100; MAX_HIGH_SCORES]);
+        assert!(!full.qualifies(mode, 100));
+        assert!(full.qualifies(mode, 101));
+        // each mode has its own list
+        assert!(full.qualifies(GameMode::Mystery, 1));
+    }
+
+    #[test]
+    fn ordering() {
+        let mut high_scores = filled(&[300, 100, 200]);
+        high_scores.insert(GameMode::Marathon, "", 200, 0, 0);
+        let scores: Vec<_> = high_scores
+            .scores(GameMode::Marathon)
+            .iter()
+            .map(|high| (high.score, high.initials.as_str()))
+            .collect();
+        // ties are placed after the earlier score
+        assert_eq!(
+            scores,
+            [(300, "ABC"), (200, "ABC"), (200, NO_INITIALS), (100, "ABC")]
+        );
+        assert_eq!(high_scores.best(GameMode::Marathon), Some(300));
+    }
+
+    #[test]
+    fn truncation() {
+        let scores: Vec<usize> = (1..=MAX_HIGH_SCORES + 5).collect();
+        let high_scores = filled(&scores);
+        let kept = high_scores.scores(GameMode::Marathon);
+        assert_eq!(kept.len(), MAX_HIGH_SCORES);
+        assert_eq!(kept.last().unwrap().score, 6);
+    }
+
+    #[test]
+    fn corrupt_file_is_backed_up() {
+        let dir = test_dir("corrupt-scores");
+        let path = dir.join(HIGH_SCORES_FILE);
+        fs::write(&path, "{ not json").unwrap();
+
+        let high_scores = HighScores::read(&path);
+        assert!(high_scores.scores(GameMode::Marathon).is_empty());
+        assert!(!path.exists());
+        let backups: Vec<_> = fs::read_dir(&dir).unwrap().flatten().collect();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(fs::read_to_string(backups[0].path()).unwrap(), "{ not json");
+
+        // the fresh scores can be saved and read back
+        filled(&[500]).write(&path);
+        assert_eq!(HighScores::read(&path).best(GameMode::Marathon), Some(500));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn newer_file_is_left_alone() {
+        let dir = test_dir("newer-scores");
+        let path = dir.join(HIGH_SCORES_FILE);
+        let contents = format!(
+            r#"{{"version": {}, "scores": []}}"#,
+            HIGH_SCORES_VERSION + 1
+        );
+        fs::write(&path, &contents).unwrap();
+
+        let high_scores = HighScores::read(&path);
+        assert!(!high_scores.qualifies(GameMode::Marathon, 1000));
+        high_scores.write(&path);
+        assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,311 @@
+use serde::Deserialize;
+use std::{collections::HashMap, fs, path::Path};
+
+const LANGUAGES_FOLDER: &str = "lang";
+const LANGUAGE_FILE_EXTENSION: &str = "json";
+
+// The text shown by the view, a language file in assets/lang replaces any of it,
+// missing strings stay in English.
+// The font only has ASCII characters, others are drawn as blanks
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Strings {
+    // the name shown by the language setting
+    pub language: String,
+    pub title: String,
+    pub welcome: String,
+    pub level: String,
+    pub time: String,
+    pub session: String,
+    pub score: String,
+    pub sandbox: String,
+    pub daily: String, // {} is replaced by the date
+    pub hold_locked: String,
+    pub paused: String,
+    pub low_frame_rate: String,
+    pub muted: String,
+    pub settings: String,
+    pub settings_keys: String,
+    pub debug_board: String,
+    pub debug_board_prompt: [String; 2],
+    pub debug_board_keys: String,
+    pub practice: String,
+    pub practice_prompt: String,
+    pub practice_cycling: String,
+    pub practice_keys: String,
+    pub whats_new: String,
+    pub whats_new_keys: String,
+    pub high_scores: String,
+    pub high_scores_keys: String,
+    pub no_high_scores: String,
+    pub replays: String,
+    pub replays_keys: String,
+    pub no_replays: String,
+    pub replay_keys: String,
+    pub replay_matched: String,
+    pub initials_prompt: String,
+    pub initials_keys: String,
+    pub summary_keys: String,
+    pub summary_failed: String,
+    pub clip_keys: String, // shown with the summary keys when clips can be saved
+    pub clip_saving: String,
+    pub clip_failed: String,
+    pub profiles: String,
+    pub profiles_keys: String,
+    pub profile_prompt: String,
+    pub profile_input_keys: String,
+    pub versus_draw: String,
+    pub versus_keys: String,
+    pub versus_controls: [String; 2], // each player's keys, shown under their board
+    pub versus_online_keys: String,
+    pub versus_you: String, // under the local player's board online or against the cpu
+    pub versus_cpu: String,
+    pub online: String,
+    pub online_prompt: String,
+    pub online_keys: String,
+    pub online_waiting_keys: String,
+    pub confirmation_keys: String,
+    pub game_over: String,
+    pub play_again: String,
+    pub controls: String,
+    pub controls_help: Vec<String>,
+    // {} is replaced by the value
+    pub music_volume: String,
+    pub now_playing: String,
+    pub level_up: String,
+    pub score_milestone: String,
+    pub best_score: String,
+    pub summary_saved: String,
+    pub profile: String,
+    pub profile_delete: String,
+    pub versus_player: String,
+    pub versus_wins: String,
+    pub online_hosting: String,
+    pub online_joining: String,
+    pub replay_speed: String,
+    pub replay_desynced: String,
+    // the rest of the text keyed by its English text: menu items, confirmations,
+    // settings and their values, modifiers, clear names, game modes,
+    // high score columns, profile picker entries, input errors and online match errors
+    pub translations: HashMap<String, String>,
+}
+
+impl Default for Strings {
+    fn default() -> Self {
+        Strings {
+            language: Strings::ENGLISH.to_owned(),
+            title: "Rustris".to_owned(),
+            welcome: "Welcome to".to_owned(),
+            level: "Level:".to_owned(),
+            time: "Time:".to_owned(),
+            session: "Session:".to_owned(),
+            score: "Score:".to_owned(),
+            sandbox: "Sandbox: L Locks".to_owned(),
+            daily: "Daily {}".to_owned(),
+            hold_locked: "Locked".to_owned(),
+            paused: "Paused".to_owned(),
+            low_frame_rate: "Performance Warning: Low Frame Rate".to_owned(),
+            muted: "MUTED".to_owned(),
+            settings: "Settings".to_owned(),
+            settings_keys: "Left/Right: Change   Esc: Back".to_owned(),
+            debug_board: "Debug Board".to_owned(),
+            debug_board_prompt: [
+                "Garbage Height (e.g. 8) or Board".to_owned(),
+                "Rows From The Bottom (e.g. IIII.LLLJJ/T..)".to_owned(),
+            ],
+            debug_board_keys: "Enter: Load   Esc: Back".to_owned(),
+            practice: "Practice".to_owned(),
+            practice_prompt: "Opening Sequence (I O T L J S Z):".to_owned(),
+            practice_cycling: "Tab: Cycle Piece (Unscored)".to_owned(),
+            practice_keys: "Enter: Start   Esc: Back".to_owned(),
+            whats_new: "What's New".to_owned(),
+            whats_new_keys: "Up/Down: Scroll   Esc: Back".to_owned(),
+            high_scores: "High Scores".to_owned(),
+            high_scores_keys: "Left/Right: Mode   Esc: Back".to_owned(),
+            no_high_scores: "No Scores Yet".to_owned(),
+            replays: "Replays".to_owned(),
+            replays_keys: "Enter: Watch   Esc: Back".to_owned(),
+            no_replays: "No Replays Yet".to_owned(),
+            replay_keys: "Space: Pause   Left/Right: 5s   +/-: Speed   Esc: Back".to_owned(),
+            replay_matched: "Final Score Matches The Recording".to_owned(),
+            initials_prompt: "New High Score! Enter Your Initials:".to_owned(),
+            initials_keys: "Enter: Save".to_owned(),
+            summary_keys: "T: Save Summary   P: Save Summary Image".to_owned(),
+            summary_failed: "Unable To Save Summary".to_owned(),
+            clip_keys: "G: Save Clip".to_owned(),
+            clip_saving: "Saving Clip...".to_owned(),
+            clip_failed: "Unable To Save Clip".to_owned(),
+            profiles: "Profiles".to_owned(),
+            profiles_keys: "Enter: Select   R: Rename   Del: Delete   Esc: Back".to_owned(),
+            profile_prompt: "Profile Name:".to_owned(),
+            profile_input_keys: "Enter: Save   Esc: Cancel".to_owned(),
+            versus_draw: "Draw!".to_owned(),
+            versus_keys: "Enter: Rematch   Esc: Menu".to_owned(),
+            versus_controls: [
+                "Move: A D   Rotate: W Q   Drop: S Space   Hold: LShift".to_owned(),
+                "Move: Left Right   Rotate: Up RCtrl   Drop: Down RShift   Hold: /".to_owned(),
+            ],
+            versus_online_keys: "Esc: Menu".to_owned(),
+            versus_you: "You".to_owned(),
+            versus_cpu: "CPU".to_owned(),
+            online: "Online Versus".to_owned(),
+            online_prompt: "Host Address To Join:".to_owned(),
+            online_keys: "Enter: Join   Tab: Host   Esc: Back".to_owned(),
+            online_waiting_keys: "Esc: Cancel".to_owned(),
+            confirmation_keys: "Enter: Yes   Esc: No".to_owned(),
+            game_over: "Game Over!".to_owned(),
+            play_again: "Press Enter To Play Again".to_owned(),
+            controls: "Controls:".to_owned(),
+            controls_help: [
+                "Move Left: Left, A",
+                "Move Right: Right, D",
+                "Rotate CW: Up, W",
+                "Rotate CCW: LCtrl, Z",
+                "Soft Drop: Down, S",
+                "Hard Drop: Space",
+                "Hold: LShift, C",
+                "Toggle Ghost: G",
+                "Adjust Music Volume: + -",
+                "Mute: M",
+            ]
+            .map(str::to_owned)
+            .to_vec(),
+            music_volume: "Music Volume: {}%".to_owned(),
+            now_playing: "Now Playing: {}".to_owned(),
+            level_up: "Level {}!".to_owned(),
+            score_milestone: "{} Points!".to_owned(),
+            best_score: "Best: {}".to_owned(),
+            summary_saved: "Saved {}".to_owned(),
+            profile: "Profile: {}".to_owned(),
+            profile_delete: "Delete {}?".to_owned(),
+            versus_player: "Player {}".to_owned(),
+            versus_wins: "Player {} Wins!".to_owned(),
+            online_hosting: "Waiting For An Opponent On Port {}".to_owned(),
+            online_joining: "Connecting To {}".to_owned(),
+            replay_speed: "Speed: {}x".to_owned(),
+            replay_desynced: "Desync! Recorded Score: {}".to_owned(),
+            translations: HashMap::new(),
+        }
+    }
+}
+
+impl Strings {
+    pub const ENGLISH: &'static str = "English";
+
+    /// the translation of the English text, or the text if it isn't translated
+    pub fn translate<'a>(&'a self, english: &'a str) -> &'a str {
+        self.translations
+            .get(english)
+            .map_or(english, |translation| translation.as_str())
+    }
+}
+
+/// replaces the {} in one of the strings with the value
+pub fn fill(template: &str, value: impl std::fmt::Display) -> String {
+    template.replacen("{}", &value.to_string(), 1)
+}
+
+// English followed by the languages found in the assets folder
+pub struct Languages {
+    languages: Vec<Strings>,
+}
+
+impl Languages {
+    /// Loads every language file in assets/lang, sorted by file name
+    pub fn load(assets_path: &Path) -> Self {
+        let languages_path = assets_path.join(LANGUAGES_FOLDER);
+        let mut language_paths: Vec<_> = match fs::read_dir(&languages_path) {
+            Ok(entries) => entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.extension()
+                        .is_some_and(|extension| extension == LANGUAGE_FILE_EXTENSION)
+                })
+                .collect(),
+            Err(e) => {
+                log::info!("no languages folder {:?}: {}", languages_path, e);
+                vec![]
+            }
+        };
+        language_paths.sort();
+        let mut languages = vec![Strings::default()];
+        for language_path in language_paths {
+            log::info!("loading language: {:?}", language_path);
+            let strings: Result<Strings, String> = fs::read_to_string(&language_path)
+                .map_err(|e| e.to_string())
+                .and_then(|contents| serde_json::from_str(&contents).map_err(|e| e.to_string()));
+            match strings {
+                Ok(mut strings) => {
+                    // files that don't name their language are named after the file
+                    if strings.language == Strings::ENGLISH {
+                        strings.language = language_path
+                            .file_stem()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .into_owned();
+                    }
+                    languages.push(strings)
+                }
+                Err(e) => log::warn!("unable to read language {:?}: {}", language_path, e),
+            }
+        }
+        Languages { languages }
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.languages
+            .iter()
+            .map(|strings| strings.language.clone())
+            .collect()
+    }
+
+    /// returns the language with the provided name or English
+    pub fn get(&self, name: &str) -> &Strings {
+        self.languages
+            .iter()
+            .find(|strings| strings.language == name)
+            .unwrap_or(&self.languages[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_languages_load_within_the_font() {
+        let languages = Languages::load(Path::new("assets"));
+        assert_eq!(languages.names(), [Strings::ENGLISH, "Espanol"]);
+        for entry in fs::read_dir(Path::new("assets").join(LANGUAGES_FOLDER)).unwrap() {
+            let path = entry.unwrap().path();
+            assert!(fs::read_to_string(&path).unwrap().is_ascii(), "{path:?}");
+        }
+
+        let english = Strings::default();
+        let espanol = languages.get("Espanol");
+        assert_eq!(espanol.title, english.title);
+        assert_eq!(espanol.translate("New Game"), "Nueva Partida");
+        assert_eq!(espanol.translate("Not Translated"), "Not Translated");
+        // the values still have somewhere to go
+        for template in [
+            &espanol.daily,
+            &espanol.music_volume,
+            &espanol.now_playing,
+            &espanol.level_up,
+            &espanol.score_milestone,
+            &espanol.best_score,
+            &espanol.summary_saved,
+            &espanol.profile,
+            &espanol.profile_delete,
+            &espanol.versus_player,
+            &espanol.versus_wins,
+            &espanol.online_hosting,
+            &espanol.online_joining,
+            &espanol.replay_speed,
+            &espanol.replay_desynced,
+        ] {
+            assert!(template.contains("{}"), "{template}");
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{audio::SoundEffects, sound::Music, theme::Themes};
+use macroquad::prelude::*;
+use std::{
+    future::{poll_fn, Future},
+    path::Path,
+    pin::pin,
+    task::Poll,
+};
+
+const FONT_FILE: &str = "04b30.ttf";
+
+// The assets loaded at startup
+pub struct Resources {
+    pub font: Font,
+    pub themes: Themes,
+    pub music: Music,
+    pub sound_effects: SoundEffects,
+}
+
+impl Resources {
+    /// Starts loading every asset at once and waits for them all,
+    /// the font is required, other assets that can't be loaded are logged and left out
+    pub async fn load(assets_path: &Path) -> Self {
+        let start_time = get_time();
+        let font_path = assets_path.join(FONT_FILE).to_string_lossy().into_owned();
+        log::info!("loading font: {:?}", font_path);
+        let ((font, themes), (music, sound_effects)) = join(
+            join(load_ttf_font(&font_path), Themes::load(assets_path)),
+            join(Music::load(assets_path), SoundEffects::load(assets_path)),
+        )
+        .await;
+        log::info!("loaded resources in {:.3}s", get_time() - start_time);
+        Resources {
+            font: font.expect("unable to load font"),
+            themes,
+            music,
+            sound_effects,
+        }
+    }
+}
+
+/// Polls both futures until they're done, so their loads are in flight together
+pub async fn join<A: Future, B: Future>(a: A, b: B) -> (A::Output, B::Output) {
+    let (mut a, mut b) = (pin!(a), pin!(b));
+    let (mut a_output, mut b_output) = (None, None);
+    poll_fn(|cx| {
+        if a_output.is_none() {
+            if let Poll::Ready(output) = a.as_mut().poll(cx) {
+                a_output = Some(output);
+            }
+        }
+        if b_output.is_none() {
+            if let Poll::Ready(output) = b.as_mut().poll(cx) {
+                b_output = Some(output);
+            }
+        }
+        if a_output.is_some() && b_output.is_some() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await;
+    (a_output.unwrap(), b_output.unwrap())
+}
+
+/// Polls every future until they're all done, returns their outputs in order
+pub async fn join_all<F: Future>(futures: Vec<F>) -> Vec<F::Output> {
+    let mut futures: Vec<_> = futures.into_iter().map(Box::pin).collect();
+    let mut outputs: Vec<Option<F::Output>> = futures.iter().map(|_| None).collect();
+    poll_fn(|cx| {
+        let mut done = true;
+        for (future, output) in futures.iter_mut().zip(outputs.iter_mut()) {
+            if output.is_none() {
+                match future.as_mut().poll(cx) {
+                    Poll::Ready(ready) => *output = Some(ready),
+                    Poll::Pending => done = false,
+                }
+            }
+        }
+        if done {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await;
+    outputs.into_iter().flatten().collect()
+}
@@ -1,15 +1,31 @@
 #![cfg_attr(all(not(debug_assertions), windows), windows_subsystem = "windows")]
 use view::window_conf;
 
+mod audio;
+mod clip;
 mod controls;
+mod cpu;
+mod daily;
 mod game;
+mod high_scores;
+mod language;
+mod loading;
+mod menu;
+mod modifier;
+mod online;
 mod playfield;
+mod profiles;
+mod replay;
 mod rustomino;
+mod scoring;
+mod script;
+mod settings;
+mod sound;
+mod storage;
+mod summary;
+mod theme;
 mod view;
 
-// TODO: load icon for rustris window
-// https://docs.rs/macroquad/0.3.25/macroquad/texture/struct.Image.html
-
 #[macroquad::main(window_conf())]
 async fn main() {
     env_logger::builder()
@@ -0,0 +1,191 @@
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuItem {
+    Continue,
+    NewGame,
+    Practice,
+    Mystery,
+    Daily,
+    Sandbox,
+    Versus,
+    VersusCpu,
+    Online,
+    HighScores,
+    Replays,
+    Settings,
+    WhatsNew,
+    Profiles,
+    Resume,
+    SaveAndQuit,
+    Exit,
+}
+
+impl MenuItem {
+    /// Items that discard the saved game ask the player for confirmation
+    /// before they are performed, returns the confirmation prompt
+    pub fn confirmation(&self) -> Option<&'static str> {
+        match self {
+            MenuItem::NewGame
+            | MenuItem::Practice
+            | MenuItem::Mystery
+            | MenuItem::Daily
+            | MenuItem::Sandbox => Some("Overwrite Saved Game?"),
+            _ => None,
+        }
+    }
+}
+
+impl Display for MenuItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MenuItem::Continue => write!(f, "Continue"),
+            MenuItem::NewGame => write!(f, "New Game"),
+            MenuItem::Practice => write!(f, "Practice"),
+            MenuItem::Mystery => write!(f, "Mystery"),
+            MenuItem::Daily => write!(f, "Daily Challenge"),
+            MenuItem::Sandbox => write!(f, "Sandbox"),
+            MenuItem::Versus => write!(f, "Versus"),
+            MenuItem::VersusCpu => write!(f, "Versus CPU"),
+            MenuItem::Online => write!(f, "Online Versus"),
+            MenuItem::HighScores => write!(f, "High Scores"),
+            MenuItem::Replays => write!(f, "Replays"),
+            MenuItem::Settings => write!(f, "Settings"),
+            MenuItem::WhatsNew => write!(f, "What's New"),
+            MenuItem::Profiles => write!(f, "Switch Profile"),
+            MenuItem::Resume => write!(f, "Resume"),
+            MenuItem::SaveAndQuit => write!(f, "Save & Quit"),
+            MenuItem::Exit => write!(f, "Exit"),
+        }
+    }
+}
+
+// An entry on the profile picker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileItem {
+    Profile(usize), // the index of the profile's name in the store
+    Guest,
+    New,
+}
+
+// A list of items the player can navigate with the up and down keys
+#[derive(Debug, Clone)]
+pub struct Menu<T> {
+    pub items: Vec<T>,
+    pub selected: usize,
+    // the item waiting for the player to confirm it
+    pub confirming: Option<T>,
+}
+
+impl<T: Copy> Menu<T> {
+    pub fn new(items: Vec<T>) -> Self {
+        Menu {
+            items,
+            selected: 0,
+            confirming: None,
+        }
+    }
+
+    pub fn selected_item(&self) -> Option<T> {
+        self.items.get(self.selected).copied()
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.items.is_empty() {
+            self.selected = (self.selected + 1) % self.items.len();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.items.is_empty() {
+            self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+        }
+    }
+}
+
+// Text typed by the player
+#[derive(Debug, Clone)]
+pub struct TextInput {
+    pub text: String,
+    // shown below the text when it couldn't be used
+    pub error: Option<String>,
+    max_length: usize,
+}
+
+impl Default for TextInput {
+    fn default() -> Self {
+        TextInput::with_max_length(40)
+    }
+}
+
+impl TextInput {
+    pub fn with_max_length(max_length: usize) -> Self {
+        TextInput {
+            text: String::new(),
+            error: None,
+            max_length,
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        if !c.is_control() && self.text.chars().count() < self.max_length {
+            self.text.push(c);
+            self.error = None;
+        }
+    }
+
+    pub fn pop(&mut self) {
+        self.text.pop();
+        self.error = None;
+    }
+}
+
+// Lines of text the player can scroll through with the up and down keys
+#[derive(Debug, Clone, Default)]
+pub struct ScrollText {
+    pub lines: Vec<String>,
+    pub scroll: usize, // index of the first line shown
+}
+
+impl ScrollText {
+    pub const VISIBLE_LINES: usize = 14;
+    const WRAP_WIDTH: usize = 40; // characters
+
+    /// Splits the text into lines, wrapping the long ones between words
+    pub fn new(text: &str) -> Self {
+        let mut lines = vec![];
+        for paragraph in text.lines() {
+            let mut line = String::new();
+            for word in paragraph.split_whitespace() {
+                if !line.is_empty()
+                    && line.chars().count() + 1 + word.chars().count() > Self::WRAP_WIDTH
+                {
+                    lines.push(std::mem::take(&mut line));
+                }
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                line.push_str(word);
+            }
+            lines.push(line);
+        }
+        ScrollText { lines, scroll: 0 }
+    }
+
+    /// the lines that fit in the panel from the scroll position
+    pub fn visible_lines(&self) -> &[String] {
+        let end = (self.scroll + Self::VISIBLE_LINES).min(self.lines.len());
+        &self.lines[self.scroll..end]
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    /// scrolls down until the last line is at the bottom of the panel
+    pub fn scroll_down(&mut self) {
+        if self.scroll + Self::VISIBLE_LINES < self.lines.len() {
+            self.scroll += 1;
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use crate::controls::Controls;
+use ::rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use strum::{EnumIter, IntoEnumIterator};
+
+const FAST_GRAVITY_FACTOR: f64 = 0.5; // multiplies the gravity delay
+const BONUS_SCORE_MULTIPLIER: usize = 2;
+
+// A mystery mode modifier, a random one is applied each time the level increases
+// and it lasts until the next one replaces it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
+pub enum Modifier {
+    // locked blocks aren't drawn
+    InvisiblePieces,
+    // left and right are swapped, as are the rotations
+    FlippedControls,
+    // gravity is twice as fast
+    FastGravity,
+    // scored clears are worth double
+    BonusScoring,
+}
+
+impl Modifier {
+    /// picks a random modifier that's different from the previous one
+    pub fn random(previous: Option<Modifier>, rng: &mut impl Rng) -> Self {
+        let modifiers: Vec<Modifier> = Modifier::iter()
+            .filter(|modifier| Some(*modifier) != previous)
+            .collect();
+        *modifiers.choose(rng).unwrap()
+    }
+
+    /// the control performed when the player uses the provided control
+    pub fn control(&self, control: &Controls) -> Controls {
+        match (self, control) {
+            (Modifier::FlippedControls, Controls::Left) => Controls::Right,
+            (Modifier::FlippedControls, Controls::Right) => Controls::Left,
+            (Modifier::FlippedControls, Controls::RotateCW) => Controls::RotateCCW,
+            (Modifier::FlippedControls, Controls::RotateCCW) => Controls::RotateCW,
+            _ => control.clone(),
+        }
+    }
+
+    /// multiplies the gravity delay
+    pub fn gravity_factor(&self) -> f64 {
+        match self {
+            Modifier::FastGravity => FAST_GRAVITY_FACTOR,
+            _ => 1.,
+        }
+    }
+
+    /// multiplies the score of each clear
+    pub fn score_multiplier(&self) -> usize {
+        match self {
+            Modifier::BonusScoring => BONUS_SCORE_MULTIPLIER,
+            _ => 1,
+        }
+    }
+
+    /// true if locked blocks are hidden
+    pub fn hides_locked_blocks(&self) -> bool {
+        *self == Modifier::InvisiblePieces
+    }
+}
+
+impl Display for Modifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Modifier::InvisiblePieces => write!(f, "Invisible Pieces"),
+            Modifier::FlippedControls => write!(f, "Flipped Controls"),
+            Modifier::FastGravity => write!(f, "Fast Gravity"),
+            Modifier::BonusScoring => write!(f, "Double Score"),
+        }
+    }
+}
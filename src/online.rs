@@ -0,0 +1,489 @@
+use crate::{controls::InputFrame, settings::Settings};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+pub const ONLINE_PORT: u16 = 7878;
+pub const ADDRESS_LENGTH: usize = 64;
+// online matches run at a fixed rate so both players simulate the same frames
+pub const FRAME_TIME: f64 = 1. / 60.;
+const MAX_FRAMES_PER_UPDATE: f64 = 4.; // frames caught up at once after waiting for the opponent
+const INPUT_DELAY: usize = 3; // frames between reading the keys and playing them, hides the latency
+const HASH_INTERVAL: u64 = 60; // frames between the board hashes compared to detect a desync
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const SILENCE_TIMEOUT: f64 = 10.; // seconds without a message before the opponent is gone
+const READ_BUFFER_SIZE: usize = 4096;
+// the longest message read, an opponent sending more without ending it is disconnected
+const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+// the 64 bit FNV-1a hash
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// true for the characters of a host name or ip address with a port
+pub fn is_address_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '.' || c == ':' || c == '-'
+}
+
+// Hashes the state both players compare with FNV-1a,
+// it's the same on every build and platform unlike the standard library's hasher
+pub struct StateHasher {
+    hash: u64,
+}
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        StateHasher {
+            hash: FNV_OFFSET_BASIS,
+        }
+    }
+}
+
+impl StateHasher {
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.hash ^= *byte as u64;
+            self.hash = self.hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    /// hashes the number as 8 little endian bytes whatever the platform's usize
+    pub fn write_number(&mut self, number: usize) {
+        self.write(&(number as u64).to_le_bytes());
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+// What the players send each other, one json message per line
+#[derive(Debug, Serialize, Deserialize)]
+enum Message {
+    // sent by the host once the opponent has joined, both games are played with its settings
+    Start {
+        version: String,
+        seed: u64,
+        settings: Box<Settings>,
+    },
+    // the player's keys for the frame
+    Input {
+        frame: u64,
+        input: InputFrame,
+    },
+    // a hash of both boards after the frame
+    Hash {
+        frame: u64,
+        hash: u64,
+    },
+    Leave,
+}
+
+// The connection to the opponent, messages are queued until the socket is ready for them
+struct Peer {
+    stream: TcpStream,
+    received: Vec<u8>, // the start of a message that hasn't fully arrived
+    unsent: Vec<u8>,
+    messages: VecDeque<Message>,
+    silent_time: f64,       // seconds since the last message arrived
+    closed: Option<String>, // why the connection ended, the messages before it are still read
+}
+
+impl Peer {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Peer {
+            stream,
+            received: vec![],
+            unsent: vec![],
+            messages: VecDeque::new(),
+            silent_time: 0.,
+            closed: None,
+        })
+    }
+
+    fn send(&mut self, message: &Message) {
+        match serde_json::to_vec(message) {
+            Ok(line) => {
+                self.unsent.extend(line);
+                self.unsent.push(b'\n');
+            }
+            Err(e) => log::error!("unable to encode message {:?}: {}", message, e),
+        }
+    }
+
+    // sends the queued messages and reads the ones that arrived,
+    // the connection is closed by an error or the opponent being silent too long
+    fn exchange(&mut self, delta_time: f64) {
+        if self.closed.is_some() {
+            return;
+        }
+        while !self.unsent.is_empty() {
+            match self.stream.write(&self.unsent) {
+                Ok(0) => return self.close("Connection Lost"),
+                Ok(written) => {
+                    self.unsent.drain(..written);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    log::warn!("unable to send to the opponent: {}", e);
+                    return self.close("Connection Lost");
+                }
+            }
+        }
+        self.silent_time += delta_time;
+        let mut buffer = [0; READ_BUFFER_SIZE];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => {
+                    self.close("Opponent Left");
+                    break;
+                }
+                Ok(read) => {
+                    self.silent_time = 0.;
+                    self.received.extend_from_slice(&buffer[..read]);
+                    // the complete messages are read before any more arrive
+                    if self.received.len() > MAX_MESSAGE_SIZE {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    log::warn!("unable to read from the opponent: {}", e);
+                    self.close("Connection Lost");
+                    break;
+                }
+            }
+        }
+        while let Some(end) = self.received.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.received.drain(..=end).collect();
+            match serde_json::from_slice(&line) {
+                Ok(Message::Leave) => self.close("Opponent Left"),
+                Ok(message) => self.messages.push_back(message),
+                Err(e) => {
+                    log::warn!("unable to read message from the opponent: {}", e);
+                    self.close("Connection Lost");
+                }
+            }
+        }
+        if self.received.len() > MAX_MESSAGE_SIZE {
+            log::warn!("message from the opponent over {} bytes", MAX_MESSAGE_SIZE);
+            self.received.clear();
+            self.close("Connection Lost");
+        }
+        if self.silent_time > SILENCE_TIMEOUT {
+            self.close("Connection Lost");
+        }
+    }
+
+    fn close(&mut self, reason: &str) {
+        if self.closed.is_none() {
+            log::info!("online connection closed: {}", reason);
+            self.closed = Some(reason.to_owned());
+        }
+    }
+}
+
+// The opponent's connection and the match it starts, returned by the lobby
+pub struct Connected {
+    peer: Peer,
+    pub local: usize, // the host is player 1 and the player who joined is player 2
+    pub seed: u64,
+    pub settings: Settings,
+}
+
+// Waiting for the opponent before an online match,
+// the host listens for them and the other player connects by address
+pub struct Lobby {
+    listener: Option<TcpListener>,
+    connecting: Option<JoinHandle<io::Result<TcpStream>>>,
+    peer: Option<Peer>, // connected to the host, waiting for the match to start
+    pub address: Option<String>, // the host being joined, None while hosting
+}
+
+impl Lobby {
+    /// Listens for the opponent on the address from the settings,
+    /// returns the reason it couldn't to show the player
+    pub fn host(address: &str) -> Result<Self, String> {
+        let listener = TcpListener::bind((address, ONLINE_PORT))
+            .and_then(|listener| listener.set_nonblocking(true).map(|()| listener))
+            .map_err(|e| {
+                log::warn!(
+                    "unable to listen on {} port {}: {}",
+                    address,
+                    ONLINE_PORT,
+                    e
+                );
+                "Unable To Host".to_owned()
+            })?;
+        log::info!(
+            "hosting an online match on {} port {}",
+            address,
+            ONLINE_PORT
+        );
+        Ok(Lobby {
+            listener: Some(listener),
+            connecting: None,
+            peer: None,
+            address: None,
+        })
+    }
+
+    /// Connects to the host in the background, the address can leave out the port
+    pub fn join(address: &str) -> Result<Self, String> {
+        let address = address.trim();
+        if address.is_empty() {
+            return Err("Enter An Address".to_owned());
+        }
+        let socket_address = if address.contains(':') {
+            address.to_owned()
+        } else {
+            format!("{address}:{ONLINE_PORT}")
+        };
+        log::info!("joining an online match: {}", socket_address);
+        // looking up the address and connecting can take seconds
+        let connecting = thread::Builder::new()
+            .spawn(move || {
+                let resolved = socket_address
+                    .to_socket_addrs()?
+                    .next()
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address found"))?;
+                TcpStream::connect_timeout(&resolved, CONNECT_TIMEOUT)
+            })
+            .map_err(|e| {
+                log::warn!("unable to start connecting: {}", e);
+                "Unable To Connect".to_owned()
+            })?;
+        Ok(Lobby {
+            listener: None,
+            connecting: Some(connecting),
+            peer: None,
+            address: Some(address.to_owned()),
+        })
+    }
+
+    /// Checks on the opponent, returns the match once both players are connected
+    /// or the reason the match can't be played
+    pub fn update(
+        &mut self,
+        settings: &Settings,
+        delta_time: f64,
+    ) -> Result<Option<Connected>, String> {
+        if let Some(listener) = &self.listener {
+            return match listener.accept() {
+                Ok((stream, address)) => {
+                    log::info!("opponent joined from {}", address);
+                    let mut peer = Peer::new(stream).map_err(|e| {
+                        log::warn!("unable to set up the connection: {}", e);
+                        "Connection Lost".to_owned()
+                    })?;
+                    let seed = ::rand::random();
+                    peer.send(&Message::Start {
+                        version: env!("CARGO_PKG_VERSION").to_owned(),
+                        seed,
+                        settings: Box::new(settings.clone()),
+                    });
+                    Ok(Some(Connected {
+                        peer,
+                        local: 0,
+                        seed,
+                        settings: settings.clone(),
+                    }))
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+                Err(e) => {
+                    log::warn!("unable to accept the opponent: {}", e);
+                    Err("Unable To Host".to_owned())
+                }
+            };
+        }
+        if self
+            .connecting
+            .as_ref()
+            .is_some_and(JoinHandle::is_finished)
+        {
+            let connected = self.connecting.take().map(JoinHandle::join);
+            let stream = match connected {
+                Some(Ok(Ok(stream))) => stream,
+                Some(Ok(Err(e))) => {
+                    log::warn!("unable to connect to {:?}: {}", self.address, e);
+                    return Err("Unable To Connect".to_owned());
+                }
+                _ => return Err("Unable To Connect".to_owned()),
+            };
+            self.peer = Some(Peer::new(stream).map_err(|e| {
+                log::warn!("unable to set up the connection: {}", e);
+                "Connection Lost".to_owned()
+            })?);
+        }
+        let Some(peer) = &mut self.peer else {
+            return Ok(None);
+        };
+        peer.exchange(delta_time);
+        match peer.messages.pop_front() {
+            Some(Message::Start {
+                version,
+                seed,
+                settings,
+            }) => {
+                if version != env!("CARGO_PKG_VERSION") {
+                    log::warn!("the host is playing version {}", version);
+                    return Err("Different Game Version".to_owned());
+                }
+                log::info!("online match started by the host");
+                let peer = self.peer.take().expect("connected to the host");
+                Ok(Some(Connected {
+                    peer,
+                    local: 1,
+                    seed,
+                    settings: *settings,
+                }))
+            }
+            Some(message) => {
+                log::warn!("unexpected message before the match: {:?}", message);
+                Err("Connection Lost".to_owned())
+            }
+            None => match &peer.closed {
+                Some(reason) => Err(reason.clone()),
+                None => Ok(None),
+            },
+        }
+    }
+}
+
+// Keeps both players' games in step over the connection.
+// Each frame is played once both players' keys for it have arrived,
+// the local keys are sent a few frames ahead so the opponent's usually have too
+pub struct OnlineMatch {
+    peer: Peer,
+    pub local: usize,
+    frame: u64,                        // the next frame to play
+    time: f64,                         // time not yet played as frames
+    keys: InputFrame,                  // the local keys since the last frame
+    inputs: [VecDeque<InputFrame>; 2], // each player's keys from the next frame on
+    hashes: [VecDeque<(u64, u64)>; 2], // each player's board hashes waiting to be compared
+    pub error: Option<String>,         // why the match ended early
+}
+
+impl OnlineMatch {
+    pub fn new(connected: Connected) -> Self {
+        OnlineMatch {
+            peer: connected.peer,
+            local: connected.local,
+            frame: 0,
+            time: 0.,
+            keys: InputFrame::default(),
+            // nobody has pressed anything in the first frames
+            inputs: [0, 1].map(|_| vec![InputFrame::default(); INPUT_DELAY].into()),
+            hashes: Default::default(),
+            error: None,
+        }
+    }
+
+    /// Adds the time and the local keys of the render frame and reads the opponent's messages
+    pub fn update(&mut self, keys: InputFrame, delta_time: f64) {
+        self.keys.merge(keys);
+        self.time = (self.time + delta_time).min(FRAME_TIME * MAX_FRAMES_PER_UPDATE);
+        self.peer.exchange(delta_time);
+        let remote = 1 - self.local;
+        while let Some(message) = self.peer.messages.pop_front() {
+            match message {
+                Message::Input { frame, input } => {
+                    let expected = self.frame + self.inputs[remote].len() as u64;
+                    if frame != expected {
+                        log::warn!("input for frame {} arrived, expected {}", frame, expected);
+                        self.peer.close("Connection Lost");
+                    }
+                    self.inputs[remote].push_back(input);
+                }
+                Message::Hash { frame, hash } => self.hashes[remote].push_back((frame, hash)),
+                message => log::warn!("unexpected message during the match: {:?}", message),
+            }
+        }
+    }
+
+    /// Both players' keys for the next frame, None while it's waiting for the opponent's
+    /// or the reason the match can't go on
+    pub fn next_frame(&mut self) -> Result<Option<[InputFrame; 2]>, String> {
+        let remote = 1 - self.local;
+        if self.inputs[remote].is_empty() {
+            return match &self.peer.closed {
+                Some(reason) => Err(reason.clone()),
+                None => Ok(None),
+            };
+        }
+        if self.time < FRAME_TIME {
+            return Ok(None);
+        }
+        self.time -= FRAME_TIME;
+        let keys = self.keys;
+        // the keys are still held in the frames after they're pressed
+        self.keys = keys.held();
+        let frame = self.frame + INPUT_DELAY as u64;
+        self.peer.send(&Message::Input { frame, input: keys });
+        self.inputs[self.local].push_back(keys);
+        let inputs = [0, 1].map(|player| self.inputs[player].pop_front().unwrap_or_default());
+        self.frame += 1;
+        Ok(Some(inputs))
+    }
+
+    /// Compares the boards after the frame just played with the opponent's every few frames,
+    /// returns an error once they differ
+    pub fn frame_played(&mut self, hash: impl FnOnce() -> u64) -> Result<(), String> {
+        if self.frame % HASH_INTERVAL == 0 {
+            let hash = hash();
+            self.peer.send(&Message::Hash {
+                frame: self.frame,
+                hash,
+            });
+            self.hashes[self.local].push_back((self.frame, hash));
+        }
+        while let (Some(local), Some(remote)) = (self.hashes[0].front(), self.hashes[1].front()) {
+            if local != remote {
+                log::error!("desync detected: {:?} {:?}", self.hashes[0], self.hashes[1]);
+                return Err("Desync Detected".to_owned());
+            }
+            self.hashes.iter_mut().for_each(|hashes| {
+                hashes.pop_front();
+            });
+        }
+        Ok(())
+    }
+
+    /// Sends the last messages, the results are shown once the match is over
+    pub fn flush(&mut self, delta_time: f64) {
+        self.peer.exchange(delta_time);
+        self.peer.messages.clear();
+    }
+
+    /// Tells the opponent the player left before the connection is dropped
+    pub fn leave(&mut self) {
+        log::info!("leaving the online match");
+        self.peer.send(&Message::Leave);
+        self.peer.exchange(0.);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_hash_is_fnv_1a() {
+        let hash = |bytes: &[u8]| {
+            let mut hasher = StateHasher::default();
+            hasher.write(bytes);
+            hasher.finish()
+        };
+        assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+}
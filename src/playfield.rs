@@ -1,6 +1,11 @@
-use crate::rustomino::{translated, Rotation, Rustomino, RustominoState, RustominoType};
+use crate::rustomino::{
+    translated, Rotation, RotationKicks, Rustomino, RustominoState, RustominoType,
+};
+use ::rand::{seq::IteratorRandom, Rng};
 use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::{fmt::Display, mem::discriminant};
+use strum::IntoEnumIterator;
 
 pub const PLAYFIELD_SLOTS: [usize; 2] = [10, 22];
 pub const PLAYFIELD_SIZE: [i32; 2] = [10, 20];
@@ -8,11 +13,14 @@ pub const PLAYFIELD_SIZE: [i32; 2] = [10, 20];
 type PlayfieldSlots = [[SlotState; PLAYFIELD_SLOTS[0]]; PLAYFIELD_SLOTS[1]];
 
 // RustrisPlayfield
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RustrisPlayfield {
     pub slots: PlayfieldSlots,
     pub active_rustomino: Option<Rustomino>,
     pub ghost_rustomino: Option<Rustomino>,
+    // the ghost isn't tracked while it's hidden
+    #[serde(skip)]
+    hide_ghost: bool,
 }
 
 impl RustrisPlayfield {
@@ -22,6 +30,29 @@ impl RustrisPlayfield {
             slots: [[SlotState::Empty; PLAYFIELD_SLOTS[0]]; PLAYFIELD_SLOTS[1]],
             active_rustomino: None,
             ghost_rustomino: None,
+            hide_ghost: false,
+        }
+    }
+
+    /// Hides or shows the ghost rustomino,
+    /// hiding it removes the ghost from the playfield
+    pub fn set_hide_ghost(&mut self, hide_ghost: bool) {
+        if hide_ghost == self.hide_ghost {
+            return;
+        }
+        if hide_ghost {
+            if let Some(ghost_rustomino) = self.ghost_rustomino.take() {
+                for slot in ghost_rustomino.playfield_slots() {
+                    if let SlotState::Ghost(_) = self.slots[slot[1] as usize][slot[0] as usize] {
+                        self.slots[slot[1] as usize][slot[0] as usize] = SlotState::Empty;
+                    }
+                }
+            }
+            self.hide_ghost = true;
+        } else {
+            self.hide_ghost = false;
+            self.ghost_rustomino = self.active_rustomino.clone();
+            self.update_ghost_rustomino(false);
         }
     }
 
@@ -37,16 +68,16 @@ impl RustrisPlayfield {
             &rustomino.playfield_slots(),
             SlotState::Occupied(rustomino.rtype),
         );
-        self.ghost_rustomino = Some(rustomino.clone());
+        if !self.hide_ghost {
+            self.ghost_rustomino = Some(rustomino.clone());
+        }
         self.active_rustomino = Some(rustomino);
         self.update_ghost_rustomino(false);
         ok
     }
 
     pub fn take_active(&mut self) -> Option<Rustomino> {
-        let Some(active_rustomino) = self.active_rustomino.take() else {
-            return None;
-        };
+        let active_rustomino = self.active_rustomino.take()?;
         log::debug!("taking active rustomino: {:?}", active_rustomino.rtype);
         log::trace!("rustomino: {:?}", active_rustomino);
         set_playfield_slot_states(
@@ -57,6 +88,28 @@ impl RustrisPlayfield {
         self.update_ghost_rustomino(false);
         Some(active_rustomino.reset())
     }
+    /// true if the rustomino can be placed without colliding
+    pub fn fits(&self, rustomino: &Rustomino) -> bool {
+        !check_collision(&self.slots, rustomino.playfield_slots())
+    }
+
+    /// moves a spawning rustomino up into the buffer rows until it doesn't collide,
+    /// it's returned unchanged if it already fits or collides at every height
+    pub fn nudge_up(&self, rustomino: Rustomino) -> Rustomino {
+        let fits = (0..PLAYFIELD_SLOTS[1] as i32)
+            .map(|nudge| ivec2(0, nudge))
+            .find(|nudge| !check_collision(&self.slots, rustomino.translated(nudge)));
+        match fits {
+            Some(nudge) if nudge != IVec2::ZERO => {
+                log::info!("nudging spawning rustomino up by {}", nudge.y);
+                let mut rustomino = rustomino;
+                rustomino.translate(nudge);
+                rustomino
+            }
+            _ => rustomino,
+        }
+    }
+
     /// checks to see if the playfield needs the next rustomino
     pub fn ready_for_next(&self) -> bool {
         self.active_rustomino.is_none()
@@ -94,13 +147,14 @@ impl RustrisPlayfield {
     }
 
     /// Attempt to rotate the active rustomino
-    pub fn rotate_active(&mut self, rotation: Rotation) -> bool {
-        let Some(active_rustomino) = self.active_rustomino.as_mut()  else {
+    pub fn rotate_active(&mut self, rotation: Rotation, kicks: RotationKicks) -> bool {
+        let Some(active_rustomino) = self.active_rustomino.as_mut() else {
             return false;
         };
 
         // check to see if the block can be rotated with or without a wall kick
-        let Some(wall_kick_trans) = check_rotation(&self.slots, active_rustomino, &rotation)  else {
+        let Some(wall_kick_trans) = check_rotation(&self.slots, active_rustomino, &rotation, kicks)
+        else {
             return false;
         };
 
@@ -126,6 +180,42 @@ impl RustrisPlayfield {
         true
     }
 
+    /// Attempt to change the type of the active rustomino
+    /// keeping its position and rotation.
+    /// Returns false if the new type would collide
+    pub fn change_active_type(&mut self, rtype: RustominoType) -> bool {
+        let Some(active_rustomino) = &self.active_rustomino else {
+            return false;
+        };
+
+        let changed = active_rustomino.with_type(rtype);
+        if check_collision(&self.slots, changed.playfield_slots()) {
+            return false;
+        }
+
+        // clear the current slot states
+        set_playfield_slot_states(
+            &mut self.slots,
+            &active_rustomino.playfield_slots(),
+            SlotState::Empty,
+        );
+
+        // set the new slot states to occupied
+        set_playfield_slot_states(
+            &mut self.slots,
+            &changed.playfield_slots(),
+            SlotState::Occupied(rtype),
+        );
+        self.active_rustomino = Some(changed);
+
+        if let Some(ghost_rustomino) = self.ghost_rustomino.as_mut() {
+            ghost_rustomino.rtype = rtype;
+        }
+        self.update_ghost_rustomino(true);
+
+        true
+    }
+
     /// Attempt to translate the active rustomino.
     /// Return true if possible
     pub fn translate_active(&mut self, direction: TranslationDirection) -> bool {
@@ -152,9 +242,11 @@ impl RustrisPlayfield {
         true
     }
 
-    pub fn hard_drop_active(&mut self) {
-        let Some(active_rustomino) = self.active_rustomino.as_mut()  else {
-            return;
+    /// Drops the active rustomino onto the stack
+    /// returns the translation it was moved by
+    pub fn hard_drop_active(&mut self) -> IVec2 {
+        let Some(active_rustomino) = self.active_rustomino.as_mut() else {
+            return IVec2::ZERO;
         };
         let delta = get_hard_drop_translation(&self.slots, active_rustomino);
         set_playfield_slot_states(
@@ -163,6 +255,22 @@ impl RustrisPlayfield {
             SlotState::Empty,
         );
         active_rustomino.translate(delta);
+        delta
+    }
+
+    /// Counts the corners around the active rustomino's center block
+    /// that are locked or outside of the playfield.
+    /// Used to detect t-spins
+    pub fn active_corners_occupied(&self) -> usize {
+        let Some(active_rustomino) = &self.active_rustomino else {
+            return 0;
+        };
+        // the first block of the T rustomino is its center
+        let center = active_rustomino.playfield_slots()[0];
+        [ivec2(-1, -1), ivec2(-1, 1), ivec2(1, -1), ivec2(1, 1)]
+            .iter()
+            .filter(|corner| check_collision(&self.slots, [center + **corner; 4]))
+            .count()
     }
 
     /// lock the active rustomino
@@ -248,7 +356,157 @@ impl RustrisPlayfield {
         completed_lines
     }
 
+    /// Replaces the locked blocks with rows of garbage up to the provided height,
+    /// each row has a single random gap. Used to reproduce bugs in debug builds
+    pub fn fill_garbage(&mut self, height: usize) {
+        let mut rng = ::rand::thread_rng();
+        let rows = (0..height.min(PLAYFIELD_SIZE[1] as usize))
+            .map(|_| {
+                let gap = rng.gen_range(0..PLAYFIELD_SLOTS[0]);
+                let rtype = RustominoType::iter().choose(&mut rng).unwrap();
+                let mut row = [Some(rtype); PLAYFIELD_SLOTS[0]];
+                row[gap] = None;
+                row
+            })
+            .collect();
+        self.set_locked_rows(rows);
+    }
+
+    /// Replaces the locked blocks with a board described in text.
+    /// Rows are separated by '/' starting with the bottom row,
+    /// each character is a rustomino type's letter or '.' for an empty slot
+    /// e.g. "IIIIIIIII./TTT.TTTTT". Used to reproduce bugs in debug builds.
+    /// Returns an error describing the first invalid row or character
+    pub fn load_ascii(&mut self, board: &str) -> Result<(), String> {
+        let lines: Vec<&str> = board.trim().split('/').collect();
+        if lines.len() > PLAYFIELD_SLOTS[1] {
+            return Err(format!("too many rows: {}", lines.len()));
+        }
+        let mut rows = vec![];
+        for line in lines {
+            let line = line.trim();
+            if line.chars().count() > PLAYFIELD_SLOTS[0] {
+                return Err(format!("row too long: {line}"));
+            }
+            let mut row = [None; PLAYFIELD_SLOTS[0]];
+            for (x, c) in line.chars().enumerate() {
+                row[x] = match c {
+                    '.' => None,
+                    c => Some(RustominoType::from_char(c).ok_or(format!("invalid block: {c}"))?),
+                };
+            }
+            rows.push(row);
+        }
+        self.set_locked_rows(rows);
+        Ok(())
+    }
+
+    /// Pushes the locked blocks up and fills the bottom with rows of garbage,
+    /// every row has a gap in the same column so the attack can be dug out.
+    /// Returns false if locked blocks were pushed off the top of the playfield
+    pub fn insert_garbage(&mut self, lines: usize, gap: usize, rtype: RustominoType) -> bool {
+        let mut garbage_row = [Some(rtype); PLAYFIELD_SLOTS[0]];
+        garbage_row[gap.min(PLAYFIELD_SLOTS[0] - 1)] = None;
+        let mut rows = vec![garbage_row; lines];
+        rows.extend(self.slots.iter().map(|slots_x| {
+            slots_x.map(|slot| match slot {
+                SlotState::Locked(rtype) => Some(rtype),
+                _ => None,
+            })
+        }));
+        let pushed_out = rows[PLAYFIELD_SLOTS[1].min(rows.len())..]
+            .iter()
+            .any(|row| row.iter().any(Option::is_some));
+        log::info!("inserting {} lines of garbage, gap: {}", lines, gap);
+        self.set_locked_rows(rows);
+        !pushed_out
+    }
+
+    // replaces every locked block with the provided rows starting at the bottom,
+    // the active rustomino is left in place
+    fn set_locked_rows(&mut self, rows: Vec<[Option<RustominoType>; PLAYFIELD_SLOTS[0]]>) {
+        for (y, slots_x) in self.slots.iter_mut().enumerate() {
+            for (x, slot) in slots_x.iter_mut().enumerate() {
+                if let SlotState::Occupied(_) = slot {
+                    continue;
+                }
+                *slot = match rows.get(y).and_then(|row| row[x]) {
+                    Some(rtype) => SlotState::Locked(rtype),
+                    None => SlotState::Empty,
+                };
+            }
+        }
+        log::debug!("locked rows replaced: playfield:\n{}", self);
+        self.update_ghost_rustomino(false);
+    }
+
     /// Returns the get complete lines of this [`RustrisPlayfield`].
+    /// the locked blocks in the provided lines
+    pub fn locked_blocks(&self, lines: &[usize]) -> Vec<([usize; 2], RustominoType)> {
+        let mut blocks = vec![];
+        for y in lines {
+            for (x, slot) in self.slots[*y].iter().enumerate() {
+                if let SlotState::Locked(rtype) = slot {
+                    blocks.push(([x, *y], *rtype));
+                }
+            }
+        }
+        blocks
+    }
+
+    /// true if clearing the provided lines leaves no locked blocks
+    pub fn is_perfect_clear(&self, lines: &[usize]) -> bool {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|(y, _)| !lines.contains(y))
+            .all(|(_, slots_x)| {
+                !slots_x
+                    .iter()
+                    .any(|slot| matches!(slot, SlotState::Locked(_)))
+            })
+    }
+
+    /// the height of each column's highest locked block above the floor, 0 if it's empty
+    pub fn column_heights(&self) -> [usize; PLAYFIELD_SLOTS[0]] {
+        let mut heights = [0; PLAYFIELD_SLOTS[0]];
+        for (y, slots_x) in self.slots.iter().enumerate() {
+            for (height, slot) in heights.iter_mut().zip(slots_x) {
+                if let SlotState::Locked(_) = slot {
+                    *height = y + 1;
+                }
+            }
+        }
+        heights
+    }
+
+    /// the number of slots without a locked block below the top of their column
+    pub fn hole_count(&self) -> usize {
+        self.column_heights()
+            .iter()
+            .enumerate()
+            .map(|(x, height)| {
+                self.slots[..*height]
+                    .iter()
+                    .filter(|slots_x| !matches!(slots_x[x], SlotState::Locked(_)))
+                    .count()
+            })
+            .sum()
+    }
+
+    /// the sum of the height differences between neighbouring columns
+    pub fn bumpiness(&self) -> usize {
+        self.column_heights()
+            .windows(2)
+            .map(|pair| pair[0].abs_diff(pair[1]))
+            .sum()
+    }
+
+    /// the completed lines that will be removed by clear_completed_lines
+    pub fn completed_lines(&self) -> Vec<usize> {
+        self.get_complete_lines()
+    }
+
     fn get_complete_lines(&self) -> Vec<usize> {
         let mut complete_lines = vec![];
         'outer: for (i, line) in self.slots.iter().enumerate() {
@@ -264,6 +522,9 @@ impl RustrisPlayfield {
     }
 
     fn update_ghost_rustomino(&mut self, translating: bool) {
+        if self.hide_ghost {
+            return;
+        }
         let Some(active_rustomino) = &self.active_rustomino else {
             log::debug!("active_rustomino is None, removing ghost rustomino");
             if !translating {
@@ -373,10 +634,11 @@ fn check_rotation(
     playfield_slots: &PlayfieldSlots,
     rustomino: &Rustomino,
     rotation: &Rotation,
+    kicks: RotationKicks,
 ) -> Option<IVec2> {
-    let wall_kick_tests = rustomino.wall_kick_tests(rotation);
+    let kick_tests = rustomino.kick_tests(rotation, kicks);
     let rotated_blocks = rustomino.rotated(rotation);
-    wall_kick_tests
+    kick_tests
         .iter()
         .find(|x| !check_collision(playfield_slots, translated(&rotated_blocks, x)))
         .copied()
@@ -452,7 +714,7 @@ impl TranslationDirection {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SlotState {
     Empty,
     Occupied(RustominoType),
@@ -471,3 +733,85 @@ impl Display for SlotState {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(ascii: &str) -> RustrisPlayfield {
+        let mut playfield = RustrisPlayfield::new();
+        playfield.load_ascii(ascii).unwrap();
+        playfield
+    }
+
+    // a rustomino of the type moved from its spawn position
+    fn moved(rtype: RustominoType, translation: IVec2) -> Rustomino {
+        let mut rustomino = Rustomino::new(rtype);
+        rustomino.translate(translation);
+        rustomino
+    }
+
+    #[test]
+    fn stack_queries() {
+        let playfield = board("IIIIIIII../I...I...../..I.......");
+        assert_eq!(playfield.column_heights(), [2, 1, 3, 1, 2, 1, 1, 1, 0, 0]);
+        // the gap under the third column's top block
+        assert_eq!(playfield.hole_count(), 1);
+        assert_eq!(playfield.bumpiness(), 8);
+
+        let empty = RustrisPlayfield::new();
+        assert_eq!(empty.column_heights(), [0; PLAYFIELD_SLOTS[0]]);
+        assert_eq!(empty.hole_count(), 0);
+        assert_eq!(empty.bumpiness(), 0);
+    }
+
+    #[test]
+    fn completed_lines_are_cleared_and_the_rows_above_collapse() {
+        let mut playfield = board("IIIIIIIIII/I........./OOOOOOOOOO/..T.......");
+        assert_eq!(playfield.completed_lines(), [0, 2]);
+        assert!(!playfield.is_perfect_clear(&[0, 2]));
+        assert_eq!(playfield.clear_completed_lines(), [0, 2]);
+        assert_eq!(playfield.column_heights(), [1, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(playfield.slots[1][2], SlotState::Locked(RustominoType::T));
+        assert!(playfield.completed_lines().is_empty());
+
+        let playfield = board("IIIIIIIIII/IIIIIIIIII");
+        assert!(playfield.is_perfect_clear(&playfield.completed_lines()));
+    }
+
+    #[test]
+    fn rustominos_collide_with_locked_blocks_walls_and_the_floor() {
+        let playfield = board("IIII..IIII/IIII..IIII");
+        // the O spawns over columns 4 and 5, rows 20 and 21
+        let floor = ivec2(0, -20);
+        assert!(playfield.fits(&moved(RustominoType::O, floor)));
+        assert!(!playfield.fits(&moved(RustominoType::O, floor + ivec2(-1, 0))));
+        assert!(!playfield.fits(&moved(RustominoType::O, floor + ivec2(0, -1))));
+        assert!(!playfield.fits(&moved(RustominoType::O, ivec2(-5, 0))));
+        assert!(!playfield.fits(&moved(RustominoType::O, ivec2(5, 0))));
+        assert!(playfield.fits(&moved(RustominoType::O, ivec2(4, 0))));
+    }
+
+    #[test]
+    fn a_dropped_rustomino_locks_on_the_stack_and_completes_lines() {
+        let mut playfield = board("IIII..IIII/IIII..IIII/III...IIII");
+        assert!(playfield.set_active(Rustomino::new(RustominoType::O)));
+        assert_eq!(playfield.hard_drop_active(), ivec2(0, -20));
+        playfield.lock_active();
+        assert!(playfield.active_rustomino.is_none());
+        assert_eq!(playfield.completed_lines(), [0, 1]);
+        playfield.clear_completed_lines();
+        assert_eq!(playfield.column_heights(), [1, 1, 1, 0, 0, 0, 1, 1, 1, 1]);
+        assert_eq!(playfield.hole_count(), 0);
+    }
+
+    #[test]
+    fn invalid_boards_are_rejected() {
+        let mut playfield = RustrisPlayfield::new();
+        assert!(playfield.load_ascii("IIIII?IIII").is_err());
+        assert!(playfield.load_ascii("IIIIIIIIIII").is_err());
+        assert!(playfield
+            .load_ascii(&vec!["I"; PLAYFIELD_SLOTS[1] + 1].join("/"))
+            .is_err());
+    }
+}
@@ -0,0 +1,348 @@
+use crate::storage;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+const PROFILES_FILE: &str = "profiles.json"; // in the shared data folder
+const PROFILES_FOLDER: &str = "profiles"; // each profile's files are in a folder named after it
+pub const PROFILE_NAME_LENGTH: usize = 16;
+pub const MAX_PROFILES: usize = 8; // as many as fit on the profile picker
+
+// The players sharing the install and the one playing.
+// Every file that belongs to a player is found through the store,
+// without an active profile the guest uses the shared folders like before profiles existed
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProfileStore {
+    names: Vec<String>, // in the order they were created
+    active: Option<String>,
+    // the store was read from its file, the picker is shown on the first launch
+    #[serde(skip)]
+    saved: bool,
+    // the folder the data and config folders are in, the platform's folders when None
+    #[serde(skip)]
+    folder: Option<PathBuf>,
+}
+
+impl ProfileStore {
+    /// Reads the profiles from the data folder, a file that can't be read plays as the guest
+    pub fn load() -> Self {
+        ProfileStore::load_in(None)
+    }
+
+    // reads the profiles from the folder's data folder, or the platform's.
+    // Names that couldn't have been typed are dropped, they name the folders that are deleted
+    fn load_in(folder: Option<PathBuf>) -> Self {
+        let mut profiles = ProfileStore {
+            folder,
+            ..ProfileStore::default()
+        };
+        let path = profiles.data_root().join(PROFILES_FILE);
+        if !path.exists() {
+            return profiles;
+        }
+        let loaded = match storage::read_json::<ProfileStore>(&path) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                log::warn!("unable to read profiles {:?}: {}", path, e);
+                return profiles;
+            }
+        };
+        for name in loaded.names {
+            match profiles.check_name(&name, None) {
+                Ok(checked) if checked == name => profiles.names.push(name),
+                _ => log::warn!("ignoring invalid profile name {:?} in {:?}", name, path),
+            }
+        }
+        profiles.active = loaded
+            .active
+            .filter(|active| profiles.names.contains(active));
+        profiles.saved = true;
+        profiles
+    }
+
+    // the folder shared by every profile's data, and the guest's
+    fn data_root(&self) -> PathBuf {
+        match &self.folder {
+            Some(folder) => create_dir(folder.join(storage::LOCAL_DATA_FOLDER)),
+            None => storage::data_dir(),
+        }
+    }
+
+    // the folder shared by every profile's config, and the guest's
+    fn config_root(&self) -> PathBuf {
+        match &self.folder {
+            Some(folder) => create_dir(folder.join(storage::LOCAL_CONFIG_FOLDER)),
+            None => storage::config_dir(),
+        }
+    }
+
+    fn save(&mut self) {
+        let path = self.data_root().join(PROFILES_FILE);
+        match storage::write_json(&path, self) {
+            Ok(()) => self.saved = true,
+            Err(e) => log::error!("unable to save profiles {:?}: {}", path, e),
+        }
+    }
+
+    /// true if the player picks a profile when the game starts,
+    /// on the first launch or once anyone has made a profile
+    pub fn pick_at_launch(&self) -> bool {
+        !self.saved || !self.names.is_empty()
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// the name of the profile playing, None for the guest
+    pub fn active(&self) -> Option<&str> {
+        self.active.as_deref()
+    }
+
+    /// Returns the folder the active profile's data is stored in
+    /// creating it if it doesn't exist
+    pub fn data_dir(&self) -> PathBuf {
+        match &self.active {
+            Some(name) => create_dir(profile_path(self.data_root(), name)),
+            None => self.data_root(),
+        }
+    }
+
+    /// Path of the provided file in the active profile's data folder
+    pub fn data_file(&self, file_name: &str) -> PathBuf {
+        self.data_dir().join(file_name)
+    }
+
+    /// Path of the provided file in the active profile's config folder
+    pub fn config_file(&self, file_name: &str) -> PathBuf {
+        match &self.active {
+            Some(name) => create_dir(profile_path(self.config_root(), name)).join(file_name),
+            None => self.config_root().join(file_name),
+        }
+    }
+
+    /// Makes the profile the active one, None plays as the guest
+    pub fn select(&mut self, name: Option<&str>) {
+        log::info!("profile selected: {:?}", name);
+        self.active = name.map(str::to_owned);
+        self.save();
+    }
+
+    /// Adds a profile, returns the name it was saved with
+    /// or the reason it couldn't be added to show the player
+    pub fn create(&mut self, name: &str) -> Result<String, String> {
+        if self.names.len() >= MAX_PROFILES {
+            return Err("Too Many Profiles".to_owned());
+        }
+        let name = self.check_name(name, None)?;
+        log::info!("profile created: {}", name);
+        self.names.push(name.clone());
+        self.save();
+        Ok(name)
+    }
+
+    /// Renames the profile and moves its folders,
+    /// returns the reason it couldn't be renamed to show the player
+    pub fn rename(&mut self, old_name: &str, new_name: &str) -> Result<(), String> {
+        let new_name = self.check_name(new_name, Some(old_name))?;
+        for root in [self.data_root(), self.config_root()] {
+            let old_dir = profile_path(root.clone(), old_name);
+            if old_dir.exists() {
+                if let Err(e) = fs::rename(&old_dir, profile_path(root, &new_name)) {
+                    log::error!("unable to move profile folder {:?}: {}", old_dir, e);
+                    return Err("Unable To Rename Profile".to_owned());
+                }
+            }
+        }
+        log::info!("profile renamed: {} to {}", old_name, new_name);
+        for name in &mut self.names {
+            if name == old_name {
+                name.clone_from(&new_name);
+            }
+        }
+        if self.active.as_deref() == Some(old_name) {
+            self.active = Some(new_name);
+        }
+        self.save();
+        Ok(())
+    }
+
+    /// Removes the profile and its files, the guest plays if it was active
+    pub fn delete(&mut self, name: &str) {
+        // only the folders of known profiles are removed
+        if !self.names.iter().any(|existing| existing == name) {
+            log::warn!("not deleting unknown profile {:?}", name);
+            return;
+        }
+        log::info!("profile deleted: {}", name);
+        for root in [self.data_root(), self.config_root()] {
+            let dir = profile_path(root, name);
+            if dir.exists() {
+                if let Err(e) = fs::remove_dir_all(&dir) {
+                    log::error!("unable to delete profile folder {:?}: {}", dir, e);
+                }
+            }
+        }
+        self.names.retain(|existing| existing != name);
+        if self.active.as_deref() == Some(name) {
+            self.active = None;
+        }
+        self.save();
+    }
+
+    // the trimmed name, or the reason it can't be used
+    fn check_name(&self, name: &str, renaming: Option<&str>) -> Result<String, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Enter A Name".to_owned());
+        }
+        // the name is a folder name, it can't lead out of the profiles folder
+        if name.chars().count() > PROFILE_NAME_LENGTH || !name.chars().all(is_name_char) {
+            return Err("Invalid Name".to_owned());
+        }
+        // some platforms don't tell folder names apart by case
+        let taken = self.names.iter().any(|existing| {
+            Some(existing.as_str()) != renaming && existing.eq_ignore_ascii_case(name)
+        });
+        if taken {
+            return Err("Name Already Used".to_owned());
+        }
+        Ok(name.to_owned())
+    }
+}
+
+/// true for the characters a profile name can have, they're safe in a folder name
+pub fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == ' ' || c == '-' || c == '_'
+}
+
+// the profile's folder in the data or config folder
+fn profile_path(root: PathBuf, name: &str) -> PathBuf {
+    root.join(PROFILES_FOLDER).join(name)
+}
+
+fn create_dir(dir: PathBuf) -> PathBuf {
+    if let Err(e) = fs::create_dir_all(&dir) {
+        log::warn!("unable to create profile folder {:?}: {}", dir, e);
+    }
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    // an empty folder for a test's files
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rustris-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn store_in(dir: &Path) -> ProfileStore {
+        ProfileStore::load_in(Some(dir.to_path_buf()))
+    }
+
+    #[test]
+    fn create_checks_the_name_and_saves_it() {
+        let dir = test_dir("profiles-create");
+        let mut profiles = store_in(&dir);
+        assert_eq!(profiles.create("  Ann "), Ok("Ann".to_owned()));
+        assert_eq!(profiles.create("ANN"), Err("Name Already Used".to_owned()));
+        assert_eq!(profiles.create("   "), Err("Enter A Name".to_owned()));
+        assert_eq!(profiles.create("../Ann"), Err("Invalid Name".to_owned()));
+        let too_long = "a".repeat(PROFILE_NAME_LENGTH + 1);
+        assert_eq!(profiles.create(&too_long), Err("Invalid Name".to_owned()));
+        for i in 1..MAX_PROFILES {
+            profiles.create(&format!("Player {i}")).unwrap();
+        }
+        assert_eq!(profiles.create("Bob"), Err("Too Many Profiles".to_owned()));
+
+        let loaded = store_in(&dir);
+        assert_eq!(loaded.names(), profiles.names());
+        assert_eq!(loaded.active(), None);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn the_guest_uses_the_shared_folders() {
+        let dir = test_dir("profiles-guest");
+        let profiles = store_in(&dir);
+        assert_eq!(
+            profiles.data_file("scores.json"),
+            dir.join(storage::LOCAL_DATA_FOLDER).join("scores.json")
+        );
+        assert_eq!(
+            profiles.config_file("settings.toml"),
+            dir.join(storage::LOCAL_CONFIG_FOLDER).join("settings.toml")
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rename_moves_the_profile_folders() {
+        let dir = test_dir("profiles-rename");
+        let mut profiles = store_in(&dir);
+        profiles.create("Ann").unwrap();
+        profiles.select(Some("Ann"));
+        let data = profiles.data_file("scores.json");
+        let config = profiles.config_file("settings.toml");
+        fs::write(&data, "{}").unwrap();
+        fs::write(&config, "").unwrap();
+
+        profiles.rename("Ann", "Bob").unwrap();
+        assert_eq!(profiles.names(), ["Bob"]);
+        assert_eq!(profiles.active(), Some("Bob"));
+        assert!(!data.exists() && !config.exists());
+        assert!(profiles.data_file("scores.json").exists());
+        assert!(profiles.config_file("settings.toml").exists());
+        // renaming to its own name in another case is allowed
+        profiles.rename("Bob", "BOB").unwrap();
+        assert_eq!(store_in(&dir).active(), Some("BOB"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn delete_removes_the_profile_folders() {
+        let dir = test_dir("profiles-delete");
+        let mut profiles = store_in(&dir);
+        profiles.create("Ann").unwrap();
+        profiles.create("Bob").unwrap();
+        profiles.select(Some("Ann"));
+        let ann_data = profiles.data_dir();
+        let ann_config = profiles.config_file("settings.toml");
+        fs::write(&ann_config, "").unwrap();
+
+        profiles.delete("Ann");
+        assert_eq!(profiles.names(), ["Bob"]);
+        assert_eq!(profiles.active(), None);
+        assert!(!ann_data.exists() && !ann_config.exists());
+        // the guest's folder is left alone
+        assert!(dir.join(storage::LOCAL_DATA_FOLDER).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn load_ignores_names_that_could_not_be_typed() {
+        let dir = test_dir("profiles-invalid");
+        let data_root = dir.join(storage::LOCAL_DATA_FOLDER);
+        fs::create_dir_all(data_root.join(PROFILES_FOLDER).join("Ann")).unwrap();
+        fs::write(
+            data_root.join(PROFILES_FILE),
+            r#"{"names": ["Ann", "..", "../../victim", "ann", " Bob"], "active": ".."}"#,
+        )
+        .unwrap();
+        let victim = dir.join("victim");
+        fs::create_dir_all(&victim).unwrap();
+
+        let mut profiles = store_in(&dir);
+        assert_eq!(profiles.names(), ["Ann"]);
+        assert_eq!(profiles.active(), None);
+        profiles.delete("../../victim");
+        profiles.delete("..");
+        assert!(victim.exists());
+        assert!(data_root.join(PROFILES_FOLDER).join("Ann").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,328 @@
+// A replay file is a json object with these fields, in this order:
+//   format        always "rustris-replay", a file without it isn't a replay
+//   version       the replay format's version, files from a newer version aren't read
+//   game_version  the version of rustris that recorded the game
+//   mode          the game mode's name
+//   score         the recorded game's final score
+//   timestamp     when the game was played, in seconds since 1970
+//   seed          the seed the rustominos were dealt from
+//   start         the game before its first frame
+//   settings      the settings the game was played with
+//   frames        the time and keys of every update of the game
+//   end_hash      a hash of the game when it ended, checked when the replay is played back
+// Version 1 replays have no format, game_version, seed or end_hash.
+// Fields added later have defaults, so older replays keep loading
+use crate::{
+    controls::InputFrame, daily, game::GameSnapshot, high_scores::GameMode, settings::Settings,
+    storage, summary::unix_time,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+// the folder in the player's data folder the replays are saved in
+pub const REPLAYS_FOLDER: &str = "replays";
+const REPLAY_FILE_PREFIX: &str = "replay";
+// the first field of every replay file
+const REPLAY_FORMAT: &str = "rustris-replay";
+// replays from a newer version of the game can't be played back
+const REPLAY_VERSION: u32 = 2;
+const MAX_REPLAYS: usize = 15; // as many as fit on the replays screen, the oldest are removed
+
+// the playback speeds the viewer cycles through
+pub const SPEEDS: [f64; 4] = [0.5, 1., 2., 4.];
+pub const NORMAL_SPEED: usize = 1;
+pub const SEEK_TIME: f64 = 5.; // seconds skipped forward or back
+
+// One update of the recorded game
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    pub delta_time: f64,
+    pub keys: InputFrame,
+    // the game was paused before this frame, its controls were cleared when it resumed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub resumed: bool,
+}
+
+// A game with high scores recorded from its first frame, its inputs are played back
+// from the same start with the same settings to watch it again
+#[derive(Serialize, Deserialize)]
+pub struct Replay {
+    #[serde(default)]
+    pub format: String,
+    pub version: u32,
+    #[serde(default)]
+    pub game_version: String,
+    pub mode: String,
+    pub score: usize, // the recorded game's final score, compared with the playback's
+    pub timestamp: u64,
+    #[serde(default)]
+    pub seed: u64,
+    pub start: GameSnapshot,
+    pub settings: Settings,
+    pub frames: Vec<ReplayFrame>,
+    #[serde(default)]
+    pub end_hash: Option<u64>, // compared with the playback's, None in version 1 replays
+}
+
+impl Replay {
+    /// Reads a replay, files that aren't replays
+    /// and replays from a newer version of the game are an error
+    pub fn load(path: &Path) -> io::Result<Replay> {
+        // the version is checked first, a newer replay's fields may not be readable
+        let contents: serde_json::Value = storage::read_json(path)?;
+        let version = contents["version"].as_u64().unwrap_or_default();
+        if version >= 2 && contents["format"] != REPLAY_FORMAT {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a rustris replay",
+            ));
+        }
+        if version > REPLAY_VERSION as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("replay version {version} is newer than this game"),
+            ));
+        }
+        let mut replay: Replay = serde_json::from_value(contents)?;
+        if replay.version < 2 {
+            replay.seed = replay.start.seed();
+        }
+        Ok(replay)
+    }
+
+    /// Writes the replay, it's always written in the current version
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        storage::write_json(path, self)
+    }
+
+    /// the length of the recording in seconds
+    pub fn duration(&self) -> f64 {
+        self.frames.iter().map(|frame| frame.delta_time).sum()
+    }
+}
+
+// The inputs of the game being played, saved at game over
+pub struct Recorder {
+    replay: Replay,
+    resumed: bool,
+}
+
+impl Recorder {
+    /// starts recording a game that hasn't been updated yet
+    pub fn start(mode: GameMode, start: GameSnapshot, settings: Settings) -> Self {
+        log::info!("recording {} replay", mode);
+        Recorder {
+            replay: Replay {
+                format: REPLAY_FORMAT.to_owned(),
+                version: REPLAY_VERSION,
+                game_version: env!("CARGO_PKG_VERSION").to_owned(),
+                mode: mode.to_string(),
+                score: 0,
+                timestamp: unix_time(),
+                seed: start.seed(),
+                start,
+                settings,
+                frames: vec![],
+                end_hash: None,
+            },
+            resumed: false,
+        }
+    }
+
+    /// records the keys the game is updated with this frame
+    pub fn record(&mut self, keys: InputFrame, delta_time: f64) {
+        self.replay.frames.push(ReplayFrame {
+            delta_time,
+            keys,
+            resumed: std::mem::take(&mut self.resumed),
+        });
+    }
+
+    /// the game was paused and its controls cleared, the next frame resumes it
+    pub fn pause(&mut self) {
+        self.resumed = true;
+    }
+
+    /// saves the finished game's replay in the folder and removes the oldest ones,
+    /// returns the replay's path
+    pub fn save(mut self, score: usize, end: &GameSnapshot, folder: &Path) -> io::Result<PathBuf> {
+        self.replay.score = score;
+        self.replay.end_hash = Some(state_hash(end));
+        fs::create_dir_all(folder)?;
+        let path = folder.join(format!(
+            "{REPLAY_FILE_PREFIX}-{}.json",
+            self.replay.timestamp
+        ));
+        self.replay.save(&path)?;
+        log::info!("replay saved: {:?}", path);
+        for old in list(folder).iter().skip(MAX_REPLAYS) {
+            if let Err(e) = fs::remove_file(&old.path) {
+                log::warn!("unable to remove old replay {:?}: {}", old.path, e);
+            }
+        }
+        Ok(path)
+    }
+}
+
+/// A hash of the game's state that's the same on every platform and version of rust,
+/// replays compare it with the state of the game they played back
+pub fn state_hash(state: &GameSnapshot) -> u64 {
+    // 64 bit FNV-1a of the state as it's saved
+    let bytes = serde_json::to_vec(state).unwrap_or_default();
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// The replay file named by --play-replay on the command line, it's watched at launch
+pub fn path_from_args() -> Result<Option<PathBuf>, String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--play-replay" {
+            let path = args.next().ok_or("--play-replay needs a file")?;
+            return Ok(Some(PathBuf::from(path)));
+        }
+    }
+    Ok(None)
+}
+
+// A saved replay on the replays screen
+#[derive(Debug, Clone)]
+pub struct ReplayEntry {
+    pub path: PathBuf,
+    pub mode: String,
+    pub score: usize,
+    pub date: u32, // yyyymmdd
+    timestamp: u64,
+}
+
+// the parts of a replay file shown on the replays screen
+#[derive(Deserialize)]
+struct ReplayHeader {
+    version: u32,
+    mode: String,
+    score: usize,
+    timestamp: u64,
+}
+
+/// The replays saved in the folder, newest first.
+/// Files that can't be read or are from a newer version aren't listed
+pub fn list(folder: &Path) -> Vec<ReplayEntry> {
+    let Ok(files) = fs::read_dir(folder) else {
+        return vec![];
+    };
+    let mut entries: Vec<ReplayEntry> = files
+        .flatten()
+        .map(|file| file.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .filter_map(|path| match storage::read_json::<ReplayHeader>(&path) {
+            Ok(header) if header.version <= REPLAY_VERSION => Some(ReplayEntry {
+                mode: header.mode,
+                score: header.score,
+                date: daily::date(header.timestamp),
+                timestamp: header.timestamp,
+                path,
+            }),
+            Ok(_) => None,
+            Err(e) => {
+                log::warn!("unable to read replay {:?}: {}", path, e);
+                None
+            }
+        })
+        .collect();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.timestamp));
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_is_newest_first_without_unreadable_or_newer_replays() {
+        let dir = std::env::temp_dir().join(format!("rustris-replays-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let header = |version: u32, score: usize, timestamp: u64| {
+            format!(
+                r#"{{"version":{version},"mode":"Marathon","score":{score},"timestamp":{timestamp},"frames":[]}}"#
+            )
+        };
+        fs::write(dir.join("replay-100.json"), header(1, 500, 100)).unwrap();
+        fs::write(dir.join("replay-200.json"), header(1, 900, 200)).unwrap();
+        fs::write(
+            dir.join("replay-300.json"),
+            header(REPLAY_VERSION + 1, 0, 300),
+        )
+        .unwrap();
+        fs::write(dir.join("replay-400.json"), "{ not a replay").unwrap();
+        fs::write(dir.join("notes.txt"), "not a replay either").unwrap();
+
+        let entries = list(&dir);
+        let scores: Vec<usize> = entries.iter().map(|entry| entry.score).collect();
+        assert_eq!(scores, [900, 500]);
+        assert_eq!(entries[0].date, 19700101);
+        assert!(Replay::load(&dir.join("replay-300.json")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // a replay saved by an earlier version in the tests folder
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn replays_from_older_versions_load() {
+        let old = Replay::load(&fixture("replay-v1.json")).unwrap();
+        assert_eq!(old.version, 1);
+        assert_eq!(old.format, "");
+        assert_eq!(old.end_hash, None);
+        // the seed is read from the start of the game
+        assert_eq!(old.seed, 2024);
+
+        let current = Replay::load(&fixture("replay-v2.json")).unwrap();
+        assert_eq!(current.format, REPLAY_FORMAT);
+        assert_eq!(current.version, REPLAY_VERSION);
+        assert_eq!(current.game_version, "0.3.2");
+        assert_eq!(current.seed, 2024);
+        assert!(current.end_hash.is_some());
+        assert_eq!(current.frames.len(), old.frames.len());
+    }
+
+    #[test]
+    fn load_refuses_newer_replays_and_other_files() {
+        let dir = std::env::temp_dir().join(format!("rustris-replay-load-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        // a newer replay is refused by its version even if its fields have changed
+        let newer = format!(
+            r#"{{"format":"{REPLAY_FORMAT}","version":{},"frames":"compressed"}}"#,
+            REPLAY_VERSION + 1
+        );
+        fs::write(dir.join("newer.json"), newer).unwrap();
+        let error = Replay::load(&dir.join("newer.json")).err().unwrap();
+        assert!(error.to_string().contains("newer than this game"));
+        // a current version file that isn't a replay
+        let contents = fs::read_to_string(fixture("replay-v2.json")).unwrap();
+        let other = contents.replacen(REPLAY_FORMAT, "something-else", 1);
+        fs::write(dir.join("other.json"), other).unwrap();
+        let error = Replay::load(&dir.join("other.json")).err().unwrap();
+        assert_eq!(error.to_string(), "not a rustris replay");
+
+        // a replay saved again is written in the current version
+        let mut old = Replay::load(&fixture("replay-v1.json")).unwrap();
+        old.format = REPLAY_FORMAT.to_owned();
+        old.version = REPLAY_VERSION;
+        old.save(&dir.join("saved.json")).unwrap();
+        assert_eq!(Replay::load(&dir.join("saved.json")).unwrap().seed, 2024);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
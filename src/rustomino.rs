@@ -1,10 +1,28 @@
-use ::rand::{seq::SliceRandom, SeedableRng};
+use ::rand::{seq::SliceRandom, Rng, SeedableRng};
 use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, fmt::Display};
 use strum::{EnumIter, IntoEnumIterator};
 
 const I_START_TRANSLATION: IVec2 = ivec2(3, 18);
 const O_T_L_J_S_Z_START_TRANSLATION: IVec2 = ivec2(3, 19);
 
+// copies of each rustomino type in a bag, ordered I, O, T, L, J, S, Z
+const EVEN_WEIGHTS: [usize; 7] = [1, 1, 1, 1, 1, 1, 1];
+// one I in every 25 rustominos
+const DROUGHT_WEIGHTS: [usize; 7] = [1, 4, 4, 4, 4, 4, 4];
+// nudges tried in order by the nudge rotation kicks, never more than a block away
+const NUDGE_KICK_TESTS: [IVec2; 4] = [ivec2(0, 0), ivec2(-1, 0), ivec2(1, 0), ivec2(0, 1)];
+// I rustominos are long enough to need two blocks sideways to rotate beside a wall
+const I_NUDGE_KICK_TESTS: [IVec2; 6] = [
+    ivec2(0, 0),
+    ivec2(-1, 0),
+    ivec2(1, 0),
+    ivec2(0, 1),
+    ivec2(-2, 0),
+    ivec2(2, 0),
+];
+
 const I_BLOCKS: [IVec2; 4] = [ivec2(0, 2), ivec2(1, 2), ivec2(2, 2), ivec2(3, 2)];
 const O_BLOCKS: [IVec2; 4] = [ivec2(1, 2), ivec2(2, 2), ivec2(2, 1), ivec2(1, 1)];
 const T_BLOCKS: [IVec2; 4] = [ivec2(1, 1), ivec2(0, 1), ivec2(1, 2), ivec2(2, 1)];
@@ -364,7 +382,7 @@ const I_WALL_KICK_TESTS: [[IVec2; 5]; 8] = [
     ],
 ];
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Rustomino {
     pub rtype: RustominoType,
     pub state: RustominoState,
@@ -432,6 +450,15 @@ impl Rustomino {
         Rustomino::new(self.rtype)
     }
 
+    /// Returns this rustomino in its starting position with its rotation kept
+    pub fn reset_keeping_rotation(self) -> Rustomino {
+        let mut rustomino = Rustomino::new(self.rtype);
+        while rustomino.rotation.direction != self.rotation.direction {
+            rustomino.rotate(&Rotation::Cw, &IVec2::ZERO);
+        }
+        rustomino
+    }
+
     pub fn translate(&mut self, delta: IVec2) {
         self.translation += delta;
     }
@@ -472,6 +499,33 @@ impl Rustomino {
         self.rotation.get_wall_kick_tests(self.rtype, rotation)
     }
 
+    /// the translations tried in order when rotating, the first that doesn't collide is used
+    pub fn kick_tests(&self, rotation: &Rotation, kicks: RotationKicks) -> Vec<IVec2> {
+        match (kicks, self.rtype) {
+            (RotationKicks::Srs, _) => self.wall_kick_tests(rotation).to_vec(),
+            (RotationKicks::Nudge, RustominoType::I) => I_NUDGE_KICK_TESTS.to_vec(),
+            (RotationKicks::Nudge, _) => NUDGE_KICK_TESTS.to_vec(),
+            (RotationKicks::Off, _) => vec![IVec2::ZERO],
+        }
+    }
+
+    /// Returns a rustomino of the provided type
+    /// in the same position and rotation as this one
+    pub fn with_type(&self, rtype: RustominoType) -> Rustomino {
+        let mut reference = Rustomino::new(self.rtype);
+        let mut rustomino = Rustomino::new(rtype);
+        while reference.rotation.direction != self.rotation.direction {
+            reference.rotate(&Rotation::Cw, &IVec2::ZERO);
+            rustomino.rotate(&Rotation::Cw, &IVec2::ZERO);
+        }
+        // wall kicks are applied to the blocks, movement to the translation
+        let wall_kicks = self.blocks[0] - reference.blocks[0];
+        rustomino.blocks = translated(&rustomino.blocks, &wall_kicks);
+        rustomino.translation += self.translation - reference.translation;
+        rustomino.state = self.state;
+        rustomino
+    }
+
     pub fn set_state(&mut self, state: RustominoState) {
         log::trace!("setting rustomino state: {:?}", state);
         self.state = state;
@@ -487,7 +541,7 @@ pub fn translated(blocks: &[IVec2; 4], delta: &IVec2) -> [IVec2; 4] {
     ]
 }
 
-#[derive(Debug, Clone, Copy, EnumIter, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, EnumIter, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RustominoType {
     I,
     O,
@@ -507,6 +561,30 @@ impl RustominoType {
     const GREEN: Color = Color::new(0.4, 0.99, 0.0, 1.0);
     const RED: Color = Color::new(1.0, 0.06, 0.24, 1.0);
 
+    /// Parses a rustomino type from its letter, ignoring case
+    pub fn from_char(c: char) -> Option<RustominoType> {
+        match c.to_ascii_uppercase() {
+            'I' => Some(RustominoType::I),
+            'O' => Some(RustominoType::O),
+            'T' => Some(RustominoType::T),
+            'L' => Some(RustominoType::L),
+            'J' => Some(RustominoType::J),
+            'S' => Some(RustominoType::S),
+            'Z' => Some(RustominoType::Z),
+            _ => None,
+        }
+    }
+
+    /// Parses a sequence of rustomino types e.g. "IJLOSTZ", whitespace is ignored.
+    /// Returns the first invalid character if the sequence can't be parsed
+    pub fn parse_sequence(sequence: &str) -> Result<Vec<RustominoType>, char> {
+        sequence
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| RustominoType::from_char(c).ok_or(c))
+            .collect()
+    }
+
     pub fn color(&self) -> Color {
         match self {
             RustominoType::I => RustominoType::CYAN,
@@ -520,13 +598,13 @@ impl RustominoType {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum RustominoState {
     Falling { time: f64 },
     Lockdown { time: f64 },
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Direction {
     N,
     E,
@@ -563,7 +641,7 @@ pub enum Rotation {
     Ccw,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct RustominoRotation {
     direction: Direction,
     n2e_trans: [IVec2; 4],
@@ -664,20 +742,110 @@ fn neg_trans(block_trans: [IVec2; 4]) -> [IVec2; 4] {
     ]
 }
 
+// How often each rustomino type is dealt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
+pub enum PieceWeights {
+    // every type once per bag
+    Even,
+    // I rustominos are rare
+    Drought,
+}
+
+impl PieceWeights {
+    /// the number of each rustomino type in a bag, ordered like RustominoType
+    fn weights(&self) -> [usize; 7] {
+        match self {
+            PieceWeights::Even => EVEN_WEIGHTS,
+            PieceWeights::Drought => DROUGHT_WEIGHTS,
+        }
+    }
+}
+
+impl Display for PieceWeights {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PieceWeights::Even => write!(f, "Even"),
+            PieceWeights::Drought => write!(f, "Drought"),
+        }
+    }
+}
+
+// How a rotation that collides is moved to a free position
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
+pub enum RotationKicks {
+    // the super rotation system's wall kick tests, which depend on the rustomino type
+    // and the rotation and can move it up to two blocks in any direction, including down
+    Srs,
+    // the nearest free position one block left, right or up,
+    // two blocks sideways for I rustominos, the same tests for every rotation
+    Nudge,
+    // the rotation fails if it collides
+    Off,
+}
+
+impl Display for RotationKicks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RotationKicks::Srs => write!(f, "SRS"),
+            RotationKicks::Nudge => write!(f, "Nudge"),
+            RotationKicks::Off => write!(f, "Off"),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct RustominoBag {
     bag: Vec<RustominoType>, // contains the next rustomino types, shuffled
     rng: rand_xoshiro::Xoshiro256PlusPlus,
+    #[serde(default)]
+    sequence: VecDeque<RustominoType>, // played in order before the random bags
+    #[serde(default = "even_weights")]
+    weights: PieceWeights,
+    #[serde(default)]
+    seed: u64, // the random number generator's seed, shown in the session summary
+}
+
+fn even_weights() -> PieceWeights {
+    PieceWeights::Even
 }
 
 impl RustominoBag {
-    pub fn new() -> Self {
+    pub fn new(weights: PieceWeights) -> Self {
+        RustominoBag::with_seed(::rand::random(), weights)
+    }
+
+    /// A bag that deals the same rustominos for the same seed and weights
+    pub fn with_seed(seed: u64, weights: PieceWeights) -> Self {
+        log::info!("rustomino bag seed: {}", seed);
         RustominoBag {
             bag: Vec::new(),
-            rng: rand_xoshiro::Xoshiro256PlusPlus::from_entropy(),
+            rng: rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(seed),
+            sequence: VecDeque::new(),
+            weights,
+            seed,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// A bag that deals the provided sequence before
+    /// the random bags take over
+    pub fn with_sequence(sequence: Vec<RustominoType>, weights: PieceWeights) -> Self {
+        log::info!("rustomino bag starting with sequence: {:?}", sequence);
+        RustominoBag {
+            sequence: sequence.into(),
+            ..RustominoBag::new(weights)
         }
     }
 
     pub fn get_next_rustomino(&mut self) -> Rustomino {
+        if let Some(rtype) = self.sequence.pop_front() {
+            log::info!("next rustomino type from sequence: {:?}", rtype);
+            return Rustomino::new(rtype);
+        }
+
         // make sure the bag isn't empty
         self.fill_rustomino_bag();
 
@@ -687,15 +855,82 @@ impl RustominoBag {
         Rustomino::new(rtype)
     }
 
-    // add one of each rustomino type to bag
+    /// the bag's random number generator, for other choices that have to be
+    /// the same in every game dealt from the same seed
+    pub fn rng(&mut self) -> &mut rand_xoshiro::Xoshiro256PlusPlus {
+        &mut self.rng
+    }
+
+    /// turns the rustomino to a random orientation with the bag's random number generator
+    pub fn randomly_rotated(&mut self, mut rustomino: Rustomino) -> Rustomino {
+        for _ in 0..self.rng.gen_range(0..4) {
+            rustomino.rotate(&Rotation::Cw, &IVec2::ZERO);
+        }
+        rustomino
+    }
+
+    // add each rustomino type to bag as many times as its weight
     // then shuffle the bag
     fn fill_rustomino_bag(&mut self) {
         if !self.bag.is_empty() {
             log::trace!("rustomino bag is not empty: {:?}", self.bag);
             return;
         }
-        self.bag.append(&mut RustominoType::iter().collect());
+        for (rtype, weight) in RustominoType::iter().zip(self.weights.weights()) {
+            self.bag.extend(std::iter::repeat_n(rtype, weight));
+        }
         self.bag.shuffle(&mut self.rng);
         log::debug!("filled rustomino bag: {:?}", self.bag);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weighted_bags_deal_each_type_by_its_weight() {
+        const PIECES: usize = 10_000;
+        const TOLERANCE: f64 = 0.01;
+        for weights in PieceWeights::iter() {
+            let mut bag = RustominoBag::with_seed(42, weights);
+            let mut counts = [0; 7];
+            for _ in 0..PIECES {
+                counts[bag.get_next_rustomino().rtype as usize] += 1;
+            }
+            let total: usize = weights.weights().iter().sum();
+            for ((rtype, count), weight) in RustominoType::iter().zip(counts).zip(weights.weights())
+            {
+                let frequency = count as f64 / PIECES as f64;
+                let expected = weight as f64 / total as f64;
+                assert!(
+                    (frequency - expected).abs() < TOLERANCE,
+                    "{weights}: {rtype:?} dealt {frequency:.3}, expected {expected:.3}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn a_full_bag_holds_each_type_as_many_times_as_its_weight() {
+        let mut bag = RustominoBag::with_seed(7, PieceWeights::Drought);
+        let size: usize = DROUGHT_WEIGHTS.iter().sum();
+        let mut counts = [0; 7];
+        for _ in 0..size {
+            counts[bag.get_next_rustomino().rtype as usize] += 1;
+        }
+        assert_eq!(counts, DROUGHT_WEIGHTS);
+    }
+
+    #[test]
+    fn the_same_seed_deals_the_same_rustominos() {
+        let deal = |seed| {
+            let mut bag = RustominoBag::with_seed(seed, PieceWeights::Even);
+            (0..21)
+                .map(|_| bag.get_next_rustomino().rtype)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(deal(9), deal(9));
+        assert_ne!(deal(9), deal(10));
+    }
+}
@@ -0,0 +1,296 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use strum::EnumIter;
+
+// Guideline scoring
+const GUIDELINE_TABLE: ScoringTable = ScoringTable {
+    line_clears: [100, 300, 500, 800],
+    t_spins: [400, 800, 1200, 1600],
+    level_multiplier: true,
+    combo_bonus: 50,
+    back_to_back_multiplier: 1.5,
+};
+
+// NES scoring, no t-spins, combos or back to back bonus
+const NES_TABLE: ScoringTable = ScoringTable {
+    line_clears: [40, 100, 300, 1200],
+    t_spins: [0, 40, 100, 300],
+    level_multiplier: true,
+    combo_bonus: 0,
+    back_to_back_multiplier: 1.0,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
+pub enum ScoringRuleset {
+    Guideline,
+    Nes,
+    Custom,
+}
+
+impl Display for ScoringRuleset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScoringRuleset::Guideline => write!(f, "Guideline"),
+            ScoringRuleset::Nes => write!(f, "NES"),
+            ScoringRuleset::Custom => write!(f, "Custom"),
+        }
+    }
+}
+
+impl ScoringRuleset {
+    /// returns the scoring table for this ruleset
+    /// the custom ruleset uses the provided table
+    pub fn table(&self, custom: &ScoringTable) -> ScoringTable {
+        match self {
+            ScoringRuleset::Guideline => GUIDELINE_TABLE,
+            ScoringRuleset::Nes => NES_TABLE,
+            ScoringRuleset::Custom => *custom,
+        }
+    }
+}
+
+/// The points awarded by a ruleset
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScoringTable {
+    // single, double, triple, rustris
+    pub line_clears: [usize; 4],
+    // t-spin with no lines, single, double, triple
+    pub t_spins: [usize; 4],
+    // if true, points are multiplied by the game level (starting at 1)
+    pub level_multiplier: bool,
+    // points per consecutive line clear
+    pub combo_bonus: usize,
+    // applied to consecutive rustrises and t-spin line clears
+    pub back_to_back_multiplier: f64,
+}
+
+impl Default for ScoringTable {
+    fn default() -> Self {
+        GUIDELINE_TABLE
+    }
+}
+
+/// The result of a locked rustomino clearing lines or performing a t-spin
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClearResult {
+    pub lines: usize,
+    pub t_spin: bool,
+    pub back_to_back: bool,
+    pub combo: usize,
+    pub score: usize,
+}
+
+impl ClearResult {
+    /// rustrises and t-spins that clear lines count toward back to back
+    pub fn is_difficult(lines: usize, t_spin: bool) -> bool {
+        lines == 4 || (t_spin && lines > 0)
+    }
+
+    /// every name returned by clear_type
+    pub const CLEAR_TYPES: [&'static str; 8] = [
+        "Single",
+        "Double",
+        "Triple",
+        "Rustris",
+        "T-Spin",
+        "T-Spin Single",
+        "T-Spin Double",
+        "T-Spin Triple",
+    ];
+
+    /// names the type of clear without back to back, e.g. "T-Spin Double"
+    pub fn clear_type(&self) -> &'static str {
+        match (self.t_spin, self.lines) {
+            (true, 0) => "T-Spin",
+            (true, 1) => "T-Spin Single",
+            (true, 2) => "T-Spin Double",
+            (true, _) => "T-Spin Triple",
+            (false, 1) => "Single",
+            (false, 2) => "Double",
+            (false, 3) => "Triple",
+            (false, _) => "Rustris",
+        }
+    }
+}
+
+// lines of garbage sent to the opponent in versus by clears of 1 to 4 lines,
+// and by t-spins of 0 to 3 lines
+const GARBAGE_LINES: [usize; 4] = [0, 1, 2, 4];
+const GARBAGE_T_SPIN_LINES: [usize; 4] = [0, 2, 4, 6];
+const GARBAGE_BACK_TO_BACK: usize = 1;
+// extra lines by combo, longer combos send the last entry
+const GARBAGE_COMBO_LINES: [usize; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+
+impl ClearResult {
+    /// the lines of garbage the clear sends to the opponent in a versus match
+    pub fn garbage_lines(&self) -> usize {
+        if self.lines == 0 {
+            return 0;
+        }
+        let base = if self.t_spin {
+            GARBAGE_T_SPIN_LINES[self.lines.min(3)]
+        } else {
+            GARBAGE_LINES[self.lines.min(4) - 1]
+        };
+        let back_to_back = if self.back_to_back {
+            GARBAGE_BACK_TO_BACK
+        } else {
+            0
+        };
+        base + back_to_back + GARBAGE_COMBO_LINES[self.combo.min(GARBAGE_COMBO_LINES.len() - 1)]
+    }
+}
+
+/// names the clear, e.g. "Back to Back T-Spin Double"
+impl Display for ClearResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.back_to_back {
+            write!(f, "Back to Back ")?;
+        }
+        write!(f, "{}", self.clear_type())
+    }
+}
+
+/// Scores a lock that cleared lines or performed a t-spin.
+/// `combo` is the number of consecutive line clears before this one
+/// `back_to_back` is true if this and the previous clear were difficult
+pub fn score_clear(
+    table: &ScoringTable,
+    lines: usize,
+    t_spin: bool,
+    level: usize,
+    combo: usize,
+    back_to_back: bool,
+) -> ClearResult {
+    let base = if t_spin {
+        table.t_spins[lines.min(3)]
+    } else {
+        match lines {
+            1..=4 => table.line_clears[lines - 1],
+            0 => 0,
+            _ => {
+                panic!("impossible number of lines cleared")
+            }
+        }
+    };
+    let base = if back_to_back {
+        (base as f64 * table.back_to_back_multiplier) as usize
+    } else {
+        base
+    };
+    let combo_score = if lines > 0 {
+        table.combo_bonus * combo
+    } else {
+        0
+    };
+    let multiplier = if table.level_multiplier { level + 1 } else { 1 };
+    ClearResult {
+        lines,
+        t_spin,
+        back_to_back,
+        combo,
+        score: (base + combo_score) * multiplier,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a custom table with every bonus, and without the level multiplier
+    const CUSTOM_TABLE: ScoringTable = ScoringTable {
+        line_clears: [10, 20, 30, 40],
+        t_spins: [5, 15, 25, 35],
+        level_multiplier: false,
+        combo_bonus: 3,
+        back_to_back_multiplier: 2.0,
+    };
+
+    fn score(
+        ruleset: ScoringRuleset,
+        lines: usize,
+        t_spin: bool,
+        combo: usize,
+        b2b: bool,
+    ) -> usize {
+        // level 1, the second level
+        score_clear(&ruleset.table(&CUSTOM_TABLE), lines, t_spin, 1, combo, b2b).score
+    }
+
+    #[test]
+    fn guideline_scoring() {
+        let ruleset = ScoringRuleset::Guideline;
+        assert_eq!(score(ruleset, 1, false, 0, false), 200);
+        assert_eq!(score(ruleset, 4, false, 0, false), 1600);
+        assert_eq!(score(ruleset, 0, true, 0, false), 800);
+        assert_eq!(score(ruleset, 2, true, 0, false), 2400);
+        assert_eq!(score(ruleset, 4, false, 0, true), 2400);
+        assert_eq!(score(ruleset, 1, false, 2, false), 400);
+    }
+
+    #[test]
+    fn nes_scoring() {
+        let ruleset = ScoringRuleset::Nes;
+        assert_eq!(score(ruleset, 1, false, 0, false), 80);
+        assert_eq!(score(ruleset, 4, false, 0, false), 2400);
+        // no t-spin, back to back or combo bonuses beyond the lines
+        assert_eq!(score(ruleset, 0, true, 0, false), 0);
+        assert_eq!(score(ruleset, 2, true, 0, false), 200);
+        assert_eq!(score(ruleset, 4, false, 0, true), 2400);
+        assert_eq!(score(ruleset, 1, false, 2, false), 80);
+    }
+
+    #[test]
+    fn custom_scoring() {
+        let ruleset = ScoringRuleset::Custom;
+        assert_eq!(score(ruleset, 1, false, 0, false), 10);
+        assert_eq!(score(ruleset, 4, false, 0, false), 40);
+        assert_eq!(score(ruleset, 0, true, 0, false), 5);
+        assert_eq!(score(ruleset, 2, true, 0, false), 25);
+        assert_eq!(score(ruleset, 4, false, 0, true), 80);
+        assert_eq!(score(ruleset, 1, false, 2, false), 16);
+    }
+
+    #[test]
+    fn combo_only_scores_line_clears() {
+        for ruleset in [ScoringRuleset::Guideline, ScoringRuleset::Custom] {
+            assert_eq!(
+                score(ruleset, 0, true, 3, false),
+                score(ruleset, 0, true, 0, false)
+            );
+        }
+    }
+
+    #[test]
+    fn difficult_clears() {
+        assert!(ClearResult::is_difficult(4, false));
+        assert!(ClearResult::is_difficult(1, true));
+        assert!(!ClearResult::is_difficult(0, true));
+        assert!(!ClearResult::is_difficult(3, false));
+    }
+
+    #[test]
+    fn garbage_lines_for_each_clear() {
+        let clear = |lines, t_spin, combo, back_to_back| ClearResult {
+            lines,
+            t_spin,
+            back_to_back,
+            combo,
+            score: 0,
+        };
+        assert_eq!(clear(0, true, 0, false).garbage_lines(), 0);
+        assert_eq!(clear(1, false, 0, false).garbage_lines(), 0);
+        assert_eq!(clear(2, false, 0, false).garbage_lines(), 1);
+        assert_eq!(clear(3, false, 0, false).garbage_lines(), 2);
+        assert_eq!(clear(4, false, 0, false).garbage_lines(), 4);
+        assert_eq!(clear(1, true, 0, false).garbage_lines(), 2);
+        assert_eq!(clear(2, true, 0, false).garbage_lines(), 4);
+        assert_eq!(clear(3, true, 0, false).garbage_lines(), 6);
+        // back to back adds a line, long combos add up to five
+        assert_eq!(clear(4, false, 0, true).garbage_lines(), 5);
+        assert_eq!(clear(1, false, 2, false).garbage_lines(), 1);
+        assert_eq!(clear(2, false, 4, false).garbage_lines(), 3);
+        assert_eq!(clear(1, false, 30, false).garbage_lines(), 5);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::controls::{self, Controls, InputFrame};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// one frame of a script, played at a fixed 60 frames a second
+pub const FRAME_TIME: f64 = 1. / controls::FRAMES_PER_SECOND;
+
+// What a script's line does at its frame
+#[derive(Debug, Clone)]
+enum Action {
+    Press(Controls),
+    Release(Controls),
+    End, // the script keeps playing until this frame
+}
+
+// An input script read from a text file, played instead of the keyboard
+// to reproduce bugs and record the same game again.
+// Each line is a command, # starts a comment:
+//   seed 42                        the rustominos are dealt from this seed
+//   board IIIIIIIII./TTT.TTTTT     locked rows to start from, as typed on the debug board
+//   12 press left                  the control's key goes down on frame 12
+//   20 release left
+//   30 tap hard_drop               pressed on frame 30 and released on the next
+//   600 end                        keeps playing until frame 600
+// Controls are named as in the RUSTRIS_KEY_ variables, ignoring case.
+// The script ends after its last frame
+pub struct Script {
+    pub path: PathBuf,
+    pub fast: bool, // played at once without drawing, the final state is printed
+    seed: Option<u64>,
+    board: Option<String>,
+    commands: Vec<(usize, Action)>, // in frame order
+    next_command: usize,
+    last_frame: usize,
+    frame: usize,        // the next frame to play
+    down: Vec<Controls>, // controls pressed and not yet released
+    time: f64,           // seconds not yet played when the script is played in real time
+}
+
+impl Script {
+    /// The script named by --script on the command line.
+    /// --seed replaces the script's seed and --fast plays it without drawing
+    pub fn from_args() -> Result<Option<Script>, String> {
+        let mut args = std::env::args().skip(1);
+        let (mut path, mut seed, mut fast) = (None, None, false);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--script" => path = Some(args.next().ok_or("--script needs a file")?),
+                "--seed" => {
+                    let value = args.next().ok_or("--seed needs a number")?;
+                    seed = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid seed: {value:?}"))?,
+                    );
+                }
+                "--fast" => fast = true,
+                // read by replay::path_from_args
+                "--play-replay" => {
+                    args.next();
+                }
+                _ => log::warn!("ignoring unknown argument: {}", arg),
+            }
+        }
+        let Some(path) = path else {
+            return Ok(None);
+        };
+        let mut script = Script::load(Path::new(&path))?;
+        script.seed = seed.or(script.seed);
+        script.fast = fast;
+        Ok(Some(script))
+    }
+
+    /// Reads a script, returns an error naming the first invalid line
+    pub fn load(path: &Path) -> Result<Script, String> {
+        log::info!("loading input script: {:?}", path);
+        let contents = fs::read_to_string(path).map_err(|e| format!("{path:?}: {e}"))?;
+        let mut script = Script {
+            path: path.to_owned(),
+            fast: false,
+            seed: None,
+            board: None,
+            commands: vec![],
+            next_command: 0,
+            last_frame: 0,
+            frame: 0,
+            down: vec![],
+            time: 0.,
+        };
+        for (index, line) in contents.lines().enumerate() {
+            script
+                .parse_line(line)
+                .map_err(|e| format!("line {}: {}", index + 1, e))?;
+        }
+        // commands on the same frame keep the order they were written in
+        script.commands.sort_by_key(|(frame, _)| *frame);
+        script.last_frame = script.commands.last().map_or(0, |(frame, _)| *frame);
+        Ok(script)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let line = line.split('#').next().unwrap_or_default();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => {}
+            ["seed", seed] => {
+                self.seed = Some(
+                    seed.parse()
+                        .map_err(|_| format!("invalid seed: {seed:?}"))?,
+                )
+            }
+            ["board", board] => self.board = Some(board.to_string()),
+            [frame, "end"] => self.commands.push((parse_frame(frame)?, Action::End)),
+            [frame, action, control] => {
+                let frame = parse_frame(frame)?;
+                let control = Controls::from_name(control)
+                    .ok_or_else(|| format!("unknown control: {control:?}"))?;
+                match *action {
+                    "press" => self.commands.push((frame, Action::Press(control))),
+                    "release" => self.commands.push((frame, Action::Release(control))),
+                    "tap" => {
+                        self.commands.push((frame, Action::Press(control.clone())));
+                        self.commands.push((frame + 1, Action::Release(control)));
+                    }
+                    _ => return Err(format!("unknown action: {action:?}")),
+                }
+            }
+            _ => return Err(format!("invalid command: {:?}", line.trim())),
+        }
+        Ok(())
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// the locked rows the game starts from, in the debug board's format
+    pub fn board(&self) -> Option<&str> {
+        self.board.as_deref()
+    }
+
+    /// frames played so far
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// the number of frames due in real time after the time since the last update
+    pub fn frames_due(&mut self, delta_time: f64) -> usize {
+        self.time += delta_time;
+        let frames = (self.time / FRAME_TIME) as usize;
+        self.time -= frames as f64 * FRAME_TIME;
+        frames
+    }
+
+    /// the keys of the next frame, None once the script has finished
+    pub fn next_keys(&mut self) -> Option<InputFrame> {
+        if self.frame > self.last_frame {
+            return None;
+        }
+        let mut pressed = vec![];
+        while let Some((_, action)) = self
+            .commands
+            .get(self.next_command)
+            .filter(|(frame, _)| *frame == self.frame)
+        {
+            match action {
+                Action::Press(control) if !self.down.contains(control) => {
+                    self.down.push(control.clone());
+                    pressed.push(control.clone());
+                }
+                Action::Release(control) => self.down.retain(|down| down != control),
+                Action::Press(_) | Action::End => {}
+            }
+            self.next_command += 1;
+        }
+        let mut keys = InputFrame::default();
+        for control in &self.down {
+            keys.hold(control, pressed.contains(control));
+        }
+        self.frame += 1;
+        Some(keys)
+    }
+}
+
+fn parse_frame(frame: &str) -> Result<usize, String> {
+    frame
+        .parse()
+        .map_err(|_| format!("invalid frame: {frame:?}"))
+}
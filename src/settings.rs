@@ -0,0 +1,717 @@
+use crate::{
+    controls::{self, TimingUnit},
+    cpu::CpuDifficulty,
+    game::GravityRamp,
+    language::Strings,
+    profiles::ProfileStore,
+    rustomino::{PieceWeights, RotationKicks},
+    scoring::{ScoringRuleset, ScoringTable},
+    sound::Music,
+    storage,
+    summary::{StatsExport, SummaryFolder},
+    theme::Theme,
+    view::{BlockStyle, GhostColor, GhostStyle, ScreenMode, ShakeIntensity, SpawnAnimation},
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, fs, io, path::Path};
+use strum::{EnumIter, IntoEnumIterator};
+
+const GRAVITY_RAMP_LENGTHS: [u32; 6] = [10, 20, 30, 60, 90, 120];
+const LINE_CLEAR_DELAYS_MS: [u32; 7] = [0, 100, 200, 300, 400, 500, 600];
+const HARD_DROP_LOCKOUTS_MS: [u32; 5] = [0, 50, 100, 150, 200];
+const MAX_AUTO_SHIFT_DELAY_MS: u32 = 500;
+const MAX_AUTO_REPEAT_DELAY_MS: u32 = 100;
+const BOARD_SCALES: [u32; 11] = [75, 80, 85, 90, 95, 100, 105, 110, 115, 120, 125];
+const BOARD_OFFSETS: [i32; 9] = [-100, -75, -50, -25, 0, 25, 50, 75, 100];
+// hosting only on this computer or on every network interface,
+// the settings file can name any other address
+const LOCAL_HOST_ADDRESS: &str = "127.0.0.1";
+const NETWORK_HOST_ADDRESS: &str = "0.0.0.0";
+pub const VOLUME_STEP: u32 = 5; // percent
+
+// Player configurable settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub scoring_ruleset: ScoringRuleset,
+    // used by the custom scoring ruleset
+    pub custom_scoring: ScoringTable,
+    pub theme: String,
+    // the language of the text, English or one found in assets/lang
+    pub language: String,
+    pub block_style: BlockStyle,
+    // outlines locked blocks with a darker shade of their color
+    pub block_borders: bool,
+    pub ghost_style: GhostStyle,
+    // the outlined ghost's color, uniform uses the theme's ghost color
+    pub ghost_color: GhostColor,
+    // lets the player change the active rustomino's type in practice games
+    pub piece_cycling: bool,
+    // outlines the best placement for the active rustomino in practice and sandbox games
+    pub placement_hints: bool,
+    // black playfield, saturated colors and outlined blocks
+    pub high_contrast: bool,
+    pub grid_lines: bool,
+    // blocks in the buffer rows above the playfield are drawn translucent
+    pub faint_buffer_rows: bool,
+    // how long completed lines flash before they collapse, 0 collapses them instantly
+    pub line_clear_delay_ms: u32,
+    pub screen_shake: ShakeIntensity,
+    // how new rustominos enter the playfield, only drawn differently
+    pub spawn_animation: SpawnAnimation,
+    // the path of a hard dropped rustomino fades out behind it
+    pub hard_drop_trail: bool,
+    // disables every flashing or moving effect, every effect checks it before animating:
+    // line clear particles, screen shake, piece entry, the hard drop trail
+    // and the line clear flash are skipped,
+    // completed lines are greyed out instead of flashing
+    // and the level background changes without blending
+    pub reduced_motion: bool,
+    pub gravity_ramp: GravityRamp,
+    // seconds or pieces until gravity reaches the next level's speed
+    pub gravity_ramp_length: u32,
+    // how often each rustomino type is dealt, drought makes I rustominos rare
+    pub piece_weights: PieceWeights,
+    // how a rotation that collides is moved to a free position
+    pub rotation_kicks: RotationKicks,
+    // rustominos are dealt in a random orientation, shown in the preview
+    pub random_orientation: bool,
+    // spawning rustominos that collide are moved up into the buffer rows before the game ends
+    pub spawn_nudge: bool,
+    // the game ends when a rustomino locks entirely above the visible playfield,
+    // when it's off the rustomino stays locked in the hidden buffer rows
+    pub lock_out: bool,
+    // the game ends when a rustomino spawns overlapping a locked block,
+    // when it's off the rustomino spawns anyway and replaces the blocks it overlaps
+    pub block_out: bool,
+    // how long hard drop is ignored after a hard drop, on top of releasing the key
+    pub hard_drop_lockout_ms: u32,
+    // the soft drop keys hard drop and the hard drop keys soft drop
+    pub swap_drop_keys: bool,
+    // how long left or right is held before it repeats (DAS) and the time between repeats (ARR),
+    // a repeat delay of 0 moves the rustomino to the wall at once
+    pub auto_shift_delay_ms: u32,
+    pub auto_repeat_delay_ms: u32,
+    // the auto shift delays are shown and changed in milliseconds or frames
+    pub auto_shift_unit: TimingUnit,
+    // a held left or right stays charged when a rustomino locks, so the next one shifts at once
+    pub das_carry: bool,
+    // a landed rustomino locks on the next gravity tick, moving it doesn't reset the lockdown
+    pub classic_lock: bool,
+    // soft drop locks a rustomino resting on the stack instead of waiting for the lockdown
+    pub soft_drop_lock: bool,
+    // both versus players are dealt the same rustominos, otherwise each bag has its own seed
+    pub versus_same_pieces: bool,
+    // draws the versus garbage waiting to be added beside each board
+    pub garbage_meter: bool,
+    // how quickly and how well the versus cpu opponent plays
+    pub cpu_difficulty: CpuDifficulty,
+    // the address an online match is hosted on, only this computer until the player changes it
+    pub online_host_address: String,
+    // percent of the default block size the board is drawn at
+    pub board_scale: u32,
+    // percent of the space beside and above the layout it's moved by, 0 centers it
+    pub board_offset_x: i32,
+    pub board_offset_y: i32,
+    // native recomputes the layout for the window, the canvas scales the design resolution
+    pub screen_mode: ScreenMode,
+    pub fullscreen: bool,
+    // the window's size when the game was last played windowed, None until it's known
+    pub window_size: Option<[u32; 2]>,
+    // pauses the game when the frame rate stays too low for fair timing
+    pub low_frame_rate_pause: bool,
+    // a background track's name, shuffle or off
+    pub music: String,
+    // percent volumes, the music can also be adjusted with + and -
+    pub music_volume: u32,
+    pub sfx_volume: u32,
+    // sound effects come from the rustomino's side of the playfield
+    pub stereo_panning: bool,
+    // silences the music and the sound effects, keeping their volumes
+    pub mute: bool,
+    // where session summaries are saved from the game over screen
+    pub summary_folder: SummaryFolder,
+    // appends each finished game to a CSV file or writes it to a JSON file
+    pub stats_export: StatsExport,
+    // the folder statistics are exported to, only set in the settings file,
+    // empty uses the data folder
+    pub stats_folder: String,
+    // the defaults an input script is played with, never saved over the player's settings
+    #[serde(skip)]
+    scripted: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            scoring_ruleset: ScoringRuleset::Guideline,
+            custom_scoring: ScoringTable::default(),
+            theme: Theme::BUILT_IN_NAME.to_owned(),
+            language: Strings::ENGLISH.to_owned(),
+            block_style: BlockStyle::Textured,
+            block_borders: true,
+            ghost_style: GhostStyle::Outline,
+            ghost_color: GhostColor::Uniform,
+            piece_cycling: false,
+            placement_hints: false,
+            high_contrast: false,
+            grid_lines: false,
+            faint_buffer_rows: false,
+            line_clear_delay_ms: 300,
+            screen_shake: ShakeIntensity::Off,
+            spawn_animation: SpawnAnimation::Fade,
+            hard_drop_trail: false,
+            reduced_motion: false,
+            gravity_ramp: GravityRamp::Stepped,
+            gravity_ramp_length: 60,
+            piece_weights: PieceWeights::Even,
+            rotation_kicks: RotationKicks::Srs,
+            random_orientation: false,
+            spawn_nudge: false,
+            lock_out: true,
+            block_out: true,
+            hard_drop_lockout_ms: 0,
+            swap_drop_keys: false,
+            auto_shift_delay_ms: controls::AUTO_SHIFT_DELAY_MS,
+            auto_repeat_delay_ms: controls::AUTO_REPEAT_DELAY_MS,
+            auto_shift_unit: TimingUnit::Milliseconds,
+            das_carry: true,
+            classic_lock: false,
+            soft_drop_lock: true,
+            versus_same_pieces: true,
+            garbage_meter: true,
+            cpu_difficulty: CpuDifficulty::Normal,
+            online_host_address: LOCAL_HOST_ADDRESS.to_owned(),
+            board_scale: 100,
+            board_offset_x: 0,
+            board_offset_y: 0,
+            screen_mode: ScreenMode::Native,
+            fullscreen: false,
+            window_size: None,
+            low_frame_rate_pause: false,
+            music: Music::SHUFFLE.to_owned(),
+            music_volume: 10,
+            sfx_volume: 50,
+            stereo_panning: true,
+            mute: false,
+            summary_folder: SummaryFolder::Data,
+            stats_export: StatsExport::Off,
+            stats_folder: String::new(),
+            scripted: false,
+        }
+    }
+}
+
+impl Settings {
+    const SETTINGS_FILE: &'static str = "settings.toml";
+    // earlier versions saved the settings as json with the saved game
+    const LEGACY_SETTINGS_FILE: &'static str = "settings.json";
+
+    /// Loads the saved settings, using the defaults if they can't be read,
+    /// unknown settings are ignored and missing ones use their defaults
+    /// so the file keeps working across versions
+    pub fn load(profiles: &ProfileStore) -> Self {
+        let settings_path = profiles.config_file(Self::SETTINGS_FILE);
+        if settings_path.exists() {
+            return Settings::read(&settings_path);
+        }
+        let legacy_path = profiles.data_file(Self::LEGACY_SETTINGS_FILE);
+        if !legacy_path.exists() {
+            return Settings::default();
+        }
+        // the json settings are moved to the config folder once
+        log::info!("migrating settings from {:?}", legacy_path);
+        let settings: Settings = match storage::read_json(&legacy_path) {
+            Ok(settings) => settings,
+            Err(e) => {
+                log::warn!("unable to load settings, using defaults: {}", e);
+                return Settings::default();
+            }
+        };
+        match settings.save(profiles) {
+            Ok(()) => {
+                if let Err(e) = fs::remove_file(&legacy_path) {
+                    log::warn!("unable to remove {:?}: {}", legacy_path, e);
+                }
+            }
+            Err(e) => log::warn!("unable to migrate settings: {}", e),
+        }
+        settings
+    }
+
+    // the settings in a toml file, or the defaults if it can't be read
+    fn read(path: &Path) -> Self {
+        match storage::read_toml(path) {
+            Ok(settings) => settings,
+            Err(e) => {
+                log::warn!("unable to load settings, using defaults: {}", e);
+                Settings::default()
+            }
+        }
+    }
+
+    /// the default settings, so a script plays the same for every player
+    pub fn for_script() -> Self {
+        Settings {
+            scripted: true,
+            ..Settings::default()
+        }
+    }
+
+    pub fn save(&self, profiles: &ProfileStore) -> io::Result<()> {
+        if self.scripted {
+            log::debug!("not saving the settings of an input script");
+            return Ok(());
+        }
+        storage::write_toml(&profiles.config_file(Self::SETTINGS_FILE), self)
+    }
+
+    /// the volume the music is played at, from 0 to 1
+    pub fn music_playback_volume(&self) -> f32 {
+        if self.mute {
+            return 0.;
+        }
+        self.music_volume as f32 / 100.
+    }
+
+    /// the volume the sound effects are played at, from 0 to 1
+    pub fn sfx_playback_volume(&self) -> f32 {
+        if self.mute {
+            return 0.;
+        }
+        self.sfx_volume as f32 / 100.
+    }
+
+    /// the scoring table of the selected ruleset
+    pub fn scoring_table(&self) -> ScoringTable {
+        self.scoring_ruleset.table(&self.custom_scoring)
+    }
+}
+
+// The settings that can be changed from the settings menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter)]
+pub enum SettingItem {
+    ScoringRuleset,
+    Theme,
+    Language,
+    BlockStyle,
+    BlockBorders,
+    GhostStyle,
+    GhostColor,
+    PieceCycling,
+    PlacementHints,
+    HighContrast,
+    GridLines,
+    FaintBufferRows,
+    LineClearDelay,
+    ScreenShake,
+    SpawnAnimation,
+    HardDropTrail,
+    ReducedMotion,
+    GravityRamp,
+    GravityRampLength,
+    PieceWeights,
+    RotationKicks,
+    RandomOrientation,
+    SpawnNudge,
+    LockOut,
+    BlockOut,
+    HardDropLockout,
+    SwapDropKeys,
+    AutoShiftUnit,
+    AutoShiftDelay,
+    AutoRepeatDelay,
+    DasCarry,
+    ClassicLock,
+    SoftDropLock,
+    VersusSamePieces,
+    GarbageMeter,
+    CpuDifficulty,
+    OnlineHostAddress,
+    BoardScale,
+    BoardOffsetX,
+    BoardOffsetY,
+    ScreenMode,
+    Fullscreen,
+    LowFrameRatePause,
+    Music,
+    MusicVolume,
+    SfxVolume,
+    StereoPanning,
+    Mute,
+    SummaryFolder,
+    StatsExport,
+}
+
+// The available values of settings that depend on loaded assets
+pub struct SettingChoices {
+    pub themes: Vec<String>,
+    pub languages: Vec<String>,
+    pub music: Vec<String>,
+}
+
+impl SettingItem {
+    /// the current value of this setting for display
+    pub fn value(&self, settings: &Settings) -> String {
+        match self {
+            SettingItem::ScoringRuleset => settings.scoring_ruleset.to_string(),
+            SettingItem::Theme => settings.theme.clone(),
+            SettingItem::Language => settings.language.clone(),
+            SettingItem::BlockStyle => settings.block_style.to_string(),
+            SettingItem::BlockBorders => on_off(settings.block_borders),
+            SettingItem::GhostStyle => settings.ghost_style.to_string(),
+            SettingItem::GhostColor => settings.ghost_color.to_string(),
+            SettingItem::PieceCycling => on_off(settings.piece_cycling),
+            SettingItem::PlacementHints => on_off(settings.placement_hints),
+            SettingItem::HighContrast => on_off(settings.high_contrast),
+            SettingItem::GridLines => on_off(settings.grid_lines),
+            SettingItem::FaintBufferRows => on_off(settings.faint_buffer_rows),
+            SettingItem::LineClearDelay => match settings.line_clear_delay_ms {
+                0 => "Off".to_owned(),
+                delay => format!("{delay}ms"),
+            },
+            SettingItem::ScreenShake => settings.screen_shake.to_string(),
+            SettingItem::SpawnAnimation => settings.spawn_animation.to_string(),
+            SettingItem::HardDropTrail => on_off(settings.hard_drop_trail),
+            SettingItem::ReducedMotion => on_off(settings.reduced_motion),
+            SettingItem::GravityRamp => settings.gravity_ramp.to_string(),
+            SettingItem::GravityRampLength => match settings.gravity_ramp {
+                GravityRamp::Time => format!("{}s", settings.gravity_ramp_length),
+                _ => settings.gravity_ramp_length.to_string(),
+            },
+            SettingItem::PieceWeights => settings.piece_weights.to_string(),
+            SettingItem::RotationKicks => settings.rotation_kicks.to_string(),
+            SettingItem::RandomOrientation => on_off(settings.random_orientation),
+            SettingItem::SpawnNudge => on_off(settings.spawn_nudge),
+            SettingItem::LockOut => on_off(settings.lock_out),
+            SettingItem::BlockOut => on_off(settings.block_out),
+            SettingItem::HardDropLockout => match settings.hard_drop_lockout_ms {
+                0 => "Off".to_owned(),
+                lockout => format!("{lockout}ms"),
+            },
+            SettingItem::SwapDropKeys => on_off(settings.swap_drop_keys),
+            SettingItem::AutoShiftUnit => settings.auto_shift_unit.to_string(),
+            SettingItem::AutoShiftDelay => settings
+                .auto_shift_unit
+                .format(settings.auto_shift_delay_ms),
+            SettingItem::AutoRepeatDelay => match settings.auto_repeat_delay_ms {
+                0 => "Instant".to_owned(),
+                delay => settings.auto_shift_unit.format(delay),
+            },
+            SettingItem::DasCarry => on_off(settings.das_carry),
+            SettingItem::ClassicLock => on_off(settings.classic_lock),
+            SettingItem::SoftDropLock => on_off(settings.soft_drop_lock),
+            SettingItem::VersusSamePieces => on_off(settings.versus_same_pieces),
+            SettingItem::GarbageMeter => on_off(settings.garbage_meter),
+            SettingItem::CpuDifficulty => settings.cpu_difficulty.to_string(),
+            SettingItem::OnlineHostAddress => match settings.online_host_address.as_str() {
+                LOCAL_HOST_ADDRESS => "This Computer".to_owned(),
+                NETWORK_HOST_ADDRESS => "Network".to_owned(),
+                address => address.to_owned(),
+            },
+            SettingItem::BoardScale => format!("{}%", settings.board_scale),
+            SettingItem::BoardOffsetX => offset(settings.board_offset_x, "Left", "Right"),
+            SettingItem::BoardOffsetY => offset(settings.board_offset_y, "Up", "Down"),
+            SettingItem::ScreenMode => settings.screen_mode.to_string(),
+            SettingItem::Fullscreen => on_off(settings.fullscreen),
+            SettingItem::LowFrameRatePause => on_off(settings.low_frame_rate_pause),
+            SettingItem::Music => settings.music.clone(),
+            SettingItem::MusicVolume => format!("{}%", settings.music_volume),
+            SettingItem::SfxVolume => format!("{}%", settings.sfx_volume),
+            SettingItem::StereoPanning => on_off(settings.stereo_panning),
+            SettingItem::Mute => on_off(settings.mute),
+            SettingItem::SummaryFolder => settings.summary_folder.to_string(),
+            SettingItem::StatsExport => settings.stats_export.to_string(),
+        }
+    }
+
+    /// changes this setting to its next or previous value
+    pub fn change(&self, settings: &mut Settings, forward: bool, choices: &SettingChoices) {
+        match self {
+            SettingItem::ScoringRuleset => {
+                settings.scoring_ruleset = cycle(settings.scoring_ruleset, forward)
+            }
+            SettingItem::Theme => {
+                settings.theme = cycle_choice(&settings.theme, &choices.themes, forward)
+            }
+            SettingItem::Language => {
+                settings.language = cycle_choice(&settings.language, &choices.languages, forward)
+            }
+            SettingItem::BlockStyle => settings.block_style = cycle(settings.block_style, forward),
+            SettingItem::BlockBorders => settings.block_borders = !settings.block_borders,
+            SettingItem::GhostStyle => settings.ghost_style = cycle(settings.ghost_style, forward),
+            SettingItem::GhostColor => settings.ghost_color = cycle(settings.ghost_color, forward),
+            SettingItem::PieceCycling => settings.piece_cycling = !settings.piece_cycling,
+            SettingItem::PlacementHints => settings.placement_hints = !settings.placement_hints,
+            SettingItem::HighContrast => settings.high_contrast = !settings.high_contrast,
+            SettingItem::GridLines => settings.grid_lines = !settings.grid_lines,
+            SettingItem::FaintBufferRows => {
+                settings.faint_buffer_rows = !settings.faint_buffer_rows
+            }
+            SettingItem::LineClearDelay => {
+                settings.line_clear_delay_ms =
+                    cycle_value(settings.line_clear_delay_ms, &LINE_CLEAR_DELAYS_MS, forward)
+            }
+            SettingItem::ScreenShake => {
+                settings.screen_shake = cycle(settings.screen_shake, forward)
+            }
+            SettingItem::SpawnAnimation => {
+                settings.spawn_animation = cycle(settings.spawn_animation, forward)
+            }
+            SettingItem::HardDropTrail => settings.hard_drop_trail = !settings.hard_drop_trail,
+            SettingItem::ReducedMotion => settings.reduced_motion = !settings.reduced_motion,
+            SettingItem::GravityRamp => {
+                settings.gravity_ramp = cycle(settings.gravity_ramp, forward)
+            }
+            SettingItem::GravityRampLength => {
+                settings.gravity_ramp_length =
+                    cycle_value(settings.gravity_ramp_length, &GRAVITY_RAMP_LENGTHS, forward)
+            }
+            SettingItem::PieceWeights => {
+                settings.piece_weights = cycle(settings.piece_weights, forward)
+            }
+            SettingItem::RotationKicks => {
+                settings.rotation_kicks = cycle(settings.rotation_kicks, forward)
+            }
+            SettingItem::RandomOrientation => {
+                settings.random_orientation = !settings.random_orientation
+            }
+            SettingItem::SpawnNudge => settings.spawn_nudge = !settings.spawn_nudge,
+            SettingItem::LockOut => settings.lock_out = !settings.lock_out,
+            SettingItem::BlockOut => settings.block_out = !settings.block_out,
+            SettingItem::HardDropLockout => {
+                settings.hard_drop_lockout_ms = cycle_value(
+                    settings.hard_drop_lockout_ms,
+                    &HARD_DROP_LOCKOUTS_MS,
+                    forward,
+                )
+            }
+            SettingItem::SwapDropKeys => settings.swap_drop_keys = !settings.swap_drop_keys,
+            SettingItem::AutoShiftUnit => {
+                settings.auto_shift_unit = cycle(settings.auto_shift_unit, forward)
+            }
+            SettingItem::AutoShiftDelay => {
+                settings.auto_shift_delay_ms = settings.auto_shift_unit.step(
+                    settings.auto_shift_delay_ms,
+                    forward,
+                    MAX_AUTO_SHIFT_DELAY_MS,
+                )
+            }
+            SettingItem::AutoRepeatDelay => {
+                settings.auto_repeat_delay_ms = settings.auto_shift_unit.step(
+                    settings.auto_repeat_delay_ms,
+                    forward,
+                    MAX_AUTO_REPEAT_DELAY_MS,
+                )
+            }
+            SettingItem::DasCarry => settings.das_carry = !settings.das_carry,
+            SettingItem::ClassicLock => settings.classic_lock = !settings.classic_lock,
+            SettingItem::SoftDropLock => settings.soft_drop_lock = !settings.soft_drop_lock,
+            SettingItem::VersusSamePieces => {
+                settings.versus_same_pieces = !settings.versus_same_pieces
+            }
+            SettingItem::GarbageMeter => settings.garbage_meter = !settings.garbage_meter,
+            SettingItem::CpuDifficulty => {
+                settings.cpu_difficulty = cycle(settings.cpu_difficulty, forward)
+            }
+            SettingItem::OnlineHostAddress => {
+                settings.online_host_address = cycle_choice(
+                    &settings.online_host_address,
+                    &[LOCAL_HOST_ADDRESS, NETWORK_HOST_ADDRESS].map(str::to_owned),
+                    forward,
+                )
+            }
+            SettingItem::BoardScale => {
+                settings.board_scale = cycle_value(settings.board_scale, &BOARD_SCALES, forward)
+            }
+            SettingItem::BoardOffsetX => {
+                settings.board_offset_x =
+                    cycle_value(settings.board_offset_x, &BOARD_OFFSETS, forward)
+            }
+            SettingItem::BoardOffsetY => {
+                settings.board_offset_y =
+                    cycle_value(settings.board_offset_y, &BOARD_OFFSETS, forward)
+            }
+            SettingItem::ScreenMode => settings.screen_mode = cycle(settings.screen_mode, forward),
+            SettingItem::Fullscreen => settings.fullscreen = !settings.fullscreen,
+            SettingItem::LowFrameRatePause => {
+                settings.low_frame_rate_pause = !settings.low_frame_rate_pause
+            }
+            SettingItem::Music => {
+                settings.music = cycle_choice(&settings.music, &choices.music, forward)
+            }
+            SettingItem::MusicVolume => {
+                settings.music_volume = step_volume(settings.music_volume, forward)
+            }
+            SettingItem::SfxVolume => {
+                settings.sfx_volume = step_volume(settings.sfx_volume, forward)
+            }
+            SettingItem::StereoPanning => settings.stereo_panning = !settings.stereo_panning,
+            SettingItem::Mute => settings.mute = !settings.mute,
+            SettingItem::SummaryFolder => {
+                settings.summary_folder = cycle(settings.summary_folder, forward)
+            }
+            SettingItem::StatsExport => {
+                settings.stats_export = cycle(settings.stats_export, forward)
+            }
+        }
+        log::info!("setting changed: {} = {}", self, self.value(settings));
+    }
+}
+
+impl Display for SettingItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingItem::ScoringRuleset => write!(f, "Scoring"),
+            SettingItem::Theme => write!(f, "Theme"),
+            SettingItem::Language => write!(f, "Language"),
+            SettingItem::BlockStyle => write!(f, "Blocks"),
+            SettingItem::BlockBorders => write!(f, "Block Borders"),
+            SettingItem::GhostStyle => write!(f, "Ghost"),
+            SettingItem::GhostColor => write!(f, "Ghost Color"),
+            SettingItem::PieceCycling => write!(f, "Practice Piece Cycling"),
+            SettingItem::PlacementHints => write!(f, "Placement Hints"),
+            SettingItem::HighContrast => write!(f, "High Contrast"),
+            SettingItem::GridLines => write!(f, "Grid Lines"),
+            SettingItem::FaintBufferRows => write!(f, "Faint Buffer Rows"),
+            SettingItem::LineClearDelay => write!(f, "Line Clear Delay"),
+            SettingItem::ScreenShake => write!(f, "Screen Shake"),
+            SettingItem::SpawnAnimation => write!(f, "Piece Entry"),
+            SettingItem::HardDropTrail => write!(f, "Hard Drop Trail"),
+            SettingItem::ReducedMotion => write!(f, "Reduce Motion & Flashes"),
+            SettingItem::GravityRamp => write!(f, "Gravity"),
+            SettingItem::GravityRampLength => write!(f, "Gravity Ramp Length"),
+            SettingItem::PieceWeights => write!(f, "Piece Weights"),
+            SettingItem::RotationKicks => write!(f, "Rotation Kicks"),
+            SettingItem::RandomOrientation => write!(f, "Random Spawn Orientation"),
+            SettingItem::SpawnNudge => write!(f, "Spawn Nudge"),
+            SettingItem::LockOut => write!(f, "Lock Out"),
+            SettingItem::BlockOut => write!(f, "Block Out"),
+            SettingItem::HardDropLockout => write!(f, "Hard Drop Lockout"),
+            SettingItem::SwapDropKeys => write!(f, "Swap Soft & Hard Drop"),
+            SettingItem::AutoShiftUnit => write!(f, "DAS & ARR Unit"),
+            SettingItem::AutoShiftDelay => write!(f, "DAS"),
+            SettingItem::AutoRepeatDelay => write!(f, "ARR"),
+            SettingItem::DasCarry => write!(f, "DAS Carry"),
+            SettingItem::ClassicLock => write!(f, "Classic Lock"),
+            SettingItem::SoftDropLock => write!(f, "Soft Drop Lock"),
+            SettingItem::VersusSamePieces => write!(f, "Versus Same Pieces"),
+            SettingItem::GarbageMeter => write!(f, "Garbage Meter"),
+            SettingItem::CpuDifficulty => write!(f, "CPU Difficulty"),
+            SettingItem::OnlineHostAddress => write!(f, "Host Online Matches On"),
+            SettingItem::BoardScale => write!(f, "Board Scale"),
+            SettingItem::BoardOffsetX => write!(f, "Board Position X"),
+            SettingItem::BoardOffsetY => write!(f, "Board Position Y"),
+            SettingItem::ScreenMode => write!(f, "Screen"),
+            SettingItem::Fullscreen => write!(f, "Fullscreen"),
+            SettingItem::LowFrameRatePause => write!(f, "Pause On Low Frame Rate"),
+            SettingItem::Music => write!(f, "Music"),
+            SettingItem::MusicVolume => write!(f, "Music Volume"),
+            SettingItem::SfxVolume => write!(f, "Sound Effects Volume"),
+            SettingItem::StereoPanning => write!(f, "Stereo Panning"),
+            SettingItem::Mute => write!(f, "Mute"),
+            SettingItem::SummaryFolder => write!(f, "Summary Folder"),
+            SettingItem::StatsExport => write!(f, "Statistics Export"),
+        }
+    }
+}
+
+/// returns the variant after (or before) the provided one, wrapping around
+fn cycle<T: IntoEnumIterator + PartialEq + Copy>(value: T, forward: bool) -> T {
+    let variants: Vec<T> = T::iter().collect();
+    let index = variants.iter().position(|v| *v == value).unwrap_or(0);
+    if forward {
+        variants[(index + 1) % variants.len()]
+    } else {
+        variants[(index + variants.len() - 1) % variants.len()]
+    }
+}
+
+/// returns the volume a step louder or quieter, between 0 and 100 percent
+pub fn step_volume(volume: u32, louder: bool) -> u32 {
+    if louder {
+        (volume + VOLUME_STEP).min(100)
+    } else {
+        volume.saturating_sub(VOLUME_STEP)
+    }
+}
+
+fn on_off(value: bool) -> String {
+    if value { "On" } else { "Off" }.to_owned()
+}
+
+/// a percent offset in the direction it moves, 0 is centered
+fn offset(value: i32, negative: &str, positive: &str) -> String {
+    match value {
+        0 => "Centered".to_owned(),
+        value if value < 0 => format!("{negative} {}%", -value),
+        value => format!("{positive} {value}%"),
+    }
+}
+
+/// returns the choice after (or before) the provided one, wrapping around
+fn cycle_choice(value: &str, choices: &[String], forward: bool) -> String {
+    if choices.is_empty() {
+        return value.to_owned();
+    }
+    let index = choices.iter().position(|c| c == value).unwrap_or(0);
+    if forward {
+        choices[(index + 1) % choices.len()].clone()
+    } else {
+        choices[(index + choices.len() - 1) % choices.len()].clone()
+    }
+}
+
+/// returns the value after (or before) the provided one, wrapping around
+fn cycle_value<T: PartialEq + Copy>(value: T, values: &[T], forward: bool) -> T {
+    let index = values.iter().position(|v| *v == value).unwrap_or(0);
+    if forward {
+        values[(index + 1) % values.len()]
+    } else {
+        values[(index + values.len() - 1) % values.len()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_settings_round_trip() {
+        let toml = toml::to_string_pretty(&Settings::default()).unwrap();
+        let settings: Settings = toml::from_str(&toml).unwrap();
+        assert_eq!(toml::to_string_pretty(&settings).unwrap(), toml);
+    }
+
+    #[test]
+    fn unknown_and_missing_settings_use_defaults() {
+        let path =
+            std::env::temp_dir().join(format!("rustris-settings-{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            "music_volume = 40\nremoved_setting = true\n\n[custom_scoring]\ncombo_bonus = 25\n",
+        )
+        .unwrap();
+        let settings = Settings::read(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(settings.music_volume, 40);
+        assert_eq!(settings.sfx_volume, Settings::default().sfx_volume);
+        assert_eq!(settings.custom_scoring.combo_bonus, 25);
+        assert_eq!(
+            settings.custom_scoring.line_clears,
+            ScoringTable::default().line_clears
+        );
+    }
+
+    #[test]
+    fn unreadable_settings_use_defaults() {
+        let path =
+            std::env::temp_dir().join(format!("rustris-invalid-{}.toml", std::process::id()));
+        fs::write(&path, "music_volume = \"loud\"").unwrap();
+        let settings = Settings::read(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(settings.music_volume, Settings::default().music_volume);
+    }
+}
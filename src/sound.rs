@@ -0,0 +1,290 @@
+use crate::{game::GameState, loading::join_all};
+use ::rand::Rng;
+use macroquad::{
+    audio::{
+        load_sound_from_bytes, play_sound, set_sound_volume, stop_sound, PlaySoundParams, Sound,
+    },
+    file::load_file,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+const MUSIC_FILE_PREFIX: &str = "background"; // background.ogg, background2.wav, ...
+const MUSIC_FILE_EXTENSIONS: [&str; 2] = ["ogg", "wav"];
+const PAUSED_MUSIC_LEVEL: f32 = 0.3; // share of the music volume played while paused
+const GAME_OVER_MUSIC_LEVEL: f32 = 0.05; // share of the music volume played after a game over
+const MUSIC_FADE_TIME: f32 = 0.4; // seconds to fade between the full and silent music levels
+const GAME_OVER_MUSIC_FADE_TIME: f32 = 2.; // the slower fade after a game over
+
+// A background music track
+struct Track {
+    name: String,
+    sound: Sound,
+    length: Option<f64>, // seconds, None if it couldn't be read from the file
+}
+
+// The background music tracks found in the assets folder,
+// plays the track selected by the music setting or shuffles them
+pub struct Music {
+    tracks: Vec<Track>,
+    selection: Option<String>, // the music setting the playing track was started for
+    playing: Option<usize>,    // index of the playing track
+    time_left: Option<f64>,    // seconds until a shuffled track ends, None while a track loops
+    level: VolumeEnvelope,     // share of the music volume for the game state
+    volume: f32,               // the playing track's volume
+}
+
+impl Music {
+    pub const SHUFFLE: &'static str = "Shuffle";
+    pub const OFF: &'static str = "Off";
+
+    /// Loads every background track in the assets folder, sorted by file name
+    pub async fn load(assets_path: &Path) -> Self {
+        let mut track_paths: Vec<_> = match fs::read_dir(assets_path) {
+            Ok(entries) => entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    let is_track_name = path
+                        .file_stem()
+                        .is_some_and(|stem| stem.to_string_lossy().starts_with(MUSIC_FILE_PREFIX));
+                    let is_track_extension = path.extension().is_some_and(|extension| {
+                        MUSIC_FILE_EXTENSIONS.contains(&extension.to_string_lossy().as_ref())
+                    });
+                    is_track_name && is_track_extension
+                })
+                .collect(),
+            Err(e) => {
+                log::warn!("unable to read assets folder {:?}: {}", assets_path, e);
+                vec![]
+            }
+        };
+        track_paths.sort();
+        let tracks = join_all(track_paths.into_iter().map(load_track).collect())
+            .await
+            .into_iter()
+            .flatten()
+            .collect();
+        Music {
+            tracks,
+            selection: None,
+            playing: None,
+            time_left: None,
+            level: VolumeEnvelope::new(1.),
+            volume: 0.,
+        }
+    }
+
+    /// the choices of the music setting: shuffle, each track, then off
+    pub fn choices(&self) -> Vec<String> {
+        let mut choices = vec![Self::SHUFFLE.to_owned()];
+        choices.extend(self.tracks.iter().map(|track| track.name.clone()));
+        choices.push(Self::OFF.to_owned());
+        choices
+    }
+
+    /// starts the track for the music setting when it changes
+    /// and advances to the next shuffled track when one ends,
+    /// the volume fades to a quieter level while paused and almost silent after a game over,
+    /// it fades back in from wherever it is when a game is resumed or started,
+    /// returns the name of the track started this frame
+    pub fn update(
+        &mut self,
+        selection: &str,
+        volume: f32,
+        state: &GameState,
+        delta_time: f64,
+    ) -> Option<&str> {
+        match state {
+            GameState::Paused => self.level.fade_to(PAUSED_MUSIC_LEVEL, MUSIC_FADE_TIME),
+            GameState::GameOver => self
+                .level
+                .fade_to(GAME_OVER_MUSIC_LEVEL, GAME_OVER_MUSIC_FADE_TIME),
+            _ => self.level.fade_to(1., MUSIC_FADE_TIME),
+        }
+        let level = self.level.update(delta_time);
+        if volume * level != self.volume {
+            self.volume = volume * level;
+            if let Some(index) = self.playing {
+                set_sound_volume(self.tracks[index].sound, self.volume);
+            }
+        }
+
+        if self.selection.as_deref() != Some(selection) {
+            self.stop();
+            self.selection = Some(selection.to_owned());
+            let index = match selection {
+                Self::OFF => return None,
+                Self::SHUFFLE => self.shuffled_track()?,
+                // a track that's no longer in the assets folder is shuffled
+                name => match self.tracks.iter().position(|track| track.name == name) {
+                    Some(index) => index,
+                    None => self.shuffled_track()?,
+                },
+            };
+            return Some(self.play(index, selection != Self::SHUFFLE));
+        }
+        let time_left = self.time_left.as_mut()?;
+        *time_left -= delta_time;
+        if *time_left > 0. {
+            return None;
+        }
+        self.stop();
+        let index = self.shuffled_track()?;
+        Some(self.play(index, false))
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(index) = self.playing.take() {
+            stop_sound(self.tracks[index].sound);
+        }
+        self.time_left = None;
+    }
+
+    /// a random track that's different from the last one when there's more than one
+    fn shuffled_track(&self) -> Option<usize> {
+        match self.tracks.len() {
+            0 => None,
+            1 => Some(0),
+            len => {
+                let mut rng = ::rand::thread_rng();
+                loop {
+                    let index = rng.gen_range(0..len);
+                    if Some(index) != self.playing {
+                        return Some(index);
+                    }
+                }
+            }
+        }
+    }
+
+    /// plays the track, shuffled tracks play once unless their length is unknown
+    /// or there's only one track, returns its name
+    fn play(&mut self, index: usize, looped: bool) -> &str {
+        let track = &self.tracks[index];
+        self.time_left = track.length.filter(|_| !looped && self.tracks.len() > 1);
+        log::info!(
+            "playing background music {} at volume: {}",
+            track.name,
+            self.volume
+        );
+        play_sound(
+            track.sound,
+            PlaySoundParams {
+                looped: self.time_left.is_none(),
+                volume: self.volume,
+            },
+        );
+        self.playing = Some(index);
+        &track.name
+    }
+}
+
+/// the length in seconds of a wav or ogg vorbis file, read from its headers
+// A volume that fades toward its target at a steady rate, updated every frame
+#[derive(Debug, Clone, Copy)]
+struct VolumeEnvelope {
+    current: f32,
+    target: f32,
+    rate: f32, // volume per second
+}
+
+impl VolumeEnvelope {
+    fn new(volume: f32) -> Self {
+        VolumeEnvelope {
+            current: volume,
+            target: volume,
+            rate: 0.,
+        }
+    }
+
+    /// fades from the current volume, replacing any fade in progress,
+    /// `fade_time` is the seconds a fade between silent and full volume takes
+    fn fade_to(&mut self, target: f32, fade_time: f32) {
+        self.target = target;
+        self.rate = 1. / fade_time;
+    }
+
+    /// moves the volume toward the target and returns it
+    fn update(&mut self, delta_time: f64) -> f32 {
+        let step = self.rate * delta_time as f32;
+        self.current = if self.current < self.target {
+            (self.current + step).min(self.target)
+        } else {
+            (self.current - step).max(self.target)
+        };
+        self.current
+    }
+}
+
+async fn load_track(track_path: PathBuf) -> Option<Track> {
+    log::info!("loading background music: {:?}", track_path);
+    let bytes = match load_file(&track_path.to_string_lossy()).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            log::warn!("unable to read background music {:?}: {}", track_path, e);
+            return None;
+        }
+    };
+    match load_sound_from_bytes(&bytes).await {
+        Ok(sound) => Some(Track {
+            name: track_path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            sound,
+            length: track_length(&bytes),
+        }),
+        Err(e) => {
+            log::warn!("unable to load background music {:?}: {}", track_path, e);
+            None
+        }
+    }
+}
+
+fn track_length(bytes: &[u8]) -> Option<f64> {
+    let u32_at = |offset: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(
+            bytes.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+    match bytes.get(0..4)? {
+        b"RIFF" => {
+            // the chunks after the wave header, the fmt chunk has the byte rate
+            let mut offset = 12;
+            let mut byte_rate = None;
+            while let (Some(id), Some(size)) = (bytes.get(offset..offset + 4), u32_at(offset + 4)) {
+                match id {
+                    b"fmt " => byte_rate = u32_at(offset + 16),
+                    b"data" => {
+                        return Some(size as f64 / byte_rate.filter(|rate| *rate > 0)? as f64)
+                    }
+                    _ => {}
+                }
+                offset += 8 + size as usize + size as usize % 2;
+            }
+            None
+        }
+        b"OggS" => {
+            // the sample rate is in the vorbis identification header,
+            // the last page's granule position is the number of samples
+            let header = find(bytes, b"\x01vorbis")?;
+            let sample_rate = u32_at(header + 12)?;
+            let last_page = bytes.windows(4).rposition(|window| window == b"OggS")?;
+            let samples =
+                u64::from_le_bytes(bytes.get(last_page + 6..last_page + 14)?.try_into().ok()?);
+            (sample_rate > 0).then(|| samples as f64 / sample_rate as f64)
+        }
+        _ => None,
+    }
+}
+
+/// the offset of the first occurrence of the pattern in the bytes
+fn find(bytes: &[u8], pattern: &[u8]) -> Option<usize> {
+    bytes
+        .windows(pattern.len())
+        .position(|window| window == pattern)
+}
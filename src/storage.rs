@@ -0,0 +1,68 @@
+use directories::ProjectDirs;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+// fallbacks when the platform directories can't be determined
+pub const LOCAL_DATA_FOLDER: &str = "data";
+pub const LOCAL_CONFIG_FOLDER: &str = "config";
+
+/// Returns the folder rustris stores its data in
+/// creating it if it doesn't exist
+pub fn data_dir() -> PathBuf {
+    let data_dir = ProjectDirs::from("", "", "rustris")
+        .map(|dirs| dirs.data_dir().to_path_buf())
+        .unwrap_or_else(|| PathBuf::from(LOCAL_DATA_FOLDER));
+    if let Err(e) = fs::create_dir_all(&data_dir) {
+        log::warn!("unable to create data folder {:?}: {}", data_dir, e);
+    }
+    data_dir
+}
+
+/// Returns the folder rustris stores its configuration in
+/// creating it if it doesn't exist
+pub fn config_dir() -> PathBuf {
+    let config_dir = ProjectDirs::from("", "", "rustris")
+        .map(|dirs| dirs.config_dir().to_path_buf())
+        .unwrap_or_else(|| PathBuf::from(LOCAL_CONFIG_FOLDER));
+    if let Err(e) = fs::create_dir_all(&config_dir) {
+        log::warn!("unable to create config folder {:?}: {}", config_dir, e);
+    }
+    config_dir
+}
+
+/// Reads and deserializes a json file
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> io::Result<T> {
+    let contents = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+/// Serializes the value and writes it to a json file.
+/// The value is written to a temporary file first and then renamed
+/// so a failed write never leaves a truncated file behind
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let contents = serde_json::to_string_pretty(value)?;
+    write_atomic(path, &contents)
+}
+
+/// Reads and deserializes a toml file
+pub fn read_toml<T: DeserializeOwned>(path: &Path) -> io::Result<T> {
+    let contents = fs::read_to_string(path)?;
+    toml::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Serializes the value and writes it to a toml file, like write_json
+pub fn write_toml<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let contents =
+        toml::to_string_pretty(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    write_atomic(path, &contents)
+}
+
+// writes a temporary file and renames it over the path
+fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, contents)?;
+    fs::rename(&temp_path, path)
+}
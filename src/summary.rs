@@ -0,0 +1,387 @@
+use crate::{
+    playfield::RustrisPlayfield, profiles::ProfileStore, scoring::ClearResult, storage,
+    theme::Theme, view,
+};
+use directories::UserDirs;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use strum::EnumIter;
+
+const SUMMARIES_FOLDER: &str = "summaries"; // in the data folder
+const SUMMARY_FILE_PREFIX: &str = "rustris-summary";
+const STATS_CSV_FILE: &str = "rustris-stats.csv"; // every game appended as a row
+const STATS_JSON_FILE_PREFIX: &str = "rustris-game"; // a file per game
+
+// the image's layout in pixels, its height fits the summary's lines
+// and the final board is drawn beside them
+const IMAGE_WIDTH: u32 = 640;
+const IMAGE_MARGIN: f32 = 30.;
+const IMAGE_LINE_HEIGHT: f32 = 30.;
+const IMAGE_BOARD_WIDTH: f32 = 170.;
+
+// Counts kept through a game for the session summary
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GameStats {
+    pub pieces: usize,                   // rustominos locked
+    pub clears: BTreeMap<String, usize>, // line clears and t-spins by type
+    #[serde(default)]
+    pub max_combo: usize, // most consecutive clears after the first
+    #[serde(default)]
+    pub key_presses: usize, // presses of the playing controls, repeats aren't counted
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryFormat {
+    Text,
+    Image,
+}
+
+impl SummaryFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            SummaryFormat::Text => "txt",
+            SummaryFormat::Image => "png",
+        }
+    }
+}
+
+// Where summaries are saved, a folder that can't be found falls back to the profile's data folder
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
+pub enum SummaryFolder {
+    #[default]
+    Data,
+    Documents,
+    Pictures,
+    Desktop,
+}
+
+impl SummaryFolder {
+    pub fn path(&self, profiles: &ProfileStore) -> PathBuf {
+        let user_dirs = UserDirs::new();
+        let folder = user_dirs.as_ref().and_then(|dirs| match self {
+            SummaryFolder::Data => None,
+            SummaryFolder::Documents => dirs.document_dir(),
+            SummaryFolder::Pictures => dirs.picture_dir(),
+            SummaryFolder::Desktop => dirs.desktop_dir(),
+        });
+        folder
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| profiles.data_file(SUMMARIES_FOLDER))
+    }
+}
+
+impl Display for SummaryFolder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SummaryFolder::Data => write!(f, "Data Folder"),
+            SummaryFolder::Documents => write!(f, "Documents"),
+            SummaryFolder::Pictures => write!(f, "Pictures"),
+            SummaryFolder::Desktop => write!(f, "Desktop"),
+        }
+    }
+}
+
+// How each finished game's statistics are exported
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
+pub enum StatsExport {
+    #[default]
+    Off,
+    Csv,
+    Json,
+}
+
+impl Display for StatsExport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StatsExport::Off => write!(f, "Off"),
+            StatsExport::Csv => write!(f, "CSV"),
+            StatsExport::Json => write!(f, "JSON"),
+        }
+    }
+}
+
+// The results of a finished game
+pub struct Summary {
+    pub mode: String,
+    pub score: usize,
+    pub level: usize,
+    pub lines: usize,
+    pub play_time: f64,
+    pub stats: GameStats,
+    pub seed: u64,
+    pub playfield: RustrisPlayfield, // the board when the game ended
+}
+
+impl Summary {
+    fn pieces_per_second(&self) -> f64 {
+        if self.play_time > 0. {
+            self.stats.pieces as f64 / self.play_time
+        } else {
+            0.
+        }
+    }
+
+    /// the summary as lines of text, shared by both formats
+    fn text_lines(&self) -> Vec<String> {
+        let pieces_per_second = self.pieces_per_second();
+        let mut lines = vec![
+            "Rustris Session Summary".to_owned(),
+            format!("Mode: {}", self.mode),
+            format!("Score: {}", self.score),
+            format!("Level: {}", self.level),
+            format!("Lines: {}", self.lines),
+            format!("Time: {}", view::format_play_time(self.play_time)),
+            format!("Pieces: {}", self.stats.pieces),
+            format!("Pieces Per Second: {pieces_per_second:.2}"),
+            format!("Seed: {}", self.seed),
+        ];
+        if self.stats.clears.is_empty() {
+            lines.push("Clears: None".to_owned());
+        } else {
+            lines.push("Clears:".to_owned());
+            lines.extend(
+                self.stats
+                    .clears
+                    .iter()
+                    .map(|(clear_type, count)| format!("  {clear_type}: {count}")),
+            );
+        }
+        lines
+    }
+}
+
+/// One game in the statistics export.
+/// The CSV columns and JSON keys are these fields in this order, they don't change
+/// between versions so the files can be graphed:
+/// timestamp (unix seconds when the game ended), mode, seed, score, level, lines,
+/// the number of each type of clear (single, double, triple, rustris, t_spin,
+/// t_spin_single, t_spin_double, t_spin_triple), pieces, max_combo,
+/// pps (pieces per second), kpp (key presses per piece) and duration (seconds played)
+#[derive(Debug, Serialize)]
+struct GameRecord {
+    timestamp: u64,
+    mode: String,
+    seed: u64,
+    score: usize,
+    level: usize,
+    lines: usize,
+    single: usize,
+    double: usize,
+    triple: usize,
+    rustris: usize,
+    t_spin: usize,
+    t_spin_single: usize,
+    t_spin_double: usize,
+    t_spin_triple: usize,
+    pieces: usize,
+    max_combo: usize,
+    pps: f64,
+    kpp: f64,
+    duration: f64,
+}
+
+impl GameRecord {
+    const CSV_HEADER: &'static str = "timestamp,mode,seed,score,level,lines,\
+        single,double,triple,rustris,t_spin,t_spin_single,t_spin_double,t_spin_triple,\
+        pieces,max_combo,pps,kpp,duration";
+
+    fn new(summary: &Summary) -> Self {
+        let [single, double, triple, rustris, t_spin, t_spin_single, t_spin_double, t_spin_triple] =
+            ClearResult::CLEAR_TYPES
+                .map(|clear_type| summary.stats.clears.get(clear_type).copied().unwrap_or(0));
+        let key_presses_per_piece = if summary.stats.pieces > 0 {
+            summary.stats.key_presses as f64 / summary.stats.pieces as f64
+        } else {
+            0.
+        };
+        GameRecord {
+            timestamp: unix_time(),
+            mode: summary.mode.clone(),
+            seed: summary.seed,
+            score: summary.score,
+            level: summary.level,
+            lines: summary.lines,
+            single,
+            double,
+            triple,
+            rustris,
+            t_spin,
+            t_spin_single,
+            t_spin_double,
+            t_spin_triple,
+            pieces: summary.stats.pieces,
+            max_combo: summary.stats.max_combo,
+            pps: summary.pieces_per_second(),
+            kpp: key_presses_per_piece,
+            duration: summary.play_time,
+        }
+    }
+
+    fn csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{:.3},{:.3},{:.3}",
+            self.timestamp,
+            self.mode,
+            self.seed,
+            self.score,
+            self.level,
+            self.lines,
+            self.single,
+            self.double,
+            self.triple,
+            self.rustris,
+            self.t_spin,
+            self.t_spin_single,
+            self.t_spin_double,
+            self.t_spin_triple,
+            self.pieces,
+            self.max_combo,
+            self.pps,
+            self.kpp,
+            self.duration
+        )
+    }
+}
+
+/// Appends the game to the CSV file or writes it to its own JSON file in the folder,
+/// an empty folder uses the profile's data folder
+pub fn export_stats(
+    summary: &Summary,
+    export: StatsExport,
+    folder: &str,
+    profiles: &ProfileStore,
+) -> io::Result<()> {
+    if export == StatsExport::Off {
+        return Ok(());
+    }
+    let folder = if folder.is_empty() {
+        profiles.data_dir()
+    } else {
+        PathBuf::from(folder)
+    };
+    fs::create_dir_all(&folder)?;
+    let record = GameRecord::new(summary);
+    match export {
+        StatsExport::Off => (),
+        StatsExport::Csv => {
+            let path = folder.join(STATS_CSV_FILE);
+            // the header is written when the file is started
+            let new_file = !path.exists();
+            let mut file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)?;
+            if new_file {
+                writeln!(file, "{}", GameRecord::CSV_HEADER)?;
+            }
+            writeln!(file, "{}", record.csv_row())?;
+            log::info!("game statistics appended to {:?}", path);
+        }
+        StatsExport::Json => {
+            let path = folder.join(format!(
+                "{STATS_JSON_FILE_PREFIX}-{}.json",
+                record.timestamp
+            ));
+            storage::write_json(&path, &record)?;
+            log::info!("game statistics written to {:?}", path);
+        }
+    }
+    Ok(())
+}
+
+// seconds since the unix epoch
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Saves the summary as a text file or an image in the folder, returns the file's path.
+/// The image is drawn to an offscreen target, the caller restores its camera afterwards
+pub fn export(
+    summary: &Summary,
+    format: SummaryFormat,
+    folder: &Path,
+    theme: &Theme,
+    text_params: &TextParams,
+) -> io::Result<PathBuf> {
+    fs::create_dir_all(folder)?;
+    let path = folder.join(format!(
+        "{SUMMARY_FILE_PREFIX}-{}.{}",
+        unix_time(),
+        format.extension()
+    ));
+    let lines = summary.text_lines();
+    match format {
+        SummaryFormat::Text => fs::write(&path, lines.join("\n") + "\n")?,
+        SummaryFormat::Image => save_image(summary, &lines, &path, theme, text_params)?,
+    }
+    log::info!("session summary saved: {:?}", path);
+    Ok(path)
+}
+
+// draws the lines and the final board in the theme's colors and saves them as a png
+fn save_image(
+    summary: &Summary,
+    lines: &[String],
+    path: &Path,
+    theme: &Theme,
+    text_params: &TextParams,
+) -> io::Result<()> {
+    let height = (IMAGE_MARGIN * 2. + lines.len() as f32 * IMAGE_LINE_HEIGHT) as u32;
+    let target = render_target(IMAGE_WIDTH, height);
+    set_camera(&Camera2D {
+        render_target: Some(target),
+        ..Camera2D::from_display_rect(Rect::new(0., 0., IMAGE_WIDTH as f32, height as f32))
+    });
+    clear_background(theme.background_color);
+    for (i, line) in lines.iter().enumerate() {
+        draw_text_ex(
+            line,
+            IMAGE_MARGIN,
+            IMAGE_MARGIN + (i + 1) as f32 * IMAGE_LINE_HEIGHT - text_params.font_size as f32 / 2.,
+            TextParams {
+                color: theme.text_color,
+                ..*text_params
+            },
+        );
+    }
+    let board_x = IMAGE_WIDTH as f32 - IMAGE_MARGIN - IMAGE_BOARD_WIDTH;
+    view::draw_thumbnail(
+        &summary.playfield,
+        theme,
+        view::Rect::new(
+            board_x,
+            IMAGE_MARGIN,
+            IMAGE_BOARD_WIDTH,
+            height as f32 - IMAGE_MARGIN * 2.,
+        ),
+    );
+    // changing the camera draws the text to the target before it's read
+    set_default_camera();
+    let image = target.texture.get_texture_data();
+    target.delete();
+
+    // render targets are stored upside down, the image is saved opaque
+    let row_length = IMAGE_WIDTH as usize * 4;
+    let mut bytes: Vec<u8> = image
+        .bytes
+        .chunks_exact(row_length)
+        .rev()
+        .flatten()
+        .copied()
+        .collect();
+    for pixel in bytes.chunks_exact_mut(4) {
+        pixel[3] = u8::MAX;
+    }
+    image::save_buffer(path, &bytes, IMAGE_WIDTH, height, image::ColorType::Rgba8)
+        .map_err(io::Error::other)
+}
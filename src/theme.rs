@@ -0,0 +1,357 @@
+use crate::rustomino::RustominoType;
+use crate::view::BlockTexture;
+use macroquad::prelude::*;
+use serde::Deserialize;
+use std::{collections::HashMap, fs, path::Path};
+use strum::IntoEnumIterator;
+
+// built-in theme colors
+const BACKGROUND_COLOR: Color = Color::new(0.0, 0.29, 0.38, 1.0);
+const STAGING_BACKGROUND_COLOR: Color = Color::new(0.0, 0.0, 0.0, 0.5);
+const PLAYFIELD_BACKGROUND_COLOR: Color = Color::new(0.0, 0.0, 0.0, 0.5);
+const PREVIEW_BACKGROUND_COLOR: Color = Color::new(0.0, 0.0, 0.0, 0.5);
+const HOLD_BACKGROUND_COLOR: Color = Color::new(0.0, 0.0, 0.0, 0.2);
+const GHOST_COLOR: Color = Color::new(0.7, 0.7, 0.7, 1.0);
+const GRID_COLOR: Color = Color::new(1.0, 1.0, 1.0, 0.08);
+// playfield backgrounds for each level, repeating after the last
+const LEVEL_COLORS: [Color; 12] = [
+    Color::new(0.0, 0.0, 0.0, 0.5),
+    Color::new(0.0, 0.05, 0.2, 0.5),
+    Color::new(0.08, 0.0, 0.22, 0.5),
+    Color::new(0.18, 0.0, 0.2, 0.5),
+    Color::new(0.22, 0.0, 0.08, 0.5),
+    Color::new(0.24, 0.05, 0.0, 0.5),
+    Color::new(0.22, 0.14, 0.0, 0.5),
+    Color::new(0.12, 0.18, 0.0, 0.5),
+    Color::new(0.0, 0.2, 0.04, 0.5),
+    Color::new(0.0, 0.2, 0.16, 0.5),
+    Color::new(0.0, 0.14, 0.24, 0.5),
+    Color::new(0.1, 0.1, 0.1, 0.5),
+];
+
+// the level the intense music starts at when the theme doesn't set one
+const INTENSE_MUSIC_LEVEL: usize = 10;
+
+const THEMES_FOLDER: &str = "themes";
+const THEME_MANIFEST: &str = "theme.json";
+const BUILT_IN_BLOCK_TEXTURE: &str = "blocks.png";
+
+// The colors and block texture used to draw the game
+pub struct Theme {
+    pub name: String,
+    pub background_color: Color,
+    pub playfield_background_color: Color,
+    pub staging_background_color: Color,
+    pub preview_background_color: Color,
+    pub hold_background_color: Color,
+    pub ghost_color: Color,
+    pub grid_color: Color,
+    pub text_color: Color,
+    // the playfield background for each level, empty to keep it the same
+    level_colors: Vec<Color>,
+    piece_colors: [Color; 7],
+    pub block_texture: Option<BlockTexture>,
+    // the background track played instead of the music setting's from the intense music level
+    intense_music: Option<String>,
+    intense_music_level: usize,
+}
+
+impl Theme {
+    pub const BUILT_IN_NAME: &'static str = "Default";
+
+    /// the theme used when no other is chosen or a theme's value is missing
+    pub fn built_in() -> Self {
+        Theme {
+            name: Theme::BUILT_IN_NAME.to_owned(),
+            background_color: BACKGROUND_COLOR,
+            playfield_background_color: PLAYFIELD_BACKGROUND_COLOR,
+            staging_background_color: STAGING_BACKGROUND_COLOR,
+            preview_background_color: PREVIEW_BACKGROUND_COLOR,
+            hold_background_color: HOLD_BACKGROUND_COLOR,
+            ghost_color: GHOST_COLOR,
+            grid_color: GRID_COLOR,
+            text_color: WHITE,
+            level_colors: LEVEL_COLORS.to_vec(),
+            piece_colors: RustominoType::iter()
+                .map(|rtype| rtype.color())
+                .collect::<Vec<Color>>()
+                .try_into()
+                .unwrap(),
+            block_texture: None,
+            intense_music: None,
+            intense_music_level: INTENSE_MUSIC_LEVEL,
+        }
+    }
+
+    pub fn piece_color(&self, rtype: RustominoType) -> Color {
+        self.piece_colors[rtype as usize]
+    }
+
+    /// the playfield background for the level, the colors repeat after the last level
+    /// returns None if the theme doesn't change the background by level
+    pub fn level_color(&self, level: usize) -> Option<Color> {
+        if self.level_colors.is_empty() {
+            return None;
+        }
+        Some(self.level_colors[level % self.level_colors.len()])
+    }
+
+    /// the background track to play at the level instead of the music setting's,
+    /// returns None below the intense music level or if the theme doesn't have one
+    pub fn intense_music(&self, level: usize) -> Option<&str> {
+        self.intense_music
+            .as_deref()
+            .filter(|_| level >= self.intense_music_level)
+    }
+
+    /// Resolves a theme from its manifest, missing values use the built-in theme.
+    /// Returns an error describing the first invalid value
+    fn from_manifest(manifest: ThemeManifest, default_name: &str) -> Result<Self, String> {
+        let built_in = Theme::built_in();
+        let color = |value: &Option<String>, default: Color| match value {
+            Some(hex) => parse_color(hex).ok_or(format!("invalid color: {hex}")),
+            None => Ok(default),
+        };
+        let level_colors = match &manifest.level_colors {
+            Some(level_colors) => level_colors
+                .iter()
+                .map(|hex| parse_color(hex).ok_or(format!("invalid color: {hex}")))
+                .collect::<Result<Vec<Color>, String>>()?,
+            // themes with their own playfield background keep it at every level
+            None if manifest.playfield_background.is_some() => vec![],
+            None => built_in.level_colors.clone(),
+        };
+        let mut piece_colors = built_in.piece_colors;
+        for (letter, hex) in &manifest.pieces {
+            let rtype = letter
+                .chars()
+                .next()
+                .and_then(RustominoType::from_char)
+                .ok_or(format!("invalid piece: {letter}"))?;
+            piece_colors[rtype as usize] =
+                parse_color(hex).ok_or(format!("invalid color: {hex}"))?;
+        }
+        Ok(Theme {
+            name: manifest.name.unwrap_or(default_name.to_owned()),
+            background_color: color(&manifest.background, built_in.background_color)?,
+            playfield_background_color: color(
+                &manifest.playfield_background,
+                built_in.playfield_background_color,
+            )?,
+            staging_background_color: color(
+                &manifest.staging_background,
+                built_in.staging_background_color,
+            )?,
+            preview_background_color: color(
+                &manifest.preview_background,
+                built_in.preview_background_color,
+            )?,
+            hold_background_color: color(
+                &manifest.hold_background,
+                built_in.hold_background_color,
+            )?,
+            ghost_color: color(&manifest.ghost, built_in.ghost_color)?,
+            grid_color: color(&manifest.grid, built_in.grid_color)?,
+            text_color: color(&manifest.text, built_in.text_color)?,
+            level_colors,
+            piece_colors,
+            block_texture: None,
+            intense_music: manifest.intense_music,
+            intense_music_level: manifest
+                .intense_music_level
+                .unwrap_or(built_in.intense_music_level),
+        })
+    }
+
+    /// Loads the theme in the provided folder
+    /// returns None if the manifest is missing or invalid
+    async fn load(theme_dir: &Path) -> Option<Self> {
+        let manifest_path = theme_dir.join(THEME_MANIFEST);
+        let folder_name = theme_dir.file_name()?.to_string_lossy().to_string();
+        let manifest: ThemeManifest = match fs::read_to_string(&manifest_path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| serde_json::from_str(&contents).map_err(|e| e.to_string()))
+        {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                log::warn!("unable to read theme manifest {:?}: {}", manifest_path, e);
+                return None;
+            }
+        };
+        let block_texture = manifest.block_texture.clone();
+        let mut theme = match Theme::from_manifest(manifest, &folder_name) {
+            Ok(theme) => theme,
+            Err(e) => {
+                log::warn!("invalid theme manifest {:?}: {}", manifest_path, e);
+                return None;
+            }
+        };
+        if let Some(block_texture) = block_texture {
+            theme.block_texture =
+                BlockTexture::load(&theme_dir.join(block_texture).to_string_lossy()).await;
+        }
+        log::info!("loaded theme: {}", theme.name);
+        Some(theme)
+    }
+}
+
+// A theme.json manifest, every value is optional
+// colors are hex strings e.g. "#004a61" or "#00000080"
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ThemeManifest {
+    name: Option<String>,
+    background: Option<String>,
+    playfield_background: Option<String>,
+    staging_background: Option<String>,
+    preview_background: Option<String>,
+    hold_background: Option<String>,
+    ghost: Option<String>,
+    grid: Option<String>,
+    text: Option<String>,
+    // piece letter to color
+    pieces: HashMap<String, String>,
+    // playfield backgrounds by level, an empty list keeps the background the same
+    // missing uses the built-in colors unless playfield_background is set
+    level_colors: Option<Vec<String>>,
+    // file name of the block texture in the theme folder
+    block_texture: Option<String>,
+    // name of a background track in the assets folder, e.g. "background2",
+    // played from the intense music level until the game ends
+    intense_music: Option<String>,
+    intense_music_level: Option<usize>,
+}
+
+// The built-in theme followed by the themes found in the assets folder
+pub struct Themes {
+    themes: Vec<Theme>,
+}
+
+impl Themes {
+    /// Loads the built-in theme and every theme folder in assets/themes
+    pub async fn load(assets_path: &Path) -> Self {
+        let mut built_in = Theme::built_in();
+        built_in.block_texture =
+            BlockTexture::load(&assets_path.join(BUILT_IN_BLOCK_TEXTURE).to_string_lossy()).await;
+        let mut themes = vec![built_in];
+
+        let themes_path = assets_path.join(THEMES_FOLDER);
+        let mut theme_dirs: Vec<_> = match fs::read_dir(&themes_path) {
+            Ok(entries) => entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir())
+                .collect(),
+            Err(e) => {
+                log::warn!("unable to read themes folder {:?}: {}", themes_path, e);
+                vec![]
+            }
+        };
+        theme_dirs.sort();
+        let theme_loads = theme_dirs
+            .iter()
+            .map(|theme_dir| Theme::load(theme_dir))
+            .collect();
+        themes.extend(
+            crate::loading::join_all(theme_loads)
+                .await
+                .into_iter()
+                .flatten(),
+        );
+        Themes { themes }
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.themes.iter().map(|theme| theme.name.clone()).collect()
+    }
+
+    /// returns the theme with the provided name or the built-in theme
+    pub fn get(&self, name: &str) -> &Theme {
+        self.themes
+            .iter()
+            .find(|theme| theme.name == name)
+            .unwrap_or(&self.themes[0])
+    }
+}
+
+/// parses a "#rrggbb" or "#rrggbbaa" hex color
+fn parse_color(hex: &str) -> Option<Color> {
+    let hex = hex.trim().trim_start_matches('#');
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    let alpha = if hex.len() == 8 { channel(6)? } else { 255 };
+    Some(Color::from_rgba(
+        channel(0)?,
+        channel(2)?,
+        channel(4)?,
+        alpha,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(manifest: &str) -> Result<Theme, String> {
+        let manifest: ThemeManifest = serde_json::from_str(manifest).map_err(|e| e.to_string())?;
+        Theme::from_manifest(manifest, "folder")
+    }
+
+    #[test]
+    fn the_shipped_theme_parses() {
+        let theme = parse(include_str!("../assets/themes/midnight/theme.json")).unwrap();
+        assert_eq!(theme.name, "Midnight");
+        assert_eq!(
+            theme.background_color,
+            Color::from_rgba(0x0b, 0x0f, 0x1e, 255)
+        );
+        assert_eq!(
+            theme.playfield_background_color,
+            Color::from_rgba(0, 0, 0, 0x99)
+        );
+        assert_eq!(
+            theme.piece_color(RustominoType::Z),
+            Color::from_rgba(0xe0, 0x44, 0x5b, 255)
+        );
+        assert_eq!(theme.level_color(10), theme.level_color(0));
+        assert_ne!(theme.level_color(1), theme.level_color(0));
+    }
+
+    #[test]
+    fn missing_values_use_the_built_in_theme() {
+        let built_in = Theme::built_in();
+        let theme = parse(r##"{ "ghost": "#102030", "pieces": { "T": "#ff00ff" } }"##).unwrap();
+        assert_eq!(theme.name, "folder");
+        assert_eq!(theme.ghost_color, Color::from_rgba(0x10, 0x20, 0x30, 255));
+        assert_eq!(theme.background_color, built_in.background_color);
+        assert_eq!(
+            theme.piece_color(RustominoType::T),
+            Color::from_rgba(255, 0, 255, 255)
+        );
+        assert_eq!(
+            theme.piece_color(RustominoType::I),
+            built_in.piece_color(RustominoType::I)
+        );
+        assert_eq!(theme.level_color(3), built_in.level_color(3));
+        assert_eq!(theme.intense_music(20), None);
+
+        // a playfield background is kept at every level unless level colors are set
+        let theme = parse(r##"{ "playfield_background": "#000000" }"##).unwrap();
+        assert_eq!(theme.level_color(3), None);
+        // unknown keys are ignored
+        assert!(parse(r#"{ "name": "Extra", "sparkles": true }"#).is_ok());
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        assert!(parse(r##"{ "background": "#12345" }"##).is_err());
+        assert!(parse(r##"{ "text": "white" }"##).is_err());
+        assert!(parse(r##"{ "level_colors": ["#000000", "#zzzzzz"] }"##).is_err());
+        assert!(parse(r##"{ "pieces": { "X": "#000000" } }"##).is_err());
+        assert!(parse(r##"{ "pieces": { "I": "#0000" } }"##).is_err());
+        assert!(parse(r#"{ "intense_music_level": "ten" }"#).is_err());
+        assert!(parse("{ not json").is_err());
+    }
+}
@@ -1,497 +1,3472 @@
-use crate::game::{self, RustrisGame};
-use crate::playfield::{self, RustrisPlayfield, SlotState};
+use crate::clip;
+use crate::daily;
+use crate::game::{self, ClearedLines, GameEvent, HardDropped, RustrisGame};
+use crate::high_scores::{GameMode, HighScores};
+use crate::language::{self, Strings};
+use crate::menu::{Menu, MenuItem, ProfileItem, ScrollText, TextInput};
+use crate::online;
+use crate::playfield::{self, SlotState};
+use crate::profiles::ProfileStore;
+use crate::replay::{self, ReplayEntry};
 use crate::rustomino::{Rustomino, RustominoType};
+use crate::scoring::ClearResult;
+use crate::settings::{SettingItem, Settings};
+use crate::theme::Theme;
+use macroquad::color::{hsl_to_rgb, rgb_to_hsl};
+use macroquad::miniquad::conf::Icon;
 use macroquad::prelude::*;
+use macroquad::telemetry;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use strum::EnumIter;
 
 const BLOCK_SIZE: i32 = 30;
 const BLOCK_PADDING: i32 = 1;
 const STAGING_PADDING: i32 = 2;
+const BLOCK_CORNER_RADIUS: f32 = 0.2; // fraction of the block size
+const BLOCK_CORNER_SEGMENTS: usize = 4; // line segments per rounded corner
+const BLOCK_BEVEL: f32 = 0.12; // fraction of the block size
+const BLOCK_HIGHLIGHT: f32 = 0.25; // how much lighter the bevel highlight is
+const BLOCK_SHADOW: f32 = 0.35; // how much darker the bevel's bottom and right edges are
+const HIGH_CONTRAST_OUTLINE_THICKNESS: f32 = 3.;
+const GHOST_OUTLINE_THICKNESS: f32 = 4.;
+const PLACEMENT_HINT_THICKNESS: f32 = 2.;
+const BLOCK_BORDER_THICKNESS: f32 = 2.;
+const BLOCK_BORDER_SHADE: f32 = 0.4; // how much darker the border is than the block
+const BUFFER_ROW_ALPHA: f32 = 0.35; // opacity of faint blocks in the buffer rows
+const GHOST_FILL_ALPHA: f32 = 0.3; // opacity of the translucent ghost
+const GARBAGE_METER_WIDTH: f32 = 6.; // fits in the gap between the hold and the playfield
+const GHOST_TINT_ALPHA: f32 = 0.6; // opacity of the outlined ghost in the piece's color
+const MAX_PARTICLES: usize = 1000;
+const PARTICLE_LIFETIME: f32 = 0.7; // seconds
+const PARTICLE_SIZE: f32 = 5.;
+const PARTICLE_SPEED: f32 = 250.; // maximum launch speed, design units per second
+const PARTICLE_GRAVITY: f32 = 900.; // design units per second squared
+const PARTICLES_PER_BLOCK: usize = 3;
+const RUSTRIS_PARTICLES_PER_BLOCK: usize = 6;
+const PERFECT_CLEAR_PARTICLES_PER_BLOCK: usize = 12;
+const HARD_DROP_SHAKE: f32 = 2.5; // design units
+const HARD_DROP_SHAKE_TIME: f32 = 0.1; // seconds
+const RUSTRIS_SHAKE: f32 = 6.; // design units
+const RUSTRIS_SHAKE_TIME: f32 = 0.25; // seconds
+const LEVEL_BLEND_TIME: f32 = 1.; // seconds to blend to the next level's background
+const LEVEL_BLEND_SUBTLE: f32 = 0.3; // how much of the level color the other boxes take
+const SPAWN_FADE_TIME: f32 = 0.1; // seconds
+const SPAWN_FADE_ALPHA: f32 = 0.3; // the alpha the fade starts from
+const SPAWN_DROP_TIME: f32 = 0.15; // seconds
+const SPAWN_DROP_DISTANCE: f32 = 2.; // blocks above the spawn position the drop starts from
+const HARD_DROP_TRAIL_TIME: f32 = 0.12; // seconds the hard drop trail takes to fade out
+const HARD_DROP_TRAIL_ALPHA: f32 = 0.5; // opacity of the trail just above the landed rustomino
+const SETTINGS_VISIBLE_ITEMS: usize = 17; // settings listed at once, the list scrolls to the selected one
+const DEBUG_BOARD_VISIBLE_CHARS: usize = 45; // the end of longer debug boards is shown
+const MODIFIER_ANNOUNCE_TIME: f64 = 3.; // seconds the new mystery modifier is shown over the playfield
+const BANNER_TIME: f32 = 1.5; // seconds a milestone banner is shown
+const BANNER_POP_TIME: f32 = 0.15; // seconds the banner takes to grow to its full size
+const BANNER_FADE_TIME: f32 = 0.4; // seconds at the end of the banner time it fades out
+const CLEAR_POPUP_TIME: f32 = 1.; // seconds a clear popup is shown
+const CLEAR_POPUP_RISE: f32 = 30.; // distance a clear popup floats up over its time
+const CLEAR_POPUP_SPACING: f32 = 50.; // distance between stacked clear popups
+const VOLUME_INDICATOR_TIME: f32 = 1.; // seconds the volume bar is shown after an adjustment
+const VOLUME_INDICATOR_FADE_TIME: f32 = 0.3; // seconds at the end of the indicator time it fades out
+const VOLUME_BAR_SIZE: [f32; 2] = [200., 12.]; // design units
+const NOW_PLAYING_TIME: f32 = 3.; // seconds a new background track's name is shown
 
-pub const BACKGROUND_COLOR: Color = Color::new(0.0, 0.29, 0.38, 1.0);
-const STAGING_BACKGROUND_COLOR: Color = Color::new(0.0, 0.0, 0.0, 0.5);
-const PLAYFIELD_BACKGROUND_COLOR: Color = Color::new(0.0, 0.0, 0.0, 0.5);
-const PREVIEW_BACKGROUND_COLOR: Color = Color::new(0.0, 0.0, 0.0, 0.5);
-const HOLD_BACKGROUND_COLOR: Color = Color::new(0.0, 0.0, 0.0, 0.2);
-const GHOST_COLOR: Color = Color::new(0.7, 0.7, 0.7, 1.0);
+const HOLD_LOCKED_COLOR: Color = Color::new(0.5, 0.5, 0.5, 1.0);
 const PAUSED_OVERLAY_COLOR: Color = Color::new(0.1, 0.1, 0.1, 0.6);
-const VIEW_SETTINGS: ViewSettings = ViewSettings::new(VIEW_WH);
+const ERROR_TEXT_COLOR: Color = Color::new(1.0, 0.3, 0.3, 1.0);
+const MENU_SELECTED_COLOR: Color = Color::new(1.0, 0.85, 0.0, 1.0);
 const CONTROLS_BACKGROUND_COLOR: Color = Color::new(0.34, 0.09, 0.12, 0.8);
-const VIEW_WH: [i32; 2] = [1024, 768];
+// alternating between attacks so each attack's lines can be told apart
+const GARBAGE_METER_COLORS: [Color; 2] = [
+    Color::new(0.9, 0.2, 0.2, 0.9),
+    Color::new(1.0, 0.55, 0.2, 0.9),
+];
+const PLACEMENT_HINT_COLOR: Color = Color::new(0.3, 1.0, 0.6, 0.5);
+const MENU_TITLE_Y: f32 = -200.; // design units from the center of the view
+const HIGH_SCORE_COLUMNS: [(&str, f32); 5] = [
+    // column headings and their x offsets from the center of the view
+    ("#", -330.),
+    ("Name", -270.),
+    ("Score", -130.),
+    ("Lines", 90.),
+    ("Level", 240.),
+];
+// the rustomino colors of the menu title's letters, in order
+const TITLE_LETTER_COLORS: [RustominoType; 7] = [
+    RustominoType::I,
+    RustominoType::O,
+    RustominoType::T,
+    RustominoType::L,
+    RustominoType::S,
+    RustominoType::J,
+    RustominoType::Z,
+];
+const HELP_TEXT_TOP: f32 = -130.; // the controls help box is beside the main menu
+const HELP_LINES_TOP: f32 = 72.; // the first line of the controls help below the box's top
+const HELP_LINE_HEIGHT: f32 = 30.;
+const VIEW_WH: [i32; 2] = [1024, 768]; // design resolution, the layout is scaled from this
+const MIN_VIEW_WH: [i32; 2] = [640, 480]; // below this size the layout stops shrinking
+
+// the backend can't report the monitor's size, a saved window size is kept below this
+// and the window manager fits the window to the monitor
+const MAX_WINDOW_WH: [i32; 2] = [7680, 4320];
+
+const THUMBNAIL_MIN_PADDED_BLOCK: f32 = 6.; // pixels, smaller thumbnail blocks touch
 
 pub fn window_conf() -> Conf {
-    Conf {
-        window_title: "Rustris".to_owned(),
-        window_width: VIEW_WH[0],
-        window_height: VIEW_WH[1],
-        window_resizable: false,
+    // the window is created before the game runs, so the settings are read here
+    // for the window size and fullscreen state of the last profile played
+    let settings = Settings::load(&ProfileStore::load());
+    let [window_width, window_height] = match settings.window_size {
+        Some(size) => [0, 1].map(|i| (size[i] as i32).clamp(MIN_VIEW_WH[i], MAX_WINDOW_WH[i])),
+        None => VIEW_WH,
+    };
+    let conf = Conf {
+        window_title: window_title(),
+        window_width,
+        window_height,
+        fullscreen: settings.fullscreen,
+        window_resizable: true,
         ..Default::default()
+    };
+    match load_window_icon() {
+        Some(icon) => Conf {
+            icon: Some(icon),
+            ..conf
+        },
+        None => conf,
+    }
+}
+
+/// the window title with the version, debug builds are marked
+fn window_title() -> String {
+    let title = format!("Rustris v{}", env!("CARGO_PKG_VERSION"));
+    if cfg!(debug_assertions) {
+        format!("{title} (debug)")
+    } else {
+        title
     }
 }
 
+/// loads the window icon from the assets folder
+/// returns None if it can't be loaded, the default icon is used instead
+fn load_window_icon() -> Option<Icon> {
+    let icon_path = game::assets_path().ok()?.join(game::WINDOW_ICON);
+    let image = match image::open(&icon_path) {
+        Ok(image) => image.to_rgba8(),
+        Err(e) => {
+            log::warn!("unable to load window icon {:?}: {}", icon_path, e);
+            return None;
+        }
+    };
+    let resized = |size: u32| {
+        image::imageops::resize(&image, size, size, image::imageops::FilterType::Lanczos3)
+            .into_raw()
+    };
+    Some(Icon {
+        small: resized(16).try_into().ok()?,
+        medium: resized(32).try_into().ok()?,
+        big: resized(64).try_into().ok()?,
+    })
+}
+
 pub struct ViewSettings {
-    pub view_w: i32,
-    pub view_h: i32,
+    pub view_w: f32,
+    pub view_h: f32,
+    pub scale: f32,             // ratio of the current layout to the design resolution
+    pub board_scale: u32,       // percent of the design block size the board is drawn at
+    pub board_offset: [i32; 2], // percent of the space around the layout it's moved by
+    pub block_size: f32,
+    pub block_padding: f32,
+    pub staging_padding: f32, // gap between the staging area and the playfield
     pub playfield_rect: Rect<f32>,
     pub staging_rect: Rect<f32>,
     pub preview_rect: Rect<f32>,
     pub hold_rect: Rect<f32>,
-    pub score_label_pos: IVec2,
-    pub level_label_pos: IVec2,
-    pub title_pos: IVec2,
-    pub level_pos: IVec2,
-    pub score_pos: IVec2,
+    pub score_label_pos: Vec2,
+    pub level_label_pos: Vec2,
+    pub game_time_label_pos: Vec2,
+    pub game_time_pos: Vec2,
+    pub session_time_label_pos: Vec2,
+    pub session_time_pos: Vec2,
+    pub title_pos: Vec2,
+    pub level_pos: Vec2,
+    pub score_pos: Vec2,
+    pub offset: Vec2, // screen shake offset applied to the playing view
 }
 
 impl ViewSettings {
-    const fn new(view_dimensions: [i32; 2]) -> Self {
-        let playfield_w =
-            (playfield::PLAYFIELD_SLOTS[0] as i32 * (BLOCK_SIZE + BLOCK_PADDING)) + BLOCK_PADDING;
-        let playfield_h = ((playfield::PLAYFIELD_SLOTS[1] as i32 - 2)
-            * (BLOCK_SIZE + BLOCK_PADDING))
-            + BLOCK_PADDING;
+    /// Computes the layout for a window of the provided dimensions.
+    /// The layout keeps the proportions of the design resolution
+    /// and is centered in the window.
+    /// The board scale resizes the blocks and the boxes around them,
+    /// larger boards are limited to the window's height.
+    /// The board offset moves the layout into the space left around it
+    /// by a window with different proportions, such as an ultrawide one
+    pub fn new(view_dimensions: [f32; 2], board_scale: u32, board_offset: [i32; 2]) -> Self {
+        ViewSettings::layout(view_dimensions, MIN_VIEW_WH, board_scale, board_offset)
+    }
+
+    /// the layout of one player's half of the view in a versus match,
+    /// player 0 is on the left and the board offset isn't used
+    pub fn split(&self, player: usize) -> ViewSettings {
+        let half_w = self.view_w / 2.;
+        let min_view_wh = [MIN_VIEW_WH[0] / 2, MIN_VIEW_WH[1]];
+        ViewSettings::layout([half_w, self.view_h], min_view_wh, self.board_scale, [0, 0])
+            .moved(vec2(half_w * player as f32, 0.))
+    }
+
+    fn layout(
+        view_dimensions: [f32; 2],
+        min_view_wh: [i32; 2],
+        board_scale: u32,
+        board_offset: [i32; 2],
+    ) -> Self {
+        let view_w = view_dimensions[0].max(min_view_wh[0] as f32);
+        let view_h = view_dimensions[1].max(min_view_wh[1] as f32);
+        let scale = (view_w / VIEW_WH[0] as f32).min(view_h / VIEW_WH[1] as f32);
+
+        // the playfield, buffer rows and a block of margin above and below fit in the window
+        let max_block_scale = view_h
+            / ((playfield::PLAYFIELD_SLOTS[1] + 2) as f32 * (BLOCK_SIZE + BLOCK_PADDING) as f32);
+        let block_scale = (scale * board_scale as f32 / 100.).min(max_block_scale.max(scale));
+        let block_size = (BLOCK_SIZE as f32 * block_scale).floor();
+        let block_padding = (BLOCK_PADDING as f32 * block_scale).round().max(1.);
+        // whole pixels so the buffer rows drawn in the staging area stay sharp
+        let staging_padding = (STAGING_PADDING as f32 * scale).round().max(1.);
+        let block_stride = block_size + block_padding;
+
+        let playfield_w = (playfield::PLAYFIELD_SLOTS[0] as f32 * block_stride) + block_padding;
+        let playfield_h =
+            ((playfield::PLAYFIELD_SLOTS[1] - 2) as f32 * block_stride) + block_padding;
         let staging_w = playfield_w;
-        let staging_h = (2 * (BLOCK_SIZE + BLOCK_PADDING)) + BLOCK_PADDING;
-        let preview_w = (4 * (BLOCK_SIZE + BLOCK_PADDING)) + BLOCK_PADDING;
+        let staging_h = (2. * block_stride) + block_padding;
+        let preview_w = (4. * block_stride) + block_padding;
         let preview_h = staging_h;
         let hold_w = preview_w;
         let hold_h = staging_h;
 
-        let playfield_x = view_dimensions[0] / 2 - playfield_w / 2;
-        let playfield_y = view_dimensions[1] / 2 - playfield_h / 2 + staging_h / 2 + 1;
+        // half of the space beside and above the layout, the offset moves it up to the edge
+        let free_w = ((view_dimensions[0] - VIEW_WH[0] as f32 * scale) / 2.).max(0.);
+        let free_h = ((view_dimensions[1] - VIEW_WH[1] as f32 * scale) / 2.).max(0.);
+        let offset_x = free_w * board_offset[0] as f32 / 100.;
+        let offset_y = free_h * board_offset[1] as f32 / 100.;
+
+        let playfield_x = (view_dimensions[0] / 2. - playfield_w / 2. + offset_x).floor();
+        let playfield_y =
+            (view_dimensions[1] / 2. - playfield_h / 2. + staging_h / 2. + 1. + offset_y).floor();
         let staging_x = playfield_x;
-        let staging_y = playfield_y - staging_h - STAGING_PADDING;
-        let preview_x = playfield_x + playfield_w + 10;
+        let staging_y = playfield_y - staging_h - staging_padding;
+        let preview_x = playfield_x + playfield_w + 10. * scale;
         let preview_y = playfield_y;
-        let hold_x = playfield_x - preview_w - 10;
+        let hold_x = playfield_x - preview_w - 10. * scale;
         let hold_y = playfield_y;
 
         Self {
             view_w: view_dimensions[0],
             view_h: view_dimensions[1],
-            playfield_rect: Rect::new(
-                playfield_x as f32,
-                playfield_y as f32,
-                playfield_w as f32,
-                playfield_h as f32,
+            scale,
+            board_scale,
+            board_offset,
+            block_size,
+            block_padding,
+            staging_padding,
+            playfield_rect: Rect::new(playfield_x, playfield_y, playfield_w, playfield_h),
+            staging_rect: Rect::new(staging_x, staging_y, staging_w, staging_h),
+            preview_rect: Rect::new(preview_x, preview_y, preview_w, preview_h),
+            hold_rect: Rect::new(hold_x, hold_y, hold_w, hold_h),
+            score_label_pos: vec2(
+                playfield_x + playfield_w + 30. * scale,
+                playfield_y + playfield_h - 30. * scale,
             ),
-            staging_rect: Rect::new(
-                staging_x as f32,
-                staging_y as f32,
-                staging_w as f32,
-                staging_h as f32,
+            level_label_pos: vec2(
+                playfield_x - 180. * scale,
+                playfield_y + playfield_h - 30. * scale,
             ),
-            preview_rect: Rect::new(
-                preview_x as f32,
-                preview_y as f32,
-                preview_w as f32,
-                preview_h as f32,
+            game_time_label_pos: vec2(
+                playfield_x - 180. * scale,
+                playfield_y + playfield_h - 100. * scale,
             ),
-            hold_rect: Rect::new(hold_x as f32, hold_y as f32, hold_w as f32, hold_h as f32),
-            score_label_pos: ivec2(
-                playfield_x + playfield_w + 30,
-                playfield_y + playfield_h - 30,
+            game_time_pos: vec2(
+                playfield_x - 180. * scale,
+                playfield_y + playfield_h - 70. * scale,
             ),
-            level_label_pos: ivec2(playfield_x - 180, playfield_y + playfield_h - 30),
-            title_pos: ivec2(playfield_x - 280, playfield_y - 50),
-            level_pos: ivec2(playfield_x - 60, playfield_y + playfield_h - 30),
-            score_pos: ivec2(
-                playfield_x + playfield_w + 150,
-                playfield_y + playfield_h - 30,
+            session_time_label_pos: vec2(
+                playfield_x - 180. * scale,
+                playfield_y + playfield_h - 170. * scale,
             ),
+            session_time_pos: vec2(
+                playfield_x - 180. * scale,
+                playfield_y + playfield_h - 140. * scale,
+            ),
+            title_pos: vec2(playfield_x - 280. * scale, playfield_y - 50. * scale),
+            level_pos: vec2(
+                playfield_x - 60. * scale,
+                playfield_y + playfield_h - 30. * scale,
+            ),
+            score_pos: vec2(
+                playfield_x + playfield_w + 150. * scale,
+                playfield_y + playfield_h - 30. * scale,
+            ),
+            offset: Vec2::ZERO,
+        }
+    }
+
+    /// returns the layout with the playing view moved by the offset,
+    /// the labels move with the playfield so the whole view shakes together
+    fn shaken(&self, offset: Vec2) -> ViewSettings {
+        ViewSettings {
+            offset: self.offset + offset,
+            ..self.moved(offset)
+        }
+    }
+
+    // the layout with the boxes and labels moved by the offset
+    fn moved(&self, offset: Vec2) -> ViewSettings {
+        let move_rect =
+            |rect: Rect<f32>| Rect::new(rect.x + offset.x, rect.y + offset.y, rect.w, rect.h);
+        ViewSettings {
+            playfield_rect: move_rect(self.playfield_rect),
+            staging_rect: move_rect(self.staging_rect),
+            preview_rect: move_rect(self.preview_rect),
+            hold_rect: move_rect(self.hold_rect),
+            score_label_pos: self.score_label_pos + offset,
+            level_label_pos: self.level_label_pos + offset,
+            game_time_label_pos: self.game_time_label_pos + offset,
+            game_time_pos: self.game_time_pos + offset,
+            session_time_label_pos: self.session_time_label_pos + offset,
+            session_time_pos: self.session_time_pos + offset,
+            title_pos: self.title_pos + offset,
+            level_pos: self.level_pos + offset,
+            score_pos: self.score_pos + offset,
+            ..*self
+        }
+    }
+
+    /// x position offset from the horizontal center of the view
+    /// the offset is in design resolution units
+    fn center_x(&self, offset: f32) -> f32 {
+        self.view_w / 2. + offset * self.scale
+    }
+
+    /// y position offset from the vertical center of the view
+    /// the offset is in design resolution units
+    fn center_y(&self, offset: f32) -> f32 {
+        self.view_h / 2. + offset * self.scale
+    }
+
+    /// scales the provided text params to the current layout
+    fn text_params(&self, text_params: &TextParams) -> TextParams {
+        TextParams {
+            font_scale: text_params.font_scale * self.scale,
+            ..*text_params
+        }
+    }
+}
+
+// Block texture, a horizontal strip of square tiles
+// ordered I, O, T, L, J, S, Z, ghost, garbage
+pub struct BlockTexture {
+    texture: Texture2D,
+    tile_size: f32,
+}
+
+impl BlockTexture {
+    const GHOST_TILE: usize = 7;
+
+    /// loads the block texture, returns None if it can't be loaded
+    /// in which case blocks are drawn with flat colors
+    pub async fn load(path: &str) -> Option<Self> {
+        match load_texture(path).await {
+            Ok(texture) => {
+                texture.set_filter(FilterMode::Linear);
+                Some(BlockTexture {
+                    texture,
+                    tile_size: texture.height(),
+                })
+            }
+            Err(e) => {
+                log::warn!("unable to load block texture, using flat colors: {}", e);
+                None
+            }
+        }
+    }
+
+    fn source_rect(&self, tile: usize) -> macroquad::math::Rect {
+        macroquad::math::Rect::new(
+            tile as f32 * self.tile_size,
+            0.,
+            self.tile_size,
+            self.tile_size,
+        )
+    }
+}
+
+// A square that flies out of a cleared block
+struct Particle {
+    pos: Vec2,
+    velocity: Vec2,
+    color: Color,
+    age: f32,
+}
+
+// The music volume bar shown at the top of the view after the volume is adjusted
+#[derive(Default)]
+pub struct VolumeIndicator {
+    time_left: f32,
+}
+
+impl VolumeIndicator {
+    pub fn show(&mut self) {
+        self.time_left = VOLUME_INDICATOR_TIME;
+    }
+
+    pub fn update(&mut self, delta_time: f64) {
+        self.time_left = (self.time_left - delta_time as f32).max(0.);
+    }
+
+    /// draws the bar with the provided volume percent, fading out at the end of its time
+    pub fn draw(
+        &self,
+        view_settings: &ViewSettings,
+        text_params: &TextParams,
+        strings: &Strings,
+        volume: u32,
+    ) {
+        if self.time_left <= 0. {
+            return;
+        }
+        let alpha = (self.time_left / VOLUME_INDICATOR_FADE_TIME).min(1.);
+        let fade = |color: Color| Color {
+            a: color.a * alpha,
+            ..color
+        };
+        let scale = view_settings.scale;
+        let bar_w = VOLUME_BAR_SIZE[0] * scale;
+        let bar_h = VOLUME_BAR_SIZE[1] * scale;
+        let padding = 10. * scale;
+        let x = (view_settings.view_w - bar_w) / 2.;
+        let y = 40. * scale;
+        draw_rectangle(
+            x - padding,
+            y - padding,
+            bar_w + padding * 2.,
+            bar_h + 40. * scale + padding * 2.,
+            fade(CONTROLS_BACKGROUND_COLOR),
+        );
+        let text_params = TextParams {
+            color: fade(WHITE),
+            ..view_settings.text_params(text_params)
+        };
+        let text = language::fill(&strings.music_volume, volume);
+        let text_size = measure_text_params(&text, &text_params);
+        draw_centered_text(view_settings, &text, y + text_size.offset_y, &text_params);
+        let bar_y = y + 30. * scale;
+        draw_rectangle(x, bar_y, bar_w, bar_h, fade(PAUSED_OVERLAY_COLOR));
+        draw_rectangle(
+            x,
+            bar_y,
+            bar_w * volume as f32 / 100.,
+            bar_h,
+            fade(MENU_SELECTED_COLOR),
+        );
+    }
+}
+
+// The name of the background track shown at the bottom of the view when it starts
+#[derive(Default)]
+pub struct NowPlaying {
+    track: String,
+    time_left: f32,
+}
+
+impl NowPlaying {
+    pub fn show(&mut self, track: &str) {
+        self.track = track.to_owned();
+        self.time_left = NOW_PLAYING_TIME;
+    }
+
+    pub fn update(&mut self, delta_time: f64) {
+        self.time_left = (self.time_left - delta_time as f32).max(0.);
+    }
+
+    /// fades out at the end of its time like the volume indicator
+    pub fn draw(&self, view_settings: &ViewSettings, text_params: &TextParams, strings: &Strings) {
+        if self.time_left <= 0. {
+            return;
+        }
+        let alpha = (self.time_left / VOLUME_INDICATOR_FADE_TIME).min(1.);
+        let text_params = TextParams {
+            color: Color { a: alpha, ..WHITE },
+            ..view_settings.text_params(text_params)
+        };
+        let text = language::fill(&strings.now_playing, &self.track);
+        let margin = 10. * view_settings.scale;
+        draw_centered_text(
+            view_settings,
+            &text,
+            view_settings.view_h - margin,
+            &text_params,
+        );
+    }
+}
+
+// The effects started by game events
+#[derive(Default)]
+pub struct Effects {
+    particles: Particles,
+    screen_shake: ScreenShake,
+    level_background: LevelBackground,
+    spawn: SpawnEntry,
+    hard_drop_trail: HardDropTrail,
+    banner: Banner,
+    clear_popups: ClearPopups,
+}
+
+impl Effects {
+    /// starts the effects for the event, none are started with reduced motion
+    pub fn handle_event(
+        &mut self,
+        view_settings: &ViewSettings,
+        theme: &Theme,
+        settings: &Settings,
+        strings: &Strings,
+        event: &GameEvent,
+    ) {
+        if settings.reduced_motion {
+            return;
+        }
+        let shake_scale = settings.screen_shake.scale() * view_settings.scale;
+        match event {
+            GameEvent::Spawn => match settings.spawn_animation {
+                SpawnAnimation::Fade => self.spawn.start(SPAWN_FADE_TIME),
+                SpawnAnimation::Drop => self.spawn.start(SPAWN_DROP_TIME),
+                SpawnAnimation::Off => {}
+            },
+            GameEvent::HardDrop(hard_dropped) => {
+                self.screen_shake
+                    .start(HARD_DROP_SHAKE * shake_scale, HARD_DROP_SHAKE_TIME);
+                if settings.hard_drop_trail {
+                    self.hard_drop_trail.start(hard_dropped);
+                }
+            }
+            GameEvent::LinesCleared(cleared_lines) => {
+                self.particles
+                    .spawn_line_clear(view_settings, theme, cleared_lines);
+                if cleared_lines.lines == 4 {
+                    self.screen_shake
+                        .start(RUSTRIS_SHAKE * shake_scale, RUSTRIS_SHAKE_TIME);
+                }
+            }
+            GameEvent::Scored(result) => self.clear_popups.start(result, strings),
+            GameEvent::LevelUp(level) => {
+                self.banner.start(language::fill(&strings.level_up, level))
+            }
+            GameEvent::ScoreMilestone(score) => self
+                .banner
+                .start(language::fill(&strings.score_milestone, score)),
+            _ => {}
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        view_settings: &ViewSettings,
+        settings: &Settings,
+        delta_time: f64,
+        level: usize,
+    ) {
+        self.particles.update(view_settings, delta_time);
+        self.screen_shake.update(delta_time);
+        self.spawn.update(delta_time);
+        self.hard_drop_trail.update(delta_time);
+        self.banner.update(delta_time);
+        self.clear_popups.update(delta_time);
+        self.level_background
+            .update(delta_time, level, settings.reduced_motion);
+        debug_assert!(
+            !settings.reduced_motion || !self.running(),
+            "effect running with reduced motion"
+        );
+    }
+
+    // true while an effect started by an event is running,
+    // the level background blend isn't started by one
+    fn running(&self) -> bool {
+        !self.particles.particles.is_empty()
+            || self.screen_shake.offset != Vec2::ZERO
+            || self.spawn.progress().is_some()
+            || !self.hard_drop_trail.cells.is_empty()
+            || self.banner.progress().is_some()
+            || !self.clear_popups.popups.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.screen_shake = ScreenShake::default();
+        self.level_background = LevelBackground::default();
+        self.spawn = SpawnEntry::default();
+        self.hard_drop_trail = HardDropTrail::default();
+        self.banner = Banner::default();
+        self.clear_popups = ClearPopups::default();
+    }
+}
+
+// Names each scored clear and its points next to the playfield,
+// popups from clears in quick succession stack below the earlier ones
+#[derive(Default)]
+struct ClearPopups {
+    popups: Vec<ClearPopup>,
+}
+
+struct ClearPopup {
+    name: String,
+    points: usize,
+    time: f32,
+}
+
+impl ClearPopups {
+    fn start(&mut self, result: &ClearResult, strings: &Strings) {
+        self.popups.push(ClearPopup {
+            name: strings.translate(&result.to_string()).to_owned(),
+            points: result.score,
+            time: 0.,
+        });
+    }
+
+    fn update(&mut self, delta_time: f64) {
+        for popup in self.popups.iter_mut() {
+            popup.time += delta_time as f32;
+        }
+        self.popups.retain(|popup| popup.time < CLEAR_POPUP_TIME);
+    }
+
+    /// floats up and fades out over its time, sandbox clears don't show points
+    fn draw(&self, view_settings: &ViewSettings, text_params: &TextParams) {
+        let playfield_rect = view_settings.playfield_rect;
+        for (i, popup) in self.popups.iter().enumerate() {
+            let progress = popup.time / CLEAR_POPUP_TIME;
+            let text_params = TextParams {
+                color: Color {
+                    a: 1. - progress * progress,
+                    ..MENU_SELECTED_COLOR
+                },
+                ..*text_params
+            };
+            let y = playfield_rect.y
+                + playfield_rect.h / 2.
+                + (i as f32 * CLEAR_POPUP_SPACING - progress * CLEAR_POPUP_RISE)
+                    * view_settings.scale;
+            let points = format!("+{}", popup.points);
+            let lines = if popup.points > 0 {
+                vec![popup.name.as_str(), points.as_str()]
+            } else {
+                vec![popup.name.as_str()]
+            };
+            for (line_index, line) in lines.into_iter().enumerate() {
+                let text_size = measure_text(
+                    line,
+                    Some(text_params.font),
+                    text_params.font_size,
+                    text_params.font_scale,
+                );
+                draw_text_ex(
+                    line,
+                    playfield_rect.x + (playfield_rect.w - text_size.width) / 2.,
+                    y + line_index as f32 * text_size.height * 1.5,
+                    text_params,
+                );
+            }
+        }
+    }
+}
+
+// A short celebration shown over the playfield when the player
+// reaches a new level or crosses a score milestone
+#[derive(Default)]
+struct Banner {
+    text: String,
+    time: f32,
+}
+
+impl Banner {
+    /// replaces any banner that's still showing
+    fn start(&mut self, text: String) {
+        self.text = text;
+        self.time = 0.;
+    }
+
+    fn update(&mut self, delta_time: f64) {
+        self.time += delta_time as f32;
+    }
+
+    /// from 0 to 1 while the banner is showing, None when there's no banner
+    fn progress(&self) -> Option<f32> {
+        if self.text.is_empty() || self.time >= BANNER_TIME {
+            return None;
+        }
+        Some(self.time / BANNER_TIME)
+    }
+
+    /// grows in, then fades out at the end of its time
+    fn draw(&self, view_settings: &ViewSettings, text_params: &TextParams) {
+        if self.progress().is_none() {
+            return;
+        }
+        let size = 0.5 + 0.5 * ease_out((self.time / BANNER_POP_TIME).min(1.));
+        let alpha = ((BANNER_TIME - self.time) / BANNER_FADE_TIME).min(1.);
+        let text_params = TextParams {
+            font_scale: text_params.font_scale * size,
+            color: Color {
+                a: alpha,
+                ..MENU_SELECTED_COLOR
+            },
+            ..*text_params
+        };
+        let text_size = measure_text(
+            &self.text,
+            Some(text_params.font),
+            text_params.font_size,
+            text_params.font_scale,
+        );
+        let playfield_rect = view_settings.playfield_rect;
+        draw_text_ex(
+            &self.text,
+            playfield_rect.x + (playfield_rect.w - text_size.width) / 2.,
+            playfield_rect.y + playfield_rect.h / 4. + text_size.height / 2.,
+            text_params,
+        );
+    }
+}
+
+// The rows a hard dropped rustomino fell through, fading out behind it
+#[derive(Default)]
+struct HardDropTrail {
+    rtype: Option<RustominoType>,
+    // playfield slots and their opacity, most opaque next to the landed rustomino
+    cells: Vec<([i32; 2], f32)>,
+    time: f32,
+}
+
+impl HardDropTrail {
+    fn start(&mut self, hard_dropped: &HardDropped) {
+        self.rtype = Some(hard_dropped.rtype);
+        self.cells.clear();
+        self.time = 0.;
+        for block in hard_dropped.landed {
+            for row in 1..=hard_dropped.distance {
+                let cell = [block[0], block[1] + row];
+                // a block above another of the same rustomino would cover the trail
+                if hard_dropped.landed.contains(&IVec2::from(cell))
+                    || self.cells.iter().any(|(c, _)| *c == cell)
+                {
+                    continue;
+                }
+                let alpha = 1. - (row - 1) as f32 / hard_dropped.distance as f32;
+                self.cells.push((cell, alpha));
+            }
+        }
+    }
+
+    fn update(&mut self, delta_time: f64) {
+        self.time += delta_time as f32;
+        if self.time >= HARD_DROP_TRAIL_TIME {
+            self.cells.clear();
+        }
+    }
+
+    fn draw(&self, view_settings: &ViewSettings, theme: &Theme) {
+        let Some(rtype) = self.rtype else {
+            return;
+        };
+        let fade = HARD_DROP_TRAIL_ALPHA * (1. - self.time / HARD_DROP_TRAIL_TIME);
+        let color = theme.piece_color(rtype);
+        for (cell, alpha) in &self.cells {
+            let rect = playfield_block_rect(view_settings, *cell);
+            draw_rectangle(
+                rect.x,
+                rect.y,
+                rect.w,
+                rect.h,
+                Color {
+                    a: color.a * fade * alpha,
+                    ..color
+                },
+            );
+        }
+    }
+}
+
+// Times the active rustomino's entry animation
+// nothing is entering until a rustomino spawns
+#[derive(Default)]
+struct SpawnEntry {
+    time: f32,
+    duration: f32,
+}
+
+impl SpawnEntry {
+    fn start(&mut self, duration: f32) {
+        self.time = 0.;
+        self.duration = duration;
+    }
+
+    fn update(&mut self, delta_time: f64) {
+        self.time += delta_time as f32;
+    }
+
+    /// how far through the animation the entry is, from 0 to 1
+    /// returns None once it's finished
+    fn progress(&self) -> Option<f32> {
+        (self.time < self.duration).then_some(self.time / self.duration)
+    }
+}
+
+// Draws the game at the design resolution to an offscreen render target
+// which is scaled to fit the window, keeping its aspect ratio
+pub struct Canvas {
+    target: RenderTarget,
+}
+
+impl Canvas {
+    pub fn new() -> Self {
+        let target = render_target(VIEW_WH[0] as u32, VIEW_WH[1] as u32);
+        target.texture.set_filter(FilterMode::Linear);
+        Canvas { target }
+    }
+
+    /// directs the following drawing to the canvas
+    pub fn begin(&self) {
+        set_camera(&Camera2D {
+            render_target: Some(self.target),
+            ..Camera2D::from_display_rect(macroquad::math::Rect::new(
+                0.,
+                0.,
+                VIEW_WH[0] as f32,
+                VIEW_WH[1] as f32,
+            ))
+        });
+    }
+
+    /// draws the canvas to the window, letterboxed or pillarboxed
+    /// with the provided color around it
+    pub fn end(&self, bar_color: Color) {
+        set_default_camera();
+        clear_background(bar_color);
+        let rect = self.window_rect();
+        draw_texture_ex(
+            self.target.texture,
+            rect.x,
+            rect.y,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(rect.w, rect.h)),
+                // render targets are stored upside down
+                flip_y: true,
+                ..Default::default()
+            },
+        );
+    }
+
+    /// where the canvas is drawn in the window, the largest size that fits
+    /// rounded to whole pixels and centered
+    fn window_rect(&self) -> Rect<f32> {
+        let scale = (screen_width() / VIEW_WH[0] as f32).min(screen_height() / VIEW_WH[1] as f32);
+        let w = (VIEW_WH[0] as f32 * scale).round();
+        let h = (VIEW_WH[1] as f32 * scale).round();
+        Rect::new(
+            ((screen_width() - w) / 2.).floor(),
+            ((screen_height() - h) / 2.).floor(),
+            w,
+            h,
+        )
+    }
+}
+
+// Logs the number of draw calls in a frame, debug builds only
+// F3 captures the next frame, its draw calls are available the frame after
+#[derive(Default)]
+pub struct DrawCallCounter {
+    frames_until_logged: Option<u8>,
+}
+
+impl DrawCallCounter {
+    pub fn update(&mut self) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        match self.frames_until_logged {
+            Some(0) => {
+                log::info!("draw calls: {}", telemetry::drawcalls().len());
+                self.frames_until_logged = None;
+            }
+            Some(frames) => self.frames_until_logged = Some(frames - 1),
+            None if is_key_pressed(KeyCode::F3) => {
+                telemetry::capture_frame();
+                self.frames_until_logged = Some(1);
+            }
+            None => {}
+        }
+    }
+}
+
+// Blends the playfield background from the previous level's color
+#[derive(Default)]
+struct LevelBackground {
+    level: usize,
+    previous_level: usize,
+    time: f32,
+}
+
+impl LevelBackground {
+    // with reduced motion the new level's color is shown straight away
+    fn update(&mut self, delta_time: f64, level: usize, reduced_motion: bool) {
+        if level != self.level {
+            self.previous_level = self.level;
+            self.level = level;
+            self.time = 0.;
+        } else {
+            self.time += delta_time as f32;
+        }
+        if reduced_motion {
+            self.time = self.time.max(LEVEL_BLEND_TIME);
+        }
+    }
+
+    fn color(&self, theme: &Theme) -> Option<Color> {
+        let color = theme.level_color(self.level)?;
+        let previous_color = theme.level_color(self.previous_level)?;
+        Some(mix(
+            previous_color,
+            color,
+            (self.time / LEVEL_BLEND_TIME).min(1.),
+        ))
+    }
+}
+
+// Moves the playing view by a random offset that decays to nothing
+#[derive(Default)]
+struct ScreenShake {
+    magnitude: f32,
+    duration: f32,
+    time: f32,
+    offset: Vec2, // chosen once per frame so every draw uses the same offset
+}
+
+impl ScreenShake {
+    /// starts a shake unless a stronger one is already running
+    fn start(&mut self, magnitude: f32, duration: f32) {
+        if magnitude <= 0. || magnitude < self.remaining_magnitude() {
+            return;
+        }
+        self.magnitude = magnitude;
+        self.duration = duration;
+        self.time = 0.;
+    }
+
+    fn remaining_magnitude(&self) -> f32 {
+        if self.time >= self.duration {
+            return 0.;
+        }
+        self.magnitude * (1. - self.time / self.duration)
+    }
+
+    fn update(&mut self, delta_time: f64) {
+        self.time += delta_time as f32;
+        let magnitude = self.remaining_magnitude();
+        self.offset = if magnitude > 0. {
+            let angle = macroquad::rand::gen_range(0., std::f32::consts::TAU);
+            vec2(angle.cos(), angle.sin()) * magnitude
+        } else {
+            Vec2::ZERO
+        };
+    }
+}
+
+// The particle effects drawn over the playfield
+#[derive(Default)]
+struct Particles {
+    particles: Vec<Particle>,
+}
+
+impl Particles {
+    /// spawns a burst from each cleared block
+    /// rustrises and perfect clears get bigger bursts
+    fn spawn_line_clear(
+        &mut self,
+        view_settings: &ViewSettings,
+        theme: &Theme,
+        cleared_lines: &ClearedLines,
+    ) {
+        let per_block = if cleared_lines.perfect_clear {
+            PERFECT_CLEAR_PARTICLES_PER_BLOCK
+        } else if cleared_lines.lines == 4 {
+            RUSTRIS_PARTICLES_PER_BLOCK
+        } else {
+            PARTICLES_PER_BLOCK
+        };
+        for (block, rtype) in &cleared_lines.blocks {
+            let rect = playfield_block_rect(view_settings, [block[0] as i32, block[1] as i32]);
+            let center = vec2(rect.x + rect.w / 2., rect.y + rect.h / 2.);
+            for _ in 0..per_block {
+                if self.particles.len() >= MAX_PARTICLES {
+                    return;
+                }
+                let angle = macroquad::rand::gen_range(0., std::f32::consts::TAU);
+                let speed = macroquad::rand::gen_range(0.3, 1.) * PARTICLE_SPEED;
+                self.particles.push(Particle {
+                    pos: center,
+                    velocity: vec2(angle.cos(), angle.sin()) * speed * view_settings.scale,
+                    color: theme.piece_color(*rtype),
+                    age: 0.,
+                });
+            }
+        }
+    }
+
+    /// moves the particles and removes the ones that have faded
+    fn update(&mut self, view_settings: &ViewSettings, delta_time: f64) {
+        let delta_time = delta_time as f32;
+        for particle in self.particles.iter_mut() {
+            particle.velocity.y += PARTICLE_GRAVITY * view_settings.scale * delta_time;
+            particle.pos += particle.velocity * delta_time;
+            particle.age += delta_time;
+        }
+        self.particles
+            .retain(|particle| particle.age < PARTICLE_LIFETIME);
+    }
+
+    fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    fn draw(&self, view_settings: &ViewSettings) {
+        let size = PARTICLE_SIZE * view_settings.scale;
+        for particle in &self.particles {
+            let alpha = 1. - particle.age / PARTICLE_LIFETIME;
+            let pos = particle.pos + view_settings.offset;
+            draw_rectangle(
+                pos.x - size / 2.,
+                pos.y - size / 2.,
+                size,
+                size,
+                Color {
+                    a: particle.color.a * alpha,
+                    ..particle.color
+                },
+            );
+        }
+    }
+}
+
+// How strongly the playing view shakes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
+pub enum ShakeIntensity {
+    Off,
+    Low,
+    Medium,
+    High,
+}
+
+impl ShakeIntensity {
+    fn scale(&self) -> f32 {
+        match self {
+            ShakeIntensity::Off => 0.,
+            ShakeIntensity::Low => 0.5,
+            ShakeIntensity::Medium => 1.,
+            ShakeIntensity::High => 1.5,
+        }
+    }
+}
+
+impl Display for ShakeIntensity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShakeIntensity::Off => write!(f, "Off"),
+            ShakeIntensity::Low => write!(f, "Low"),
+            ShakeIntensity::Medium => write!(f, "Medium"),
+            ShakeIntensity::High => write!(f, "High"),
+        }
+    }
+}
+
+// How the game is fit to the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
+pub enum ScreenMode {
+    // the layout is recomputed for the window's size
+    Native,
+    // drawn at the design resolution and scaled to fit the window
+    Canvas,
+}
+
+impl ScreenMode {
+    /// the dimensions the layout is computed for
+    pub fn view_dimensions(&self) -> [f32; 2] {
+        match self {
+            ScreenMode::Native => [screen_width(), screen_height()],
+            ScreenMode::Canvas => [VIEW_WH[0] as f32, VIEW_WH[1] as f32],
+        }
+    }
+}
+
+impl Display for ScreenMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScreenMode::Native => write!(f, "Native"),
+            ScreenMode::Canvas => write!(f, "Scaled Canvas"),
+        }
+    }
+}
+
+// How new rustominos enter the playfield
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
+pub enum SpawnAnimation {
+    Off,
+    Fade,
+    // drops in from above
+    Drop,
+}
+
+impl Display for SpawnAnimation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpawnAnimation::Off => write!(f, "Off"),
+            SpawnAnimation::Fade => write!(f, "Fade"),
+            SpawnAnimation::Drop => write!(f, "Drop"),
+        }
+    }
+}
+
+// How blocks are drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
+pub enum BlockStyle {
+    // the theme's block texture, square blocks if the theme doesn't have one
+    Textured,
+    Square,
+    // rounded corners with a bevel highlight
+    Rounded,
+    // lighter top and left edges and darker bottom and right edges
+    Bevel,
+}
+
+impl Display for BlockStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockStyle::Textured => write!(f, "Textured"),
+            BlockStyle::Square => write!(f, "Square"),
+            BlockStyle::Rounded => write!(f, "Rounded"),
+            BlockStyle::Bevel => write!(f, "Bevel"),
+        }
+    }
+}
+
+// How the ghost rustomino is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
+pub enum GhostStyle {
+    Outline,
+    // filled with the piece's color
+    Translucent,
+    Off,
+}
+
+impl Display for GhostStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GhostStyle::Outline => write!(f, "Outline"),
+            GhostStyle::Translucent => write!(f, "Translucent"),
+            GhostStyle::Off => write!(f, "Off"),
+        }
+    }
+}
+
+// The color of the outlined ghost rustomino
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
+pub enum GhostColor {
+    // the theme's ghost color for every piece
+    Uniform,
+    // a translucent version of the piece's own color
+    Piece,
+}
+
+impl Display for GhostColor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GhostColor::Uniform => write!(f, "Uniform"),
+            GhostColor::Piece => write!(f, "Per Piece"),
+        }
+    }
+}
+
+// How a block's colors are changed when it's drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BlockShade {
+    Normal,
+    // held rustominos that can't be used and lines cleared with reduced motion
+    Greyed,
+    // drawn with the provided opacity, blocks in the buffer rows,
+    // the translucent ghost and rustominos fading in
+    Translucent(f32),
+}
+
+impl BlockShade {
+    /// the shade of a block in the provided playfield row
+    fn playfield_row(settings: &Settings, y: usize) -> Self {
+        if settings.faint_buffer_rows && y >= playfield::PLAYFIELD_SIZE[1] as usize {
+            BlockShade::Translucent(BUFFER_ROW_ALPHA)
+        } else {
+            BlockShade::Normal
+        }
+    }
+
+    /// the fill color of the block with the shade applied
+    fn fill(&self, color: Color) -> Color {
+        match self {
+            BlockShade::Greyed => HOLD_LOCKED_COLOR,
+            _ => self.fade(color),
         }
     }
-}
-
-pub fn draw(game: &RustrisGame, font_20pt: &TextParams, font_30pt: &TextParams) {
-    match game.state {
-        game::GameState::Menu => {
-            draw_playing_backgound();
-            draw_menu(font_30pt);
-            draw_help_text(font_30pt, font_20pt);
-        }
-        game::GameState::Playing => {
-            draw_playing_backgound();
-            draw_playing(&game.playfield, &game.next_rustomino, &game.held_rustomino);
-            draw_playing_overlay(font_20pt, game.level, game.score);
-        }
-        game::GameState::Paused => {
-            draw_playing_backgound();
-            draw_playing(&game.playfield, &game.next_rustomino, &game.held_rustomino);
-            draw_playing_overlay(font_20pt, game.level, game.score);
-            draw_paused(font_30pt);
-            draw_help_text(font_30pt, font_20pt);
-        }
-        game::GameState::GameOver => {
-            draw_playing_backgound();
-            draw_playing(&game.playfield, &game.next_rustomino, &game.held_rustomino);
-            draw_playing_overlay(font_20pt, game.level, game.score);
-            draw_gameover(font_30pt)
-        }
+
+    /// reduces the opacity of translucent blocks, used for outlines that aren't greyed
+    fn fade(&self, color: Color) -> Color {
+        match self {
+            BlockShade::Translucent(alpha) => Color {
+                a: color.a * alpha,
+                ..color
+            },
+            _ => color,
+        }
+    }
+
+    /// the shade with its opacity multiplied
+    fn with_alpha(self, alpha: f32) -> Self {
+        match self {
+            BlockShade::Normal => BlockShade::Translucent(alpha),
+            BlockShade::Translucent(shade_alpha) => BlockShade::Translucent(shade_alpha * alpha),
+            BlockShade::Greyed => BlockShade::Greyed,
+        }
+    }
+}
+
+// A block drawn in the playing view
+#[derive(Debug, Clone, Copy)]
+struct BlockDraw {
+    rect: Rect<f32>,
+    tile: BlockTile,
+    shade: BlockShade,
+    locked: bool,       // locked blocks get a border
+    flash: Option<f32>, // line clear progress, clearing blocks flash white
+}
+
+impl BlockDraw {
+    fn new(rect: Rect<f32>, tile: BlockTile, shade: BlockShade) -> Self {
+        BlockDraw {
+            rect,
+            tile,
+            shade,
+            locked: false,
+            flash: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BlockTile {
+    Rustomino(RustominoType),
+    // the type of the rustomino the ghost belongs to
+    Ghost(RustominoType),
+}
+
+impl BlockTile {
+    fn index(&self) -> usize {
+        match self {
+            BlockTile::Rustomino(rtype) => *rtype as usize,
+            BlockTile::Ghost(_) => BlockTexture::GHOST_TILE,
+        }
+    }
+}
+
+pub fn draw(
+    game: &RustrisGame,
+    view_settings: &ViewSettings,
+    theme: &Theme,
+    effects: &Effects,
+    session_play_time: f64,
+    font_20pt: &TextParams,
+    font_30pt: &TextParams,
+) {
+    // the playing view shakes, the menus and overlays drawn over it don't
+    let playing_view = &view_settings.shaken(effects.screen_shake.offset);
+    let level_color = effects.level_background.color(theme);
+    let font_20pt = &TextParams {
+        color: theme.text_color,
+        ..view_settings.text_params(font_20pt)
+    };
+    let font_30pt = &TextParams {
+        color: theme.text_color,
+        ..view_settings.text_params(font_30pt)
+    };
+    let strings = &game.strings;
+    match game.state {
+        game::GameState::Menu => {
+            draw_playing_backgound(view_settings, theme, &game.settings, None);
+            draw_main_menu(view_settings, theme, game, font_20pt, font_30pt);
+        }
+        game::GameState::Playing => {
+            draw_playing_backgound(playing_view, theme, &game.settings, level_color);
+            draw_playing(playing_view, theme, game, effects);
+            effects.particles.draw(playing_view);
+            draw_playing_overlay(playing_view, font_20pt, game, session_play_time);
+            draw_hold_locked(playing_view, font_20pt, strings, game.hold_used());
+            effects.clear_popups.draw(playing_view, font_20pt);
+            effects.banner.draw(playing_view, font_30pt);
+        }
+        game::GameState::Paused => {
+            draw_playing_backgound(playing_view, theme, &game.settings, level_color);
+            draw_playing(playing_view, theme, game, effects);
+            effects.particles.draw(playing_view);
+            draw_playing_overlay(playing_view, font_20pt, game, session_play_time);
+            draw_hold_locked(playing_view, font_20pt, strings, game.hold_used());
+            draw_paused(view_settings, &game.menu, font_30pt, strings);
+            if game.low_frame_rate_pause() {
+                draw_centered_text(
+                    view_settings,
+                    &strings.low_frame_rate,
+                    view_settings.center_y(-150.),
+                    &TextParams {
+                        color: ERROR_TEXT_COLOR,
+                        ..*font_20pt
+                    },
+                );
+            }
+            draw_help_text(view_settings, font_30pt, font_20pt, strings);
+            draw_confirmation(view_settings, &game.menu, font_30pt, font_20pt, strings);
+        }
+        game::GameState::GameOver => {
+            draw_playing_backgound(playing_view, theme, &game.settings, level_color);
+            draw_playing(playing_view, theme, game, effects);
+            effects.particles.draw(playing_view);
+            draw_playing_overlay(playing_view, font_20pt, game, session_play_time);
+            draw_hold_locked(playing_view, font_20pt, strings, game.hold_used());
+            // the panel is shown once the board has turned grey
+            if game.game_over_progress() >= 1. {
+                draw_gameover(
+                    view_settings,
+                    font_30pt,
+                    font_20pt,
+                    strings,
+                    game.initials_input.as_ref(),
+                    game.summary_message.as_deref(),
+                    game.game_over_rule(),
+                )
+            }
+        }
+        game::GameState::Practice => {
+            draw_playing_backgound(view_settings, theme, &game.settings, None);
+            draw_practice(
+                view_settings,
+                &game.practice_input,
+                &game.settings,
+                font_30pt,
+                font_20pt,
+                strings,
+            );
+        }
+        game::GameState::Settings => {
+            draw_playing_backgound(view_settings, theme, &game.settings, None);
+            draw_settings(
+                view_settings,
+                &game.settings_menu,
+                &game.settings,
+                font_30pt,
+                font_20pt,
+                strings,
+            );
+        }
+        game::GameState::DebugBoard => {
+            draw_playing_backgound(playing_view, theme, &game.settings, level_color);
+            draw_playing(playing_view, theme, game, effects);
+            draw_debug_board(
+                view_settings,
+                &game.debug_board_input,
+                font_30pt,
+                font_20pt,
+                strings,
+            );
+        }
+        game::GameState::WhatsNew => {
+            draw_playing_backgound(view_settings, theme, &game.settings, None);
+            draw_whats_new(
+                view_settings,
+                &game.whats_new,
+                font_30pt,
+                font_20pt,
+                strings,
+            );
+        }
+        game::GameState::Replays => {
+            draw_playing_backgound(view_settings, theme, &game.settings, None);
+            draw_replays(
+                view_settings,
+                &game.replays,
+                &game.replay_menu,
+                font_30pt,
+                font_20pt,
+                strings,
+            );
+        }
+        game::GameState::HighScores => {
+            draw_playing_backgound(view_settings, theme, &game.settings, None);
+            draw_high_scores(
+                view_settings,
+                &game.high_scores,
+                font_30pt,
+                font_20pt,
+                strings,
+            );
+        }
+        // the players' games are drawn by draw_versus
+        game::GameState::Versus => {
+            draw_playing_backgound(view_settings, theme, &game.settings, None);
+        }
+        game::GameState::Online => {
+            draw_playing_backgound(view_settings, theme, &game.settings, None);
+            draw_online(
+                view_settings,
+                &game.online_input,
+                game.online_lobby.as_ref(),
+                font_30pt,
+                font_20pt,
+                strings,
+            );
+        }
+        game::GameState::Profiles => {
+            draw_playing_backgound(view_settings, theme, &game.settings, None);
+            draw_profiles(
+                view_settings,
+                &game.profiles,
+                &game.profile_menu,
+                game.profile_input.as_ref(),
+                font_30pt,
+                font_20pt,
+                strings,
+            );
+        }
+    }
+    if game.settings.mute {
+        draw_muted(view_settings, font_20pt, &strings.muted);
+    }
+}
+
+/// the main menu drawn over the game the cpu plays when the menu is left idle
+pub fn draw_demo(
+    game: &RustrisGame,
+    demo: &RustrisGame,
+    view_settings: &ViewSettings,
+    theme: &Theme,
+    effects: &Effects,
+    font_20pt: &TextParams,
+    font_30pt: &TextParams,
+) {
+    let font_20pt = &TextParams {
+        color: theme.text_color,
+        ..view_settings.text_params(font_20pt)
+    };
+    let font_30pt = &TextParams {
+        color: theme.text_color,
+        ..view_settings.text_params(font_30pt)
+    };
+    draw_playing_backgound(view_settings, theme, &demo.settings, None);
+    draw_playing(view_settings, theme, demo, effects);
+    draw_main_menu(view_settings, theme, game, font_20pt, font_30pt);
+}
+
+/// the replay being watched with its position, speed and playback keys,
+/// once it has finished the final score is checked against the recording
+pub fn draw_replay(
+    viewer: &game::ReplayViewer,
+    view_settings: &ViewSettings,
+    theme: &Theme,
+    effects: &Effects,
+    font_20pt: &TextParams,
+    font_30pt: &TextParams,
+) {
+    let game = &viewer.game;
+    let strings = &game.strings;
+    let font_20pt = &TextParams {
+        color: theme.text_color,
+        ..view_settings.text_params(font_20pt)
+    };
+    let font_30pt = &TextParams {
+        color: theme.text_color,
+        ..view_settings.text_params(font_30pt)
+    };
+    draw_playing_backgound(view_settings, theme, &game.settings, None);
+    draw_playing(view_settings, theme, game, effects);
+    draw_playing_overlay(view_settings, font_20pt, game, game.play_time());
+    if let game::GameState::GameOver = game.state {
+        draw_centered_text(
+            view_settings,
+            &strings.game_over,
+            view_settings.center_y(-100.),
+            font_30pt,
+        );
+    }
+    let position = [
+        strings.translate(&viewer.replay.mode).to_owned(),
+        format!(
+            "{} / {}",
+            format_play_time(viewer.elapsed()),
+            format_play_time(viewer.duration)
+        ),
+        language::fill(&strings.replay_speed, replay::SPEEDS[viewer.speed]),
+    ]
+    .join("   ");
+    draw_centered_text(
+        view_settings,
+        &position,
+        view_settings.center_y(-320.),
+        font_20pt,
+    );
+    if viewer.paused {
+        draw_centered_text(
+            view_settings,
+            &strings.paused,
+            view_settings.center_y(-290.),
+            &TextParams {
+                color: MENU_SELECTED_COLOR,
+                ..*font_20pt
+            },
+        );
+    }
+    if viewer.desynced() {
+        draw_centered_text(
+            view_settings,
+            &language::fill(&strings.replay_desynced, viewer.replay.score),
+            view_settings.center_y(-60.),
+            &TextParams {
+                color: ERROR_TEXT_COLOR,
+                ..*font_20pt
+            },
+        );
+    } else if viewer.finished() {
+        draw_centered_text(
+            view_settings,
+            &strings.replay_matched,
+            view_settings.center_y(-60.),
+            &TextParams {
+                color: MENU_SELECTED_COLOR,
+                ..*font_20pt
+            },
+        );
+    }
+    draw_centered_text(
+        view_settings,
+        &strings.replay_keys,
+        view_settings.center_y(330.),
+        font_20pt,
+    );
+}
+
+// the menu, with the best score, profile and controls around it
+fn draw_main_menu(
+    view_settings: &ViewSettings,
+    theme: &Theme,
+    game: &RustrisGame,
+    font_20pt: &TextParams,
+    font_30pt: &TextParams,
+) {
+    let strings = &game.strings;
+    draw_menu(view_settings, theme, &game.menu, font_30pt, strings);
+    draw_best_score(
+        view_settings,
+        font_20pt,
+        strings,
+        game.high_scores.best(GameMode::default()),
+    );
+    draw_profile_name(view_settings, font_20pt, strings, game.profiles.active());
+    draw_help_text(view_settings, font_30pt, font_20pt, strings);
+    draw_confirmation(view_settings, &game.menu, font_30pt, font_20pt, strings);
+}
+
+pub fn draw_playing_backgound(
+    view_settings: &ViewSettings,
+    theme: &Theme,
+    settings: &Settings,
+    level_color: Option<Color>,
+) {
+    // the other boxes get a subtler version of the level's color, keeping their alpha
+    let level_tint = |color: Color| match level_color {
+        Some(level_color) => Color {
+            a: color.a,
+            ..mix(color, level_color, LEVEL_BLEND_SUBTLE)
+        },
+        None => color,
+    };
+    // high contrast draws the blocks over pure black
+    let (staging_color, playfield_color) = if settings.high_contrast {
+        (BLACK, BLACK)
+    } else {
+        (
+            level_tint(theme.staging_background_color),
+            level_color.unwrap_or(theme.playfield_background_color),
+        )
+    };
+    draw_rectangle(
+        view_settings.staging_rect.x,
+        view_settings.staging_rect.y,
+        view_settings.staging_rect.w,
+        view_settings.staging_rect.h,
+        staging_color,
+    );
+
+    draw_rectangle(
+        view_settings.playfield_rect.x,
+        view_settings.playfield_rect.y,
+        view_settings.playfield_rect.w,
+        view_settings.playfield_rect.h,
+        playfield_color,
+    );
+
+    draw_rectangle(
+        view_settings.preview_rect.x,
+        view_settings.preview_rect.y,
+        view_settings.preview_rect.w,
+        view_settings.preview_rect.h,
+        level_tint(theme.preview_background_color),
+    );
+
+    draw_rectangle(
+        view_settings.hold_rect.x,
+        view_settings.hold_rect.y,
+        view_settings.hold_rect.w,
+        view_settings.hold_rect.h,
+        level_tint(theme.hold_background_color),
+    );
+
+    if settings.grid_lines {
+        draw_playfield_grid(view_settings, theme.grid_color);
+    }
+}
+
+/// draws 1px lines in the padding between the playfield's blocks
+/// the lines are positioned from the block rects so they always line up
+fn draw_playfield_grid(view_settings: &ViewSettings, color: Color) {
+    let playfield_rect = view_settings.playfield_rect;
+    for x in 1..playfield::PLAYFIELD_SIZE[0] {
+        let block_rect = playfield_block_rect(view_settings, [x, 0]);
+        draw_rectangle(
+            block_rect.x - view_settings.block_padding,
+            playfield_rect.y,
+            1.,
+            playfield_rect.h,
+            color,
+        );
+    }
+    for y in 1..playfield::PLAYFIELD_SIZE[1] {
+        let block_rect = playfield_block_rect(view_settings, [0, y]);
+        draw_rectangle(
+            playfield_rect.x,
+            block_rect.y + block_rect.h,
+            playfield_rect.w,
+            1.,
+            color,
+        );
+    }
+}
+
+pub fn draw_playing(
+    view_settings: &ViewSettings,
+    theme: &Theme,
+    game: &RustrisGame,
+    effects: &Effects,
+) {
+    let settings = &game.settings;
+    let playfield = &game.playfield;
+    let next_rustomino = &game.next_rustomino;
+    let held_rustomino = &game.held_rustomino;
+    let hold_used = game.hold_used();
+    let line_clear = game.line_clear();
+    let hold_swap = game.hold_swap();
+    let hide_locked = game
+        .modifier()
+        .is_some_and(|modifier| modifier.hides_locked_blocks());
+
+    // with reduced motion the effect is never started
+    let spawn_progress = effects.spawn.progress();
+    // after a top out the rows below the sweep line turn grey, from the bottom up
+    let game_over_rows = match game.state {
+        game::GameState::GameOver => {
+            (game.game_over_progress() * playfield::PLAYFIELD_SLOTS[1] as f32).ceil() as usize
+        }
+        _ => 0,
+    };
+
+    // the blocks are collected and drawn together in passes
+    let mut blocks =
+        Vec::with_capacity(playfield::PLAYFIELD_SLOTS[0] * playfield::PLAYFIELD_SLOTS[1]);
+
+    for (y, slots_x) in playfield.slots.iter().enumerate() {
+        // completed lines shrink and flash while they're being cleared
+        // with reduced motion they're greyed out instead
+        let clearing = line_clear.is_some_and(|line_clear| line_clear.lines.contains(&y));
+        let clear_progress = line_clear
+            .filter(|_| clearing && !settings.reduced_motion)
+            .map(|line_clear| line_clear.progress());
+        let shade = if (clearing && settings.reduced_motion) || y < game_over_rows {
+            BlockShade::Greyed
+        } else {
+            BlockShade::playfield_row(settings, y)
+        };
+        for (x, slot) in slots_x.iter().enumerate() {
+            match slot {
+                // invisible pieces still show the lines they complete
+                SlotState::Locked(_) if hide_locked && !clearing => {}
+                // the active rustomino is drawn sliding in from its box
+                SlotState::Occupied(_) if hold_swap.is_some() => {}
+                SlotState::Locked(rtype) | SlotState::Occupied(rtype) => {
+                    let mut rect = playfield_block_rect(view_settings, [x as i32, y as i32]);
+                    let mut shade = shade;
+                    if let Some(progress) = clear_progress {
+                        let shrink = rect.h * progress;
+                        rect = Rect::new(rect.x, rect.y + shrink / 2., rect.w, rect.h - shrink);
+                    }
+                    // the active rustomino's entry, the ghost isn't animated
+                    if let (SlotState::Occupied(_), Some(progress)) = (slot, spawn_progress) {
+                        match settings.spawn_animation {
+                            SpawnAnimation::Fade => {
+                                let alpha = SPAWN_FADE_ALPHA + (1. - SPAWN_FADE_ALPHA) * progress;
+                                shade = shade.with_alpha(alpha)
+                            }
+                            SpawnAnimation::Drop => {
+                                rect.y -= (1. - ease_out(progress)) * SPAWN_DROP_DISTANCE * rect.h;
+                            }
+                            SpawnAnimation::Off => {}
+                        }
+                    }
+                    blocks.push(BlockDraw {
+                        locked: matches!(slot, SlotState::Locked(_)),
+                        flash: clear_progress,
+                        ..BlockDraw::new(rect, BlockTile::Rustomino(*rtype), shade)
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(next) = next_rustomino {
+        // display the preview
+        for rect in next_rustomino_rects(view_settings, next) {
+            blocks.push(BlockDraw::new(
+                rect,
+                BlockTile::Rustomino(next.rtype),
+                BlockShade::Normal,
+            ));
+        }
+    }
+
+    if let Some(held) = held_rustomino {
+        // greyed out while hold can't be used
+        let shade = if hold_used {
+            BlockShade::Greyed
+        } else {
+            BlockShade::Normal
+        };
+        // the held rustomino slides from where it left the playfield
+        let progress = hold_swap.map(|hold_swap| ease_out(hold_swap.progress()));
+        for (i, slot) in held.blocks.iter().enumerate() {
+            let mut rect = hold_block_rect(view_settings, [slot[0], slot[1]]);
+            if let (Some(hold_swap), Some(progress)) = (hold_swap, progress) {
+                let from = hold_swap.outgoing[i];
+                rect = lerp_rect(
+                    playfield_block_rect(view_settings, [from[0], from[1]]),
+                    rect,
+                    progress,
+                );
+            }
+            blocks.push(BlockDraw::new(
+                rect,
+                BlockTile::Rustomino(held.rtype),
+                shade,
+            ));
+        }
+    }
+
+    // the incoming rustomino slides from its box to its spawn position
+    if let (Some(hold_swap), Some(active)) = (hold_swap, &playfield.active_rustomino) {
+        let progress = ease_out(hold_swap.progress());
+        for (block, slot) in active.blocks.iter().zip(active.playfield_slots()) {
+            let from = match hold_swap.source {
+                game::HoldSource::Hold => hold_block_rect(view_settings, [block[0], block[1]]),
+                game::HoldSource::Next => next_block_rect(view_settings, [block[0], block[1]]),
+            };
+            let to = playfield_block_rect(view_settings, [slot[0], slot[1]]);
+            blocks.push(BlockDraw::new(
+                lerp_rect(from, to, progress),
+                BlockTile::Rustomino(active.rtype),
+                BlockShade::Normal,
+            ));
+        }
+    }
+
+    // behind the blocks so the landed rustomino covers it
+    effects.hard_drop_trail.draw(view_settings, theme);
+    draw_blocks(view_settings, theme, settings, &blocks);
+
+    // the ghost is drawn over the active rustomino's outlines when they overlap
+    if let Some(ghost) = &playfield.ghost_rustomino {
+        let mut ghost_blocks = Vec::with_capacity(ghost.blocks.len());
+        for block in ghost.playfield_slots() {
+            let rect = playfield_block_rect(view_settings, [block[0], block[1]]);
+            let (tile, shade) = match settings.ghost_style {
+                GhostStyle::Outline => (
+                    BlockTile::Ghost(ghost.rtype),
+                    BlockShade::playfield_row(settings, block[1] as usize),
+                ),
+                // the piece's own color so the theme's palette applies
+                GhostStyle::Translucent => (
+                    BlockTile::Rustomino(ghost.rtype),
+                    BlockShade::Translucent(GHOST_FILL_ALPHA),
+                ),
+                GhostStyle::Off => continue,
+            };
+            ghost_blocks.push(BlockDraw::new(rect, tile, shade));
+        }
+        draw_blocks(view_settings, theme, settings, &ghost_blocks);
+    }
+
+    // a faint second ghost in its own color where the hints would drop the rustomino
+    if let Some(hint) = game.placement_hint() {
+        for block in hint.playfield_slots() {
+            draw_block_outline(
+                settings.block_style,
+                &playfield_block_rect(view_settings, [block[0], block[1]]),
+                PLACEMENT_HINT_THICKNESS * view_settings.scale,
+                PLACEMENT_HINT_COLOR,
+            );
+        }
+    }
+}
+
+pub fn draw_playing_overlay(
+    view_settings: &ViewSettings,
+    text_params: &TextParams,
+    game: &RustrisGame,
+    session_play_time: f64,
+) {
+    let game_level = game.level;
+    let score = game.score;
+    let strings = &game.strings;
+
+    draw_text_ex(
+        &strings.title,
+        view_settings.title_pos.x,
+        view_settings.title_pos.y,
+        *text_params,
+    );
+
+    draw_text_ex(
+        &strings.level,
+        view_settings.level_label_pos.x,
+        view_settings.level_label_pos.y,
+        *text_params,
+    );
+
+    draw_text_ex(
+        &game_level.to_string(),
+        view_settings.level_pos.x,
+        view_settings.level_pos.y,
+        *text_params,
+    );
+
+    let play_times = [
+        (
+            &strings.time,
+            view_settings.game_time_label_pos,
+            view_settings.game_time_pos,
+            game.play_time(),
+        ),
+        (
+            &strings.session,
+            view_settings.session_time_label_pos,
+            view_settings.session_time_pos,
+            session_play_time,
+        ),
+    ];
+    for (label, label_pos, pos, play_time) in play_times {
+        draw_text_ex(label, label_pos.x, label_pos.y, *text_params);
+        draw_text_ex(&format_play_time(play_time), pos.x, pos.y, *text_params);
+    }
+
+    draw_text_ex(
+        &strings.score,
+        view_settings.score_label_pos.x,
+        view_settings.score_label_pos.y,
+        *text_params,
+    );
+
+    draw_text_ex(
+        &score.to_string(),
+        view_settings.score_pos.x,
+        view_settings.score_pos.y,
+        *text_params,
+    );
+
+    if let Some(modifier) = game.modifier() {
+        let modifier = modifier.to_string();
+        draw_modifier(
+            view_settings,
+            text_params,
+            game,
+            strings.translate(&modifier),
+        );
+    } else if game.sandbox() {
+        draw_modifier(view_settings, text_params, game, &strings.sandbox);
+    } else if let Some(GameMode::Daily(date)) = game.mode() {
+        let daily = language::fill(&strings.daily, daily::format_date(date));
+        draw_modifier(view_settings, text_params, game, &daily);
+    }
+}
+
+/// shows the mystery modifier, the sandbox label or the daily challenge's date
+/// below the preview box,
+/// it's announced over the playfield when the level starts
+fn draw_modifier(
+    view_settings: &ViewSettings,
+    text_params: &TextParams,
+    game: &RustrisGame,
+    modifier: &str,
+) {
+    draw_text_ex(
+        modifier,
+        view_settings.preview_rect.x,
+        view_settings.preview_rect.y + view_settings.preview_rect.h + 30. * view_settings.scale,
+        *text_params,
+    );
+    if game.level_time() >= MODIFIER_ANNOUNCE_TIME {
+        return;
+    }
+    let text_size = measure_text(
+        modifier,
+        Some(text_params.font),
+        text_params.font_size,
+        text_params.font_scale,
+    );
+    let playfield_rect = view_settings.playfield_rect;
+    draw_text_ex(
+        modifier,
+        playfield_rect.x + (playfield_rect.w - text_size.width) / 2.,
+        playfield_rect.y + playfield_rect.h / 3.,
+        TextParams {
+            color: MENU_SELECTED_COLOR,
+            ..*text_params
+        },
+    );
+}
+
+/// formats seconds as m:ss, or h:mm:ss after an hour
+pub fn format_play_time(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+/// shows that hold is unavailable below the hold box
+pub fn draw_hold_locked(
+    view_settings: &ViewSettings,
+    text_params: &TextParams,
+    strings: &Strings,
+    hold_used: bool,
+) {
+    if !hold_used {
+        return;
+    }
+    draw_text_ex(
+        &strings.hold_locked,
+        view_settings.hold_rect.x,
+        view_settings.hold_rect.y + view_settings.hold_rect.h + 30. * view_settings.scale,
+        TextParams {
+            color: HOLD_LOCKED_COLOR,
+            ..*text_params
+        },
+    );
+}
+
+pub fn draw_paused(
+    view_settings: &ViewSettings,
+    menu: &Menu<MenuItem>,
+    text_params: &TextParams,
+    strings: &Strings,
+) {
+    draw_rectangle(
+        0.,
+        0.,
+        view_settings.view_w,
+        view_settings.view_h,
+        PAUSED_OVERLAY_COLOR,
+    );
+    draw_centered_text(
+        view_settings,
+        &strings.paused,
+        view_settings.center_y(-90.),
+        text_params,
+    );
+    let items = menu_item_names(menu, strings);
+    draw_menu_items(
+        view_settings,
+        &items,
+        menu.selected,
+        vec2(0., -40.),
+        text_params,
+    );
+}
+
+/// shown in the top right corner of every screen while the audio is muted
+fn draw_muted(view_settings: &ViewSettings, text_params: &TextParams, text: &str) {
+    let text_size = measure_text(
+        text,
+        Some(text_params.font),
+        text_params.font_size,
+        text_params.font_scale,
+    );
+    let margin = 10. * view_settings.scale;
+    draw_text_ex(
+        text,
+        view_settings.view_w - text_size.width - margin,
+        margin + text_size.offset_y,
+        *text_params,
+    );
+}
+
+pub fn draw_menu(
+    view_settings: &ViewSettings,
+    theme: &Theme,
+    menu: &Menu<MenuItem>,
+    text_params: &TextParams,
+    strings: &Strings,
+) {
+    draw_rectangle(
+        0.,
+        0.,
+        view_settings.view_w,
+        view_settings.view_h,
+        PAUSED_OVERLAY_COLOR,
+    );
+    // each letter of the title is drawn in the color of a rustomino,
+    // the line is laid out from the measured width of each part
+    let mut parts = vec![(format!("{} ", strings.welcome), text_params.color)];
+    parts.extend(
+        strings
+            .title
+            .chars()
+            .zip(TITLE_LETTER_COLORS.iter().cycle())
+            .map(|(letter, rtype)| (letter.to_string(), theme.piece_color(*rtype))),
+    );
+    parts.push(("!".to_owned(), text_params.color));
+    let widths: Vec<f32> = parts
+        .iter()
+        .map(|(text, _)| measure_text_params(text, text_params).width)
+        .collect();
+    let mut x = view_settings.center_x(0.) - widths.iter().sum::<f32>() / 2.;
+    for ((text, color), width) in parts.iter().zip(widths) {
+        draw_text_ex(
+            text,
+            x,
+            view_settings.center_y(MENU_TITLE_Y),
+            TextParams {
+                color: *color,
+                ..*text_params
+            },
+        );
+        x += width;
+    }
+
+    let items = menu_item_names(menu, strings);
+    draw_menu_items(
+        view_settings,
+        &items,
+        menu.selected,
+        vec2(-230., -100.),
+        text_params,
+    );
+}
+
+pub fn draw_settings(
+    view_settings: &ViewSettings,
+    menu: &Menu<SettingItem>,
+    settings: &Settings,
+    font_30pt: &TextParams,
+    font_20pt: &TextParams,
+    strings: &Strings,
+) {
+    draw_rectangle(
+        0.,
+        0.,
+        view_settings.view_w,
+        view_settings.view_h,
+        PAUSED_OVERLAY_COLOR,
+    );
+    draw_centered_text(
+        view_settings,
+        &strings.settings,
+        view_settings.center_y(-200.),
+        font_30pt,
+    );
+    // the list scrolls once the selected item is past the last visible one
+    let first = (menu.selected + 1).saturating_sub(SETTINGS_VISIBLE_ITEMS);
+    let items: Vec<String> = menu
+        .items
+        .iter()
+        .skip(first)
+        .take(SETTINGS_VISIBLE_ITEMS)
+        .map(|item| {
+            format!(
+                "{}: < {} >",
+                strings.translate(&item.to_string()),
+                strings.translate(&item.value(settings))
+            )
+        })
+        .collect();
+    draw_menu_items(
+        view_settings,
+        &items,
+        menu.selected - first,
+        vec2(0., -130.),
+        font_20pt,
+    );
+    draw_centered_text(
+        view_settings,
+        &strings.settings_keys,
+        view_settings.center_y(300.),
+        font_20pt,
+    );
+}
+
+/// draws the debug board screen over the game, where the developer types
+/// a garbage height or a board with rows separated by '/' from the bottom up
+pub fn draw_debug_board(
+    view_settings: &ViewSettings,
+    input: &TextInput,
+    font_30pt: &TextParams,
+    font_20pt: &TextParams,
+    strings: &Strings,
+) {
+    draw_rectangle(
+        0.,
+        0.,
+        view_settings.view_w,
+        view_settings.view_h,
+        PAUSED_OVERLAY_COLOR,
+    );
+    draw_centered_text(
+        view_settings,
+        &strings.debug_board,
+        view_settings.center_y(-200.),
+        font_30pt,
+    );
+    for (text, offset) in strings.debug_board_prompt.iter().zip([-140., -110.]) {
+        draw_text_ex(
+            text,
+            view_settings.center_x(-400.),
+            view_settings.center_y(offset),
+            *font_20pt,
+        );
+    }
+    // long boards are scrolled to show the end being typed
+    let chars = input.text.chars().count();
+    let text: String = input
+        .text
+        .chars()
+        .skip(chars.saturating_sub(DEBUG_BOARD_VISIBLE_CHARS))
+        .collect();
+    draw_text_ex(
+        &format!("{text}_"),
+        view_settings.center_x(-400.),
+        view_settings.center_y(-60.),
+        TextParams {
+            color: MENU_SELECTED_COLOR,
+            ..*font_20pt
+        },
+    );
+    if let Some(error) = &input.error {
+        draw_text_ex(
+            strings.translate(error),
+            view_settings.center_x(-400.),
+            view_settings.center_y(-20.),
+            TextParams {
+                color: ERROR_TEXT_COLOR,
+                ..*font_20pt
+            },
+        );
+    }
+    draw_centered_text(
+        view_settings,
+        &strings.debug_board_keys,
+        view_settings.center_y(300.),
+        font_20pt,
+    );
+}
+
+/// draws the practice screen where the player types the opening sequence
+pub fn draw_practice(
+    view_settings: &ViewSettings,
+    input: &TextInput,
+    settings: &Settings,
+    font_30pt: &TextParams,
+    font_20pt: &TextParams,
+    strings: &Strings,
+) {
+    draw_rectangle(
+        0.,
+        0.,
+        view_settings.view_w,
+        view_settings.view_h,
+        PAUSED_OVERLAY_COLOR,
+    );
+    draw_centered_text(
+        view_settings,
+        &strings.practice,
+        view_settings.center_y(-200.),
+        font_30pt,
+    );
+    draw_text_ex(
+        &strings.practice_prompt,
+        view_settings.center_x(-330.),
+        view_settings.center_y(-120.),
+        *font_20pt,
+    );
+    draw_text_ex(
+        &format!("{}_", input.text),
+        view_settings.center_x(-330.),
+        view_settings.center_y(-80.),
+        TextParams {
+            color: MENU_SELECTED_COLOR,
+            ..*font_20pt
+        },
+    );
+    if let Some(error) = &input.error {
+        draw_text_ex(
+            strings.translate(error),
+            view_settings.center_x(-330.),
+            view_settings.center_y(-40.),
+            TextParams {
+                color: ERROR_TEXT_COLOR,
+                ..*font_20pt
+            },
+        );
+    }
+    if settings.piece_cycling {
+        draw_centered_text(
+            view_settings,
+            &strings.practice_cycling,
+            view_settings.center_y(260.),
+            font_20pt,
+        );
     }
+    draw_centered_text(
+        view_settings,
+        &strings.practice_keys,
+        view_settings.center_y(300.),
+        font_20pt,
+    );
 }
 
-pub fn draw_playing_backgound() {
+/// draws the scrollable version notes, lines starting with "# " are headings
+pub fn draw_whats_new(
+    view_settings: &ViewSettings,
+    notes: &ScrollText,
+    font_30pt: &TextParams,
+    font_20pt: &TextParams,
+    strings: &Strings,
+) {
     draw_rectangle(
-        VIEW_SETTINGS.staging_rect.x,
-        VIEW_SETTINGS.staging_rect.y,
-        VIEW_SETTINGS.staging_rect.w,
-        VIEW_SETTINGS.staging_rect.h,
-        STAGING_BACKGROUND_COLOR,
+        0.,
+        0.,
+        view_settings.view_w,
+        view_settings.view_h,
+        PAUSED_OVERLAY_COLOR,
     );
-
-    draw_rectangle(
-        VIEW_SETTINGS.playfield_rect.x,
-        VIEW_SETTINGS.playfield_rect.y,
-        VIEW_SETTINGS.playfield_rect.w,
-        VIEW_SETTINGS.playfield_rect.h,
-        PLAYFIELD_BACKGROUND_COLOR,
+    draw_centered_text(
+        view_settings,
+        &strings.whats_new,
+        view_settings.center_y(-200.),
+        font_30pt,
     );
-
-    draw_rectangle(
-        VIEW_SETTINGS.preview_rect.x,
-        VIEW_SETTINGS.preview_rect.y,
-        VIEW_SETTINGS.preview_rect.w,
-        VIEW_SETTINGS.preview_rect.h,
-        PREVIEW_BACKGROUND_COLOR,
+    let line_height = font_20pt.font_size as f32 * 1.5;
+    for (i, line) in notes.visible_lines().iter().enumerate() {
+        let (text, color) = match line.strip_prefix("# ") {
+            Some(heading) => (heading, MENU_SELECTED_COLOR),
+            None => (line.as_str(), font_20pt.color),
+        };
+        draw_text_ex(
+            text,
+            view_settings.center_x(-400.),
+            view_settings.center_y(-140. + i as f32 * line_height),
+            TextParams {
+                color,
+                ..*font_20pt
+            },
+        );
+    }
+    draw_centered_text(
+        view_settings,
+        &strings.whats_new_keys,
+        view_settings.center_y(300.),
+        font_20pt,
     );
+}
 
-    draw_rectangle(
-        VIEW_SETTINGS.hold_rect.x,
-        VIEW_SETTINGS.hold_rect.y,
-        VIEW_SETTINGS.hold_rect.w,
-        VIEW_SETTINGS.hold_rect.h,
-        HOLD_BACKGROUND_COLOR,
+/// draws the best marathon score under the menu's title
+fn draw_best_score(
+    view_settings: &ViewSettings,
+    text_params: &TextParams,
+    strings: &Strings,
+    best: Option<usize>,
+) {
+    let Some(best) = best else {
+        return;
+    };
+    let text = language::fill(&strings.best_score, best);
+    let text_size = measure_text_params(&text, text_params);
+    draw_text_ex(
+        &text,
+        view_settings.center_x(-230.) - text_size.width / 2.,
+        view_settings.center_y(-150.),
+        *text_params,
     );
 }
 
-pub fn draw_playing(
-    playfield: &RustrisPlayfield,
-    next_rustomino: &Option<Rustomino>,
-    held_rustomino: &Option<Rustomino>,
-) {
-    for (y, slots_x) in playfield.slots.iter().enumerate() {
-        for (x, slot) in slots_x.iter().enumerate() {
-            match slot {
-                SlotState::Locked(rtype) | SlotState::Occupied(rtype) => {
-                    // draw the block
-                    let rect = playfield_block_rect([x as i32, y as i32]);
-                    draw_rectangle(rect.x, rect.y, rect.w, rect.h, rtype.color());
-                }
-                _ => {}
-            }
-        }
+/// draws the versus garbage waiting to be added as a segment per line
+/// in a bar beside the playfield, filling from the bottom with the oldest attack,
+/// it empties when the garbage is added at the next lock
+fn draw_garbage_meter(view_settings: &ViewSettings, attacks: impl Iterator<Item = usize>) {
+    let playfield_rect = view_settings.playfield_rect;
+    let block_stride = view_settings.block_size + view_settings.block_padding;
+    let width = GARBAGE_METER_WIDTH * view_settings.scale;
+    let x = playfield_rect.x - width - view_settings.block_padding;
+    let lines = attacks
+        .enumerate()
+        .flat_map(|(attack, lines)| std::iter::repeat_n(GARBAGE_METER_COLORS[attack % 2], lines));
+    for (line, color) in lines
+        .take(playfield::PLAYFIELD_SIZE[1] as usize)
+        .enumerate()
+    {
+        let y = playfield_rect.y + playfield_rect.h - (line + 1) as f32 * block_stride;
+        draw_rectangle(x, y, width, view_settings.block_size, color);
     }
+}
 
-    if let Some(next) = next_rustomino {
-        for slot in next.blocks {
-            // display the preview
-            // draw the block
-            let rect = next_block_rect([slot[0], slot[1]]);
-            draw_rectangle(rect.x, rect.y, rect.w, rect.h, next.rtype.color());
+/// draws each versus player's game on their half of the view with their keys under it,
+/// and the results over both once the match is over
+pub fn draw_versus(
+    game: &RustrisGame,
+    versus: &game::Versus,
+    view_settings: &ViewSettings,
+    theme: &Theme,
+    font_20pt: &TextParams,
+    font_30pt: &TextParams,
+) {
+    let strings = &game.strings;
+    for (index, (player, effects)) in versus.players.iter().zip(&versus.effects).enumerate() {
+        let player_view = &view_settings.split(index);
+        let playing_view = &player_view.shaken(effects.screen_shake.offset);
+        let level_color = effects.level_background.color(theme);
+        let font_20pt = &TextParams {
+            color: theme.text_color,
+            ..player_view.text_params(font_20pt)
+        };
+        let font_30pt = &TextParams {
+            color: theme.text_color,
+            ..player_view.text_params(font_30pt)
+        };
+        draw_playing_backgound(playing_view, theme, &player.settings, level_color);
+        draw_playing(playing_view, theme, player, effects);
+        // the local player's setting, online matches use the host's settings for the games
+        if game.settings.garbage_meter {
+            draw_garbage_meter(playing_view, player.pending_attacks());
         }
-    }
+        effects.particles.draw(playing_view);
+        draw_playing_overlay(playing_view, font_20pt, player, player.play_time());
+        draw_hold_locked(playing_view, font_20pt, strings, player.hold_used());
+        effects.clear_popups.draw(playing_view, font_20pt);
+        effects.banner.draw(playing_view, font_30pt);
 
-    if let Some(held) = held_rustomino {
-        for slot in held.blocks {
-            // display the preview
-            // draw the block
-            let rect = hold_block_rect([slot[0], slot[1]]);
-            draw_rectangle(rect.x, rect.y, rect.w, rect.h, held.rtype.color());
-        }
+        // the player's name above the board and their keys below it,
+        // online the local player's board is marked instead
+        let playfield_rect = player_view.playfield_rect;
+        let staging_rect = player_view.staging_rect;
+        let center_x = playfield_rect.x + playfield_rect.w / 2.;
+        let name = language::fill(&strings.versus_player, index + 1);
+        let name_size = measure_text_params(&name, font_20pt);
+        draw_text_ex(
+            &name,
+            center_x - name_size.width / 2.,
+            staging_rect.y - 15. * player_view.scale,
+            *font_20pt,
+        );
+        let keys = match (&versus.online, &versus.cpu) {
+            (Some(online), _) if online.local == index => &strings.versus_you,
+            (Some(_), _) => continue,
+            (None, Some(_)) if index == 0 => &strings.versus_you,
+            (None, Some(_)) => &strings.versus_cpu,
+            (None, None) => &strings.versus_controls[index],
+        };
+        let keys_size = measure_text_params(keys, font_20pt);
+        draw_text_ex(
+            keys,
+            center_x - keys_size.width / 2.,
+            playfield_rect.y + playfield_rect.h + 30. * player_view.scale,
+            *font_20pt,
+        );
     }
 
-    if let Some(ghost) = &playfield.ghost_rustomino {
-        for block in ghost.playfield_slots() {
-            // draw the block
-            let rect = playfield_block_rect([block[0], block[1]]);
-            draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 4., GHOST_COLOR);
+    if versus.results_shown() {
+        let font_20pt = &TextParams {
+            color: theme.text_color,
+            ..view_settings.text_params(font_20pt)
+        };
+        let font_30pt = &TextParams {
+            color: theme.text_color,
+            ..view_settings.text_params(font_30pt)
+        };
+        draw_rectangle(
+            0.,
+            0.,
+            view_settings.view_w,
+            view_settings.view_h,
+            PAUSED_OVERLAY_COLOR,
+        );
+        let result = match (versus.online_error(), versus.winner) {
+            (Some(error), _) => strings.translate(error).to_owned(),
+            (None, Some(winner)) => language::fill(&strings.versus_wins, winner + 1),
+            (None, None) => strings.versus_draw.clone(),
+        };
+        draw_centered_text(
+            view_settings,
+            &result,
+            view_settings.center_y(-60.),
+            font_30pt,
+        );
+        for (index, player) in versus.players.iter().enumerate() {
+            let score = format!(
+                "{} {} {}",
+                language::fill(&strings.versus_player, index + 1),
+                strings.score,
+                player.score
+            );
+            draw_centered_text(
+                view_settings,
+                &score,
+                view_settings.center_y(index as f32 * 30.),
+                font_20pt,
+            );
         }
+        let keys = if versus.online.is_some() {
+            &strings.versus_online_keys
+        } else {
+            &strings.versus_keys
+        };
+        draw_centered_text(view_settings, keys, view_settings.center_y(90.), font_20pt);
+    }
+    if game.settings.mute {
+        let font_20pt = &TextParams {
+            color: theme.text_color,
+            ..view_settings.text_params(font_20pt)
+        };
+        draw_muted(view_settings, font_20pt, &strings.muted);
     }
 }
 
-pub fn draw_playing_overlay(text_params: &TextParams, game_level: usize, score: usize) {
-    draw_text_ex(
-        "Rustris",
-        VIEW_SETTINGS.title_pos.x as f32,
-        VIEW_SETTINGS.title_pos.y as f32,
-        *text_params,
+/// draws the address being typed to join an online match,
+/// or what the player is waiting for once they're hosting or joining
+pub fn draw_online(
+    view_settings: &ViewSettings,
+    input: &TextInput,
+    lobby: Option<&online::Lobby>,
+    font_30pt: &TextParams,
+    font_20pt: &TextParams,
+    strings: &Strings,
+) {
+    draw_rectangle(
+        0.,
+        0.,
+        view_settings.view_w,
+        view_settings.view_h,
+        PAUSED_OVERLAY_COLOR,
     );
-
-    draw_text_ex(
-        "Level:",
-        VIEW_SETTINGS.level_label_pos.x as f32,
-        VIEW_SETTINGS.level_label_pos.y as f32,
-        *text_params,
+    draw_centered_text(
+        view_settings,
+        &strings.online,
+        view_settings.center_y(-200.),
+        font_30pt,
     );
-
-    draw_text_ex(
-        &game_level.to_string(),
-        VIEW_SETTINGS.level_pos.x as f32,
-        VIEW_SETTINGS.level_pos.y as f32,
-        *text_params,
+    if let Some(lobby) = lobby {
+        let waiting = match &lobby.address {
+            Some(address) => language::fill(&strings.online_joining, address),
+            None => language::fill(&strings.online_hosting, online::ONLINE_PORT),
+        };
+        draw_centered_text(
+            view_settings,
+            &waiting,
+            view_settings.center_y(-20.),
+            font_20pt,
+        );
+        draw_centered_text(
+            view_settings,
+            &strings.online_waiting_keys,
+            view_settings.center_y(300.),
+            font_20pt,
+        );
+        return;
+    }
+    draw_centered_text(
+        view_settings,
+        &strings.online_prompt,
+        view_settings.center_y(-60.),
+        font_20pt,
     );
-
-    draw_text_ex(
-        "Score:",
-        VIEW_SETTINGS.score_label_pos.x as f32,
-        VIEW_SETTINGS.score_label_pos.y as f32,
-        *text_params,
+    draw_centered_text(
+        view_settings,
+        &format!("{}_", input.text),
+        view_settings.center_y(-20.),
+        &TextParams {
+            color: MENU_SELECTED_COLOR,
+            ..*font_20pt
+        },
+    );
+    if let Some(error) = &input.error {
+        draw_centered_text(
+            view_settings,
+            strings.translate(error),
+            view_settings.center_y(20.),
+            &TextParams {
+                color: ERROR_TEXT_COLOR,
+                ..*font_20pt
+            },
+        );
+    }
+    draw_centered_text(
+        view_settings,
+        &strings.online_keys,
+        view_settings.center_y(300.),
+        font_20pt,
     );
+}
 
+/// draws the active profile's name under the menu's title, nothing for the guest
+fn draw_profile_name(
+    view_settings: &ViewSettings,
+    text_params: &TextParams,
+    strings: &Strings,
+    profile: Option<&str>,
+) {
+    let Some(profile) = profile else {
+        return;
+    };
+    let text = language::fill(&strings.profile, profile);
+    let text_size = measure_text_params(&text, text_params);
     draw_text_ex(
-        &score.to_string(),
-        VIEW_SETTINGS.score_pos.x as f32,
-        VIEW_SETTINGS.score_pos.y as f32,
+        &text,
+        view_settings.center_x(230.) - text_size.width / 2.,
+        view_settings.center_y(-150.),
         *text_params,
     );
 }
 
-pub fn draw_paused(text_params: &TextParams) {
+/// draws the profile picker, with the name being typed for a new or renamed profile
+/// and the prompt to confirm deleting one
+pub fn draw_profiles(
+    view_settings: &ViewSettings,
+    profiles: &ProfileStore,
+    menu: &Menu<ProfileItem>,
+    input: Option<&TextInput>,
+    font_30pt: &TextParams,
+    font_20pt: &TextParams,
+    strings: &Strings,
+) {
     draw_rectangle(
         0.,
         0.,
-        VIEW_SETTINGS.view_w as f32,
-        VIEW_SETTINGS.view_h as f32,
+        view_settings.view_w,
+        view_settings.view_h,
         PAUSED_OVERLAY_COLOR,
     );
-    draw_text_ex(
-        "Paused",
-        (VIEW_SETTINGS.view_w / 2 - 75) as f32,
-        (VIEW_SETTINGS.view_h / 2 - 90) as f32,
-        *text_params,
+    draw_centered_text(
+        view_settings,
+        &strings.profiles,
+        view_settings.center_y(-200.),
+        font_30pt,
     );
-}
-
-pub fn draw_menu(text_params: &TextParams) {
+    let profile_name = |item: ProfileItem| match item {
+        ProfileItem::Profile(index) => profiles.names()[index].clone(),
+        ProfileItem::Guest => strings.translate("Guest").to_owned(),
+        ProfileItem::New => strings.translate("New Profile").to_owned(),
+    };
+    let items: Vec<String> = menu.items.iter().copied().map(profile_name).collect();
+    draw_menu_items(
+        view_settings,
+        &items,
+        menu.selected,
+        vec2(0., -130.),
+        font_20pt,
+    );
+    if let Some(input) = input {
+        draw_centered_text(
+            view_settings,
+            &strings.profile_prompt,
+            view_settings.center_y(140.),
+            font_20pt,
+        );
+        draw_centered_text(
+            view_settings,
+            &format!("{}_", input.text),
+            view_settings.center_y(180.),
+            &TextParams {
+                color: MENU_SELECTED_COLOR,
+                ..*font_20pt
+            },
+        );
+        if let Some(error) = &input.error {
+            draw_centered_text(
+                view_settings,
+                strings.translate(error),
+                view_settings.center_y(220.),
+                &TextParams {
+                    color: ERROR_TEXT_COLOR,
+                    ..*font_20pt
+                },
+            );
+        }
+    }
+    let keys = if input.is_some() {
+        &strings.profile_input_keys
+    } else {
+        &strings.profiles_keys
+    };
+    draw_centered_text(view_settings, keys, view_settings.center_y(300.), font_20pt);
+    let Some(deleting) = menu.confirming else {
+        return;
+    };
     draw_rectangle(
         0.,
         0.,
-        VIEW_SETTINGS.view_w as f32,
-        VIEW_SETTINGS.view_h as f32,
+        view_settings.view_w,
+        view_settings.view_h,
         PAUSED_OVERLAY_COLOR,
     );
-    draw_text_ex(
-        "Welcome to",
-        (VIEW_SETTINGS.view_w / 2 - 230) as f32,
-        (VIEW_SETTINGS.view_h / 2 - 90) as f32,
-        *text_params,
+    draw_centered_text(
+        view_settings,
+        &language::fill(&strings.profile_delete, profile_name(deleting)),
+        view_settings.center_y(-30.),
+        font_30pt,
     );
-    draw_text_ex(
-        "R",
-        560.,
-        (VIEW_SETTINGS.view_h / 2 - 90) as f32,
-        TextParams {
-            font: text_params.font,
-            font_size: 30,
-            color: RustominoType::I.color(),
-            ..Default::default()
-        },
+    draw_centered_text(
+        view_settings,
+        &strings.confirmation_keys,
+        view_settings.center_y(20.),
+        font_20pt,
     );
-    draw_text_ex(
-        "u",
-        588.,
-        (VIEW_SETTINGS.view_h / 2 - 90) as f32,
-        TextParams {
-            font: text_params.font,
-            font_size: 30,
-            color: RustominoType::O.color(),
-            ..Default::default()
-        },
+}
+
+/// draws the shown mode's high scores in a table, best first
+/// the saved replays, newest first, with their date, mode and final score
+pub fn draw_replays(
+    view_settings: &ViewSettings,
+    replays: &[ReplayEntry],
+    menu: &Menu<usize>,
+    font_30pt: &TextParams,
+    font_20pt: &TextParams,
+    strings: &Strings,
+) {
+    draw_rectangle(
+        0.,
+        0.,
+        view_settings.view_w,
+        view_settings.view_h,
+        PAUSED_OVERLAY_COLOR,
     );
-    draw_text_ex(
-        "s",
-        612.,
-        (VIEW_SETTINGS.view_h / 2 - 90) as f32,
-        TextParams {
-            font: text_params.font,
-            font_size: 30,
-            color: RustominoType::T.color(),
-            ..Default::default()
-        },
+    draw_centered_text(
+        view_settings,
+        &strings.replays,
+        view_settings.center_y(-200.),
+        font_30pt,
     );
-    draw_text_ex(
-        "t",
-        637.,
-        (VIEW_SETTINGS.view_h / 2 - 90) as f32,
-        TextParams {
-            font: text_params.font,
-            font_size: 30,
-            color: RustominoType::L.color(),
-            ..Default::default()
-        },
+    if replays.is_empty() {
+        draw_centered_text(
+            view_settings,
+            &strings.no_replays,
+            view_settings.center_y(-60.),
+            font_20pt,
+        );
+    }
+    let items: Vec<String> = menu
+        .items
+        .iter()
+        .map(|&index| {
+            let replay = &replays[index];
+            format!(
+                "{}   {}   {}",
+                daily::format_date(replay.date),
+                strings.translate(&replay.mode),
+                replay.score
+            )
+        })
+        .collect();
+    draw_menu_items(
+        view_settings,
+        &items,
+        menu.selected,
+        vec2(0., -130.),
+        font_20pt,
     );
-    draw_text_ex(
-        "r",
-        662.,
-        (VIEW_SETTINGS.view_h / 2 - 90) as f32,
-        TextParams {
-            font: text_params.font,
-            font_size: 30,
-            color: RustominoType::S.color(),
-            ..Default::default()
-        },
+    draw_centered_text(
+        view_settings,
+        &strings.replays_keys,
+        view_settings.center_y(300.),
+        font_20pt,
     );
-    draw_text_ex(
-        "i",
-        686.,
-        (VIEW_SETTINGS.view_h / 2 - 90) as f32,
-        TextParams {
-            font: text_params.font,
-            font_size: 30,
-            color: RustominoType::J.color(),
-            ..Default::default()
-        },
+}
+
+pub fn draw_high_scores(
+    view_settings: &ViewSettings,
+    high_scores: &HighScores,
+    font_30pt: &TextParams,
+    font_20pt: &TextParams,
+    strings: &Strings,
+) {
+    draw_rectangle(
+        0.,
+        0.,
+        view_settings.view_w,
+        view_settings.view_h,
+        PAUSED_OVERLAY_COLOR,
     );
-    draw_text_ex(
-        "s",
-        700.,
-        (VIEW_SETTINGS.view_h / 2 - 90) as f32,
-        TextParams {
-            font: text_params.font,
-            font_size: 30,
-            color: RustominoType::Z.color(),
-            ..Default::default()
+    draw_centered_text(
+        view_settings,
+        &strings.high_scores,
+        view_settings.center_y(-200.),
+        font_30pt,
+    );
+    let mode = high_scores.shown_mode;
+    draw_centered_text(
+        view_settings,
+        &format!("< {} >", strings.translate(&mode.to_string())),
+        view_settings.center_y(-150.),
+        &TextParams {
+            color: MENU_SELECTED_COLOR,
+            ..*font_20pt
         },
     );
-    draw_text_ex(
-        "!",
-        725.,
-        (VIEW_SETTINGS.view_h / 2 - 90) as f32,
-        *text_params,
+    let scores = high_scores.scores(mode);
+    if scores.is_empty() {
+        draw_centered_text(
+            view_settings,
+            &strings.no_high_scores,
+            view_settings.center_y(-60.),
+            font_20pt,
+        );
+    } else {
+        for (heading, x) in HIGH_SCORE_COLUMNS {
+            draw_text_ex(
+                strings.translate(heading),
+                view_settings.center_x(x),
+                view_settings.center_y(-100.),
+                *font_20pt,
+            );
+        }
+        let line_height = font_20pt.font_size as f32 * 1.5;
+        for (i, high_score) in scores.iter().enumerate() {
+            let row = [
+                (i + 1).to_string(),
+                high_score.initials.clone(),
+                high_score.score.to_string(),
+                high_score.lines.to_string(),
+                high_score.level.to_string(),
+            ];
+            for (text, (_, x)) in row.iter().zip(HIGH_SCORE_COLUMNS) {
+                draw_text_ex(
+                    text,
+                    view_settings.center_x(x),
+                    view_settings.center_y(-60. + i as f32 * line_height),
+                    *font_20pt,
+                );
+            }
+        }
+    }
+    draw_centered_text(
+        view_settings,
+        &strings.high_scores_keys,
+        view_settings.center_y(300.),
+        font_20pt,
     );
+}
 
+/// the menu's items in the language
+fn menu_item_names(menu: &Menu<MenuItem>, strings: &Strings) -> Vec<String> {
+    menu.items
+        .iter()
+        .map(|item| strings.translate(&item.to_string()).to_owned())
+        .collect()
+}
+
+/// draws the text centered horizontally in the view, at a y position in pixels
+fn draw_centered_text(view_settings: &ViewSettings, text: &str, y: f32, text_params: &TextParams) {
+    let text_size = measure_text_params(text, text_params);
     draw_text_ex(
-        "Press Enter To Start",
-        (VIEW_SETTINGS.view_w / 2 - 253) as f32,
-        (VIEW_SETTINGS.view_h / 2 - 30) as f32,
+        text,
+        view_settings.center_x(0.) - text_size.width / 2.,
+        y,
         *text_params,
     );
 }
 
-pub fn draw_gameover(text_params: &TextParams) {
+/// the size of the text drawn with the text params
+fn measure_text_params(text: &str, text_params: &TextParams) -> TextDimensions {
+    measure_text(
+        text,
+        Some(text_params.font),
+        text_params.font_size,
+        text_params.font_scale,
+    )
+}
+
+/// draws the menu's items centered starting at the provided offset
+/// the selected item is highlighted
+fn draw_menu_items(
+    view_settings: &ViewSettings,
+    items: &[String],
+    selected: usize,
+    offset: Vec2, // design units from the center of the view
+    text_params: &TextParams,
+) {
+    let line_height = text_params.font_size as f32 * 1.2;
+    for (i, text) in items.iter().enumerate() {
+        let color = if i == selected {
+            MENU_SELECTED_COLOR
+        } else {
+            text_params.color
+        };
+        let text_size = measure_text(
+            text,
+            Some(text_params.font),
+            text_params.font_size,
+            text_params.font_scale,
+        );
+        draw_text_ex(
+            text,
+            view_settings.center_x(offset.x) - text_size.width / 2.,
+            view_settings.center_y(offset.y + i as f32 * line_height),
+            TextParams {
+                color,
+                ..*text_params
+            },
+        );
+    }
+}
+
+/// draws the confirmation prompt over the menu
+/// if the player needs to confirm the selected item
+fn draw_confirmation(
+    view_settings: &ViewSettings,
+    menu: &Menu<MenuItem>,
+    font_30pt: &TextParams,
+    font_20pt: &TextParams,
+    strings: &Strings,
+) {
+    let Some(prompt) = menu.confirming.and_then(|item| item.confirmation()) else {
+        return;
+    };
     draw_rectangle(
         0.,
         0.,
-        VIEW_SETTINGS.view_w as f32,
-        VIEW_SETTINGS.view_h as f32,
+        view_settings.view_w,
+        view_settings.view_h,
         PAUSED_OVERLAY_COLOR,
     );
-    draw_text_ex(
-        "Game Over!",
-        (VIEW_SETTINGS.view_w / 2 - 122) as f32,
-        (VIEW_SETTINGS.view_h / 2 - 30) as f32,
-        *text_params,
+    draw_centered_text(
+        view_settings,
+        strings.translate(prompt),
+        view_settings.center_y(-30.),
+        font_30pt,
     );
-    draw_text_ex(
-        "Press Enter To Play Again",
-        (VIEW_SETTINGS.view_w / 2 - 310) as f32,
-        (VIEW_SETTINGS.view_h / 2 + 30) as f32,
-        *text_params,
+    draw_centered_text(
+        view_settings,
+        &strings.confirmation_keys,
+        view_settings.center_y(20.),
+        font_20pt,
     );
 }
 
-///
-pub fn draw_help_text(font_30pt: &TextParams, font_20pt: &TextParams) {
-    draw_rectangle(285., 410., 445., 305., CONTROLS_BACKGROUND_COLOR);
-
-    draw_text_ex(
-        "Controls:",
-        305.,
-        (VIEW_SETTINGS.view_h / 2 + 65) as f32,
-        *font_30pt,
+/// draws the game over panel, or the initials prompt if the score made the high scores
+pub fn draw_gameover(
+    view_settings: &ViewSettings,
+    font_30pt: &TextParams,
+    font_20pt: &TextParams,
+    strings: &Strings,
+    initials: Option<&TextInput>,
+    summary_message: Option<&str>,
+    rule: Option<game::GameOverRule>,
+) {
+    draw_rectangle(
+        0.,
+        0.,
+        view_settings.view_w,
+        view_settings.view_h,
+        PAUSED_OVERLAY_COLOR,
     );
-    draw_text_ex(
-        "Move Left: Left, A",
-        315.,
-        (VIEW_SETTINGS.view_h / 2 + 98) as f32,
-        *font_20pt,
+    // the rule that ended the game is shown above the title
+    if let Some(rule) = rule {
+        let rule_y = if initials.is_some() { -105. } else { -75. };
+        draw_centered_text(
+            view_settings,
+            strings.translate(&rule.to_string()),
+            view_settings.center_y(rule_y),
+            font_20pt,
+        );
+    }
+    let Some(initials) = initials else {
+        draw_centered_text(
+            view_settings,
+            &strings.game_over,
+            view_settings.center_y(-30.),
+            font_30pt,
+        );
+        draw_centered_text(
+            view_settings,
+            &strings.play_again,
+            view_settings.center_y(30.),
+            font_30pt,
+        );
+        let summary_keys = if clip::AVAILABLE {
+            format!("{}   {}", strings.summary_keys, strings.clip_keys)
+        } else {
+            strings.summary_keys.clone()
+        };
+        draw_centered_text(
+            view_settings,
+            &summary_keys,
+            view_settings.center_y(90.),
+            font_20pt,
+        );
+        if let Some(message) = summary_message {
+            draw_centered_text(
+                view_settings,
+                message,
+                view_settings.center_y(130.),
+                &TextParams {
+                    color: MENU_SELECTED_COLOR,
+                    ..*font_20pt
+                },
+            );
+        }
+        return;
+    };
+    draw_centered_text(
+        view_settings,
+        &strings.game_over,
+        view_settings.center_y(-60.),
+        font_30pt,
     );
-
-    draw_text_ex(
-        "Move Right: Right, D",
-        315.,
-        (VIEW_SETTINGS.view_h / 2 + 128) as f32,
-        *font_20pt,
+    draw_centered_text(
+        view_settings,
+        &strings.initials_prompt,
+        view_settings.center_y(-10.),
+        font_20pt,
     );
-
-    draw_text_ex(
-        "Rotate CW: Up, W",
-        315.,
-        (VIEW_SETTINGS.view_h / 2 + 157) as f32,
-        *font_20pt,
+    draw_centered_text(
+        view_settings,
+        &format!("{}_", initials.text),
+        view_settings.center_y(40.),
+        &TextParams {
+            color: MENU_SELECTED_COLOR,
+            ..*font_30pt
+        },
     );
-
-    draw_text_ex(
-        "Rotate CCW: LCtrl, Z",
-        315.,
-        (VIEW_SETTINGS.view_h / 2 + 187) as f32,
-        *font_20pt,
+    draw_centered_text(
+        view_settings,
+        &strings.initials_keys,
+        view_settings.center_y(90.),
+        font_20pt,
     );
+}
 
-    draw_text_ex(
-        "Soft Drop: Down, S",
-        315.,
-        (VIEW_SETTINGS.view_h / 2 + 217) as f32,
-        *font_20pt,
+/// draws the controls help box
+pub fn draw_help_text(
+    view_settings: &ViewSettings,
+    font_30pt: &TextParams,
+    font_20pt: &TextParams,
+    strings: &Strings,
+) {
+    // the box grows to fit longer translations
+    let lines_width = strings
+        .controls_help
+        .iter()
+        .map(|line| {
+            measure_text(
+                line,
+                Some(font_20pt.font),
+                font_20pt.font_size,
+                font_20pt.font_scale,
+            )
+            .width
+        })
+        .fold(0., f32::max);
+    let lines_height = strings.controls_help.len().saturating_sub(1) as f32 * HELP_LINE_HEIGHT;
+    draw_rectangle(
+        view_settings.center_x(20.),
+        view_settings.center_y(HELP_TEXT_TOP),
+        (445. * view_settings.scale).max(lines_width + 60. * view_settings.scale),
+        (HELP_LINES_TOP + lines_height + 23.) * view_settings.scale,
+        CONTROLS_BACKGROUND_COLOR,
     );
 
     draw_text_ex(
-        "Hard Drop: Space",
-        315.,
-        (VIEW_SETTINGS.view_h / 2 + 247) as f32,
-        *font_20pt,
+        &strings.controls,
+        view_settings.center_x(40.),
+        view_settings.center_y(HELP_TEXT_TOP + 39.),
+        *font_30pt,
     );
 
-    draw_text_ex(
-        "Hold: LShift, C",
-        315.,
-        (VIEW_SETTINGS.view_h / 2 + 277) as f32,
-        *font_20pt,
+    for (i, line) in strings.controls_help.iter().enumerate() {
+        draw_text_ex(
+            line,
+            view_settings.center_x(50.),
+            view_settings.center_y(HELP_TEXT_TOP + HELP_LINES_TOP + i as f32 * HELP_LINE_HEIGHT),
+            *font_20pt,
+        );
+    }
+}
+
+/// draws the blocks in two passes, first the fills then the outlines and flashes,
+/// so the blocks drawn with the block texture are batched into a single draw call
+/// instead of alternating with the flat colored outlines
+fn draw_blocks(
+    view_settings: &ViewSettings,
+    theme: &Theme,
+    settings: &Settings,
+    blocks: &[BlockDraw],
+) {
+    for block in blocks {
+        draw_block(view_settings, theme, settings, block);
+    }
+    for block in blocks {
+        draw_block_overlay(view_settings, theme, settings, block);
+    }
+}
+
+/// draws a small picture of the board's visible rows centered in the rect,
+/// independent of the layout so it can show a board other than the one being played.
+/// Blocks are drawn flat in the theme's colors, the ghost isn't shown
+pub fn draw_thumbnail(board: &playfield::RustrisPlayfield, theme: &Theme, rect: Rect<f32>) {
+    let [columns, rows] = playfield::PLAYFIELD_SIZE.map(|size| size as f32);
+    let block_size = (rect.w / columns).min(rect.h / rows);
+    let x = rect.x + (rect.w - block_size * columns) / 2.;
+    let y = rect.y + (rect.h - block_size * rows) / 2.;
+    draw_rectangle(
+        x,
+        y,
+        block_size * columns,
+        block_size * rows,
+        theme.playfield_background_color,
     );
+    // small blocks aren't separated so they stay visible
+    let padding = if block_size >= THUMBNAIL_MIN_PADDED_BLOCK {
+        BLOCK_PADDING as f32
+    } else {
+        0.
+    };
+    for (row, slots_x) in board
+        .slots
+        .iter()
+        .take(playfield::PLAYFIELD_SIZE[1] as usize)
+        .enumerate()
+    {
+        for (column, slot) in slots_x.iter().enumerate() {
+            let (SlotState::Locked(rtype) | SlotState::Occupied(rtype)) = slot else {
+                continue;
+            };
+            draw_rectangle(
+                x + column as f32 * block_size + padding,
+                // the bottom row is drawn at the bottom of the rect
+                y + (rows - 1. - row as f32) * block_size + padding,
+                block_size - padding * 2.,
+                block_size - padding * 2.,
+                theme.piece_color(*rtype),
+            );
+        }
+    }
+}
 
-    draw_text_ex(
-        "Adjust Music Volume: + -",
-        315.,
-        (VIEW_SETTINGS.view_h / 2 + 307) as f32,
-        *font_20pt,
+/// draws a single block in the selected style, the textured style uses
+/// the theme's block texture if it has one, otherwise the theme's colors
+fn draw_block(view_settings: &ViewSettings, theme: &Theme, settings: &Settings, block: &BlockDraw) {
+    let BlockDraw {
+        rect, tile, shade, ..
+    } = *block;
+    let block_texture = theme
+        .block_texture
+        .as_ref()
+        .filter(|_| settings.block_style == BlockStyle::Textured);
+    if let Some(block_texture) = block_texture {
+        let tint = match tile {
+            BlockTile::Ghost(rtype) if settings.ghost_color == GhostColor::Piece => {
+                ghost_color(theme, settings, rtype, shade)
+            }
+            _ => shade.fill(WHITE),
+        };
+        draw_texture_ex(
+            block_texture.texture,
+            rect.x,
+            rect.y,
+            tint,
+            DrawTextureParams {
+                dest_size: Some(vec2(rect.w, rect.h)),
+                source: Some(block_texture.source_rect(tile.index())),
+                ..Default::default()
+            },
+        );
+    } else {
+        match tile {
+            BlockTile::Rustomino(rtype) => {
+                let color = if settings.high_contrast {
+                    saturate(theme.piece_color(rtype))
+                } else {
+                    theme.piece_color(rtype)
+                };
+                draw_flat_block(settings.block_style, &rect, shade.fill(color));
+            }
+            // the ghost is outlined whatever the block style
+            BlockTile::Ghost(rtype) => {
+                draw_block_outline(
+                    settings.block_style,
+                    &rect,
+                    GHOST_OUTLINE_THICKNESS * view_settings.scale,
+                    ghost_color(theme, settings, rtype, shade),
+                );
+            }
+        }
+    }
+}
+
+/// draws the outlines and the line clear flash over a block
+fn draw_block_overlay(
+    view_settings: &ViewSettings,
+    theme: &Theme,
+    settings: &Settings,
+    block: &BlockDraw,
+) {
+    let BlockDraw {
+        rect,
+        tile,
+        shade,
+        locked,
+        flash,
+    } = *block;
+    if settings.high_contrast {
+        // every block is outlined, textured ghosts get a solid outline too
+        let (thickness, color) = match tile {
+            BlockTile::Rustomino(_) => (HIGH_CONTRAST_OUTLINE_THICKNESS, shade.fade(WHITE)),
+            BlockTile::Ghost(rtype) => (
+                GHOST_OUTLINE_THICKNESS,
+                ghost_color(theme, settings, rtype, shade),
+            ),
+        };
+        draw_block_outline(
+            settings.block_style,
+            &rect,
+            thickness * view_settings.scale,
+            color,
+        );
+    }
+    if let (true, BlockTile::Rustomino(rtype)) = (locked, tile) {
+        draw_block_border(view_settings, theme, settings, &rect, rtype, shade);
+    }
+    if let Some(progress) = flash {
+        draw_rectangle(
+            rect.x,
+            rect.y,
+            rect.w,
+            rect.h,
+            Color::new(1., 1., 1., 1. - progress),
+        );
+    }
+}
+
+/// the ghost's outline color, either the theme's ghost color
+/// or a translucent version of the piece's color, high contrast makes it opaque
+fn ghost_color(
+    theme: &Theme,
+    settings: &Settings,
+    rtype: RustominoType,
+    shade: BlockShade,
+) -> Color {
+    let color = match settings.ghost_color {
+        GhostColor::Uniform => theme.ghost_color,
+        GhostColor::Piece => Color {
+            a: GHOST_TINT_ALPHA,
+            ..theme.piece_color(rtype)
+        },
+    };
+    shade.fade(if settings.high_contrast {
+        Color { a: 1., ..color }
+    } else {
+        color
+    })
+}
+
+// outlines a locked block with a darker shade of its color
+// so neighboring blocks of the same color can be told apart
+fn draw_block_border(
+    view_settings: &ViewSettings,
+    theme: &Theme,
+    settings: &Settings,
+    rect: &Rect<f32>,
+    rtype: RustominoType,
+    shade: BlockShade,
+) {
+    // high contrast already outlines every block
+    if !settings.block_borders || settings.high_contrast {
+        return;
+    }
+    draw_block_outline(
+        settings.block_style,
+        rect,
+        BLOCK_BORDER_THICKNESS * view_settings.scale,
+        darken(shade.fill(theme.piece_color(rtype)), BLOCK_BORDER_SHADE),
     );
-    // Hold: LShift, C Music Volume: + -
 }
 
-fn next_block_rect(block: [i32; 2]) -> Rect<f32> {
+fn draw_flat_block(block_style: BlockStyle, rect: &Rect<f32>, color: Color) {
+    match block_style {
+        BlockStyle::Textured | BlockStyle::Square => {
+            draw_rectangle(rect.x, rect.y, rect.w, rect.h, color)
+        }
+        BlockStyle::Bevel => {
+            // the shadow fills the block and the highlight covers its top left half,
+            // the edges meet diagonally at the corners
+            let top_left = vec2(rect.x, rect.y);
+            let top_right = vec2(rect.x + rect.w, rect.y);
+            let bottom_left = vec2(rect.x, rect.y + rect.h);
+            draw_rectangle(rect.x, rect.y, rect.w, rect.h, darken(color, BLOCK_SHADOW));
+            draw_triangle(
+                top_left,
+                top_right,
+                bottom_left,
+                lighten(color, BLOCK_HIGHLIGHT),
+            );
+            let bevel = rect.w * BLOCK_BEVEL;
+            draw_rectangle(
+                rect.x + bevel,
+                rect.y + bevel,
+                rect.w - bevel * 2.,
+                rect.h - bevel * 2.,
+                color,
+            );
+        }
+        BlockStyle::Rounded => {
+            let radius = rect.w * BLOCK_CORNER_RADIUS;
+            draw_rounded_rectangle(rect, radius, color);
+            // a lighter inset raised toward the top left gives the bevel
+            let bevel = rect.w * BLOCK_BEVEL;
+            let highlight = Rect::new(
+                rect.x + bevel / 2.,
+                rect.y + bevel / 2.,
+                rect.w - bevel * 1.5,
+                rect.h - bevel * 1.5,
+            );
+            draw_rounded_rectangle(&highlight, radius, lighten(color, BLOCK_HIGHLIGHT));
+        }
+    }
+}
+
+/// draws an outline that stays inside the block
+fn draw_block_outline(block_style: BlockStyle, rect: &Rect<f32>, thickness: f32, color: Color) {
+    match block_style {
+        BlockStyle::Textured | BlockStyle::Square | BlockStyle::Bevel => {
+            draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, thickness, color);
+        }
+        BlockStyle::Rounded => {
+            // inset by half the thickness so the outline stays inside the block
+            let inset = thickness / 2.;
+            let outline = Rect::new(
+                rect.x + inset,
+                rect.y + inset,
+                rect.w - thickness,
+                rect.h - thickness,
+            );
+            let points = rounded_rectangle_points(&outline, rect.w * BLOCK_CORNER_RADIUS);
+            for (i, point) in points.iter().enumerate() {
+                let next = points[(i + 1) % points.len()];
+                draw_line(point.x, point.y, next.x, next.y, thickness, color);
+            }
+            // fill the gaps where the outline's segments meet
+            for point in points {
+                draw_circle(point.x, point.y, inset, color);
+            }
+        }
+    }
+}
+
+/// draws a filled rectangle with rounded corners
+fn draw_rounded_rectangle(rect: &Rect<f32>, radius: f32, color: Color) {
+    let center = vec2(rect.x + rect.w / 2., rect.y + rect.h / 2.);
+    let points = rounded_rectangle_points(rect, radius);
+    // the outline is convex so it can be filled as a fan from its center
+    for (i, point) in points.iter().enumerate() {
+        draw_triangle(center, *point, points[(i + 1) % points.len()], color);
+    }
+}
+
+/// the outline of a rectangle with rounded corners, clockwise from the top left
+fn rounded_rectangle_points(rect: &Rect<f32>, radius: f32) -> Vec<Vec2> {
+    let radius = radius.min(rect.w / 2.).min(rect.h / 2.);
+    // corner centers and the angle each corner's arc starts at
+    let corners = [
+        (vec2(rect.x + radius, rect.y + radius), 180f32),
+        (vec2(rect.x + rect.w - radius, rect.y + radius), 270.),
+        (vec2(rect.x + rect.w - radius, rect.y + rect.h - radius), 0.),
+        (vec2(rect.x + radius, rect.y + rect.h - radius), 90.),
+    ];
+    let mut points = Vec::with_capacity(corners.len() * (BLOCK_CORNER_SEGMENTS + 1));
+    for (center, start) in corners {
+        for segment in 0..=BLOCK_CORNER_SEGMENTS {
+            let angle = (start + 90. * segment as f32 / BLOCK_CORNER_SEGMENTS as f32).to_radians();
+            points.push(center + vec2(angle.cos(), angle.sin()) * radius);
+        }
+    }
+    points
+}
+
+/// fully saturates the color keeping its hue, used by high contrast
+fn saturate(color: Color) -> Color {
+    let (hue, _, _) = rgb_to_hsl(color);
+    Color {
+        a: color.a,
+        ..hsl_to_rgb(hue, 1., 0.5)
+    }
+}
+
+/// blends from one color to another, amount is from 0 to 1
+fn mix(from: Color, to: Color, amount: f32) -> Color {
+    Color::new(
+        from.r + (to.r - from.r) * amount,
+        from.g + (to.g - from.g) * amount,
+        from.b + (to.b - from.b) * amount,
+        from.a + (to.a - from.a) * amount,
+    )
+}
+
+/// moves the rect toward another by the provided amount
+fn lerp_rect(from: Rect<f32>, to: Rect<f32>, amount: f32) -> Rect<f32> {
+    Rect::new(
+        from.x + (to.x - from.x) * amount,
+        from.y + (to.y - from.y) * amount,
+        from.w + (to.w - from.w) * amount,
+        from.h + (to.h - from.h) * amount,
+    )
+}
+
+/// eases an animation's progress so it slows down toward the end
+fn ease_out(progress: f32) -> f32 {
+    1. - (1. - progress).powi(2)
+}
+
+/// moves the color toward black by the provided amount
+fn darken(color: Color, amount: f32) -> Color {
+    Color::new(
+        color.r * (1. - amount),
+        color.g * (1. - amount),
+        color.b * (1. - amount),
+        color.a,
+    )
+}
+
+/// moves the color toward white by the provided amount
+fn lighten(color: Color, amount: f32) -> Color {
+    Color::new(
+        color.r + (1. - color.r) * amount,
+        color.g + (1. - color.g) * amount,
+        color.b + (1. - color.b) * amount,
+        color.a,
+    )
+}
+
+fn next_block_rect(view_settings: &ViewSettings, block: [i32; 2]) -> Rect<f32> {
+    let block_stride = view_settings.block_size + view_settings.block_padding;
     // block[x,y] absolute units
-    let x = VIEW_SETTINGS.preview_rect.x
-        + (block[0] as f32 * (BLOCK_SIZE + BLOCK_PADDING) as f32)
-        + 1.0;
+    let x = view_settings.preview_rect.x + (block[0] as f32 * block_stride) + 1.0;
     // get bottom left of playfield_rect
-    let y = VIEW_SETTINGS.preview_rect.y + VIEW_SETTINGS.preview_rect.h
-        - (block[1] as f32 * (BLOCK_SIZE + BLOCK_PADDING) as f32);
+    let y = view_settings.preview_rect.y + view_settings.preview_rect.h
+        - (block[1] as f32 * block_stride);
+
+    Rect::new(x, y, view_settings.block_size, view_settings.block_size)
+}
 
-    Rect::new(x, y, BLOCK_SIZE as f32, BLOCK_SIZE as f32)
+/// the rects of the next rustomino's blocks in the preview box,
+/// rotated rustominos that don't fit in the box are shrunk and centered in it
+fn next_rustomino_rects(view_settings: &ViewSettings, next: &Rustomino) -> Vec<Rect<f32>> {
+    let min_x = next.blocks.iter().map(|block| block[0]).min().unwrap_or(0);
+    let min_y = next.blocks.iter().map(|block| block[1]).min().unwrap_or(0);
+    let max_x = next.blocks.iter().map(|block| block[0]).max().unwrap_or(0);
+    let max_y = next.blocks.iter().map(|block| block[1]).max().unwrap_or(0);
+    // the spawn orientations fill the two rows of the box
+    if min_y >= 1 && max_y <= 2 {
+        return next
+            .blocks
+            .iter()
+            .map(|block| next_block_rect(view_settings, [block[0], block[1]]))
+            .collect();
+    }
+    let rows = (max_y - min_y + 1) as f32;
+    let columns = (max_x - min_x + 1) as f32;
+    let fit = (2. / rows).min(1.);
+    let block_stride = (view_settings.block_size + view_settings.block_padding) * fit;
+    let block_size = view_settings.block_size * fit;
+    let preview_rect = view_settings.preview_rect;
+    let left = preview_rect.x + (preview_rect.w - columns * block_stride) / 2.;
+    let top = preview_rect.y + (preview_rect.h - rows * block_stride) / 2.;
+    next.blocks
+        .iter()
+        .map(|block| {
+            Rect::new(
+                left + (block[0] - min_x) as f32 * block_stride,
+                top + (max_y - block[1]) as f32 * block_stride,
+                block_size,
+                block_size,
+            )
+        })
+        .collect()
 }
 
-fn hold_block_rect(block: [i32; 2]) -> Rect<f32> {
+fn hold_block_rect(view_settings: &ViewSettings, block: [i32; 2]) -> Rect<f32> {
+    let block_stride = view_settings.block_size + view_settings.block_padding;
     // block[x,y] absolute units
-    let x =
-        VIEW_SETTINGS.hold_rect.x + (block[0] as f32 * (BLOCK_SIZE + BLOCK_PADDING) as f32) + 1.0;
+    let x = view_settings.hold_rect.x + (block[0] as f32 * block_stride) + 1.0;
     // get bottom left of playfield_rect
-    let y = VIEW_SETTINGS.hold_rect.y + VIEW_SETTINGS.hold_rect.h
-        - (block[1] as f32 * (BLOCK_SIZE + BLOCK_PADDING) as f32);
+    let y =
+        view_settings.hold_rect.y + view_settings.hold_rect.h - (block[1] as f32 * block_stride);
 
-    Rect::new(x, y, BLOCK_SIZE as f32, BLOCK_SIZE as f32)
+    Rect::new(x, y, view_settings.block_size, view_settings.block_size)
 }
 
-fn playfield_block_rect(block: [i32; 2]) -> Rect<f32> {
+fn playfield_block_rect(view_settings: &ViewSettings, block: [i32; 2]) -> Rect<f32> {
+    let block_stride = view_settings.block_size + view_settings.block_padding;
     // block[x,y] absolute units
-    let x = VIEW_SETTINGS.staging_rect.x
-        + (block[0] as f32 * (BLOCK_SIZE + BLOCK_PADDING) as f32)
-        + 1.0;
+    let x = view_settings.staging_rect.x + (block[0] as f32 * block_stride) + 1.0;
     // get bottom left of playfield_rect
-    let y = VIEW_SETTINGS.playfield_rect.y + VIEW_SETTINGS.playfield_rect.h
-        - ((block[1] + 1) as f32 * (BLOCK_SIZE + BLOCK_PADDING) as f32)
+    let mut y = view_settings.playfield_rect.y + view_settings.playfield_rect.h
+        - ((block[1] + 1) as f32 * block_stride)
         - 1.0;
+    // the buffer rows are drawn in the staging area, above the gap between it and the playfield
+    if block[1] >= playfield::PLAYFIELD_SIZE[1] {
+        y -= view_settings.staging_padding;
+    }
 
-    Rect::new(x, y, BLOCK_SIZE as f32, BLOCK_SIZE as f32)
+    Rect::new(x, y, view_settings.block_size, view_settings.block_size)
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -527,3 +3502,137 @@ impl<T> From<Rect<T>> for [T; 4] {
         [value.x, value.y, value.w, value.h]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESIGN_VIEW: [f32; 2] = [VIEW_WH[0] as f32, VIEW_WH[1] as f32];
+
+    // true if the inner rect is entirely inside the outer one
+    fn contains(outer: Rect<f32>, inner: Rect<f32>) -> bool {
+        inner.x >= outer.x
+            && inner.y >= outer.y
+            && inner.x + inner.w <= outer.x + outer.w
+            && inner.y + inner.h <= outer.y + outer.h
+    }
+
+    #[test]
+    fn buffer_rows_are_drawn_in_the_staging_area() {
+        let view = ViewSettings::new(DESIGN_VIEW, 100, [0, 0]);
+        let stride = view.block_size + view.block_padding;
+        let top_row = playfield::PLAYFIELD_SIZE[1] - 1;
+        let row_y = |y: i32| playfield_block_rect(&view, [0, y]).y;
+
+        // row 19 is the top row of the playfield, rows 20 and 21 are above the gap
+        assert!(contains(
+            view.playfield_rect,
+            playfield_block_rect(&view, [0, top_row])
+        ));
+        assert_eq!(row_y(top_row), view.playfield_rect.y);
+        for y in [top_row + 1, top_row + 2] {
+            for x in [0, playfield::PLAYFIELD_SIZE[0] - 1] {
+                assert!(contains(
+                    view.staging_rect,
+                    playfield_block_rect(&view, [x, y])
+                ));
+            }
+        }
+        assert_eq!(
+            row_y(top_row + 1),
+            row_y(top_row) - stride - view.staging_padding
+        );
+        assert_eq!(row_y(top_row + 2), row_y(top_row + 1) - stride);
+        assert_eq!(row_y(top_row + 2), view.staging_rect.y + view.block_padding);
+    }
+
+    #[test]
+    fn board_scale_moves_cells_proportionally() {
+        let cell = [7, 12];
+        let offsets: Vec<Vec2> = [75, 100, 125]
+            .iter()
+            .map(|&board_scale| {
+                let view = ViewSettings::new(DESIGN_VIEW, board_scale, [0, 0]);
+                // every cell of the playfield is inside the board
+                for x in 0..playfield::PLAYFIELD_SIZE[0] {
+                    for y in 0..playfield::PLAYFIELD_SIZE[1] {
+                        assert!(contains(
+                            view.playfield_rect,
+                            playfield_block_rect(&view, [x, y])
+                        ));
+                    }
+                }
+                // the cell's distance from the bottom left corner of the board in strides
+                let rect = playfield_block_rect(&view, cell);
+                let stride = view.block_size + view.block_padding;
+                vec2(
+                    rect.x - view.playfield_rect.x,
+                    view.playfield_rect.y + view.playfield_rect.h - rect.y,
+                ) / stride
+            })
+            .collect();
+        for offset in &offsets[1..] {
+            assert!((*offset - offsets[0]).length() < 0.1, "{offsets:?}");
+        }
+        // a view tall enough that the largest board isn't limited by its height
+        let tall_view = [DESIGN_VIEW[0], DESIGN_VIEW[1] * 2.];
+        let sizes: Vec<f32> = [75, 100, 125]
+            .iter()
+            .map(|&board_scale| ViewSettings::new(tall_view, board_scale, [0, 0]).block_size)
+            .collect();
+        assert_eq!(
+            sizes,
+            [
+                (BLOCK_SIZE as f32 * 0.75).floor(),
+                BLOCK_SIZE as f32,
+                (BLOCK_SIZE as f32 * 1.25).floor()
+            ]
+        );
+    }
+
+    #[test]
+    fn no_effect_starts_with_reduced_motion() {
+        let view = ViewSettings::new(DESIGN_VIEW, 100, [0, 0]);
+        let theme = Theme::built_in();
+        let strings = Strings::default();
+        let mut settings = Settings::default();
+        settings.reduced_motion = true;
+        settings.hard_drop_trail = true;
+        let landed = [ivec2(0, 0), ivec2(1, 0), ivec2(2, 0), ivec2(3, 0)];
+        let events = [
+            GameEvent::Spawn,
+            GameEvent::HardDrop(HardDropped {
+                rtype: RustominoType::I,
+                landed,
+                distance: 18,
+            }),
+            GameEvent::LinesCleared(ClearedLines {
+                blocks: (0..10).map(|x| ([x, 0], RustominoType::I)).collect(),
+                lines: 4,
+                perfect_clear: true,
+                combo: 2,
+                locked: landed,
+            }),
+            GameEvent::Scored(ClearResult {
+                lines: 4,
+                t_spin: false,
+                back_to_back: true,
+                combo: 2,
+                score: 1200,
+            }),
+            GameEvent::LevelUp(2),
+            GameEvent::ScoreMilestone(10000),
+        ];
+        let mut effects = Effects::default();
+        for event in &events {
+            effects.handle_event(&view, &theme, &settings, &strings, event);
+        }
+        // the update's debug assertion checks the same
+        effects.update(&view, &settings, 1. / 60., 2);
+        assert!(!effects.running());
+
+        settings.reduced_motion = false;
+        effects.handle_event(&view, &theme, &settings, &strings, &GameEvent::Spawn);
+        assert!(effects.running());
+    }
+}